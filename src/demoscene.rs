@@ -10,10 +10,8 @@ use zip::ZipArchive;
 use crate::download::{ACGDownloader, ACGQuality, TextureDownloader};
 use crate::geometry::{Plane, Sphere, Triangle};
 use crate::light::{Attenuation, PointLight};
-use crate::material::{
-    BumpPower, Bumpmap, ChessBoard, ChessBoardMode, Fresnel, Matte, Phong, ScaleUV,
-};
-use crate::sampler::{Adjust, NormalMap, Perlin, SamplerExt, Texel};
+use crate::material::{ChessBoard, ChessBoardMode, Fresnel, Material, Matte, Pbr, Phong, ScaleUV};
+use crate::sampler::{Adjust, NormalMap, Perlin, Sampler, SamplerExt, Texel};
 use crate::scene::BoxScene;
 use crate::types::{Camera, Color, Float, Point, RResult, Vector, Vectorx};
 use crate::{point, vec3};
@@ -68,6 +66,26 @@ fn load_tex3(
     ))
 }
 
+/// Build a [`Pbr`] material from a downloaded texture set.
+///
+/// Not all texture sets have a metalness map, so missing ones are treated as
+/// fully dielectric.
+fn pbr_material<F: Float + Texel>(
+    color: DynamicImage,
+    normal: DynamicImage,
+    roughness: DynamicImage,
+    metalness: RResult<DynamicImage>,
+) -> impl Material<F> {
+    let metallic = metalness.map_or_else(|_| F::ZERO.dynsampler(), |img| img.mipmap().dynsampler());
+
+    Pbr::new(
+        color.mipmap(),
+        metallic,
+        roughness.mipmap(),
+        Some(NormalMap::new(normal.mipmap())),
+    )
+}
+
 #[allow(clippy::too_many_lines)]
 pub fn construct_demo_scene<F>(scene: &mut BoxScene<F>) -> RResult<()>
 where
@@ -134,36 +152,12 @@ where
         (0.1).into(),
         ChessBoard::new(
             ChessBoardMode::UV,
-            Bumpmap::new(
-                BumpPower(F::HALF),
-                NormalMap::new(tex0b.bilinear()),
-                Phong::new()
-                    .with_ke_map(Some(tex0a.bilinear()))
-                    .with_kd_map(Option::<Color<F>>::None)
-                    .with_ks_map(Option::<Color<F>>::None)
-                    .with_pow_map(Some(tex0r.bilinear())),
-            ),
-            Bumpmap::new(
-                BumpPower(F::HALF),
-                NormalMap::new(tex1b.bilinear()),
-                Phong::new()
-                    .with_ke_map(Some(tex1a.bilinear()))
-                    .with_kd_map(Option::<Color<F>>::None)
-                    .with_ks_map(Option::<Color<F>>::None)
-                    .with_pow_map(Some(tex1r.bilinear())),
-            ),
+            pbr_material(tex0a, tex0b, tex0r, tex0m),
+            pbr_material(tex1a, tex1b, tex1r, tex1m),
         ),
     )));
 
-    let mat_bmp2 = Bumpmap::new(
-        BumpPower(F::HALF),
-        NormalMap::new(tex2b.bilinear()),
-        Phong::new()
-            .with_ke_map(Some(tex2a.bilinear()))
-            .with_kd_map(Option::<Color<F>>::None)
-            .with_ks_map(Option::<Color<F>>::None)
-            .with_pow_map(Some(tex2r.bilinear())),
-    );
+    let mat_bmp2 = pbr_material::<F>(tex2a, tex2b, tex2r, tex2m);
 
     let obj = Obj::load("models/teapot.obj")?;
 
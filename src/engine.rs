@@ -6,23 +6,29 @@ use parking_lot::RwLock;
 
 #[cfg(feature = "gui")]
 use egui::ColorImage;
-use image::{ImageBuffer, Rgba};
+use image::{ImageBuffer, Rgb, Rgb32FImage, Rgba};
 use workerpool::thunk::{Thunk, ThunkWorker};
 use workerpool::Pool;
 
 use crate::material::{ColorDebug, Material};
+use crate::pathtracer::PathTracer;
 use crate::scene::{BoxScene, RayTracer};
 use crate::tracer::Tracer;
-use crate::types::{Color, Float, Point, Ray, RayFlags};
+use crate::types::{AntiAlias, Color, Float, Ray, RayFlags, ToneMapper};
 
 type RenderFunc<F> = fn(&Tracer<F>, Ray<F>) -> Color<F>;
+type SceneLock<F> = Arc<RwLock<BoxScene<F>>>;
 
+#[derive(Clone, Copy)]
 pub struct RenderJob<F: Float> {
     first_line: Option<u32>,
     last_line: Option<u32>,
     mult_x: u32,
     mult_y: u32,
     flags: RayFlags,
+    camera: usize,
+    passes: u32,
+    aa: AntiAlias<F>,
     func: RenderFunc<F>,
 }
 
@@ -35,10 +41,13 @@ impl<F: Float> RenderJob<F> {
             mult_x: 1,
             mult_y: 1,
             flags: RayFlags::default(),
+            camera: 0,
+            passes: 1,
+            aa: AntiAlias::new(),
             func: |tracer, ray| {
                 tracer
                     .ray_trace(&ray)
-                    .map_or_else(|| tracer.scene().background, Color::clamped)
+                    .unwrap_or_else(|| tracer.scene().background)
             },
         }
     }
@@ -98,11 +107,43 @@ impl<F: Float> RenderJob<F> {
         }
     }
 
+    /// Render using the [`PathTracer`] instead of the default [`Tracer`].
+    ///
+    /// Path traced images are noisy, so this is best combined with
+    /// [`RenderJob::with_passes`].
+    #[must_use]
+    pub const fn with_func_path_trace(self) -> Self {
+        Self {
+            func: |tracer, ray| {
+                PathTracer::new(tracer.scene())
+                    .ray_trace(&ray)
+                    .unwrap_or_else(|| tracer.scene().background)
+            },
+            ..self
+        }
+    }
+
     #[must_use]
     pub const fn with_ray_flags(self, flags: RayFlags) -> Self {
         Self { flags, ..self }
     }
 
+    #[must_use]
+    pub const fn with_camera(self, camera: usize) -> Self {
+        Self { camera, ..self }
+    }
+
+    /// Render the image progressively, averaging over `passes` passes.
+    #[must_use]
+    pub const fn with_passes(self, passes: u32) -> Self {
+        Self { passes, ..self }
+    }
+
+    #[must_use]
+    pub const fn with_antialias(self, aa: AntiAlias<F>) -> Self {
+        Self { aa, ..self }
+    }
+
     #[must_use]
     pub fn get_lines(&self, height: u32) -> (u32, u32) {
         (
@@ -125,6 +166,21 @@ impl<F: Float> RenderJob<F> {
     pub const fn get_flags(&self) -> RayFlags {
         self.flags
     }
+
+    #[must_use]
+    pub const fn get_camera(&self) -> usize {
+        self.camera
+    }
+
+    #[must_use]
+    pub const fn get_passes(&self) -> u32 {
+        self.passes
+    }
+
+    #[must_use]
+    pub const fn get_antialias(&self) -> AntiAlias<F> {
+        self.aa
+    }
 }
 
 pub struct RenderSpan<F: Float> {
@@ -132,6 +188,7 @@ pub struct RenderSpan<F: Float> {
     pub mult_x: u32,
     pub mult_y: u32,
     pub pixels: Vec<Color<F>>,
+    pub generation: u64,
 }
 
 impl<F: Float> RenderSpan<F> {
@@ -149,16 +206,16 @@ impl<F: Float> RenderSpan<F> {
         base..base + size
     }
 
-    pub fn pixel_iter(&self) -> impl Iterator<Item = (u32, u32, Rgba<u8>)> + '_ {
+    pub fn pixel_iter(&self) -> impl Iterator<Item = (u32, u32, Color<F>)> + '_ {
         self.pixels.iter().enumerate().flat_map(move |(idx, pix)| {
-            let rgba = Rgba(pix.to_array4());
+            let pix = *pix;
             let base_x = idx as u32 * self.mult_x;
             let base_y = self.line;
 
             let xs = base_x..base_x + self.mult_x;
             let ys = base_y..base_y + self.mult_y;
 
-            ys.cartesian_product(xs).map(move |(y, x)| (x, y, rgba))
+            ys.cartesian_product(xs).map(move |(y, x)| (x, y, pix))
         })
     }
 }
@@ -171,6 +228,16 @@ pub struct RenderEngine<F: Float> {
     dirty: Vec<bool>,
     width: u32,
     height: u32,
+    job: Option<(RenderJob<F>, SceneLock<F>)>,
+    generation: u64,
+    accum: Vec<Color<F>>,
+    passes: Vec<u32>,
+    pixels: Vec<Color<F>>,
+    tonemap: ToneMapper<F>,
+    /// First pass of each line, for adaptive anti-aliasing
+    coarse: Vec<Option<Vec<Color<F>>>>,
+    /// Lines submitted for the second pass of adaptive anti-aliasing
+    refined: Vec<bool>,
 }
 
 pub struct RenderEngineIter<'a, F: Float> {
@@ -204,6 +271,14 @@ impl<F: Float> RenderEngine<F> {
             dirty: vec![false; height as usize],
             width,
             height,
+            job: None,
+            generation: 0,
+            accum: vec![Color::BLACK; (width * height) as usize],
+            passes: vec![0; height as usize],
+            pixels: vec![Color::BLACK; (width * height) as usize],
+            tonemap: ToneMapper::default(),
+            coarse: vec![None; height as usize],
+            refined: vec![false; height as usize],
         }
     }
 
@@ -213,48 +288,125 @@ impl<F: Float> RenderEngine<F> {
 
     pub fn submit(&mut self, job: &RenderJob<F>, lock: &Arc<RwLock<BoxScene<F>>>) {
         let (a, b) = job.get_lines(self.img.height());
+        let (_mult_x, mult_y) = job.get_mult();
+
+        self.generation += 1;
+        self.job = Some((*job, Arc::clone(lock)));
+
+        if job.get_passes() > 1 {
+            self.accum.fill(Color::BLACK);
+            self.passes.fill(0);
+        }
+
+        self.coarse.fill(None);
+        self.refined.fill(false);
+
+        for y in (a..b).step_by(mult_y as usize) {
+            self.submit_line(job, lock, y);
+        }
+    }
+
+    /// Adaptive anti-aliasing renders a first pass with a single sample per
+    /// pixel, and then refines each line once its neighbours are known
+    fn is_adaptive(job: &RenderJob<F>) -> bool {
+        job.get_antialias().threshold().is_some() && job.get_passes() == 1
+    }
+
+    fn submit_line(&mut self, job: &RenderJob<F>, lock: &Arc<RwLock<BoxScene<F>>>, y: u32) {
         let (mult_x, mult_y) = job.get_mult();
         let func = job.get_func();
         let flags = job.get_flags();
+        let camera = job.get_camera();
+        let aa = job.get_antialias();
+        let generation = self.generation;
 
         let (width, height) = (self.width, self.height);
-        let offset = Point::from((mult_x, mult_y)) / F::TWO;
-        let size = Point::from((width, height));
 
-        for y in (a..b).step_by(mult_y as usize) {
-            let dirty = &mut self.dirty[y as usize];
-            if *dirty {
-                continue;
-            }
-            *dirty = true;
-
-            let lock = Arc::clone(lock);
-
-            self.pool.execute_to(
-                self.tx.clone(),
-                #[allow(clippy::significant_drop_tightening)]
-                Thunk::of(move || {
-                    let scene = lock.read();
-                    let tracer = Tracer::new(&scene);
-                    let camera = &tracer.scene().cameras[0];
-
-                    let pixels = (0..width)
-                        .step_by(mult_x as usize)
-                        .map(|x| {
-                            let point = Point::from((x, y));
-                            let ray = camera.get_ray((point + offset) / size).with_flags(flags);
-                            func(&tracer, ray)
-                        })
-                        .collect();
-
-                    RenderSpan {
-                        line: y,
-                        mult_x,
-                        mult_y,
-                        pixels,
+        /* For adaptive jobs, the first pass of this line and its neighbours
+         * (if that is done), or `None` to render the first pass */
+        let adaptive = Self::is_adaptive(job);
+        let rows = self.coarse[y as usize]
+            .as_ref()
+            .filter(|_| adaptive)
+            .map(|_| {
+                let row = |y: Option<u32>| y.and_then(|y| self.coarse.get(y as usize)?.clone());
+                [
+                    row(y.checked_sub(mult_y)),
+                    row(Some(y)),
+                    row(Some(y + mult_y)),
+                ]
+            });
+
+        let dirty = &mut self.dirty[y as usize];
+        if *dirty {
+            return;
+        }
+        *dirty = true;
+
+        let lock = Arc::clone(lock);
+
+        self.pool.execute_to(
+            self.tx.clone(),
+            #[allow(clippy::significant_drop_tightening)]
+            Thunk::of(move || {
+                let scene = lock.read();
+                let tracer = Tracer::new(&scene);
+                let camera = &tracer.scene().cameras[camera];
+
+                let pixel = aa.footprint(width, height, (mult_x, mult_y));
+                let sample = |point| {
+                    func(
+                        &tracer,
+                        camera.get_pixel_ray(point, pixel).with_flags(flags),
+                    )
+                };
+
+                let mult = (mult_x, mult_y);
+                let pixels = match &rows {
+                    Some(rows) => {
+                        let rows = rows.each_ref().map(Option::as_deref);
+                        aa.refine_line(width, height, mult, y, rows, sample)
                     }
-                }),
-            );
+                    None if adaptive => aa.first_pass().render_line(width, height, mult, y, sample),
+                    None => aa.render_line(width, height, mult, y, sample),
+                };
+
+                RenderSpan {
+                    line: y,
+                    mult_x,
+                    mult_y,
+                    pixels,
+                    generation,
+                }
+            }),
+        );
+    }
+
+    /// Submit the second pass of adaptive anti-aliasing for line `y`, once
+    /// the first pass of it and its neighbours (within `lines`) is done
+    fn submit_refine(
+        &mut self,
+        job: &RenderJob<F>,
+        lock: &Arc<RwLock<BoxScene<F>>>,
+        lines: (u32, u32),
+        y: u32,
+    ) {
+        let (_mult_x, mult_y) = job.get_mult();
+        let (a, b) = lines;
+
+        let ready = |y: Option<u32>| {
+            y.filter(|y| (a..b).contains(y))
+                .map_or(true, |y| self.coarse[y as usize].is_some())
+        };
+
+        if (a..b).contains(&y)
+            && !self.refined[y as usize]
+            && ready(Some(y))
+            && ready(y.checked_sub(mult_y))
+            && ready(Some(y + mult_y))
+        {
+            self.refined[y as usize] = true;
+            self.submit_line(job, lock, y);
         }
     }
 
@@ -266,6 +418,36 @@ impl<F: Float> RenderEngine<F> {
         }
     }
 
+    /// Set the tone mapping used for the displayed image, and redraw it.
+    pub fn set_tonemap(&mut self, tonemap: ToneMapper<F>) {
+        self.tonemap = tonemap;
+
+        for (idx, color) in self.pixels.iter().enumerate() {
+            let (x, y) = (idx as u32 % self.width, idx as u32 / self.width);
+            self.img
+                .put_pixel(x, y, Rgba(tonemap.apply(*color).to_array4()));
+        }
+    }
+
+    #[must_use]
+    pub const fn get_tonemap(&self) -> ToneMapper<F> {
+        self.tonemap
+    }
+
+    /// Rendered image as unclamped linear radiance
+    #[must_use]
+    pub fn get_hdr_image(&self) -> Rgb32FImage {
+        ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            Rgb(self.pixels[(y * self.width + x) as usize].to_f32_array())
+        })
+    }
+
+    fn put_pixel(&mut self, x: u32, y: u32, color: Color<F>) {
+        self.pixels[(y * self.width + x) as usize] = color;
+        self.img
+            .put_pixel(x, y, Rgba(self.tonemap.apply(color).to_array4()));
+    }
+
     #[must_use]
     #[cfg(feature = "gui")]
     pub fn get_epaint_image(&self) -> ColorImage {
@@ -276,25 +458,92 @@ impl<F: Float> RenderEngine<F> {
 
     #[must_use]
     pub fn progress(&self) -> (usize, usize) {
-        let act = self.pool.queued_count();
-        let max = self.height as usize;
-        (act, max)
+        match &self.job {
+            Some((job, _)) if job.get_passes() > 1 => {
+                let passes = job.get_passes() as usize;
+                let done: usize = self.passes.iter().map(|p| *p as usize).sum();
+                let max = self.height as usize * passes;
+                (max.saturating_sub(done), max)
+            }
+            _ => (self.pool.queued_count(), self.height as usize),
+        }
     }
 
     pub fn update(&mut self) -> bool {
         let mut recv = false;
 
+        let height = self.height as usize;
+
         while let Ok(span) = self.rx.try_recv() {
-            for y in span.iter_y() {
+            for y in span.iter_y().filter(|y| *y < height) {
                 self.dirty[y] = false;
             }
 
-            for (x, y, color) in span.pixel_iter() {
-                self.img.put_pixel(x, y, color);
+            recv = true;
+
+            let Some((job, lock)) = self.job.clone() else {
+                continue;
+            };
+
+            /* This span was still in flight when a new job was submitted,
+             * so the line was skipped. Render it again for the current job. */
+            if span.generation != self.generation {
+                self.submit_line(&job, &lock, span.line);
+                continue;
             }
 
-            recv = true;
+            if job.get_passes() > 1 {
+                self.accumulate(&span);
+
+                if self.passes[span.line as usize] < job.get_passes() {
+                    self.submit_line(&job, &lock, span.line);
+                }
+            } else {
+                for (x, y, color) in span.pixel_iter() {
+                    if x < self.width && y < self.height {
+                        self.put_pixel(x, y, color);
+                    }
+                }
+
+                /* First pass of an adaptive job: refine this line, and its
+                 * neighbours, as soon as their surroundings are known */
+                let line = span.line as usize;
+                if Self::is_adaptive(&job) && self.coarse[line].is_none() {
+                    self.coarse[line] = Some(span.pixels);
+
+                    let lines = job.get_lines(self.height);
+                    let (_mult_x, mult_y) = job.get_mult();
+                    let neighbours = [
+                        span.line.checked_sub(mult_y),
+                        Some(span.line),
+                        Some(span.line + mult_y),
+                    ];
+                    for y in neighbours.into_iter().flatten() {
+                        self.submit_refine(&job, &lock, lines, y);
+                    }
+                }
+            }
         }
         recv
     }
+
+    fn accumulate(&mut self, span: &RenderSpan<F>) {
+        let (width, height) = (self.width as usize, self.height as usize);
+
+        for y in span.iter_y().filter(|y| *y < height) {
+            self.passes[y] += 1;
+            let count = F::from_u32(self.passes[y]);
+
+            for (idx, pix) in span.pixels.iter().enumerate() {
+                let base_x = idx * span.mult_x as usize;
+
+                for x in (base_x..base_x + span.mult_x as usize).filter(|x| *x < width) {
+                    let acc = &mut self.accum[y * width + x];
+                    *acc += *pix;
+                    let color = *acc / count;
+                    self.put_pixel(x as u32, y as u32, color);
+                }
+            }
+        }
+    }
 }
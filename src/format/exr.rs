@@ -0,0 +1,228 @@
+//! Minimal `OpenEXR` writer, for uncompressed 32-bit float images with any
+//! number of channels.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use image::Rgb32FImage;
+
+use crate::types::RResult;
+
+/// A named image channel, with one value per pixel in row-major order
+pub type Channel = (String, Vec<f32>);
+
+/// Append a header attribute (name, type, size and value) to `header`
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend(name.as_bytes());
+    header.push(0);
+    header.extend(kind.as_bytes());
+    header.push(0);
+    header.extend((value.len() as u32).to_le_bytes());
+    header.extend(value);
+}
+
+fn box2i(width: u32, height: u32) -> Vec<u8> {
+    [0, 0, width as i32 - 1, height as i32 - 1]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect()
+}
+
+fn header(width: u32, height: u32, channels: &[&Channel]) -> Vec<u8> {
+    /* Magic number, and version 2 (single-part scanline) */
+    let mut header = vec![0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0];
+
+    let mut chlist = vec![];
+    for (name, _) in channels {
+        chlist.extend(name.as_bytes());
+        chlist.push(0);
+        chlist.extend(2i32.to_le_bytes()); /* pixel type: FLOAT */
+        chlist.extend([0, 0, 0, 0]); /* pLinear, reserved */
+        chlist.extend(1i32.to_le_bytes()); /* x sampling */
+        chlist.extend(1i32.to_le_bytes()); /* y sampling */
+    }
+    chlist.push(0);
+
+    attribute(&mut header, "channels", "chlist", &chlist);
+    attribute(&mut header, "compression", "compression", &[0]);
+    attribute(&mut header, "dataWindow", "box2i", &box2i(width, height));
+    attribute(&mut header, "displayWindow", "box2i", &box2i(width, height));
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1.0f32.to_le_bytes(),
+    );
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1.0f32.to_le_bytes(),
+    );
+    header.push(0);
+
+    header
+}
+
+/// Write `channels` to `out`, as a single-part scanline `OpenEXR` file.
+///
+/// Channels may be given in any order, and layers are separated by a dot in
+/// the channel name (e.g. `depth.Z`).
+pub fn write_channels(
+    out: &mut impl Write,
+    width: u32,
+    height: u32,
+    channels: &[Channel],
+) -> RResult<()> {
+    /* The file format requires channels in alphabetical order */
+    let mut channels: Vec<&Channel> = channels.iter().collect();
+    channels.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let header = header(width, height, &channels);
+    out.write_all(&header)?;
+
+    /* Offset table: one uncompressed block per scanline */
+    let data_size = 4 * channels.len() as u32 * width;
+    let block_size = 8 + u64::from(data_size);
+    let first = header.len() as u64 + 8 * u64::from(height);
+
+    for y in 0..u64::from(height) {
+        out.write_all(&(first + y * block_size).to_le_bytes())?;
+    }
+
+    let width = width as usize;
+    for y in 0..height {
+        out.write_all(&y.to_le_bytes())?;
+        out.write_all(&data_size.to_le_bytes())?;
+
+        let ofs = y as usize * width;
+        for (_, data) in &channels {
+            for value in &data[ofs..ofs + width] {
+                out.write_all(&value.to_le_bytes())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Split `img` into its `R`, `G` and `B` channels.
+#[must_use]
+pub fn rgb_channels(img: &Rgb32FImage) -> Vec<Channel> {
+    ["R", "G", "B"]
+        .iter()
+        .enumerate()
+        .map(|(idx, name)| {
+            let data = img.pixels().map(|p| p.0[idx]).collect();
+            ((*name).to_string(), data)
+        })
+        .collect()
+}
+
+/// Write `img` to `out`, as a single-part scanline `OpenEXR` file.
+pub fn write(out: &mut impl Write, img: &Rgb32FImage) -> RResult<()> {
+    let channels = rgb_channels(img);
+    write_channels(out, img.width(), img.height(), &channels)
+}
+
+/// Save `img` as an `OpenEXR` file at `path`.
+pub fn save(path: impl AsRef<Path>, img: &Rgb32FImage) -> RResult<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write(&mut out, img)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Save `channels` as an `OpenEXR` file at `path`.
+pub fn save_channels(
+    path: impl AsRef<Path>,
+    width: u32,
+    height: u32,
+    channels: &[Channel],
+) -> RResult<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write_channels(&mut out, width, height, channels)?;
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_channels, Channel};
+
+    fn u32_at(data: &[u8], ofs: usize) -> u32 {
+        u32::from_le_bytes(data[ofs..ofs + 4].try_into().unwrap())
+    }
+
+    fn cstr_at(data: &[u8], ofs: usize) -> &str {
+        let len = data[ofs..].iter().position(|&b| b == 0).unwrap();
+        std::str::from_utf8(&data[ofs..ofs + len]).unwrap()
+    }
+
+    #[test]
+    fn test_exr_roundtrip() {
+        let (width, height) = (3, 2);
+        let channel = |name: &str, base: f32| -> Channel {
+            let data = (0..width * height).map(|i| base + i as f32).collect();
+            (name.to_string(), data)
+        };
+        let channels = [channel("Z", 100.0), channel("G", 10.0), channel("B", 0.5)];
+
+        let mut data = vec![];
+        write_channels(&mut data, width, height, &channels).unwrap();
+
+        assert_eq!(data[..8], [0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+
+        /* Header attributes, up to the empty name that ends the header */
+        let mut ofs = 8;
+        let mut attrs = vec![];
+        while data[ofs] != 0 {
+            let name = cstr_at(&data, ofs);
+            let kind = cstr_at(&data, ofs + name.len() + 1);
+            ofs += name.len() + kind.len() + 2;
+            let size = u32_at(&data, ofs) as usize;
+            attrs.push((name, kind, &data[ofs + 4..ofs + 4 + size]));
+            ofs += 4 + size;
+        }
+        ofs += 1;
+
+        let (_, kind, chlist) = attrs[0];
+        assert_eq!(kind, "chlist");
+        let mut names = vec![];
+        let mut pos = 0;
+        while chlist[pos] != 0 {
+            let name = cstr_at(chlist, pos);
+            assert_eq!(u32_at(chlist, pos + name.len() + 1), 2);
+            names.push(name);
+            pos += name.len() + 1 + 16;
+        }
+        assert_eq!(names, ["B", "G", "Z"]);
+
+        let window = attrs.iter().find(|attr| attr.0 == "dataWindow").unwrap();
+        assert_eq!(u32_at(window.2, 8), width - 1);
+        assert_eq!(u32_at(window.2, 12), height - 1);
+
+        /* Offset table, pointing at one block per scanline */
+        for y in 0..height as usize {
+            let entry = ofs + 8 * y;
+            let block = u64::from_le_bytes(data[entry..entry + 8].try_into().unwrap()) as usize;
+            assert_eq!(u32_at(&data, block), y as u32);
+            assert_eq!(u32_at(&data, block + 4), 4 * 3 * width);
+
+            let pixels = &data[block + 8..];
+            for (idx, base) in [0.5, 10.0, 100.0].iter().enumerate() {
+                for x in 0..width as usize {
+                    let value = f32::from_bits(u32_at(pixels, 4 * (idx * width as usize + x)));
+                    assert_eq!(value, base + (y * width as usize + x) as f32);
+                }
+            }
+        }
+        assert_eq!(
+            data.len(),
+            ofs + 8 * height as usize + height as usize * (8 + 12 * width as usize)
+        );
+    }
+}
@@ -0,0 +1,1376 @@
+//! Loader for glTF 2.0 scenes, in both text (`.gltf`) and binary (`.glb`) form.
+//!
+//! Nodes become nested [`Group`]s with the transform of the node, meshes
+//! become [`TriangleMesh`]es (shared through [`Instance`]s when used by more
+//! than one node), and cameras and punctual lights (`KHR_lights_punctual`)
+//! are added to the scene at their world position.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use base64::Engine;
+use camino::{Utf8Path, Utf8PathBuf};
+use cgmath::{Deg, InnerSpace, Matrix4, Quaternion, Rad, SquareMatrix, Transform as _, Vector4};
+use image::DynamicImage;
+use serde::Deserialize;
+
+use crate::geometry::{FiniteGeometry, Group, Instance, Triangle, TriangleMesh};
+use crate::light::{Attenuation, DirectionalLight, PointLight, SpotLight};
+use crate::material::{BoxMaterial, BumpPower, Bumpmap, Fresnel, Pbr, Phong, Smart};
+use crate::sampler::{DynSampler, FileSampler, NormalMap, Sampler, SamplerExt, Texel};
+use crate::scene::BoxScene;
+use crate::types::{
+    Camera, Color, Error, Float, Lens, Lerp, MaterialId, NamedObject, Point, Projection, RResult,
+    Vector, Vectorx,
+};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct Document {
+    scene: Option<usize>,
+    scenes: Vec<DocScene>,
+    nodes: Vec<Node>,
+    meshes: Vec<Mesh>,
+    accessors: Vec<Accessor>,
+    buffer_views: Vec<BufferView>,
+    buffers: Vec<Buffer>,
+    materials: Vec<DocMaterial>,
+    textures: Vec<Texture>,
+    images: Vec<DocImage>,
+    cameras: Vec<DocCamera>,
+    extensions: DocExtensions,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct DocScene {
+    nodes: Vec<usize>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Node {
+    name: Option<String>,
+    children: Vec<usize>,
+    mesh: Option<usize>,
+    camera: Option<usize>,
+    matrix: Option<[f32; 16]>,
+    translation: Option<[f32; 3]>,
+    rotation: Option<[f32; 4]>,
+    scale: Option<[f32; 3]>,
+    extensions: NodeExtensions,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct NodeExtensions {
+    #[serde(rename = "KHR_lights_punctual")]
+    light: Option<NodeLight>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NodeLight {
+    light: usize,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Mesh {
+    name: Option<String>,
+    primitives: Vec<Primitive>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Primitive {
+    attributes: HashMap<String, usize>,
+    indices: Option<usize>,
+    material: Option<usize>,
+    #[serde(default = "Primitive::triangles")]
+    mode: u32,
+}
+
+impl Primitive {
+    const TRIANGLES: u32 = 4;
+    const TRIANGLE_STRIP: u32 = 5;
+    const TRIANGLE_FAN: u32 = 6;
+
+    const fn triangles() -> u32 {
+        Self::TRIANGLES
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Accessor {
+    buffer_view: Option<usize>,
+    #[serde(default)]
+    byte_offset: usize,
+    component_type: u32,
+    #[serde(default)]
+    normalized: bool,
+    count: usize,
+    #[serde(rename = "type")]
+    kind: String,
+    sparse: Option<Sparse>,
+}
+
+/// Values of an accessor that differ from its buffer view (or from zero)
+#[derive(Debug, Deserialize)]
+struct Sparse {
+    count: usize,
+    indices: SparseIndices,
+    values: SparseValues,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SparseIndices {
+    buffer_view: usize,
+    #[serde(default)]
+    byte_offset: usize,
+    component_type: u32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SparseValues {
+    buffer_view: usize,
+    #[serde(default)]
+    byte_offset: usize,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BufferView {
+    buffer: usize,
+    #[serde(default)]
+    byte_offset: usize,
+    byte_length: usize,
+    byte_stride: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Buffer {
+    uri: Option<String>,
+    byte_length: usize,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct DocMaterial {
+    name: Option<String>,
+    pbr_metallic_roughness: PbrMetallicRoughness,
+    normal_texture: Option<TextureInfo>,
+    emissive_texture: Option<TextureInfo>,
+    emissive_factor: [f32; 3],
+    extensions: MaterialExtensions,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct PbrMetallicRoughness {
+    base_color_factor: [f32; 4],
+    base_color_texture: Option<TextureInfo>,
+    metallic_factor: f32,
+    roughness_factor: f32,
+    metallic_roughness_texture: Option<TextureInfo>,
+}
+
+impl Default for PbrMetallicRoughness {
+    fn default() -> Self {
+        Self {
+            base_color_factor: [1.0; 4],
+            base_color_texture: None,
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            metallic_roughness_texture: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TextureInfo {
+    index: usize,
+    #[serde(default)]
+    tex_coord: usize,
+    #[serde(default = "TextureInfo::unit")]
+    scale: f32,
+}
+
+impl TextureInfo {
+    const fn unit() -> f32 {
+        1.0
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+struct MaterialExtensions {
+    #[serde(rename = "KHR_materials_ior")]
+    ior: Option<ExtIor>,
+    #[serde(rename = "KHR_materials_transmission")]
+    transmission: Option<ExtTransmission>,
+    #[serde(rename = "KHR_materials_emissive_strength")]
+    emissive_strength: Option<ExtEmissiveStrength>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct ExtIor {
+    ior: f32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExtTransmission {
+    transmission_factor: f32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExtEmissiveStrength {
+    emissive_strength: f32,
+}
+
+#[derive(Debug, Deserialize)]
+struct Texture {
+    source: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DocImage {
+    uri: Option<String>,
+    buffer_view: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DocCamera {
+    name: Option<String>,
+    #[serde(rename = "type")]
+    kind: String,
+    perspective: Option<Perspective>,
+    orthographic: Option<Orthographic>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Perspective {
+    yfov: f32,
+    aspect_ratio: Option<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Orthographic {
+    xmag: f32,
+    ymag: f32,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct DocExtensions {
+    #[serde(rename = "KHR_lights_punctual")]
+    lights: Option<Lights>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Lights {
+    lights: Vec<DocLight>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DocLight {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default = "DocLight::white")]
+    color: [f32; 3],
+    #[serde(default = "DocLight::unit")]
+    intensity: f32,
+    spot: Option<Spot>,
+}
+
+impl DocLight {
+    const fn white() -> [f32; 3] {
+        [1.0; 3]
+    }
+
+    const fn unit() -> f32 {
+        1.0
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Spot {
+    #[serde(default)]
+    inner_cone_angle: f32,
+    #[serde(default = "Spot::outer")]
+    outer_cone_angle: f32,
+}
+
+impl Spot {
+    const fn outer() -> f32 {
+        std::f32::consts::FRAC_PI_4
+    }
+}
+
+/// JSON and binary chunk of a `.glb` file
+fn split_glb(data: &[u8]) -> RResult<(&[u8], Option<&[u8]>)> {
+    const JSON: u32 = 0x4E4F_534A;
+    const BIN: u32 = 0x004E_4942;
+
+    let word = |ofs: usize| -> RResult<u32> {
+        data.get(ofs..ofs + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or_else(|| Error::ParseError("truncated glb file".into()))
+    };
+
+    if word(4)? != 2 {
+        return Err(Error::ParseUnsupported(format!("glb version {}", word(4)?)));
+    }
+
+    let (mut json, mut bin) = (None, None);
+    let mut ofs = 12;
+    while ofs + 8 <= data.len() {
+        let len = word(ofs)? as usize;
+        let chunk = data
+            .get(ofs + 8..ofs + 8 + len)
+            .ok_or_else(|| Error::ParseError("truncated glb chunk".into()))?;
+        match word(ofs + 4)? {
+            JSON => json = json.or(Some(chunk)),
+            BIN => bin = bin.or(Some(chunk)),
+            _ => {}
+        }
+        ofs += 8 + len;
+    }
+
+    let json = json.ok_or_else(|| Error::ParseMissingKey("glb json chunk".into()))?;
+    Ok((json, bin))
+}
+
+/// Vertex attribute `name`, checked to have one value per position
+fn per_vertex<T>(name: &str, values: Vec<T>, count: usize) -> RResult<Vec<T>> {
+    if values.len() != count {
+        return Err(Error::ParseError(format!(
+            "{name} has {} values for {count} vertices",
+            values.len()
+        )));
+    }
+    Ok(values)
+}
+
+fn decode_data_uri(uri: &str) -> Option<RResult<Vec<u8>>> {
+    let (_, data) = uri.strip_prefix("data:")?.split_once(";base64,")?;
+    Some(
+        base64::engine::general_purpose::STANDARD
+            .decode(data)
+            .map_err(|err| Error::ParseError(format!("invalid data uri: {err}"))),
+    )
+}
+
+/// One channel of a metallic/roughness texture, scaled by its factor
+#[derive(Debug)]
+struct Channel<F: Float + Texel> {
+    samp: DynSampler<F, Color<F>>,
+    get: fn(Color<F>) -> F,
+    factor: F,
+}
+
+impl<F: Float + Texel> Sampler<F, F> for Channel<F> {
+    fn sample(&self, uv: Point<F>) -> F {
+        (self.get)(self.samp.sample(uv)) * self.factor
+    }
+
+    fn sample_grad(&self, uv: Point<F>, dx: Point<F>, dy: Point<F>) -> F {
+        (self.get)(self.samp.sample_grad(uv, dx, dy)) * self.factor
+    }
+
+    fn dimensions(&self) -> (u32, u32) {
+        self.samp.dimensions()
+    }
+
+    #[cfg(feature = "gui")]
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str) -> bool {
+        ui.label(name);
+        ui.label("texture");
+        ui.end_row();
+        false
+    }
+}
+
+struct GltfLoader<'a, F: Float + Texel> {
+    doc: Document,
+    resdir: &'a Utf8Path,
+    scene: &'a mut BoxScene<F>,
+    buffers: Vec<Vec<u8>>,
+    materials: HashMap<usize, MaterialId>,
+    images: HashMap<usize, (DynamicImage, Option<Utf8PathBuf>)>,
+    /* Meshes used by more than one node, shared through instances */
+    shared: HashMap<usize, Arc<dyn FiniteGeometry<F>>>,
+    uses: Vec<usize>,
+}
+
+impl<'a, F: Float + Texel> GltfLoader<'a, F> {
+    fn new(
+        json: &[u8],
+        bin: Option<&[u8]>,
+        resdir: &'a Utf8Path,
+        scene: &'a mut BoxScene<F>,
+    ) -> RResult<Self> {
+        let doc: Document = serde_json::from_slice(json)?;
+
+        let mut buffers = vec![];
+        for (idx, buffer) in doc.buffers.iter().enumerate() {
+            let mut data = match (&buffer.uri, bin) {
+                (Some(uri), _) => match decode_data_uri(uri) {
+                    Some(data) => data?,
+                    None => std::fs::read(resdir.join(uri))?,
+                },
+                /* Only the first buffer of a .glb file may refer to its
+                 * binary chunk */
+                (None, Some(bin)) if idx == 0 => bin.to_vec(),
+                (None, _) => return Err(Error::ParseMissingKey(format!("buffer {idx} uri"))),
+            };
+            if data.len() < buffer.byte_length {
+                return Err(Error::ParseError(format!("buffer {idx} is too short")));
+            }
+            data.truncate(buffer.byte_length);
+            buffers.push(data);
+        }
+
+        let mut uses = vec![0; doc.meshes.len()];
+        for node in &doc.nodes {
+            if let Some(count) = node.mesh.and_then(|mesh| uses.get_mut(mesh)) {
+                *count += 1;
+            }
+        }
+
+        Ok(Self {
+            doc,
+            resdir,
+            scene,
+            buffers,
+            materials: HashMap::new(),
+            images: HashMap::new(),
+            shared: HashMap::new(),
+            uses,
+        })
+    }
+
+    fn view(&self, idx: usize) -> RResult<(&[u8], Option<usize>)> {
+        let view = self
+            .doc
+            .buffer_views
+            .get(idx)
+            .ok_or_else(|| Error::ParseMissingKey(format!("buffer view {idx}")))?;
+        let data = self
+            .buffers
+            .get(view.buffer)
+            .and_then(|buf| buf.get(view.byte_offset..view.byte_offset + view.byte_length))
+            .ok_or_else(|| Error::ParseError(format!("buffer view {idx} out of range")))?;
+        Ok((data, view.byte_stride))
+    }
+
+    /// `count` elements with `N` components of type `kind` each, starting at
+    /// `offset` in buffer view `view`, converted to floats (and normalized,
+    /// if `normalized` is set)
+    fn elements<const N: usize>(
+        &self,
+        view: usize,
+        offset: usize,
+        count: usize,
+        kind: u32,
+        normalized: bool,
+    ) -> RResult<Vec<[f32; N]>> {
+        let size = match kind {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            other => return Err(Error::ParseUnsupported(format!("component type {other}"))),
+        };
+
+        let (data, stride) = self.view(view)?;
+        let stride = stride.unwrap_or(size * N);
+
+        let component = |bytes: &[u8]| -> f32 {
+            let (value, max) = match kind {
+                5120 => (f32::from(bytes[0] as i8), 127.0),
+                5121 => (f32::from(bytes[0]), 255.0),
+                5122 => (f32::from(i16::from_le_bytes([bytes[0], bytes[1]])), 32767.0),
+                5123 => (f32::from(u16::from_le_bytes([bytes[0], bytes[1]])), 65535.0),
+                5125 => {
+                    let value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                    return value as f32;
+                }
+                _ => return f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            };
+            if normalized {
+                (value / max).max(-1.0)
+            } else {
+                value
+            }
+        };
+
+        (0..count)
+            .map(|n| {
+                let start = offset + n * stride;
+                let elem = data
+                    .get(start..start + size * N)
+                    .ok_or_else(|| Error::ParseError(format!("buffer view {view} out of range")))?;
+                Ok(std::array::from_fn(|c| component(&elem[c * size..])))
+            })
+            .collect()
+    }
+
+    /// Elements of accessor `idx`, with `N` components each, converted to
+    /// floats (and normalized, if the accessor says so)
+    fn read<const N: usize>(&self, idx: usize) -> RResult<Vec<[f32; N]>> {
+        let acc = self
+            .doc
+            .accessors
+            .get(idx)
+            .ok_or_else(|| Error::ParseMissingKey(format!("accessor {idx}")))?;
+
+        let comps = match acc.kind.as_str() {
+            "SCALAR" => 1,
+            "VEC2" => 2,
+            "VEC3" => 3,
+            "VEC4" => 4,
+            "MAT4" => 16,
+            other => return Err(Error::ParseUnsupported(format!("accessor type {other}"))),
+        };
+        if comps != N {
+            return Err(Error::ParseError(format!(
+                "accessor {idx} has type {}, expected {N} components",
+                acc.kind
+            )));
+        }
+
+        let (kind, normalized) = (acc.component_type, acc.normalized);
+
+        /* Accessors without a buffer view are all zeros */
+        let mut res = match acc.buffer_view {
+            Some(view) => self.elements(view, acc.byte_offset, acc.count, kind, normalized)?,
+            None => vec![[0.0; N]; acc.count],
+        };
+
+        if let Some(sparse) = &acc.sparse {
+            let ind = &sparse.indices;
+            let indices = self.elements::<1>(
+                ind.buffer_view,
+                ind.byte_offset,
+                sparse.count,
+                ind.component_type,
+                false,
+            )?;
+            let val = &sparse.values;
+            let values = self.elements(
+                val.buffer_view,
+                val.byte_offset,
+                sparse.count,
+                kind,
+                normalized,
+            )?;
+
+            for ([index], value) in indices.into_iter().zip(values) {
+                *res.get_mut(index as usize).ok_or_else(|| {
+                    Error::ParseError(format!(
+                        "sparse index {index} out of range in accessor {idx}"
+                    ))
+                })? = value;
+            }
+        }
+
+        Ok(res)
+    }
+
+    fn read_indices(&self, idx: usize) -> RResult<Vec<usize>> {
+        Ok(self.read::<1>(idx)?.iter().map(|i| i[0] as usize).collect())
+    }
+
+    fn image(&mut self, texture: usize) -> RResult<&(DynamicImage, Option<Utf8PathBuf>)> {
+        if !self.images.contains_key(&texture) {
+            let source = self
+                .doc
+                .textures
+                .get(texture)
+                .and_then(|tex| tex.source)
+                .and_then(|src| self.doc.images.get(src))
+                .ok_or_else(|| Error::ParseMissingKey(format!("texture {texture} image")))?;
+
+            let res = match (&source.uri, source.buffer_view) {
+                (Some(uri), _) => {
+                    if let Some(data) = decode_data_uri(uri) {
+                        (image::load_from_memory(&data?)?, None)
+                    } else {
+                        let path = self.resdir.join(uri);
+                        info!("Loading [{path}]");
+                        (image::open(&path)?, Some(path))
+                    }
+                }
+                (None, Some(view)) => (image::load_from_memory(self.view(view)?.0)?, None),
+                (None, None) => {
+                    return Err(Error::ParseMissingKey(format!("texture {texture} data")))
+                }
+            };
+            self.images.insert(texture, res);
+        }
+        Ok(&self.images[&texture])
+    }
+
+    /// Sampler for `info`, or `None` (with a warning) if the texture can not
+    /// be used
+    fn sampler3(&mut self, info: Option<&TextureInfo>) -> Option<DynSampler<F, Color<F>>> {
+        let info = info?;
+        if info.tex_coord != 0 {
+            warn!("Ignoring texture on TEXCOORD_{}", info.tex_coord);
+            return None;
+        }
+        match self.image(info.index) {
+            Ok((img, Some(path))) => {
+                Some(FileSampler::new(path.clone(), img.clone().mipmap()).dynsampler())
+            }
+            Ok((img, None)) => Some(img.clone().mipmap().dynsampler()),
+            Err(err) => {
+                warn!("Missing texture {}: {err}", info.index);
+                None
+            }
+        }
+    }
+
+    /// Material for a glTF metallic/roughness material.
+    ///
+    /// Materials with a metallic/roughness texture load as [`Pbr`], reading
+    /// roughness from the green and metallic from the blue channel (scaled by
+    /// their factors). Others get the closest [`Smart`] material, where metals
+    /// reflect their base color and rough surfaces get a wider specular
+    /// highlight, with normal maps applied through a [`Bumpmap`].
+    fn material(&mut self, idx: Option<usize>) -> MaterialId {
+        let Some(idx) = idx else {
+            return self.scene.materials.default();
+        };
+        if let Some(id) = self.materials.get(&idx) {
+            return *id;
+        }
+
+        let mat = self.doc.materials.get(idx).cloned().unwrap_or_default();
+        let pbr = &mat.pbr_metallic_roughness;
+
+        let [r, g, b, _] = pbr.base_color_factor;
+        let base = Color::from([r, g, b]);
+        let metallic = F::from_f32(pbr.metallic_factor.clamp(0.0, 1.0));
+        let roughness = F::from_f32(pbr.roughness_factor.clamp(0.0, 1.0));
+
+        /* Phong exponent for a lobe about as wide as the microfacet one */
+        let alpha = (roughness * roughness).max(F::from_f32(0.01));
+        let pow = (F::TWO / (alpha * alpha) - F::TWO).clamp(F::ONE, F::from_u32(1000));
+
+        let strength = mat
+            .extensions
+            .emissive_strength
+            .as_ref()
+            .map_or(1.0, |ext| ext.emissive_strength);
+        let ke = Color::from(mat.emissive_factor) * F::from_f32(strength);
+
+        let transmission = mat
+            .extensions
+            .transmission
+            .as_ref()
+            .map_or(0.0, |ext| ext.transmission_factor);
+        let name = mat.name.clone().unwrap_or_else(|| format!("material{idx}"));
+
+        /* Textured metallic/roughness maps directly onto [`Pbr`], which has
+         * no transmission, so transmissive materials stay on [`Smart`] */
+        if transmission <= 0.0 {
+            if let Some(mr) = self.sampler3(pbr.metallic_roughness_texture.as_ref()) {
+                let color = self
+                    .sampler3(pbr.base_color_texture.as_ref())
+                    .unwrap_or_else(|| base.dynsampler());
+                let metallic = Channel {
+                    samp: mr.clone(),
+                    get: |c| c.b,
+                    factor: metallic,
+                };
+                let roughness = Channel {
+                    samp: mr,
+                    get: |c| c.g,
+                    factor: roughness,
+                };
+                let normal = self
+                    .sampler3(mat.normal_texture.as_ref())
+                    .map(NormalMap::new);
+                let pbr = Pbr::new(color, metallic, roughness, normal).with_ke(ke);
+
+                let id = self
+                    .scene
+                    .materials
+                    .insert(Box::new(NamedObject::new(name, pbr)));
+                self.materials.insert(idx, id);
+                return id;
+            }
+        }
+
+        let phong = Phong::<F, _, _, Color<F>, F>::new()
+            .with_kd(base * (F::ONE - metallic))
+            .with_kd_map(self.sampler3(pbr.base_color_texture.as_ref()))
+            .with_ks(Color::WHITE.lerp(base, metallic) * (F::ONE - roughness))
+            .with_pow(pow)
+            .with_ke(ke)
+            .with_ke_map(self.sampler3(mat.emissive_texture.as_ref()));
+
+        let ior = mat.extensions.ior.as_ref().map_or(1.5, |ext| ext.ior);
+
+        let fresnel = Fresnel::new(
+            F::from_f32(ior),
+            base * F::from_f32(transmission),
+            base * metallic * (F::ONE - roughness),
+        );
+
+        let smart = Smart::make(phong, fresnel);
+
+        let res: BoxMaterial<F> = match self.sampler3(mat.normal_texture.as_ref()) {
+            Some(normal) => {
+                let scale = mat.normal_texture.as_ref().map_or(1.0, |info| info.scale);
+                let bump = Bumpmap::new(
+                    BumpPower(F::from_f32(scale.max(0.01))),
+                    NormalMap::new(normal),
+                    smart,
+                );
+                Box::new(NamedObject::new(name, bump))
+            }
+            None => Box::new(NamedObject::new(name, smart)),
+        };
+
+        let id = self.scene.materials.insert(res);
+        self.materials.insert(idx, id);
+        id
+    }
+
+    /// Read vertex colours, which may be RGB or RGBA (alpha is ignored)
+    fn read_colors(&self, idx: usize) -> RResult<Vec<Color<F>>> {
+        let rgba = self
+            .doc
+            .accessors
+            .get(idx)
+            .is_some_and(|acc| acc.kind == "VEC4");
+        if rgba {
+            Ok(self
+                .read::<4>(idx)?
+                .into_iter()
+                .map(|[r, g, b, _]| Color::from([r, g, b]))
+                .collect())
+        } else {
+            Ok(self.read::<3>(idx)?.into_iter().map(Color::from).collect())
+        }
+    }
+
+    fn primitive(&mut self, prim: &Primitive, tris: &mut Vec<Triangle<F>>) -> RResult<()> {
+        let Some(&pos) = prim.attributes.get("POSITION") else {
+            return Err(Error::ParseMissingKey("POSITION".into()));
+        };
+
+        let points: Vec<Vector<F>> = self
+            .read::<3>(pos)?
+            .into_iter()
+            .map(Vector::from_f32s)
+            .collect();
+        let count = points.len();
+        let normals = match prim.attributes.get("NORMAL") {
+            Some(&idx) => Some(per_vertex("NORMAL", self.read::<3>(idx)?, count)?),
+            None => None,
+        };
+        let uvs = match prim.attributes.get("TEXCOORD_0") {
+            Some(&idx) => Some(per_vertex("TEXCOORD_0", self.read::<2>(idx)?, count)?),
+            None => None,
+        };
+        let colors = match prim.attributes.get("COLOR_0") {
+            Some(&idx) => Some(per_vertex("COLOR_0", self.read_colors(idx)?, count)?),
+            None => None,
+        };
+        let indices = match prim.indices {
+            Some(idx) => self.read_indices(idx)?,
+            None => (0..points.len()).collect(),
+        };
+
+        let faces: Vec<[usize; 3]> = match prim.mode {
+            Primitive::TRIANGLES => indices
+                .chunks_exact(3)
+                .map(|f| [f[0], f[1], f[2]])
+                .collect(),
+            Primitive::TRIANGLE_STRIP => indices
+                .windows(3)
+                .enumerate()
+                .map(|(n, f)| {
+                    if n % 2 == 0 {
+                        [f[0], f[1], f[2]]
+                    } else {
+                        [f[1], f[0], f[2]]
+                    }
+                })
+                .collect(),
+            Primitive::TRIANGLE_FAN => indices
+                .iter()
+                .skip(1)
+                .zip(indices.iter().skip(2))
+                .map(|(b, c)| [indices[0], *b, *c])
+                .collect(),
+            mode => {
+                warn!("Skipping primitive with mode {mode} (not triangles)");
+                return Ok(());
+            }
+        };
+
+        let mat = self.material(prim.material);
+        let mut degenerate = 0;
+
+        for [a, b, c] in faces {
+            if [a, b, c].iter().any(|&i| i >= points.len()) {
+                return Err(Error::ParseError(format!(
+                    "vertex index out of range in {a}, {b}, {c}"
+                )));
+            }
+            let (pa, pb, pc) = (points[a], points[b], points[c]);
+
+            /* Triangles without area can not be hit, and have no normal */
+            let face = (pb - pa).cross(pc - pa);
+            let area2 = face.magnitude2();
+            if area2.is_nan() || area2 <= F::ZERO {
+                degenerate += 1;
+                continue;
+            }
+            let face = face.normalize();
+
+            /* Zero vertex normals fall back to the face normal */
+            let (na, nb, nc) = normals.as_ref().map_or((face, face, face), |nml| {
+                let n = |i: usize| {
+                    let n = Vector::from_f32s(nml[i]);
+                    if n.magnitude2() > F::ZERO {
+                        n.normalize()
+                    } else {
+                        face
+                    }
+                };
+                (n(a), n(b), n(c))
+            });
+
+            let (ta, tb, tc) = uvs
+                .as_ref()
+                .map_or((Point::ZERO, Point::ZERO, Point::ZERO), |uv| {
+                    (uv[a].into(), uv[b].into(), uv[c].into())
+                });
+
+            let mut tri = Triangle::new(pa, pb, pc, na, nb, nc, ta, tb, tc, mat);
+            if let Some(col) = &colors {
+                tri = tri.with_colors(col[a], col[b], col[c]);
+            }
+            tris.push(tri);
+        }
+
+        if degenerate > 0 {
+            warn!("Skipped {degenerate} degenerate triangle(s)");
+        }
+        Ok(())
+    }
+
+    /// Geometry for mesh `idx`, or `None` if it has no usable triangles
+    fn mesh(&mut self, idx: usize) -> RResult<Option<Box<dyn FiniteGeometry<F>>>> {
+        if let Some(geo) = self.shared.get(&idx) {
+            return Ok(Some(Box::new(Instance::new(
+                geo.clone(),
+                Matrix4::identity(),
+            ))));
+        }
+
+        let mesh = self
+            .doc
+            .meshes
+            .get_mut(idx)
+            .map(std::mem::take)
+            .ok_or_else(|| Error::ParseMissingKey(format!("mesh {idx}")))?;
+
+        let mut tris = vec![];
+        for prim in &mesh.primitives {
+            self.primitive(prim, &mut tris)?;
+        }
+        let name = mesh.name.clone().unwrap_or_else(|| format!("mesh{idx}"));
+        self.doc.meshes[idx] = mesh;
+
+        if tris.is_empty() {
+            warn!("Skipping mesh [{name}] without triangles");
+            return Ok(None);
+        }
+
+        let mesh = NamedObject::new(name, TriangleMesh::new(tris, Matrix4::identity()));
+
+        if self.uses[idx] > 1 {
+            let geo: Arc<dyn FiniteGeometry<F>> = Arc::new(mesh);
+            self.shared.insert(idx, geo.clone());
+            return Ok(Some(Box::new(Instance::new(geo, Matrix4::identity()))));
+        }
+
+        Ok(Some(Box::new(mesh)))
+    }
+
+    fn node_matrix(node: &Node) -> Matrix4<F> {
+        let m = |v: f32| F::from_f32(v);
+
+        if let Some(mat) = node.matrix {
+            let col = |c: usize| {
+                Vector4::new(
+                    m(mat[c * 4]),
+                    m(mat[c * 4 + 1]),
+                    m(mat[c * 4 + 2]),
+                    m(mat[c * 4 + 3]),
+                )
+            };
+            return Matrix4::from_cols(col(0), col(1), col(2), col(3));
+        }
+
+        let [tx, ty, tz] = node.translation.unwrap_or([0.0; 3]);
+        let [x, y, z, w] = node.rotation.unwrap_or([0.0, 0.0, 0.0, 1.0]);
+        let [sx, sy, sz] = node.scale.unwrap_or([1.0; 3]);
+
+        Matrix4::from_translation(Vector::new(m(tx), m(ty), m(tz)))
+            * Matrix4::from(Quaternion::new(m(w), m(x), m(y), m(z)))
+            * Matrix4::from_nonuniform_scale(m(sx), m(sy), m(sz))
+    }
+
+    fn camera(&mut self, idx: usize, name: Option<&String>, world: &Matrix4<F>) {
+        let Some(cam) = self.doc.cameras.get(idx) else {
+            warn!("Missing camera {idx}");
+            return;
+        };
+
+        /* Cameras look down their -Z axis, with +Y up */
+        let pos = world.w.truncate();
+        let dir = world.transform_vector(-Vector::UNIT_Z);
+        let updir = world.transform_vector(Vector::UNIT_Y);
+
+        let camera = match (cam.kind.as_str(), &cam.perspective, &cam.orthographic) {
+            ("perspective", Some(p), _) => {
+                let fov = Deg::from(Rad(F::from_f32(p.yfov))).0;
+                let aspect = F::from_f32(p.aspect_ratio.unwrap_or(1.0));
+                Camera::build(pos, dir, updir, fov, aspect)
+            }
+            ("orthographic", _, Some(o)) => {
+                /* The view height is tan(fov / 2) at the focal distance */
+                let fov = Deg::from(Rad(F::from_f32(o.ymag.atan() * 2.0))).0;
+                let aspect = F::from_f32(o.xmag / o.ymag);
+                Camera::build(pos, dir, updir, fov, aspect)
+                    .with_lens(Lens::new(F::ZERO, F::ONE))
+                    .with_projection(Projection::Orthographic)
+            }
+            (kind, _, _) => {
+                warn!("Skipping camera {idx} of type {kind:?}");
+                return;
+            }
+        };
+
+        let name = name
+            .or(cam.name.as_ref())
+            .cloned()
+            .unwrap_or_else(|| format!("camera{idx}"));
+        self.scene.add_camera(camera.with_name(name));
+    }
+
+    /// Add light `idx` at `world`. Intensities are used as they are, with
+    /// inverse square falloff for point and spot lights.
+    fn light(&mut self, idx: usize, world: &Matrix4<F>) {
+        let Some(light) = self
+            .doc
+            .extensions
+            .lights
+            .as_ref()
+            .and_then(|l| l.lights.get(idx))
+        else {
+            warn!("Missing light {idx}");
+            return;
+        };
+
+        let pos = world.w.truncate();
+        let dir = world.transform_vector(-Vector::UNIT_Z).normalize();
+        let color = Color::from(light.color) * F::from_f32(light.intensity);
+        let attn = Attenuation::inverse_square();
+
+        match light.kind.as_str() {
+            "point" => self.scene.add_light(PointLight::new(pos, attn, color)),
+            "directional" => self.scene.add_light(DirectionalLight::new(dir, color)),
+            "spot" => {
+                let (inner, outer) = light.spot.as_ref().map_or((0.0, Spot::outer()), |spot| {
+                    (spot.inner_cone_angle, spot.outer_cone_angle)
+                });
+                self.scene.add_light(SpotLight {
+                    attn,
+                    umbra: Rad(F::from_f32(inner)),
+                    penumbra: Rad(F::from_f32(outer)),
+                    pos,
+                    dir,
+                    color,
+                });
+            }
+            kind => warn!("Skipping light {idx} of type {kind:?}"),
+        }
+    }
+
+    /// Geometry for node `idx` and its children, or `None` if there is none.
+    /// Cameras and lights are added to the scene along the way.
+    fn node(
+        &mut self,
+        idx: usize,
+        parent: &Matrix4<F>,
+        depth: usize,
+    ) -> RResult<Option<Box<dyn FiniteGeometry<F>>>> {
+        if depth > 256 {
+            return Err(Error::ParseError(
+                "node hierarchy is too deep (cyclic?)".into(),
+            ));
+        }
+
+        let node = self
+            .doc
+            .nodes
+            .get_mut(idx)
+            .map(std::mem::take)
+            .ok_or_else(|| Error::ParseMissingKey(format!("node {idx}")))?;
+
+        let local = Self::node_matrix(&node);
+        let world = parent * local;
+
+        if let Some(cam) = node.camera {
+            self.camera(cam, node.name.as_ref(), &world);
+        }
+        if let Some(light) = &node.extensions.light {
+            self.light(light.light, &world);
+        }
+
+        let mut geo = vec![];
+        if let Some(mesh) = node.mesh {
+            geo.extend(self.mesh(mesh)?);
+        }
+        for child in &node.children {
+            geo.extend(self.node(*child, &world, depth + 1)?);
+        }
+
+        let name = node.name.clone().unwrap_or_else(|| format!("node{idx}"));
+        self.doc.nodes[idx] = node;
+
+        if geo.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Box::new(NamedObject::new(
+            name,
+            Group::new(geo, local),
+        ))))
+    }
+
+    fn load(mut self, name: String) -> RResult<GltfGroup<F>> {
+        let scene = self.doc.scene.unwrap_or(0);
+        let roots = if let Some(scene) = self.doc.scenes.get(scene) {
+            scene.nodes.clone()
+        } else {
+            /* Without scenes, every node which is not a child is a root */
+            let children: Vec<usize> = self
+                .doc
+                .nodes
+                .iter()
+                .flat_map(|n| n.children.clone())
+                .collect();
+            (0..self.doc.nodes.len())
+                .filter(|n| !children.contains(n))
+                .collect()
+        };
+
+        let mut geo = vec![];
+        for root in roots {
+            geo.extend(self.node(root, &Matrix4::identity(), 0)?);
+        }
+
+        info!(
+            "loaded gltf file: {} node(s), {} mesh(es), {} material(s)",
+            self.doc.nodes.len(),
+            self.doc.meshes.len(),
+            self.materials.len()
+        );
+
+        Ok(NamedObject::new(name, Group::new(geo, Matrix4::identity())))
+    }
+}
+
+/// All geometry from a glTF file
+pub type GltfGroup<F> = NamedObject<Group<F, Box<dyn FiniteGeometry<F>>>>;
+
+/// Load the scene in a `.gltf` or `.glb` file (in memory), with external
+/// files relative to `resdir`
+pub fn load_data<F: Float + Texel>(
+    data: &[u8],
+    resdir: &Utf8Path,
+    name: String,
+    scene: &mut BoxScene<F>,
+) -> RResult<GltfGroup<F>> {
+    let (json, bin) = if data.starts_with(b"glTF") {
+        split_glb(data)?
+    } else {
+        (data, None)
+    };
+    GltfLoader::new(json, bin, resdir, scene)?.load(name)
+}
+
+/// Load the glTF file at `path` into `scene`, with its cameras and lights
+pub fn load<F: Float + Texel>(path: &Utf8Path, scene: &mut BoxScene<F>) -> RResult<()> {
+    let data = std::fs::read(path)?;
+    let resdir = path.parent().unwrap_or_else(|| Utf8Path::new("."));
+    let name = path.file_name().unwrap_or_default().to_string();
+    let group = load_data(&data, resdir, name, scene)?;
+    scene.add_object(group);
+    scene.recompute_bvh()
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::Engine;
+    use camino::Utf8Path;
+    use cgmath::MetricSpace;
+
+    use super::load_data;
+    use crate::light::Attenuation;
+    use crate::scene::BoxScene;
+    use crate::types::{Color, Error, Ray, Vector, Vectorx};
+
+    /// Triangle in the XY plane, placed by a node at z = -2, with a camera
+    /// and a point light
+    fn document() -> (String, Vec<u8>) {
+        let mut bin = vec![];
+        for v in [[-1.0f32, -1.0, 0.0], [1.0, -1.0, 0.0], [0.0, 1.0, 0.0]] {
+            bin.extend(v.iter().flat_map(|c| c.to_le_bytes()));
+        }
+        bin.extend([0u16, 1, 2, 0].iter().flat_map(|i| i.to_le_bytes()));
+
+        let json = r#"{
+            "asset": { "version": "2.0" },
+            "scene": 0,
+            "scenes": [ { "nodes": [0, 1, 2] } ],
+            "nodes": [
+                { "name": "tri", "mesh": 0, "translation": [0, 0, -2] },
+                { "name": "cam", "camera": 0, "translation": [0, 0, 5] },
+                { "extensions": { "KHR_lights_punctual": { "light": 0 } }, "translation": [0, 3, 0] }
+            ],
+            "meshes": [ { "primitives": [ { "attributes": { "POSITION": 0 }, "indices": 1, "material": 0 } ] } ],
+            "materials": [ { "pbrMetallicRoughness": { "baseColorFactor": [1, 0, 0, 1], "metallicFactor": 0 } } ],
+            "cameras": [ { "type": "perspective", "perspective": { "yfov": 0.8, "znear": 0.1 } } ],
+            "extensions": { "KHR_lights_punctual": { "lights": [ { "type": "point", "intensity": 10 } ] } },
+            "accessors": [
+                { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" },
+                { "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }
+            ],
+            "bufferViews": [
+                { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+                { "buffer": 0, "byteOffset": 36, "byteLength": 6 }
+            ],
+            "buffers": [ { BUFFER "byteLength": 44 } ]
+        }"#;
+
+        (json.to_string(), bin)
+    }
+
+    fn check(scene: &mut BoxScene<f64>) {
+        scene.recompute_bvh().unwrap();
+
+        assert_eq!(scene.cameras.len(), 1);
+        assert_eq!(scene.lights.len(), 1);
+        assert!(
+            scene.cameras[0]
+                .position()
+                .distance(Vector::new(0.0, 0.0, 5.0))
+                < 1e-6
+        );
+
+        let ray = Ray::new(Vector::new(0.0, 0.0, 5.0), -Vector::UNIT_Z);
+        let hit = scene.intersect(&ray).unwrap();
+        assert!(hit.pos.distance(Vector::new(0.0, 0.0, -2.0)) < 1e-6);
+
+        let ray = Ray::new(Vector::new(3.0, 0.0, 5.0), -Vector::UNIT_Z);
+        assert!(scene.intersect(&ray).is_none());
+    }
+
+    /// `json` with `bin` embedded as a data uri
+    fn with_buffer(json: &str, bin: &[u8]) -> String {
+        let uri = base64::engine::general_purpose::STANDARD.encode(bin);
+        json.replace(
+            "BUFFER",
+            &format!(r#""uri": "data:application/octet-stream;base64,{uri}","#),
+        )
+    }
+
+    /// Load `json` with `bin` embedded as a data uri
+    fn embedded(json: &str, bin: &[u8]) -> BoxScene<f64> {
+        let json = with_buffer(json, bin);
+        let mut scene = BoxScene::empty();
+        let group = load_data(
+            json.as_bytes(),
+            Utf8Path::new("."),
+            "test".into(),
+            &mut scene,
+        );
+        scene.add_object(group.unwrap());
+        scene
+    }
+
+    /// Replace the third vertex with one on the line through the first two
+    fn flatten(bin: &mut [u8]) {
+        for (i, c) in [0.0f32, -1.0, 0.0].iter().enumerate() {
+            bin[24 + i * 4..28 + i * 4].copy_from_slice(&c.to_le_bytes());
+        }
+    }
+
+    #[test]
+    fn test_gltf_embedded() {
+        let (json, bin) = document();
+        let mut scene = embedded(&json, &bin);
+        check(&mut scene);
+    }
+
+    #[test]
+    fn test_gltf_sparse() {
+        let (json, mut bin) = document();
+        flatten(&mut bin);
+
+        /* Sparse accessor restores the third vertex */
+        bin.extend([2u16, 0].iter().flat_map(|i| i.to_le_bytes()));
+        bin.extend([0.0f32, 1.0, 0.0].iter().flat_map(|c| c.to_le_bytes()));
+
+        let json = json
+            .replace(
+                r#""count": 3, "type": "VEC3" }"#,
+                r#""count": 3, "type": "VEC3", "sparse": {
+                    "count": 1,
+                    "indices": { "bufferView": 2, "componentType": 5123 },
+                    "values": { "bufferView": 3 } } }"#,
+            )
+            .replace(
+                r#"{ "buffer": 0, "byteOffset": 36, "byteLength": 6 }"#,
+                r#"{ "buffer": 0, "byteOffset": 36, "byteLength": 6 },
+                { "buffer": 0, "byteOffset": 44, "byteLength": 2 },
+                { "buffer": 0, "byteOffset": 48, "byteLength": 12 }"#,
+            )
+            .replace(r#""byteLength": 44 }"#, r#""byteLength": 60 }"#);
+
+        let mut scene = embedded(&json, &bin);
+        check(&mut scene);
+    }
+
+    #[test]
+    fn test_gltf_degenerate() {
+        let (json, mut bin) = document();
+        flatten(&mut bin);
+
+        /* Degenerate triangles are skipped rather than producing NaN normals */
+        let mut scene = embedded(&json, &bin);
+        scene.recompute_bvh().unwrap();
+        let ray = Ray::new(Vector::new(0.01, -0.99, 5.0), -Vector::UNIT_Z);
+        assert!(scene.intersect(&ray).is_none());
+    }
+
+    #[test]
+    fn test_gltf_zero_normals() {
+        let (json, mut bin) = document();
+        bin.extend([0u8; 36]);
+
+        let json = json
+            .replace(
+                r#""attributes": { "POSITION": 0 }"#,
+                r#""attributes": { "POSITION": 0, "NORMAL": 2 }"#,
+            )
+            .replace(
+                r#"{ "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }"#,
+                r#"{ "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" },
+                { "bufferView": 2, "componentType": 5126, "count": 3, "type": "VEC3" }"#,
+            )
+            .replace(
+                r#"{ "buffer": 0, "byteOffset": 36, "byteLength": 6 }"#,
+                r#"{ "buffer": 0, "byteOffset": 36, "byteLength": 6 },
+                { "buffer": 0, "byteOffset": 44, "byteLength": 36 }"#,
+            )
+            .replace(r#""byteLength": 44 }"#, r#""byteLength": 80 }"#);
+
+        /* Zero normals fall back to the face normal */
+        let mut scene = embedded(&json, &bin);
+        check(&mut scene);
+        let ray = Ray::new(Vector::new(0.01, 0.01, 5.0), -Vector::UNIT_Z);
+        let nml = scene.intersect(&ray).unwrap().nml();
+        assert!((nml.z.abs() - 1.0).abs() < 1e-6, "{nml:?}");
+    }
+
+    #[test]
+    fn test_gltf_short_attribute() {
+        let (json, mut bin) = document();
+        bin.extend([0u8; 24]);
+
+        let json = json
+            .replace(
+                r#""attributes": { "POSITION": 0 }"#,
+                r#""attributes": { "POSITION": 0, "NORMAL": 2 }"#,
+            )
+            .replace(
+                r#"{ "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }"#,
+                r#"{ "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" },
+                { "bufferView": 2, "componentType": 5126, "count": 2, "type": "VEC3" }"#,
+            )
+            .replace(
+                r#"{ "buffer": 0, "byteOffset": 36, "byteLength": 6 }"#,
+                r#"{ "buffer": 0, "byteOffset": 36, "byteLength": 6 },
+                { "buffer": 0, "byteOffset": 44, "byteLength": 24 }"#,
+            )
+            .replace(r#""byteLength": 44 }"#, r#""byteLength": 68 }"#);
+
+        /* Too few normals is an error, not an out of bounds panic */
+        let json = with_buffer(&json, &bin);
+        let mut scene = BoxScene::<f64>::empty();
+        let res = load_data(
+            json.as_bytes(),
+            Utf8Path::new("."),
+            "test".into(),
+            &mut scene,
+        );
+        assert!(matches!(res, Err(Error::ParseError(msg)) if msg.starts_with("NORMAL")));
+    }
+
+    #[test]
+    fn test_gltf_light_falloff() {
+        /* Punctual lights fall off with the inverse square of distance */
+        let att = Attenuation::<f64>::inverse_square();
+        for d in [0.5, 1.0, 2.0, 10.0] {
+            let color = att.attenuate(Color::WHITE, d, d * d);
+            assert!((color.r - 1.0 / (d * d)).abs() < 1e-9, "{d}: {color:?}");
+        }
+    }
+
+    #[test]
+    fn test_gltf_binary() {
+        let (json, mut bin) = document();
+        let mut json = json.replace("BUFFER", "").into_bytes();
+
+        /* Chunks are padded to 4 bytes */
+        json.resize(json.len().next_multiple_of(4), b' ');
+        bin.resize(bin.len().next_multiple_of(4), 0);
+
+        let mut glb = vec![];
+        let word =
+            |glb: &mut Vec<u8>, w: usize| glb.extend(u32::try_from(w).unwrap().to_le_bytes());
+        glb.extend(b"glTF");
+        word(&mut glb, 2);
+        word(&mut glb, 12 + 8 + json.len() + 8 + bin.len());
+        word(&mut glb, json.len());
+        glb.extend(b"JSON");
+        glb.extend(&json);
+        word(&mut glb, bin.len());
+        glb.extend(b"BIN\0");
+        glb.extend(&bin);
+
+        let mut scene = BoxScene::empty();
+        let group = load_data(&glb, Utf8Path::new("."), "test".into(), &mut scene);
+        scene.add_object(group.unwrap());
+        check(&mut scene);
+    }
+}
@@ -0,0 +1,285 @@
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::types::{Error, Float, RResult};
+
+/// Photometric profile of a light fixture, from an IES (LM-63) file.
+///
+/// Holds the luminous intensity (in candela) measured at a grid of angles:
+/// vertical angles are measured from the aiming direction of the fixture
+/// (0 is straight down for ceiling lights), and horizontal angles go around
+/// it. Only type C photometry (used by almost all architectural fixtures) is
+/// supported.
+#[derive(Clone, Debug)]
+pub struct IesProfile<F: Float> {
+    vertical: Vec<F>,
+    horizontal: Vec<F>,
+    /// Candela values, for each horizontal angle in turn
+    candela: Vec<F>,
+    max: F,
+    source: Option<Utf8PathBuf>,
+}
+
+/// Index of the interval in `angles` that holds `angle`, and the position
+/// within it, or `None` if it is outside of `angles`
+fn locate<F: Float>(angles: &[F], angle: F) -> Option<(usize, F)> {
+    let last = angles.len() - 1;
+    if last == 0 {
+        return Some((0, F::ZERO));
+    }
+    if angle < angles[0] || angle > angles[last] {
+        return None;
+    }
+
+    let idx = angles
+        .partition_point(|a| *a <= angle)
+        .saturating_sub(1)
+        .min(last - 1);
+    let span = angles[idx + 1] - angles[idx];
+    let frac = if span > F::ZERO {
+        (angle - angles[idx]) / span
+    } else {
+        F::ZERO
+    };
+    Some((idx, frac.clamp(F::ZERO, F::ONE)))
+}
+
+impl<F: Float> IesProfile<F> {
+    pub fn load(path: &Utf8Path) -> RResult<Self> {
+        let text = std::fs::read(path)?;
+        /* Many files are written in latin-1, but only ascii is needed */
+        let text = String::from_utf8_lossy(&text);
+        Ok(Self {
+            source: Some(path.to_path_buf()),
+            ..Self::parse(&text)?
+        })
+    }
+
+    pub fn parse(text: &str) -> RResult<Self> {
+        let mut lines = text.lines();
+
+        /* Skip the version and keyword lines, up to the tilt specification */
+        let tilt = lines
+            .by_ref()
+            .find_map(|line| line.trim().strip_prefix("TILT="))
+            .ok_or_else(|| Error::ParseError("ies: missing TILT= line".into()))?
+            .trim()
+            .to_string();
+
+        let mut numbers = lines
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|word| !word.is_empty())
+            .map(|word| {
+                word.parse::<f64>()
+                    .map(F::from_f64)
+                    .map_err(|_| Error::ParseError(format!("ies: invalid number {word:?}")))
+            });
+        let mut next = || {
+            numbers
+                .next()
+                .unwrap_or_else(|| Err(Error::ParseError("ies: unexpected end of file".into())))
+        };
+        let count = |value: F| {
+            value
+                .to_usize()
+                .ok_or_else(|| Error::ParseError("ies: invalid count".into()))
+        };
+
+        /* Lamp to luminaire geometry (1 to 3), then the number of tilt
+         * angles, the angles and their multiplying factors (not used) */
+        if tilt == "INCLUDE" {
+            let geometry = next()?;
+            if ![F::ONE, F::TWO, F::from_u32(3)].contains(&geometry) {
+                return Err(Error::ParseError(format!(
+                    "ies: invalid lamp to luminaire geometry {geometry}"
+                )));
+            }
+            let pairs = count(next()?)?;
+            for _ in 0..pairs * 2 {
+                next()?;
+            }
+        } else if tilt != "NONE" {
+            warn!("ies: ignoring tilt file {tilt}");
+        }
+
+        let _lamps = next()?;
+        let _lumens = next()?;
+        let multiplier = next()?;
+        let nv = count(next()?)?;
+        let nh = count(next()?)?;
+        let kind = next()?;
+        let _units = next()?;
+        let _size = [next()?, next()?, next()?];
+        let ballast = next()?;
+        let _future = next()?;
+        let _watts = next()?;
+
+        if kind != F::ONE {
+            return Err(Error::ParseUnsupported(format!(
+                "ies: photometric type {kind} (only type C is supported)"
+            )));
+        }
+        if nv == 0 || nh == 0 {
+            return Err(Error::ParseError("ies: empty candela table".into()));
+        }
+
+        let vertical = (0..nv).map(|_| next()).collect::<RResult<Vec<F>>>()?;
+        let horizontal = (0..nh).map(|_| next()).collect::<RResult<Vec<F>>>()?;
+        let candela = (0..nv * nh)
+            .map(|_| next().map(|cd| cd * multiplier * ballast))
+            .collect::<RResult<Vec<F>>>()?;
+
+        let max = candela.iter().copied().fold(F::ZERO, F::max);
+
+        Ok(Self {
+            vertical,
+            horizontal,
+            candela,
+            max,
+            source: None,
+        })
+    }
+
+    /// File the profile was loaded from, if any
+    #[must_use]
+    pub fn source(&self) -> Option<&Utf8Path> {
+        self.source.as_deref()
+    }
+
+    /// Highest intensity in the profile, in candela
+    #[must_use]
+    pub const fn max(&self) -> F {
+        self.max
+    }
+
+    /// Horizontal angle `phi` (in degrees), folded into the range covered
+    /// by the profile, according to its symmetry
+    fn fold(&self, phi: F) -> F {
+        let deg = |d: u32| F::from_u32(d);
+        let mut phi = phi % deg(360);
+        if phi < F::ZERO {
+            phi += deg(360);
+        }
+
+        let first = self.horizontal[0];
+        let last = self.horizontal[self.horizontal.len() - 1];
+        if last <= deg(90) {
+            /* Symmetric in each quadrant */
+            if phi > deg(180) {
+                phi = deg(360) - phi;
+            }
+            if phi > deg(90) {
+                phi = deg(180) - phi;
+            }
+        } else if last <= deg(180) && phi > deg(180) {
+            /* Symmetric about the 0-180 degree plane */
+            phi = deg(360) - phi;
+        } else if first >= deg(90) && last <= deg(270) && (phi < deg(90) || phi > deg(270)) {
+            /* Symmetric about the 90-270 degree plane */
+            phi = deg(180) - phi;
+            if phi < F::ZERO {
+                phi += deg(360);
+            }
+        }
+        phi
+    }
+
+    /// Intensity in candela, at vertical angle `theta` and horizontal angle
+    /// `phi` (both in degrees)
+    #[must_use]
+    pub fn candela(&self, theta: F, phi: F) -> F {
+        let nv = self.vertical.len();
+        let Some((v, fv)) = locate(&self.vertical, theta) else {
+            return F::ZERO;
+        };
+        let Some((h, fh)) = locate(&self.horizontal, self.fold(phi)) else {
+            return F::ZERO;
+        };
+
+        let value = |h: usize, v: usize| {
+            let v1 = (v + 1).min(nv - 1);
+            let a = self.candela[h * nv + v];
+            let b = self.candela[h * nv + v1];
+            a + (b - a) * fv
+        };
+
+        let h1 = (h + 1).min(self.horizontal.len() - 1);
+        let (a, b) = (value(h, v), value(h1, v));
+        a + (b - a) * fh
+    }
+
+    /// Intensity at vertical angle `theta` and horizontal angle `phi` (both
+    /// in degrees), relative to the brightest direction
+    #[must_use]
+    pub fn intensity(&self, theta: F, phi: F) -> F {
+        if self.max > F::ZERO {
+            self.candela(theta, phi) / self.max
+        } else {
+            F::ZERO
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IesProfile;
+
+    const PROFILE: &str = "IESNA:LM-63-2002
+[TEST] test
+[MANUFAC] rustray
+TILT=NONE
+1 1000 2.0 3 2 1 2 0.1 0.1 0.0
+1.0 1.0 50
+0 45 90
+0 90
+100 50 0
+200, 100, 0
+";
+
+    #[test]
+    fn test_ies_parse() {
+        let ies = IesProfile::<f64>::parse(PROFILE).unwrap();
+        assert_eq!(ies.max(), 400.0);
+
+        /* Multiplier applied, interpolated between angles */
+        assert_eq!(ies.candela(0.0, 0.0), 200.0);
+        assert_eq!(ies.candela(22.5, 0.0), 150.0);
+        assert_eq!(ies.candela(0.0, 45.0), 300.0);
+        assert_eq!(ies.candela(120.0, 0.0), 0.0);
+
+        /* Quadrant symmetry: 270 degrees matches 90 degrees */
+        assert_eq!(ies.candela(0.0, 270.0), 400.0);
+        assert_eq!(ies.intensity(45.0, 90.0), 0.5);
+    }
+
+    #[test]
+    fn test_ies_lateral_symmetry() {
+        let text = PROFILE
+            .replace(
+                "1.0 1.0 50\n0 45 90\n0 90\n",
+                "1.0 1.0 50\n0 45 90\n90 180 270\n",
+            )
+            .replace(" 3 2 1 2 ", " 3 3 1 2 ")
+            .replace("200, 100, 0\n", "200, 100, 0\n300 150 0\n");
+        let ies = IesProfile::<f64>::parse(&text).unwrap();
+
+        /* Data from 90 to 270 degrees, mirrored about that plane */
+        assert_eq!(ies.candela(0.0, 90.0), 200.0);
+        assert_eq!(ies.candela(0.0, 180.0), 400.0);
+        assert_eq!(ies.candela(0.0, 0.0), 400.0);
+        assert_eq!(ies.candela(0.0, 45.0), 300.0);
+        assert_eq!(ies.candela(0.0, 315.0), 500.0);
+        assert_eq!(ies.candela(0.0, -45.0), 500.0);
+    }
+
+    #[test]
+    fn test_ies_tilt_include() {
+        /* Tilt angles and factors may span several lines */
+        let tilt = "TILT=INCLUDE\n1\n3\n0 45\n90\n1 0.9\n0.8";
+        let ies = IesProfile::<f64>::parse(&PROFILE.replace("TILT=NONE", tilt)).unwrap();
+        assert_eq!(ies.max(), 400.0);
+        assert_eq!(ies.candela(22.5, 0.0), 150.0);
+
+        let tilt = "TILT=INCLUDE\n4\n1\n0\n1";
+        assert!(IesProfile::<f64>::parse(&PROFILE.replace("TILT=NONE", tilt)).is_err());
+    }
+}
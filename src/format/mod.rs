@@ -1,3 +1,7 @@
+pub mod exr;
+pub mod gltf;
+pub mod ies;
 pub mod obj;
 pub mod ply;
 pub mod sbt2;
+pub mod sbtwriter;
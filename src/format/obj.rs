@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::io::Cursor;
 use std::path::Path;
 
 use obj::{Obj, ObjMaterial};
@@ -6,8 +7,8 @@ use obj::{Obj, ObjMaterial};
 use cgmath::{InnerSpace, Matrix4, SquareMatrix};
 
 use crate::geometry::{FiniteGeometry, Group, Triangle, TriangleMesh};
-use crate::material::{BoxMaterial, BumpPower, Bumpmap, Fresnel, Phong, Smart};
-use crate::sampler::{NormalMap, Sampler, SamplerExt, Texel};
+use crate::material::{BoxMaterial, BumpPower, Bumpmap, Fresnel, Pbr, Phong, Smart};
+use crate::sampler::{FileSampler, NormalMap, Sampler, SamplerExt, Texel};
 use crate::scene::BoxScene;
 use crate::types::{Color, Float, MaterialId, NamedObject, Point, RResult, Vector, Vectorx};
 
@@ -23,7 +24,8 @@ fn obj_sampler1<F: Float + Texel>(
             },
             |img| {
                 info!("Loading [{}]", kd);
-                img.bilinear().dynsampler()
+                let path = resdir.join(kd).to_string_lossy().to_string();
+                FileSampler::new(path, img.mipmap()).dynsampler()
             },
         )
     })
@@ -41,13 +43,113 @@ fn obj_sampler3<F: Float + Texel>(
             },
             |img| {
                 info!("Loading [{}]", kd);
-                img.bilinear().dynsampler()
+                let path = resdir.join(kd).to_string_lossy().to_string();
+                FileSampler::new(path, img.mipmap()).dynsampler()
             },
         )
     })
 }
 
-fn load_material<F: Float>(resdir: &Path, omat: &obj::Material) -> BoxMaterial<F> {
+/// PBR extensions to the .mtl format (`Pr`, `Pm`, `map_Pr`, `map_Pm`, `norm`),
+/// which are not supported by the `obj` crate.
+#[derive(Clone, Debug, Default)]
+struct MtlPbr {
+    pr: Option<f32>,
+    pm: Option<f32>,
+    map_pr: Option<String>,
+    map_pm: Option<String>,
+    norm: Option<String>,
+}
+
+impl MtlPbr {
+    const fn is_pbr(&self) -> bool {
+        self.pr.is_some() || self.pm.is_some() || self.map_pr.is_some() || self.map_pm.is_some()
+    }
+}
+
+/// Strip PBR extension lines from .mtl source, collecting them per material.
+///
+/// The `obj` crate rejects unknown instructions, so these must be removed
+/// before the remaining source is passed on to it.
+fn parse_mtl_pbr(data: &str, ext: &mut HashMap<String, MtlPbr>) -> String {
+    let mut res = String::new();
+    let mut name = String::new();
+
+    for line in data.lines() {
+        let line = line.trim();
+        let (key, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let value = value.trim();
+
+        if key == "newmtl" {
+            name = value.to_string();
+        }
+
+        let pbr = ext.entry(name.clone()).or_default();
+        match key {
+            "Pr" => pbr.pr = value.parse().ok(),
+            "Pm" => pbr.pm = value.parse().ok(),
+            "map_Pr" => pbr.map_pr = Some(value.to_string()),
+            "map_Pm" => pbr.map_pm = Some(value.to_string()),
+            "norm" | "map_Norm" => pbr.norm = Some(value.to_string()),
+            /* Other PBR extensions are unused, but must still be skipped */
+            "Ps" | "Pc" | "Pcr" | "aniso" | "anisor" | "map_Ps" => {}
+            _ => {
+                res.push_str(line);
+                res.push('\n');
+            }
+        }
+    }
+
+    res
+}
+
+fn load_mtls(obj: &mut Obj) -> RResult<HashMap<String, MtlPbr>> {
+    let mut ext = HashMap::new();
+
+    obj.load_mtls_fn(|dir, name| {
+        let data = std::fs::read_to_string(dir.join(name))?;
+        Ok(Cursor::new(parse_mtl_pbr(&data, &mut ext)))
+    })?;
+
+    Ok(ext)
+}
+
+fn load_material_pbr<F: Float>(
+    resdir: &Path,
+    omat: &obj::Material,
+    pbr: &MtlPbr,
+) -> BoxMaterial<F> {
+    let color = obj_sampler3(resdir, &omat.map_kd).map_or_else(
+        || omat.kd.map_or(Color::WHITE, Color::from).dynsampler(),
+        Sampler::dynsampler,
+    );
+    let metallic = obj_sampler1(resdir, &pbr.map_pm).map_or_else(
+        || F::from_f32(pbr.pm.unwrap_or(0.0)).dynsampler(),
+        Sampler::dynsampler,
+    );
+    let roughness = obj_sampler1(resdir, &pbr.map_pr).map_or_else(
+        || F::from_f32(pbr.pr.unwrap_or(0.5)).dynsampler(),
+        Sampler::dynsampler,
+    );
+    let normal = obj_sampler3(resdir, &pbr.norm.clone().or_else(|| omat.map_bump.clone()))
+        .map(NormalMap::new);
+
+    let mat = Pbr::new(color, metallic, roughness, normal)
+        .with_ke(omat.ke.map_or(Color::BLACK, Color::from))
+        .with_ambient(omat.ka.map_or(Color::BLACK, Into::into));
+
+    Box::new(NamedObject::new(omat.name.clone(), mat))
+}
+
+fn load_material<F: Float>(
+    resdir: &Path,
+    omat: &obj::Material,
+    pbr: Option<&MtlPbr>,
+) -> BoxMaterial<F> {
+    if let Some(pbr) = pbr.filter(|pbr| pbr.is_pbr()) {
+        return load_material_pbr(resdir, omat, pbr);
+    }
+
     let phong = Phong::new()
         .with_ke(omat.ke.map_or(Color::BLACK, Color::from))
         .with_kd(omat.kd.map_or(Color::WHITE, Color::from))
@@ -131,10 +233,21 @@ fn load_mesh<F: Float>(
     tris
 }
 
-pub fn load<F: Float + Texel>(mut obj: Obj, scene: &mut BoxScene<F>) -> RResult<()> {
+/// All geometry from an obj file
+pub type ObjGroup<F> = NamedObject<Group<F, Box<dyn FiniteGeometry<F>>>>;
+
+pub fn load<F: Float + Texel>(obj: Obj, scene: &mut BoxScene<F>) -> RResult<()> {
+    let group = load_group(obj, scene)?;
+    scene.add_object(group);
+    Ok(())
+}
+
+/// Load the geometry in `obj` as a single group, without adding it to
+/// `scene` (only its materials are added)
+pub fn load_group<F: Float + Texel>(mut obj: Obj, scene: &mut BoxScene<F>) -> RResult<ObjGroup<F>> {
     let mut corner = Vector::new(F::max_value(), F::max_value(), F::max_value());
 
-    obj.load_mtls()?;
+    let pbr = load_mtls(&mut obj)?;
     let position = &obj.data.position;
     let objects = &obj.data.objects;
     let texture = &obj.data.texture;
@@ -163,7 +276,7 @@ pub fn load<F: Float + Texel>(mut obj: Obj, scene: &mut BoxScene<F>) -> RResult<
     let mut faces = 0;
     let mut meshes = 0;
     let mut groups = 0;
-    let mut group = Group::empty();
+    let mut group: Group<F, Box<dyn FiniteGeometry<F>>> = Group::empty();
 
     for o in &obj.data.objects {
         info!("Object: {}", o.name);
@@ -173,7 +286,7 @@ pub fn load<F: Float + Texel>(mut obj: Obj, scene: &mut BoxScene<F>) -> RResult<
             info!("  group: {}", g.name);
             let mat = if let Some(ObjMaterial::Mtl(ref omat)) = g.material {
                 *hashmat.entry(&omat.name).or_insert_with(|| {
-                    let mat = load_material(&obj.path, omat);
+                    let mat = load_material(&obj.path, omat, pbr.get(&omat.name));
                     scene.materials.insert(mat)
                 })
             } else {
@@ -191,7 +304,7 @@ pub fn load<F: Float + Texel>(mut obj: Obj, scene: &mut BoxScene<F>) -> RResult<
 
         if !geos.is_empty() {
             let grp = Group::new(geos, Matrix4::identity());
-            group.add_object(NamedObject::new(o.name.clone(), grp));
+            group.add_object(Box::new(NamedObject::new(o.name.clone(), grp)));
             groups += 1;
         }
     }
@@ -204,14 +317,37 @@ pub fn load<F: Float + Texel>(mut obj: Obj, scene: &mut BoxScene<F>) -> RResult<
         faces,
     );
 
-    scene.add_object(NamedObject::new(
+    Ok(NamedObject::new(
         obj.path
             .file_name()
             .unwrap_or_default()
             .to_string_lossy()
             .into(),
         group,
-    ));
+    ))
+}
 
-    Ok(())
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::parse_mtl_pbr;
+
+    #[test]
+    fn test_parse_mtl_pbr() {
+        let mut ext = HashMap::new();
+        let src = parse_mtl_pbr(
+            "newmtl a\nKd 1 1 1\nPr 0.25\nPm 1\nmap_Pr rough.png\nPs 0.1\nnewmtl b\nKd 0 0 0\n",
+            &mut ext,
+        );
+
+        assert_eq!(src, "newmtl a\nKd 1 1 1\nnewmtl b\nKd 0 0 0\n");
+
+        let a = &ext["a"];
+        assert_eq!(a.pr, Some(0.25));
+        assert_eq!(a.pm, Some(1.0));
+        assert_eq!(a.map_pr.as_deref(), Some("rough.png"));
+        assert!(a.is_pbr());
+        assert!(!ext["b"].is_pbr());
+    }
 }
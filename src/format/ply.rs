@@ -6,9 +6,10 @@ use cgmath::{Matrix4, SquareMatrix};
 use num_traits::Zero;
 
 use crate::geometry::{Triangle, TriangleMesh};
+use crate::point;
 use crate::sampler::Texel;
 use crate::scene::BoxScene;
-use crate::types::{Error, Float, Point, RResult, Vector, Vectorx};
+use crate::types::{Color, Error, Float, Point, RResult, Vector, Vectorx};
 
 use ply_rs::{parser, ply};
 
@@ -17,7 +18,12 @@ pub struct PlyParser<F: Float> {
 }
 
 #[derive(Copy, Clone, Debug)]
-struct Vertex<F: Float>(Vector<F>, Vector<F>);
+struct Vertex<F: Float> {
+    pos: Vector<F>,
+    nml: Vector<F>,
+    uv: Point<F>,
+    color: Color<F>,
+}
 
 #[derive(Debug)]
 struct Face<F: Float> {
@@ -25,28 +31,70 @@ struct Face<F: Float> {
     uv: Vec<F>,
 }
 
+/// Numeric value of a scalar property
+fn scalar(property: &ply::Property) -> Option<f64> {
+    match *property {
+        ply::Property::Char(v) => Some(f64::from(v)),
+        ply::Property::UChar(v) => Some(f64::from(v)),
+        ply::Property::Short(v) => Some(f64::from(v)),
+        ply::Property::UShort(v) => Some(f64::from(v)),
+        ply::Property::Int(v) => Some(f64::from(v)),
+        ply::Property::UInt(v) => Some(f64::from(v)),
+        ply::Property::Float(v) => Some(f64::from(v)),
+        ply::Property::Double(v) => Some(v),
+        _ => None,
+    }
+}
+
+/// Colour channel value of a scalar property, normalized to `0..1` for
+/// integer types
+fn channel(property: &ply::Property) -> Option<f64> {
+    match *property {
+        ply::Property::UChar(v) => Some(f64::from(v) / f64::from(u8::MAX)),
+        ply::Property::UShort(v) => Some(f64::from(v) / f64::from(u16::MAX)),
+        ply::Property::Float(v) => Some(f64::from(v)),
+        ply::Property::Double(v) => Some(v),
+        _ => None,
+    }
+}
+
 impl<F: Float> ply::PropertyAccess for Vertex<F> {
     fn new() -> Self {
-        Self(Vector::ZERO, Vector::ZERO)
+        Self {
+            pos: Vector::ZERO,
+            nml: Vector::ZERO,
+            uv: Point::ZERO,
+            color: Color::WHITE,
+        }
     }
 
     fn set_property(&mut self, key: String, property: ply::Property) {
-        match property {
-            ply::Property::Float(v) => match key.as_ref() {
-                "x" => self.0.x = F::from_f32(v),
-                "y" => self.0.y = F::from_f32(v),
-                "z" => self.0.z = F::from_f32(v),
-                "nx" => self.1.x = F::from_f32(v),
-                "ny" => self.1.y = F::from_f32(v),
-                "nz" => self.1.z = F::from_f32(v),
-                "s" | "t" | "tx" | "ty" | "tz" | "bx" | "by" | "bz" => {}
-                k => error!("Vertex: Unexpected key/value combination: key: {k}"),
-            },
-            ply::Property::UChar(_v) => match key.as_ref() {
-                "red" | "green" | "blue" | "alpha" => {}
-                k => error!("Vertex: Unexpected key/value combination: key: {k}"),
-            },
-            t => error!("Vertex: Unexpected type: {t:?}"),
+        let value = match key.as_ref() {
+            "red" | "green" | "blue" | "alpha" => channel(&property),
+            _ => scalar(&property),
+        };
+
+        let Some(v) = value.map(F::from_f64) else {
+            error!("Vertex: Unexpected type: {property:?} (key: {key})");
+            return;
+        };
+
+        match key.as_ref() {
+            "x" => self.pos.x = v,
+            "y" => self.pos.y = v,
+            "z" => self.pos.z = v,
+            "nx" => self.nml.x = v,
+            "ny" => self.nml.y = v,
+            "nz" => self.nml.z = v,
+            "s" | "u" | "texture_u" | "texture_s" => self.uv.x = v,
+            /* Texture origin is bottom-left, like .obj */
+            "t" | "v" | "texture_v" | "texture_t" => self.uv.y = F::ONE - v,
+            "red" => self.color.r = v,
+            "green" => self.color.g = v,
+            "blue" => self.color.b = v,
+            "alpha" | "tx" | "ty" | "tz" | "bx" | "by" | "bz" | "quality" | "confidence"
+            | "intensity" => {}
+            k => error!("Vertex: Unexpected key/value combination: key: {k}"),
         }
     }
 }
@@ -60,42 +108,65 @@ impl<F: Float> ply::PropertyAccess for Face<F> {
     }
     fn set_property(&mut self, key: String, property: ply::Property) {
         match (key.as_ref(), property) {
-            ("vertex_indices", ply::Property::ListInt(vec)) => {
+            ("vertex_indices" | "vertex_index", ply::Property::ListInt(vec)) => {
                 self.idx = vec.into_iter().map(|x| x as usize).collect();
             }
-            ("vertex_indices", ply::Property::ListUInt(vec)) => {
+            ("vertex_indices" | "vertex_index", ply::Property::ListUInt(vec)) => {
                 self.idx = vec.into_iter().map(|x| x as usize).collect();
             }
-            ("vertex_index", ply::Property::ListUInt(vec)) => {
-                self.idx = vec.into_iter().map(|x| x as usize).collect();
+            ("vertex_indices" | "vertex_index", ply::Property::ListUShort(vec)) => {
+                self.idx = vec.into_iter().map(usize::from).collect();
             }
             ("texcoord", ply::Property::ListFloat(vec)) => {
                 self.uv = vec.into_iter().map(F::from_f32).collect();
             }
+            ("texcoord", ply::Property::ListDouble(vec)) => {
+                self.uv = vec.into_iter().map(F::from_f64).collect();
+            }
             ("red" | "green" | "blue" | "alpha" | "flags" | "texnumber", _) => {}
             (k, t) => error!("Face: Unexpected key/value combination: key: {k} (type {t:?})"),
         }
     }
 }
 
+impl<F: Float> Face<F> {
+    /// Texture coordinate of corner `n`, if the face has per-corner
+    /// coordinates
+    fn uv(&self, n: usize) -> Option<Point<F>> {
+        if self.uv.len() != self.idx.len() * 2 {
+            return None;
+        }
+        Some(point!(self.uv[n * 2], F::ONE - self.uv[n * 2 + 1]))
+    }
+}
+
 impl<F: Float + Texel> PlyParser<F> {
+    /// Load a .ply mesh (ascii or binary) into `scene`, including vertex
+    /// colours and texture coordinates.
     pub fn parse_file(file: &mut impl BufRead, scene: &mut BoxScene<F>) -> RResult<()> {
         let vertex_parser = parser::Parser::<Vertex<F>>::new();
         let face_parser = parser::Parser::<Face<F>>::new();
+        let other_parser = parser::Parser::<ply::DefaultElement>::new();
 
-        let header = vertex_parser.read_header(file).unwrap();
+        let header = vertex_parser.read_header(file)?;
 
         let mut vertex_list = Vec::new();
         let mut face_list = Vec::new();
+        let mut has_color = false;
         for (_, element) in &header.elements {
             match element.name.as_ref() {
                 "vertex" => {
                     vertex_list = vertex_parser.read_payload_for_element(file, element, &header)?;
+                    has_color = element.properties.contains_key("red");
                 }
                 "face" => {
                     face_list = face_parser.read_payload_for_element(file, element, &header)?;
                 }
-                other => return Err(Error::ParseUnsupported(other.to_owned())),
+                other => {
+                    /* Payload must still be consumed, to reach later elements */
+                    warn!("Ignoring unsupported ply element: {other}");
+                    other_parser.read_payload_for_element(file, element, &header)?;
+                }
             }
         }
         info!("vl: {:#?}", vertex_list.len());
@@ -105,32 +176,35 @@ impl<F: Float + Texel> PlyParser<F> {
 
         let mut tris = vec![];
         for face in &face_list {
-            for n in 1..(face.idx.len() - 1) {
-                let mut a = vertex_list[face.idx[0]];
-                let mut b = vertex_list[face.idx[n]];
-                let mut c = vertex_list[face.idx[n + 1]];
-                let n = (a.0 - b.0).cross(a.0 - c.0);
-                if a.1.is_zero() {
-                    a.1 = n;
-                }
-                if b.1.is_zero() {
-                    b.1 = n;
+            let vertex = |i: usize| {
+                vertex_list.get(face.idx[i]).copied().ok_or_else(|| {
+                    Error::ParseError(format!("Invalid vertex index: {}", face.idx[i]))
+                })
+            };
+            for n in 1..face.idx.len().saturating_sub(1) {
+                let mut a = vertex(0)?;
+                let mut b = vertex(n)?;
+                let mut c = vertex(n + 1)?;
+                let nml = (a.pos - b.pos).cross(a.pos - c.pos);
+                for v in [&mut a, &mut b, &mut c] {
+                    if v.nml.is_zero() {
+                        v.nml = nml;
+                    }
                 }
-                if c.1.is_zero() {
-                    c.1 = n;
+
+                let [ta, tb, tc] = if let (Some(ta), Some(tb), Some(tc)) =
+                    (face.uv(0), face.uv(n), face.uv(n + 1))
+                {
+                    [ta, tb, tc]
+                } else {
+                    [a.uv, b.uv, c.uv]
+                };
+
+                let mut tri =
+                    Triangle::new(a.pos, b.pos, c.pos, a.nml, b.nml, c.nml, ta, tb, tc, mat);
+                if has_color {
+                    tri = tri.with_colors(a.color, b.color, c.color);
                 }
-                let tri = Triangle::new(
-                    a.0,
-                    b.0,
-                    c.0,
-                    a.1,
-                    b.1,
-                    c.1,
-                    Point::ZERO,
-                    Point::ZERO,
-                    Point::ZERO,
-                    mat,
-                );
                 tris.push(tri);
             }
         }
@@ -140,3 +214,84 @@ impl<F: Float + Texel> PlyParser<F> {
         scene.recompute_bvh()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use cgmath::MetricSpace;
+
+    use super::PlyParser;
+    use crate::scene::BoxScene;
+    use crate::types::{Ray, Vector, Vectorx};
+
+    const HEADER: &str = "ply
+format FORMAT 1.0
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+property float s
+property float t
+element face 1
+property list uchar int vertex_indices
+end_header
+";
+
+    const VERTICES: [([f32; 3], [u8; 3], [f32; 2]); 4] = [
+        ([-1.0, -1.0, 0.0], [255, 0, 0], [0.0, 0.0]),
+        ([1.0, -1.0, 0.0], [0, 255, 0], [1.0, 0.0]),
+        ([1.0, 1.0, 0.0], [0, 0, 255], [1.0, 1.0]),
+        ([-1.0, 1.0, 0.0], [255, 255, 255], [0.0, 1.0]),
+    ];
+
+    fn check(data: &[u8]) {
+        let mut scene = BoxScene::<f64>::empty();
+        PlyParser::parse_file(&mut Cursor::new(data), &mut scene).unwrap();
+
+        let ray = Ray::new(Vector::new(0.5, -0.5, 5.0), -Vector::UNIT_Z);
+        let mut hit = scene.intersect(&ray).unwrap();
+        assert!(hit.pos.distance(Vector::new(0.5, -0.5, 0.0)) < 1e-6);
+
+        /* Weights are 1/4 red, 1/2 green and 1/4 blue at this point */
+        let color = hit.color();
+        assert!((color.r - 0.25).abs() < 1e-6);
+        assert!((color.g - 0.5).abs() < 1e-6);
+        assert!((color.b - 0.25).abs() < 1e-6);
+
+        /* Texture coordinates are kept, with t flipped like .obj */
+        let uv = hit.uv();
+        assert!((uv.x - 0.75).abs() < 1e-6);
+        assert!((uv.y - 0.75).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_ply_ascii() {
+        let mut data = HEADER.replace("FORMAT", "ascii");
+        for ([x, y, z], [r, g, b], [s, t]) in VERTICES {
+            data += &format!("{x} {y} {z} {r} {g} {b} {s} {t}\n");
+        }
+        data += "4 0 1 2 3\n";
+        check(data.as_bytes());
+    }
+
+    #[test]
+    fn test_ply_binary() {
+        let mut data = HEADER
+            .replace("FORMAT", "binary_little_endian")
+            .into_bytes();
+        for (pos, color, st) in VERTICES {
+            pos.iter().for_each(|v| data.extend(v.to_le_bytes()));
+            data.extend(color);
+            st.iter().for_each(|v| data.extend(v.to_le_bytes()));
+        }
+        data.push(4);
+        [0i32, 1, 2, 3]
+            .iter()
+            .for_each(|v| data.extend(v.to_le_bytes()));
+        check(&data);
+    }
+}
@@ -1,6 +1,9 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::fmt::Debug;
 use std::hash::{Hash, Hasher};
 use std::str::FromStr;
+use std::sync::Arc;
 
 use camino::Utf8Path;
 use itertools::Itertools;
@@ -9,16 +12,27 @@ use obj::Obj;
 use pest::iterators::{Pair, Pairs};
 use pest_derive::Parser;
 
-use cgmath::{Deg, InnerSpace, Matrix, Matrix4, Rad, SquareMatrix, Vector4};
+use cgmath::{Deg, InnerSpace, Matrix, Matrix4, Quaternion, Rad, SquareMatrix, Vector4};
 
+use crate::format::ies::IesProfile;
 use crate::geometry::{
-    Cone, Cube, Cylinder, FiniteGeometry, Sphere, Square, Triangle, TriangleMesh,
+    Cone, Cube, Cylinder, Disc, FiniteGeometry, Group, Instance, Plane, Sphere, Square, Triangle,
+    TriangleMesh,
+};
+use crate::light::{
+    AreaLight, Attenuation, DirectionalLight, Emitter, EnvironmentLight, Light, Photometric,
+    PointLight, SpotLight,
+};
+use crate::material::{BoxMaterial, BumpPower, Bumpmap, Pbr, Smart, Triblend};
+use crate::sampler::{
+    DynSampler, FileSampler, NormalMap, Sampler, SamplerExt, ShineMap, Texel, UvTransform,
 };
-use crate::light::{AreaLight, Attenuation, DirectionalLight, PointLight, SpotLight};
-use crate::material::{BoxMaterial, BumpPower, Bumpmap, Smart, Triblend};
-use crate::sampler::{DynSampler, NormalMap, Sampler, SamplerExt, ShineMap, Texel};
 use crate::scene::BoxScene;
-use crate::types::{Camera, Color, Error, Float, MaterialId, Point, RResult, Vector, Vectorx};
+use crate::types::{
+    Animation, Camera, CameraTracks, Color, Error, Float, Interpolation, Keyframe, Lens, Lerp,
+    LightTracks, MaterialId, Medium, ObjectTracks, Point, Projection, RResult, SamplePattern,
+    Track, Transform, Vector, Vectorx,
+};
 
 #[derive(Copy, Clone, Debug)]
 pub enum SbtVersion {
@@ -109,12 +123,81 @@ pub struct SbtBlock<'a, F: Float> {
 pub type SbtDict<'a, F> = HashMap<&'a str, SbtValue<'a, F>>;
 pub type SbtTuple<'a, F> = Vec<SbtValue<'a, F>>;
 
+/// Load an image texture from `map("file", ..)` arguments, which may be
+/// followed by `wrap=`, `border=` (converted by `border`), `scale=`,
+/// `rotate=` (in radians, like `rotate(..)`) and `offset=`
+fn load_map<F, T>(
+    resdir: &Utf8Path,
+    args: &[SbtValue<F>],
+    border: impl Fn(&SbtValue<F>) -> RResult<T>,
+) -> RResult<DynSampler<F, T>>
+where
+    F: Float + Texel,
+    T: Texel<Ratio = F> + Copy + 'static,
+    image::DynamicImage: Sampler<u32, T>,
+{
+    let Some((SbtValue::Str(name), args)) = args.split_first() else {
+        return Err(Error::ParseError(format!(
+            "expected map(\"filename\", ..), found {args:?}"
+        )));
+    };
+
+    let file = resdir.join(name.as_ref());
+    info!("name: {file:?}");
+    let mut img = image::open(&file)?.mipmap();
+
+    let mut scale = Point::new(F::ONE, F::ONE);
+    let mut rotation = Rad(F::ZERO);
+    let mut offset = Point::ZERO;
+
+    for arg in args {
+        let SbtValue::Named(box SbtBlock { name, value }) = arg else {
+            return Err(Error::ParseError(format!(
+                "expected named map argument, found {arg:?}"
+            )));
+        };
+        match (*name, value) {
+            ("wrap", SbtValue::Block(mode)) => img = img.with_wrap(mode.name.parse()?),
+            ("border", value) => img = img.with_border(border(value)?),
+            ("scale", SbtValue::Tuple(tuple)) => scale = tuple.point()?,
+            ("scale", value) => scale = Point::new(value.float()?, value.float()?),
+            ("rotate", value) => rotation = Rad(value.float()?),
+            ("offset", SbtValue::Tuple(tuple)) => offset = tuple.point()?,
+            (name, value) => {
+                return Err(Error::ParseUnsupported(format!(
+                    "map argument {name}={value:?}"
+                )))
+            }
+        }
+    }
+
+    Ok(UvTransform::new(FileSampler::new(&file, img))
+        .with_scale(scale)
+        .with_rotation(rotation)
+        .with_offset(offset)
+        .dynsampler())
+}
+
 trait SDict<F: Float + Texel> {
     fn get_result(&self, name: &str) -> RResult<&SbtValue<'_, F>>;
     fn float(&self, name: &str) -> RResult<F>;
     fn color(&self, name: &str) -> RResult<Color<F>>;
     fn shinemap(&self, name: &str, resdir: &Utf8Path) -> RResult<DynSampler<F, F>>;
-    fn sampler3(&self, name: &str, resdir: &Utf8Path) -> RResult<DynSampler<F, Color<F>>>;
+    fn sampler<T>(
+        &self,
+        name: &str,
+        resdir: &Utf8Path,
+        value: impl Fn(&SbtValue<F>) -> RResult<T>,
+    ) -> RResult<DynSampler<F, T>>
+    where
+        T: Texel<Ratio = F> + Sampler<F, T> + Copy + 'static,
+        image::DynamicImage: Sampler<u32, T>;
+    fn sampler1(&self, name: &str, resdir: &Utf8Path) -> RResult<DynSampler<F, F>> {
+        self.sampler(name, resdir, |value| value.float())
+    }
+    fn sampler3(&self, name: &str, resdir: &Utf8Path) -> RResult<DynSampler<F, Color<F>>> {
+        self.sampler(name, resdir, |value| value.tuple()?.color())
+    }
     fn string(&self, name: &str) -> RResult<&str>;
     fn vector(&self, name: &str) -> RResult<Vector<F>>;
     fn boolean(&self, name: &str) -> RResult<bool>;
@@ -175,42 +258,41 @@ impl<'a, F: Float + Texel> SDict<F> for SbtDict<'a, F> {
     }
 
     fn shinemap(&self, name: &str, resdir: &Utf8Path) -> RResult<DynSampler<F, F>> {
-        let load = |name| {
-            info!("{:?}", resdir.join(name));
-            Ok(
-                ShineMap::new(image::open(resdir.join(name))?.bilinear(), F::from_u32(128))
-                    .dynsampler(),
-            )
+        let load = |args: &[SbtValue<F>]| {
+            let img = load_map(resdir, args, |value| value.float())?;
+            Ok(ShineMap::new(img, F::from_u32(128)).dynsampler())
         };
 
         match self.get_result(name)? {
             SbtValue::Int(int) => Ok((F::from_f64(*int as f64)).dynsampler()),
             SbtValue::Float(float) => Ok((*float).dynsampler()),
-            SbtValue::Str(name) => load(name),
-            SbtValue::Block(box SbtBlock { name: "map", value }) => load(&value.tuple()?.string()?),
+            SbtValue::Str(name) => load(&[SbtValue::Str(name.clone())]),
+            SbtValue::Block(box SbtBlock { name: "map", value }) => load(value.tuple()?),
             _ => Err(Error::ParseError(format!(
                 "Could not parse sampler, found {self:?}"
             ))),
         }
     }
 
-    fn sampler3(&self, name: &str, resdir: &Utf8Path) -> RResult<DynSampler<F, Color<F>>> {
-        let load = |filename| {
-            let file = resdir.join(filename);
-            info!("name: {file:?}");
-            Ok(image::open(file)?.bilinear().dynsampler())
-        };
-
+    /// Constant value (converted by `value`), or image texture from a
+    /// filename or `map(..)` block
+    fn sampler<T>(
+        &self,
+        name: &str,
+        resdir: &Utf8Path,
+        value: impl Fn(&SbtValue<F>) -> RResult<T>,
+    ) -> RResult<DynSampler<F, T>>
+    where
+        T: Texel<Ratio = F> + Sampler<F, T> + Copy + 'static,
+        image::DynamicImage: Sampler<u32, T>,
+    {
         match self.get_result(name)? {
-            SbtValue::Tuple(tuple) => Ok(tuple.color()?.dynsampler()),
-            SbtValue::Str(name) => load(*name),
-            SbtValue::Block(box SbtBlock { name: "map", value }) => {
-                let name = value.tuple()?.string()?;
-                load(name)
-            }
-            _ => Err(Error::ParseError(format!(
-                "Could not parse sampler, found {self:?}"
-            ))),
+            SbtValue::Str(name) => load_map(resdir, &[SbtValue::Str(name.clone())], value),
+            SbtValue::Block(box SbtBlock {
+                name: "map",
+                value: args,
+            }) => load_map(resdir, args.tuple()?, value),
+            constant => Ok(value(constant)?.dynsampler()),
         }
     }
 
@@ -295,10 +377,12 @@ impl<'a, F: Float + Texel> STuple<F> for SbtTuple<'a, F> {
 pub enum SbtValue<'a, F: Float> {
     Int(i64),
     Float(F),
-    Str(&'a str),
+    Str(Cow<'a, str>),
     Dict(SbtDict<'a, F>),
     Tuple(SbtTuple<'a, F>),
     Block(Box<SbtBlock<'a, F>>),
+    /// Named tuple argument, like `wrap=mirror`
+    Named(Box<SbtBlock<'a, F>>),
     Bool(bool),
 }
 
@@ -323,7 +407,7 @@ impl<'a, F: Float + Texel> SbtValue<'a, F> {
         }
     }
 
-    pub fn string(&self) -> RResult<&'a str> {
+    pub fn string(&self) -> RResult<&str> {
         if let SbtValue::Str(s) = self {
             Ok(s)
         } else {
@@ -368,7 +452,7 @@ impl<'a, F: Float + Texel> SbtValue<'a, F> {
             SbtValue::Str(s) => hasher.write(s.as_bytes()),
             SbtValue::Dict(dict) => dict.hash_item(hasher),
             SbtValue::Tuple(t) => t.iter().for_each(|val| val.hash_item(hasher)),
-            SbtValue::Block(b) => {
+            SbtValue::Block(b) | SbtValue::Named(b) => {
                 hasher.write(b.name.as_bytes());
                 b.value.hash_item(hasher);
             }
@@ -438,7 +522,25 @@ impl SbtParser2 {
 
     pub fn parse_string<'a, F: Float>(pr: &Pair<'a, Rule>) -> RResult<SbtValue<'a, F>> {
         let val = pr.as_str();
-        Ok(SbtValue::Str(&val[1..val.len() - 1]))
+        let val = &val[1..val.len() - 1];
+        if !val.contains('\\') {
+            return Ok(SbtValue::Str(Cow::Borrowed(val)));
+        }
+
+        /* Only `\"` and `\\` are escapes, so other backslashes (like in
+         * windows paths) are kept as they are */
+        let mut res = String::with_capacity(val.len());
+        let mut chars = val.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (c, chars.peek()) {
+                ('\\', Some(&next @ ('"' | '\\'))) => {
+                    res.push(next);
+                    chars.next();
+                }
+                _ => res.push(c),
+            }
+        }
+        Ok(SbtValue::Str(Cow::Owned(res)))
     }
 
     pub fn parse_value<F: Float>(pr: Pair<Rule>) -> RResult<SbtValue<F>> {
@@ -452,6 +554,7 @@ impl SbtParser2 {
             Rule::string => Self::parse_string(&pr)?,
             Rule::boolean => Self::parse_boolean(&pr)?,
             Rule::block => SbtValue::Block(Box::new(Self::parse_block(pr)?)),
+            Rule::named => SbtValue::Named(Box::new(Self::parse_block(pr)?)),
             other => return Err(Error::ParseUnsupported(format!("{other:?}"))),
         };
         Ok(value)
@@ -460,7 +563,11 @@ impl SbtParser2 {
     pub fn parse_block<F: Float>(pr: Pair<Rule>) -> RResult<SbtBlock<F>> {
         let mut pr = pr.into_inner();
         let name = pr.next().unwrap().as_str();
-        let value = Self::parse_value(pr.next().unwrap())?;
+        /* Blocks without a value, like `mirror` */
+        let value = match pr.next() {
+            Some(value) => Self::parse_value(value)?,
+            None => SbtValue::Tuple(vec![]),
+        };
         Ok(SbtBlock { name, value })
     }
 
@@ -494,6 +601,8 @@ pub struct SbtBuilder<'a, F: Float> {
     material: SbtDict<'a, F>,
     scene: &'a mut BoxScene<F>,
     hashmat: HashMap<u64, MaterialId>,
+    defs: HashMap<String, Arc<dyn FiniteGeometry<F>>>,
+    tracks: Vec<(usize, ObjectTracks<F>)>,
 }
 
 impl<'a, F> SbtBuilder<'a, F>
@@ -509,6 +618,8 @@ where
             material: SbtDict::new(),
             scene,
             hashmat: HashMap::new(),
+            defs: HashMap::new(),
+            tracks: vec![],
         }
     }
 
@@ -519,6 +630,16 @@ where
         let look_at = dict.vector("look_at");
         let aspectratio = dict.float("aspectratio").unwrap_or(F::ONE);
         let fov = dict.float("fov").unwrap_or_else(|_| F::from_f32(55.0));
+        let name = dict.string("name").ok();
+        let aperture = dict.float("aperture").unwrap_or(F::ZERO);
+        let blades = dict.float("blades").unwrap_or(F::ZERO);
+
+        /* Focus on the look_at point, unless told otherwise */
+        let focal_distance = dict.float("focal_distance").unwrap_or_else(|_| {
+            look_at
+                .as_ref()
+                .map_or(F::ONE, |look_at| (*look_at - position).magnitude())
+        });
 
         if viewdir.is_none() && look_at.is_ok() {
             viewdir = Some(look_at? - position);
@@ -534,14 +655,28 @@ where
         info!("  aspectratio: {:?}", aspectratio);
         info!("  updir: {:?}", updir);
         info!("  fov: {:?}", fov);
+        info!("  name: {:?}", name);
+        info!("  aperture: {:?}", aperture);
+        info!("  focal_distance: {:?}", focal_distance);
 
-        Ok(Camera::build(
-            position,
-            viewdir.unwrap(),
-            updir,
-            fov,
-            aspectratio,
-        ))
+        let lens = Lens::new(aperture, focal_distance).with_blades(blades.to_u32().unwrap_or(0));
+
+        let projection = dict
+            .string("projection")
+            .map_or(Ok(Projection::Perspective), str::parse)?;
+        info!("  projection: {}", projection);
+
+        let shutter_open = dict.float("shutter_open").unwrap_or(F::ZERO);
+        let shutter_close = dict.float("shutter_close").unwrap_or(shutter_open);
+        info!("  shutter: {:?}..{:?}", shutter_open, shutter_close);
+
+        let mut camera = Camera::build(position, viewdir.unwrap(), updir, fov, aspectratio)
+            .with_lens(lens)
+            .with_projection(projection)
+            .with_shutter(shutter_open, shutter_close);
+        camera.name = name.map(ToString::to_string);
+
+        Ok(camera)
     }
 
     fn parse_point_light(dict: &impl SDict<F>) -> RResult<PointLight<F>> {
@@ -574,6 +709,27 @@ where
         Ok(res)
     }
 
+    /// Wrap `light` in the photometric profile from the `ies` file, if any,
+    /// aimed in direction `dir`
+    fn parse_ies(
+        dict: &impl SDict<F>,
+        resdir: &Utf8Path,
+        light: impl Light<F> + Debug + 'static,
+        dir: Vector<F>,
+    ) -> RResult<Box<dyn Light<F>>> {
+        let Ok(name) = dict.string("ies") else {
+            return Ok(Box::new(light));
+        };
+        let file = resdir.join(name);
+        info!("IES profile: {file:?}");
+
+        let mut res = Photometric::new(light, IesProfile::load(&file)?, dir);
+        if let Ok(upd) = dict.vector("updir") {
+            res = res.with_updir(upd.normalize());
+        }
+        Ok(Box::new(res))
+    }
+
     fn parse_area_light(dict: &impl SDict<F>) -> RResult<AreaLight<F>> {
         let pos = dict.vector("position")?;
         let dir = dict.vector("direction")?.normalize();
@@ -582,7 +738,70 @@ where
         let upd = dict.vector("updir")?.normalize();
         let width = dict.float("width").unwrap_or(F::ONE);
         let height = dict.float("height").unwrap_or(F::ONE);
-        let res = AreaLight::new(attn, pos, dir, upd, color, width, height);
+        let samples = dict.float("samples").unwrap_or_else(|_| F::from_u32(8));
+        let xres = dict.float("xres").unwrap_or(samples);
+        let yres = dict.float("yres").unwrap_or(samples);
+        let pattern = dict
+            .string("pattern")
+            .map_or(Ok(SamplePattern::Jittered), str::parse)?;
+        let threshold = dict.float("threshold").ok();
+        let res = AreaLight::new(attn, pos, dir, upd, color, width, height)
+            .with_samples(xres.to_u32().unwrap_or(1), yres.to_u32().unwrap_or(1))
+            .with_pattern(pattern)
+            .with_threshold(threshold);
+        info!("{:7.3?}", res);
+        Ok(res)
+    }
+
+    /// Light emitted by the surface of a sphere (`sphere_light`), a disc
+    /// (`disc_light`), or any single object given as `shape` (`emitter`)
+    fn parse_emitter(&mut self, name: &str, dict: &impl SDict<F>) -> RResult<Emitter<F>> {
+        let color = dict.color("color").or_else(|_| dict.color("colour"))?;
+        let samples = dict.float("samples").unwrap_or_else(|_| F::from_u32(16));
+        let radius = dict.float("radius").unwrap_or(F::ONE);
+
+        /* The surface is drawn in the colour of the light */
+        let mat = self.scene.materials.insert(Box::new(color));
+
+        let geo: Box<dyn FiniteGeometry<F>> = match name {
+            "sphere_light" => {
+                let pos = dict.vector("position")?;
+                let xfrm = Matrix4::from_translation(pos) * Matrix4::from_scale(radius);
+                Box::new(Sphere::new(xfrm, mat))
+            }
+            "disc_light" => {
+                let pos = dict.vector("position")?;
+                let dir = dict.vector("direction")?.normalize();
+                let rot = Quaternion::from_arc(Vector::UNIT_Z, dir, Some(Vector::UNIT_X));
+                let xfrm = Matrix4::from_translation(pos)
+                    * Matrix4::from(rot)
+                    * Matrix4::from_scale(radius);
+                Box::new(Disc::new(xfrm, mat))
+            }
+            _ => {
+                let mut geo =
+                    self.build_geometry(dict.get_result("shape")?, Matrix4::identity())?;
+                if geo.len() != 1 {
+                    return Err(Error::ParseError(format!(
+                        "emitter shape must be a single object, found {}",
+                        geo.len()
+                    )));
+                }
+                let mut geo = geo.remove(0);
+                if let Some(obj) = geo.material() {
+                    obj.set_material(mat);
+                }
+                geo
+            }
+        };
+
+        /* Emitters fall off with distance by themselves */
+        let attn = Attenuation {
+            c: dict.float("quadratic_attenuation_coeff").unwrap_or(F::ZERO),
+            ..dict.attenuation()?
+        };
+
+        let res = Emitter::new(attn, color, geo, mat).with_samples(samples.to_u32().unwrap_or(1));
         info!("{:7.3?}", res);
         Ok(res)
     }
@@ -596,6 +815,169 @@ where
         Ok(res)
     }
 
+    fn parse_environment(dict: &impl SDict<F>, resdir: &Utf8Path) -> RResult<EnvironmentLight<F>> {
+        let name = match dict.get_result("map")? {
+            SbtValue::Block(box SbtBlock { name: "map", value }) => value.tuple()?.string()?,
+            value => value.string()?,
+        };
+        let file = resdir.join(name);
+        info!("Environment: {file:?}");
+
+        let intensity = dict.float("intensity").unwrap_or(F::ONE);
+        let rotation = dict.float("rotation").unwrap_or(F::ZERO);
+
+        let res = EnvironmentLight::new(image::open(&file)?.into_rgb32f())
+            .with_source(file)
+            .with_intensity(intensity)
+            .with_rotation(rotation);
+        info!("{:7.3?}", res);
+        Ok(res)
+    }
+
+    /// Parse keyframes, like `(key(0, (1, 2, 3)), key(24, (4, 5, 6)))`
+    fn parse_track<T: Lerp<Ratio = F>>(
+        value: &SbtValue<F>,
+        interp: Interpolation,
+        parse: impl Fn(&SbtTuple<F>) -> RResult<T>,
+    ) -> RResult<Track<F, T>> {
+        let keys = match value {
+            SbtValue::Tuple(keys) => keys.as_slice(),
+            key => std::slice::from_ref(key),
+        };
+
+        let keys = keys
+            .iter()
+            .map(|key| match key {
+                SbtValue::Block(box SbtBlock {
+                    name: "key",
+                    value: SbtValue::Tuple(args),
+                }) => match args.as_slice() {
+                    [frame, SbtValue::Tuple(value)] => Ok(Keyframe {
+                        frame: frame.float()?,
+                        value: parse(value)?,
+                    }),
+                    _ => Err(Error::ParseError(format!("invalid keyframe: {args:?}"))),
+                },
+                other => Err(Error::ParseError(format!(
+                    "expected keyframe, found {other:?}"
+                ))),
+            })
+            .collect::<RResult<_>>()?;
+
+        Ok(Track::new(keys, interp))
+    }
+
+    fn parse_vector_track(
+        dict: &impl SDict<F>,
+        name: &str,
+        interp: Interpolation,
+    ) -> RResult<Option<Track<F, Vector<F>>>> {
+        dict.get_result(name)
+            .ok()
+            .map(|value| Self::parse_track(value, interp, |t: &SbtTuple<F>| t.vector3()))
+            .transpose()
+    }
+
+    fn parse_interpolation(dict: &impl SDict<F>) -> RResult<Interpolation> {
+        dict.string("interpolation")
+            .map_or(Ok(Interpolation::Linear), str::parse)
+    }
+
+    fn parse_camera_tracks(dict: &impl SDict<F>) -> RResult<CameraTracks<F>> {
+        let interp = Self::parse_interpolation(dict)?;
+        Ok(CameraTracks {
+            position: Self::parse_vector_track(dict, "position", interp)?,
+            look_at: Self::parse_vector_track(dict, "look_at", interp)?,
+        })
+    }
+
+    fn parse_light_tracks(dict: &impl SDict<F>) -> RResult<LightTracks<F>> {
+        let interp = Self::parse_interpolation(dict)?;
+        let color = dict
+            .get_result("color")
+            .or_else(|_| dict.get_result("colour"))
+            .ok()
+            .map(|value| Self::parse_track(value, interp, |t: &SbtTuple<F>| t.color()))
+            .transpose()?;
+
+        Ok(LightTracks {
+            position: Self::parse_vector_track(dict, "position", interp)?,
+            color,
+        })
+    }
+
+    /// Record the keyframes in the `animate` key of a light, if any, for the
+    /// light which is about to be added as number `idx`
+    fn animate_light(
+        animation: &mut Animation<F>,
+        idx: usize,
+        dict: &impl SDict<F>,
+    ) -> RResult<()> {
+        if let Ok(anim) = dict.dict("animate") {
+            animation
+                .lights
+                .push((idx, Self::parse_light_tracks(anim)?));
+        }
+        Ok(())
+    }
+
+    /// Build the geometry in `blk`, animated by the keyframes in `anim`
+    /// (applied at this level of the hierarchy)
+    fn build_animation(
+        &mut self,
+        anim: &SbtDict<F>,
+        blk: &SbtValue<F>,
+        xfrm: Matrix4<F>,
+    ) -> RResult<Vec<Box<dyn FiniteGeometry<F>>>> {
+        let interp = Self::parse_interpolation(anim)?;
+        let translate = Self::parse_vector_track(anim, "translate", interp)?;
+        let rotate = Self::parse_vector_track(anim, "rotate", interp)?;
+        let scale = Self::parse_vector_track(anim, "scale", interp)?;
+
+        let mut objs = self.build_geometry(blk, xfrm)?;
+        for obj in &mut objs {
+            let Some(id) = obj.get_id() else {
+                continue;
+            };
+            if let Some(obj) = obj.transform() {
+                self.tracks.push((
+                    id,
+                    ObjectTracks {
+                        path: vec![],
+                        parent: xfrm,
+                        base: *obj.get_transform(),
+                        motion: obj.get_motion().copied(),
+                        translate: translate.clone(),
+                        rotate: rotate.clone(),
+                        scale: scale.clone(),
+                    },
+                ));
+            }
+        }
+        Ok(objs)
+    }
+
+    /// Medium from the `absorption`, `scattering` and `anisotropy` keys, if
+    /// any of the coefficients are given
+    fn parse_medium(
+        color: impl Fn(&'static str) -> RResult<Color<F>>,
+        float: impl Fn(&'static str) -> RResult<F>,
+    ) -> Option<Medium<F>> {
+        let (absorption, scattering) = (color("absorption"), color("scattering"));
+        if absorption.is_err() && scattering.is_err() {
+            return None;
+        }
+
+        let res = Medium::new(
+            absorption.unwrap_or(Color::BLACK),
+            scattering.unwrap_or(Color::BLACK),
+        )
+        .with_anisotropy(float("anisotropy").unwrap_or(F::ZERO))
+        .with_step(float("step").unwrap_or(F::ONE));
+        info!("{:7.3?}", res);
+        Some(res)
+    }
+
     fn parse_material_props(&mut self, dict: &impl SDict<F>) -> MaterialId {
         let black = |_| Color::BLACK.dynsampler();
         let float = |name| dict.float(name).or_else(|_| self.material.float(name));
@@ -622,7 +1004,33 @@ where
             .or_else(|_| colormap("specular"))
             .unwrap_or_else(black);
 
-        let smart = Smart::new(idx, shi, emis, diff, spec, tran, refl).with_ambient(ambi);
+        /* Metallic/roughness materials (e.g. from PBR texture sets) */
+        let floatmap = |name| {
+            dict.sampler1(name, self.resdir)
+                .or_else(|_| self.material.sampler1(name, self.resdir))
+        };
+
+        let (metallic, roughness) = (floatmap("metallic"), floatmap("roughness"));
+
+        if metallic.is_ok() || roughness.is_ok() {
+            let metallic = metallic.unwrap_or_else(|_| F::ZERO.dynsampler());
+            let roughness = roughness.unwrap_or_else(|_| F::HALF.dynsampler());
+            let base = colormap("base_color").unwrap_or(diff);
+            let normal = colormap("normal")
+                .or_else(|_| colormap("bump"))
+                .ok()
+                .map(NormalMap::new);
+
+            let pbr = Pbr::new(base, metallic, roughness, normal)
+                .with_ke(color("emissive").unwrap_or(Color::BLACK))
+                .with_ambient(ambi);
+
+            return self.scene.materials.insert(Box::new(pbr));
+        }
+
+        let smart = Smart::new(idx, shi, emis, diff, spec, tran, refl)
+            .with_ambient(ambi)
+            .with_medium(Self::parse_medium(&color, &float));
 
         let res: BoxMaterial<F> = match colormap("bump").ok() {
             None => Box::new(smart),
@@ -652,6 +1060,11 @@ where
         self.parse_material(dict.dict("material").unwrap_or(&SbtDict::new()))
     }
 
+    /// Triangle mesh from the `points` and `faces` keys, or loaded from the
+    /// file in `objfile`.
+    ///
+    /// An `objfile` mesh is placed as stored in the file, and ignores the
+    /// transforms around the `polymesh`.
     fn parse_polymesh(
         &mut self,
         xfrm: Matrix4<F>,
@@ -662,6 +1075,7 @@ where
         let mut faces = vec![];
         let mut normals = vec![];
         let mut texture_uvs = vec![];
+        let mut colors = vec![];
         let mut materials = vec![];
 
         for normal in dict.tuple("normals").into_iter().flatten() {
@@ -673,11 +1087,16 @@ where
         for uv in dict.tuple("texture_uv").into_iter().flatten() {
             texture_uvs.push(uv.tuple()?.point()?);
         }
+        for color in dict.tuple("colors").into_iter().flatten() {
+            colors.push(color.tuple()?.color()?);
+        }
         if let Ok(path) = dict.string("objfile") {
             info!("Reading {}", path);
-            let obj = Obj::load(self.resdir.join(path))?;
-            crate::format::obj::load(obj, self.scene)?;
-            return Ok(vec![]);
+            let file = self.resdir.join(path);
+            let obj = Obj::load(&file)?;
+            let mut group = crate::format::obj::load_group(obj, self.scene)?;
+            group.obj = group.obj.with_source(file);
+            return Ok(vec![Box::new(group)]);
         }
 
         for point in dict.tuple("points")? {
@@ -736,7 +1155,7 @@ where
             /* let ac = points[face[0]] - points[face[2]]; */
             /* let n = ab.cross(ac); */
 
-            tris.push(Triangle::new(
+            let tri = Triangle::new(
                 points[face[0]],
                 points[face[1]],
                 points[face[2]],
@@ -748,12 +1167,144 @@ where
                 texture_uvs[face[1]],
                 texture_uvs[face[2]],
                 m,
-            ));
+            );
+
+            if colors.is_empty() {
+                tris.push(tri);
+            } else {
+                tris.push(tri.with_colors(colors[face[0]], colors[face[1]], colors[face[2]]));
+            }
         }
 
         Ok(vec![Box::new(TriangleMesh::new(tris, xfrm * pos_xfrm))])
     }
 
+    #[allow(clippy::too_many_lines)]
+    /// Parse a chain of transformations without geometry, like
+    /// `translate(1, 0, 0, rotate(0, 1, 0, 1.57))`
+    fn parse_transform(blk: &SbtValue<F>) -> RResult<Matrix4<F>> {
+        let SbtValue::Block(box SbtBlock {
+            name,
+            value: SbtValue::Tuple(tuple),
+        }) = blk
+        else {
+            return Err(Error::ParseUnsupported(format!("not a transform: {blk:?}")));
+        };
+
+        let (args, inner) = match tuple.split_last() {
+            Some((inner @ SbtValue::Block(_), args)) => (args, Self::parse_transform(inner)?),
+            _ => (tuple.as_slice(), Matrix4::identity()),
+        };
+
+        let res = match (*name, args) {
+            ("translate", [x, y, z]) => {
+                Matrix4::from_translation(Vector::new(x.float()?, y.float()?, z.float()?))
+            }
+            ("scale", [s]) => Matrix4::from_scale(s.float()?),
+            ("scale", [x, y, z]) => {
+                Matrix4::from_nonuniform_scale(x.float()?, y.float()?, z.float()?)
+            }
+            ("rotate", [x, y, z, w]) => Matrix4::from_axis_angle(
+                Vector::new(x.float()?, y.float()?, z.float()?).normalize(),
+                Rad(w.float()?),
+            ),
+            /* Matrix columns, as written by SbtWriter (SBT 1.0 order) */
+            (
+                "transform",
+                [SbtValue::Tuple(x), SbtValue::Tuple(y), SbtValue::Tuple(z), SbtValue::Tuple(w)],
+            ) => Matrix4::from_cols(x.vector4()?, y.vector4()?, z.vector4()?, w.vector4()?),
+            other => return Err(Error::ParseUnsupported(format!("unhandled: {other:#?}"))),
+        };
+
+        Ok(res * inner)
+    }
+
+    /// Build the geometry in `blk`, moving from its position at time 0 to
+    /// `end` (applied at this level of the hierarchy) at time 1
+    fn build_motion(
+        &mut self,
+        end: &SbtValue<F>,
+        blk: &SbtValue<F>,
+        xfrm: Matrix4<F>,
+    ) -> RResult<Vec<Box<dyn FiniteGeometry<F>>>> {
+        /* Convert the end transform to world space, for the objects below */
+        let inv = xfrm
+            .invert()
+            .ok_or_else(|| Error::ParseError("singular transform".into()))?;
+        let delta = xfrm * Self::parse_transform(end)? * inv;
+        info!("motion [{:5.2?}]", delta);
+
+        let mut objs = self.build_geometry(blk, xfrm)?;
+        for obj in &mut objs {
+            if let Some(obj) = obj.transform() {
+                let start = obj.get_motion().unwrap_or_else(|| obj.get_transform());
+                let end = Transform::new(delta * start.matrix());
+                obj.set_motion(Some(&end));
+            }
+        }
+        Ok(objs)
+    }
+
+    /// Build the geometry in `blk` once, to be placed any number of times
+    /// with `instance`
+    fn build_define(&mut self, name: &str, blk: &SbtValue<F>) -> RResult<()> {
+        let tracks = self.tracks.len();
+        let mut objs = self.build_geometry(blk, Matrix4::identity())?;
+        info!("define {name:?}: {} object(s)", objs.len());
+
+        /* Shared geometry can not move on its own */
+        if self.tracks.len() > tracks {
+            return Err(Error::ParseUnsupported(format!(
+                "animate inside define {name:?} (animate the instance instead)"
+            )));
+        }
+
+        let geo: Arc<dyn FiniteGeometry<F>> = match objs.pop() {
+            Some(obj) if objs.is_empty() => Arc::from(obj),
+            Some(obj) => {
+                objs.push(obj);
+                Arc::new(Group::new(objs, Matrix4::identity()))
+            }
+            None => return Err(Error::ParseError(format!("empty definition {name:?}"))),
+        };
+
+        self.defs.insert(name.to_string(), geo);
+        Ok(())
+    }
+
+    /// Place a copy of the geometry defined as `name`, moved by the
+    /// `translate`, `rotate` and `scale` keys (if any) in `dict`
+    fn build_instance(
+        &self,
+        name: &str,
+        dict: &impl SDict<F>,
+        xfrm: Matrix4<F>,
+    ) -> RResult<Vec<Box<dyn FiniteGeometry<F>>>> {
+        let geo = self
+            .defs
+            .get(name)
+            .ok_or_else(|| Error::ParseError(format!("unknown definition {name:?}")))?;
+
+        let mut local = Matrix4::identity();
+        if let Ok(pos) = dict.vector("translate") {
+            local = local * Matrix4::from_translation(pos);
+        }
+        if let Ok(rot) = dict.tuple("rotate") {
+            let [x, y, z, w] = rot.vector4()?.into();
+            local = local * Matrix4::from_axis_angle(Vector::new(x, y, z).normalize(), Rad(w));
+        }
+        match dict.get_result("scale") {
+            Ok(SbtValue::Tuple(scale)) => {
+                let scale = scale.vector3()?;
+                local = local * Matrix4::from_nonuniform_scale(scale.x, scale.y, scale.z);
+            }
+            Ok(scale) => local = local * Matrix4::from_scale(scale.float()?),
+            Err(_) => {}
+        }
+
+        Ok(vec![Box::new(Instance::new(geo.clone(), xfrm * local))])
+    }
+
     #[allow(clippy::too_many_lines)]
     fn build_geometry(
         &mut self,
@@ -762,6 +1313,14 @@ where
     ) -> RResult<Vec<Box<dyn FiniteGeometry<F>>>> {
         /* info!("block: {:#?}", blk); */
         match blk {
+            SbtValue::Block(box SbtBlock {
+                name: "instance",
+                value: SbtValue::Block(box SbtBlock { name, value }),
+            }) => match value {
+                SbtValue::Dict(dict) => self.build_instance(name, dict, xfrm),
+                _ => self.build_instance(name, &SbtDict::new(), xfrm),
+            },
+
             SbtValue::Block(box SbtBlock {
                 name,
                 value: SbtValue::Tuple(tuple),
@@ -812,6 +1371,10 @@ where
                     self.build_geometry(blk, xfrm * x2)
                 }
 
+                ("motion", [end, blk]) => self.build_motion(end, blk, xfrm),
+
+                ("animate", [SbtValue::Dict(anim), blk]) => self.build_animation(anim, blk, xfrm),
+
                 other => Err(Error::ParseUnsupported(format!("unhandled: {other:#?}"))),
             },
 
@@ -857,6 +1420,11 @@ where
                         ))])
                     }
 
+                    ("disc", dict) => Ok(vec![Box::new(Disc::new(
+                        xfrm,
+                        self.parse_material_obj(dict),
+                    ))]),
+
                     ("cylinder", dict) => {
                         /* info!("Cube(xfrm={:7.4?})", xfrm); */
                         Ok(vec![Box::new(Cylinder::new(
@@ -894,26 +1462,69 @@ where
             let lights = &mut scene.lights;
             match (blk.name, blk.value) {
                 ("camera", SbtValue::Dict(ref dict)) => {
+                    if let Ok(anim) = dict.dict("animate") {
+                        let tracks = Self::parse_camera_tracks(anim)?;
+                        scene.animation.cameras.push((scene.cameras.len(), tracks));
+                    }
                     scene.cameras.push(Self::parse_camera(dict)?);
                 }
                 ("directional_light", SbtValue::Dict(ref dict)) => {
+                    Self::animate_light(&mut scene.animation, lights.len(), dict)?;
                     lights.push(Box::new(Self::parse_directional_light(dict)?));
                 }
                 ("point_light", SbtValue::Dict(ref dict)) => {
-                    lights.push(Box::new(Self::parse_point_light(dict)?));
+                    Self::animate_light(&mut scene.animation, lights.len(), dict)?;
+                    let light = Self::parse_point_light(dict)?;
+                    let dir = dict.vector("direction").unwrap_or(-Vector::UNIT_Y);
+                    lights.push(Self::parse_ies(dict, self.resdir, light, dir)?);
                 }
                 ("ambient_light", SbtValue::Dict(ref dict)) => {
                     scene.ambient = dict.color("color").or_else(|_| dict.color("colour"))?;
                 }
+                ("background", SbtValue::Dict(ref dict)) => {
+                    scene.background = dict.color("color").or_else(|_| dict.color("colour"))?;
+                }
                 ("spot_light", SbtValue::Dict(ref dict)) => {
-                    lights.push(Box::new(Self::parse_spot_light(dict)?));
+                    Self::animate_light(&mut scene.animation, lights.len(), dict)?;
+                    let light = Self::parse_spot_light(dict)?;
+                    let dir = light.dir;
+                    lights.push(Self::parse_ies(dict, self.resdir, light, dir)?);
                 }
                 ("material", SbtValue::Dict(dict)) => self.material.extend(dict),
 
+                /* Infinite, so only at the top level */
+                ("plane", SbtValue::Dict(ref dict)) => {
+                    let pos = dict.vector("position").unwrap_or(Vector::ZERO);
+                    let u = dict.vector("u").unwrap_or(Vector::UNIT_X);
+                    let v = dict.vector("v").unwrap_or(-Vector::UNIT_Z);
+                    let mat = self.parse_material_obj(dict);
+                    self.scene.add_geometry(Plane::new(pos, u, v, mat));
+                }
+
+                ("define", SbtValue::Block(box SbtBlock { name, value })) => {
+                    self.build_define(name, &value)?;
+                }
+
+                ("fog", SbtValue::Dict(ref dict)) => {
+                    scene.medium =
+                        Self::parse_medium(|name| dict.color(name), |name| dict.float(name));
+                }
+
+                ("environment", SbtValue::Dict(ref dict)) => {
+                    scene.environment = Some(Self::parse_environment(dict, self.resdir)?);
+                }
+
                 ("area_light" | "area_light_rect", SbtValue::Dict(ref dict)) => {
+                    Self::animate_light(&mut scene.animation, lights.len(), dict)?;
                     lights.push(Box::new(Self::parse_area_light(dict)?));
                 }
 
+                (name @ ("sphere_light" | "disc_light" | "emitter"), SbtValue::Dict(ref dict)) => {
+                    Self::animate_light(&mut scene.animation, lights.len(), dict)?;
+                    let light = self.parse_emitter(name, dict)?;
+                    self.scene.lights.push(Box::new(light));
+                }
+
                 (name, value) => {
                     let block = SbtValue::Block(Box::new(SbtBlock { name, value }));
                     for obj in self.build_geometry(&block, Matrix4::identity())? {
@@ -923,6 +1534,161 @@ where
             }
         }
 
+        /* Objects are only in their final place in the hierarchy now */
+        for (id, tracks) in self.tracks {
+            self.scene
+                .animation
+                .add_object(&mut self.scene.root, id, tracks)?;
+        }
+
+        /* Start at the first frame, so still images match the animation */
+        if !self.scene.animation.is_empty() {
+            self.scene.set_frame(F::ZERO)?;
+        }
+
         self.scene.recompute_bvh()
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use camino::{Utf8Path, Utf8PathBuf};
+    use cgmath::InnerSpace;
+    use pest::Parser;
+
+    use super::{Rule, SbtBuilder, SbtParser2};
+    use crate::format::sbtwriter::SbtWriter;
+    use crate::scene::BoxScene;
+    use crate::types::{Ray, Vector};
+
+    /// Scratch directory for tests, removed again when dropped.
+    pub struct TempDir(Utf8PathBuf);
+
+    impl TempDir {
+        pub fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("rustray-{name}-{}", std::process::id()));
+            let dir = Utf8PathBuf::from_path_buf(dir).unwrap();
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        pub fn path(&self) -> &Utf8Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    pub fn load_in(resdir: &Utf8Path, text: &str) -> BoxScene<f64> {
+        let mut scene = BoxScene::empty();
+        let prog = SbtParser2::parse(Rule::program, text).unwrap();
+        let prog = SbtParser2::ast(prog).unwrap();
+        SbtBuilder::new(resdir, &mut scene).build(prog).unwrap();
+        scene
+    }
+
+    pub fn load(text: &str) -> BoxScene<f64> {
+        load_in(Utf8Path::new("."), text)
+    }
+
+    pub fn save_in(resdir: &Utf8Path, scene: &BoxScene<f64>) -> String {
+        let mut out = vec![];
+        SbtWriter::new(scene)
+            .with_resdir(resdir)
+            .write(&mut out)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    pub fn save(scene: &BoxScene<f64>) -> String {
+        let mut out = vec![];
+        SbtWriter::new(scene).write(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_sbt_animate_in_define() {
+        /* Shared geometry cannot move on its own, so this is an error
+         * rather than a silently ignored animation */
+        let text = r"
+SBT-raytracer 1.0
+define ball { animate({ translate = (key(0, (0, 0, 0)), key(10, (0, 1, 0))); }, sphere { }) }
+instance ball { }
+";
+        let mut scene = BoxScene::<f64>::empty();
+        let prog = SbtParser2::parse(Rule::program, text).unwrap();
+        let prog = SbtParser2::ast(prog).unwrap();
+        assert!(SbtBuilder::new(Utf8Path::new("."), &mut scene)
+            .build(prog)
+            .is_err());
+    }
+
+    #[test]
+    fn test_sbt_objfile_placement() {
+        let dir = TempDir::new("obj");
+        std::fs::write(
+            dir.path().join("tri.obj"),
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n",
+        )
+        .unwrap();
+
+        let scene = load_in(
+            dir.path(),
+            r#"
+SBT-raytracer 1.0
+translate(0, 0, -2, polymesh { objfile = "tri.obj"; })
+"#,
+        );
+
+        /* The mesh stays where the file puts it */
+        let ray = Ray::new(
+            Vector::new(-0.2, 0.2, 10.0),
+            Vector::new(0.001, 0.001, -1.0).normalize(),
+        );
+        let hit = scene.intersect(&ray).unwrap();
+        assert!(hit.pos.z.abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_sbt_map_args() {
+        let dir = TempDir::new("map");
+        let dir = dir.path();
+        image::RgbImage::from_fn(4, 4, |x, y| image::Rgb([(x * 60) as u8, (y * 60) as u8, 0]))
+            .save(dir.join("tex.png"))
+            .unwrap();
+
+        let scene = load_in(
+            dir,
+            r#"
+SBT-raytracer 1.0
+sphere { material = {
+    diffuse = map("tex.png", wrap=border, border=(1, 0, 1), scale=(4, 2), offset=(0.5, 0));
+    specular = map("tex.png", wrap=mirror);
+    emissive = map("tex.png", wrap=repeat, rotate=0.5);
+    transmissive = map("tex.png");
+}; }
+"#,
+        );
+        let text = save_in(dir, &scene);
+        let copy = save_in(dir, &load_in(dir, &text));
+
+        for arg in [
+            "wrap=border,",
+            "border=(1.0, 0.0, 1.0),",
+            "scale=(4.0, 2.0),",
+            "offset=(0.5, 0.0)",
+        ] {
+            assert!(text.contains(arg));
+        }
+        assert_eq!(copy, text);
+        assert!(text.contains(r#"map("tex.png", wrap=mirror)"#));
+
+        /* Clamp is the default, and rotations are in radians like rotate(..) */
+        assert!(text.contains(r#"map("tex.png", wrap=repeat, rotate=0.5)"#));
+        assert!(text.contains(r#"map("tex.png")"#));
+    }
+}
@@ -0,0 +1,769 @@
+//! Writer for the SBT format, the inverse of [`crate::format::sbt2`]
+
+use std::cell::RefCell;
+use std::fmt::{self, Display};
+use std::io::Write;
+use std::sync::Arc;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use cgmath::{Matrix4, SquareMatrix};
+
+use crate::geometry::FiniteGeometry;
+use crate::material::Material;
+use crate::sampler::{Sampler, Texel};
+use crate::scene::{BoxScene, SceneObject};
+use crate::types::{
+    Camera, CameraTracks, Color, Error, Float, Interpolation, Lerp, LightTracks, MaterialId,
+    ObjectTracks, Point, RResult, Track, Transform, Vector,
+};
+
+/// Value in an SBT file, as written by [`SbtWriter`]
+#[derive(Clone, Debug)]
+pub enum SbtNode<F> {
+    Int(i64),
+    Float(F),
+    Str(String),
+    Bool(bool),
+    /// A bare name, like `mirror`
+    Ident(String),
+    /// A named tuple argument, like `wrap=mirror`
+    Named(&'static str, Box<Self>),
+    Tuple(Vec<Self>),
+    Dict(Vec<(&'static str, Self)>),
+    /// A list of geometry, like `{ sphere { .. } box { .. } }`
+    Group(Vec<Self>),
+    Block(String, Box<Self>),
+}
+
+impl<F: Float> SbtNode<F> {
+    pub fn block(name: impl Into<String>, value: Self) -> Self {
+        Self::Block(name.into(), Box::new(value))
+    }
+
+    #[must_use]
+    pub fn vector(vec: Vector<F>) -> Self {
+        Self::Tuple(vec![
+            Self::Float(vec.x),
+            Self::Float(vec.y),
+            Self::Float(vec.z),
+        ])
+    }
+
+    #[must_use]
+    pub fn color(color: Color<F>) -> Self {
+        Self::Tuple(vec![
+            Self::Float(color.r),
+            Self::Float(color.g),
+            Self::Float(color.b),
+        ])
+    }
+
+    #[must_use]
+    pub fn point(point: Point<F>) -> Self {
+        Self::Tuple(vec![Self::Float(point.x), Self::Float(point.y)])
+    }
+
+    /// Columns of `mat`, as used by the SBT 1.0 `transform` block
+    fn columns(mat: &Matrix4<F>) -> Vec<Self> {
+        let cols: [[F; 4]; 4] = (*mat).into();
+        cols.iter()
+            .map(|col| Self::Tuple(col.iter().copied().map(Self::Float).collect()))
+            .collect()
+    }
+
+    /// Place `node` at `xfrm`, moving to `motion` (if any) while the shutter
+    /// is open
+    #[must_use]
+    pub fn transform(xfrm: &Transform<F>, motion: Option<&Transform<F>>, node: Self) -> Self {
+        let mut args = Self::columns(xfrm.matrix());
+        args.push(node);
+        let res = Self::block("transform", Self::Tuple(args));
+
+        /* The motion block holds the change from start to end */
+        let delta = motion.and_then(|end| Some(end.matrix() * xfrm.matrix().invert()?));
+        match delta {
+            Some(delta) => {
+                let end = Self::block("transform", Self::Tuple(Self::columns(&delta)));
+                Self::block("motion", Self::Tuple(vec![end, res]))
+            }
+            None => res,
+        }
+    }
+
+    const fn is_scalar(&self) -> bool {
+        matches!(
+            self,
+            Self::Int(_) | Self::Float(_) | Self::Str(_) | Self::Bool(_) | Self::Ident(_)
+        )
+    }
+
+    fn is_simple(&self) -> bool {
+        match self {
+            Self::Tuple(items) => items.iter().all(Self::is_scalar),
+            Self::Named(_, value) => value.is_simple(),
+            other => other.is_scalar(),
+        }
+    }
+
+    fn fmt_float(f: &mut fmt::Formatter<'_>, value: F) -> fmt::Result {
+        /* The parser reads numbers without a decimal point as integers */
+        let text = value.to_string();
+        if text.contains(['.', 'e', 'E']) {
+            write!(f, "{text}")
+        } else {
+            write!(f, "{text}.0")
+        }
+    }
+
+    fn fmt_indent(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        let pad = "    ".repeat(indent + 1);
+        let end = "    ".repeat(indent);
+
+        match self {
+            Self::Int(int) => write!(f, "{int}"),
+            Self::Float(float) => Self::fmt_float(f, *float),
+            Self::Str(text) => {
+                let text = text.replace('\\', "\\\\").replace('"', "\\\"");
+                write!(f, "\"{text}\"")
+            }
+            Self::Bool(b) => write!(f, "{b}"),
+            Self::Ident(name) => write!(f, "{name}"),
+            Self::Named(name, value) => {
+                write!(f, "{name}=")?;
+                value.fmt_indent(f, indent)
+            }
+            Self::Tuple(items) if items.iter().all(Self::is_simple) && items.len() <= 4 => {
+                write!(f, "(")?;
+                for (idx, item) in items.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    item.fmt_indent(f, indent)?;
+                }
+                write!(f, ")")
+            }
+            Self::Tuple(items) => {
+                writeln!(f, "(")?;
+                for (idx, item) in items.iter().enumerate() {
+                    write!(f, "{pad}")?;
+                    item.fmt_indent(f, indent + 1)?;
+                    writeln!(f, "{}", if idx + 1 < items.len() { "," } else { "" })?;
+                }
+                write!(f, "{end})")
+            }
+            Self::Dict(items) if items.is_empty() => write!(f, "{{}}"),
+            Self::Dict(items) => {
+                writeln!(f, "{{")?;
+                for (key, value) in items {
+                    write!(f, "{pad}{key} = ")?;
+                    value.fmt_indent(f, indent + 1)?;
+                    writeln!(f, ";")?;
+                }
+                write!(f, "{end}}}")
+            }
+            Self::Group(items) => {
+                writeln!(f, "{{")?;
+                for item in items {
+                    write!(f, "{pad}")?;
+                    item.fmt_indent(f, indent + 1)?;
+                    writeln!(f)?;
+                }
+                write!(f, "{end}}}")
+            }
+            Self::Block(name, value) => {
+                write!(f, "{name}")?;
+                if !matches!(**value, Self::Tuple(_)) {
+                    write!(f, " ")?;
+                }
+                value.fmt_indent(f, indent)
+            }
+        }
+    }
+}
+
+impl<F: Float> Display for SbtNode<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_indent(f, 0)
+    }
+}
+
+/// Writes a scene as an SBT file, which can be loaded again with
+/// [`crate::format::sbt2::SbtBuilder`].
+///
+/// Objects, lights and materials describe themselves (see
+/// [`crate::geometry::Geometry::to_sbt`]), and anything that can not be
+/// expressed in SBT is skipped with a warning (see [`SbtWriter::skipped`]).
+/// Animation tracks are saved as `animate` blocks, except for objects nested
+/// inside other objects.
+pub struct SbtWriter<'a, F: Float> {
+    scene: &'a BoxScene<F>,
+    resdir: Option<Utf8PathBuf>,
+    /* Shared geometry used by instances, by address */
+    defs: RefCell<Vec<(usize, SbtNode<F>)>>,
+    /* Parts of the scene that could not be written */
+    skipped: RefCell<Vec<String>>,
+}
+
+impl<'a, F: Float> SbtWriter<'a, F> {
+    #[must_use]
+    pub const fn new(scene: &'a BoxScene<F>) -> Self {
+        Self {
+            scene,
+            resdir: None,
+            defs: RefCell::new(vec![]),
+            skipped: RefCell::new(vec![]),
+        }
+    }
+
+    /// Write paths to files in `resdir` relative to it
+    #[must_use]
+    pub fn with_resdir(self, resdir: &Utf8Path) -> Self {
+        let resdir = if resdir.as_str().is_empty() {
+            Utf8Path::new(".")
+        } else {
+            resdir
+        };
+        Self {
+            resdir: resdir.canonicalize_utf8().ok(),
+            ..self
+        }
+    }
+
+    /// Save `scene` to `path`, with file references relative to it.
+    ///
+    /// Unless `lossy` is set, nothing is written when parts of the scene can
+    /// not be saved, so files are never replaced by an incomplete copy.
+    pub fn save(scene: &BoxScene<F>, path: &Utf8Path, lossy: bool) -> RResult<()> {
+        info!("Saving scene to {path:?}");
+        let writer =
+            SbtWriter::new(scene).with_resdir(path.parent().unwrap_or_else(|| Utf8Path::new(".")));
+        let mut out = vec![];
+        writer.write(&mut out)?;
+
+        let skipped = writer.skipped();
+        if !lossy && !skipped.is_empty() {
+            return Err(Error::IncompleteSave(skipped.join(", ")));
+        }
+        Ok(std::fs::write(path, out)?)
+    }
+
+    /// Everything that was left out by the last [`Self::blocks`] or
+    /// [`Self::write`]
+    #[must_use]
+    pub fn skipped(&self) -> Vec<String> {
+        self.skipped.borrow().clone()
+    }
+
+    fn skip(&self, what: String) {
+        warn!("Skipping {what}");
+        self.skipped.borrow_mut().push(what);
+    }
+
+    /// Path to a resource file, relative to the resource dir if possible
+    #[must_use]
+    pub fn path(&self, path: &Utf8Path) -> String {
+        let path = path
+            .canonicalize_utf8()
+            .unwrap_or_else(|_| path.to_path_buf());
+        self.resdir
+            .as_ref()
+            .and_then(|dir| path.strip_prefix(dir).ok())
+            .unwrap_or(&path)
+            .to_string()
+    }
+
+    /// Reference to an image file, like `map("texture.png")`
+    #[must_use]
+    pub fn map(&self, path: &Utf8Path) -> SbtNode<F> {
+        SbtNode::block("map", SbtNode::Tuple(vec![SbtNode::Str(self.path(path))]))
+    }
+
+    /// A `map(..)` for a texture sampler, including its wrap mode and uv
+    /// transform (with border values converted by `value`)
+    pub fn map_sampler<T: Texel>(
+        &self,
+        samp: &impl Sampler<F, T>,
+        value: impl Fn(T) -> SbtNode<F>,
+    ) -> Option<SbtNode<F>> {
+        let SbtNode::Block(name, mut args) = self.map(samp.source()?) else {
+            return None;
+        };
+        if let SbtNode::Tuple(items) = args.as_mut() {
+            items.extend(
+                samp.sbt_args(&value)
+                    .into_iter()
+                    .map(|(key, arg)| SbtNode::Named(key, Box::new(arg))),
+            );
+        }
+        Some(SbtNode::Block(name, args))
+    }
+
+    /// Value of a sampler: a `map(..)` for textures, or the value of constant
+    /// samplers (converted by `value`)
+    pub fn sampler<T: Texel>(
+        &self,
+        samp: &impl Sampler<F, T>,
+        value: impl Fn(T) -> SbtNode<F>,
+    ) -> Option<SbtNode<F>> {
+        if samp.source().is_some() {
+            return self.map_sampler(samp, value);
+        }
+        if samp.dimensions() == (1, 1) {
+            return Some(value(samp.sample(Point::ZERO)));
+        }
+        None
+    }
+
+    /// Material properties for `mat`, as an SBT dictionary
+    #[must_use]
+    pub fn material(&self, mat: MaterialId) -> Option<SbtNode<F>> {
+        let res = self.scene.materials.mats.get(&mat)?.to_sbt(self);
+        if res.is_none() {
+            self.skip(format!("material {mat:?} (using default)"));
+        }
+        res
+    }
+
+    /// Geometry block `name`, with the material `mat` and other `props`
+    #[must_use]
+    pub fn object(
+        &self,
+        name: &str,
+        mat: MaterialId,
+        mut props: Vec<(&'static str, SbtNode<F>)>,
+    ) -> SbtNode<F> {
+        if let Some(dict) = self.material(mat) {
+            props.insert(0, ("material", dict));
+        }
+        SbtNode::block(name, SbtNode::Dict(props))
+    }
+
+    /// Name of the `define` block for the shared geometry `geo`, written
+    /// before all other objects
+    pub fn define(&self, geo: &Arc<dyn FiniteGeometry<F>>) -> Option<String> {
+        let addr = Arc::as_ptr(geo).cast::<()>() as usize;
+        let name = |idx| format!("shared{idx}");
+
+        if let Some(idx) = self.defs.borrow().iter().position(|def| def.0 == addr) {
+            return Some(name(idx));
+        }
+
+        /* Shared geometry can hold instances too, which are defined first */
+        let node = geo.to_sbt(self)?;
+        let mut defs = self.defs.borrow_mut();
+        defs.push((addr, node));
+        Some(name(defs.len() - 1))
+    }
+
+    fn camera(camera: &Camera<F>) -> SbtNode<F> {
+        let mut props = vec![];
+        if let Some(name) = &camera.name {
+            props.push(("name", SbtNode::Str(name.clone())));
+        }
+        props.extend([
+            ("position", SbtNode::vector(camera.position())),
+            ("viewdir", SbtNode::vector(camera.direction())),
+            ("updir", SbtNode::vector(camera.updir())),
+            ("fov", SbtNode::Float(camera.fov())),
+            ("aspectratio", SbtNode::Float(camera.aspect_ratio())),
+            (
+                "projection",
+                SbtNode::Str(camera.get_projection().to_string()),
+            ),
+            ("aperture", SbtNode::Float(camera.lens.aperture)),
+            ("focal_distance", SbtNode::Float(camera.lens.focal_distance)),
+            ("blades", SbtNode::Int(i64::from(camera.lens.blades))),
+            ("shutter_open", SbtNode::Float(camera.shutter_open)),
+            ("shutter_close", SbtNode::Float(camera.shutter_close)),
+        ]);
+        SbtNode::block("camera", SbtNode::Dict(props))
+    }
+
+    fn color_block(name: &str, color: Color<F>) -> SbtNode<F> {
+        SbtNode::block(name, SbtNode::Dict(vec![("color", SbtNode::color(color))]))
+    }
+
+    /// Keyframes of `track`, as `(key(frame, value), ..)`
+    fn track<T: Lerp<Ratio = F>>(
+        name: &'static str,
+        track: Option<&Track<F, T>>,
+        value: impl Fn(T) -> SbtNode<F>,
+    ) -> Option<(&'static str, Interpolation, SbtNode<F>)> {
+        let track = track?;
+        let keys = track.keys().iter().map(|key| {
+            let args = vec![SbtNode::Float(key.frame), value(key.value)];
+            SbtNode::block("key", SbtNode::Tuple(args))
+        });
+        Some((name, track.interp, SbtNode::Tuple(keys.collect())))
+    }
+
+    /// Dictionary for an `animate` key or block, with the `tracks` that are
+    /// present
+    fn animate<const N: usize>(
+        tracks: [Option<(&'static str, Interpolation, SbtNode<F>)>; N],
+    ) -> SbtNode<F> {
+        let mut interp = Interpolation::Linear;
+        let mut props = vec![];
+        for (name, track_interp, node) in tracks.into_iter().flatten() {
+            interp = track_interp;
+            props.push((name, node));
+        }
+        if interp != Interpolation::Linear {
+            props.push(("interpolation", SbtNode::Str(interp.to_string())));
+        }
+        SbtNode::Dict(props)
+    }
+
+    fn camera_tracks(tracks: &CameraTracks<F>) -> SbtNode<F> {
+        Self::animate([
+            Self::track("position", tracks.position.as_ref(), SbtNode::vector),
+            Self::track("look_at", tracks.look_at.as_ref(), SbtNode::vector),
+        ])
+    }
+
+    fn light_tracks(tracks: &LightTracks<F>) -> SbtNode<F> {
+        Self::animate([
+            Self::track("position", tracks.position.as_ref(), SbtNode::vector),
+            Self::track("color", tracks.color.as_ref(), SbtNode::color),
+        ])
+    }
+
+    fn object_tracks(tracks: &ObjectTracks<F>) -> SbtNode<F> {
+        Self::animate([
+            Self::track("translate", tracks.translate.as_ref(), SbtNode::vector),
+            Self::track("rotate", tracks.rotate.as_ref(), SbtNode::vector),
+            Self::track("scale", tracks.scale.as_ref(), SbtNode::vector),
+        ])
+    }
+
+    /// Add the `animate` key `tracks` to the camera or light block `node`
+    fn with_animate(&self, mut node: SbtNode<F>, tracks: Option<SbtNode<F>>) -> SbtNode<F> {
+        let Some(tracks) = tracks else {
+            return node;
+        };
+        match &mut node {
+            SbtNode::Block(_, value) => match value.as_mut() {
+                SbtNode::Dict(props) => props.push(("animate", tracks)),
+                _ => self.skip(format!("animation of {node}")),
+            },
+            _ => self.skip(format!("animation of {node}")),
+        }
+        node
+    }
+
+    /// `node`, placed at `mat` (unless that is the identity)
+    fn place(mat: &Matrix4<F>, node: SbtNode<F>) -> SbtNode<F> {
+        if *mat == Matrix4::identity() {
+            node
+        } else {
+            SbtNode::transform(&Transform::new(*mat), None, node)
+        }
+    }
+
+    /// Top-level object number `idx` (written as `node`), inside an
+    /// `animate` block for its tracks, if any
+    fn animate_object(&self, idx: usize, node: SbtNode<F>) -> SbtNode<F> {
+        let anim = &self.scene.animation;
+        let mut tracks = anim.objects.iter().filter(|tracks| tracks.path == [idx]);
+        let Some(tracks) = tracks.next_back() else {
+            return node;
+        };
+
+        /* The object is written as it is at the current frame, so undo the
+         * animation at that frame inside the animate block */
+        let parent = tracks.parent;
+        let Some(undo) = tracks
+            .matrix(anim.frame)
+            .invert()
+            .and_then(|cur| Some(cur * parent.invert()?))
+        else {
+            self.skip(format!("animation of object {idx} (not invertible)"));
+            return node;
+        };
+
+        let args = vec![Self::object_tracks(tracks), Self::place(&undo, node)];
+        Self::place(&parent, SbtNode::block("animate", SbtNode::Tuple(args)))
+    }
+
+    /// All top-level blocks for the scene
+    #[must_use]
+    pub fn blocks(&self) -> Vec<SbtNode<F>> {
+        let scene = self.scene;
+        let anim = &scene.animation;
+        self.skipped.borrow_mut().clear();
+
+        let mut res: Vec<SbtNode<F>> = scene
+            .cameras
+            .iter()
+            .enumerate()
+            .map(|(idx, camera)| {
+                let tracks = anim.cameras.iter().rfind(|(cam, _)| *cam == idx);
+                let tracks = tracks.map(|(_, tracks)| Self::camera_tracks(tracks));
+                self.with_animate(Self::camera(camera), tracks)
+            })
+            .collect();
+
+        res.push(Self::color_block("ambient_light", scene.ambient));
+        res.push(Self::color_block("background", scene.background));
+        if let Some(medium) = &scene.medium {
+            res.push(SbtNode::block(
+                "fog",
+                SbtNode::Dict(medium.to_sbt().to_vec()),
+            ));
+        }
+
+        let lights = scene.all_lights().enumerate().filter_map(|(idx, light)| {
+            let Some(node) = light.to_sbt(self) else {
+                self.skip(format!("light {:?}", light.get_name()));
+                return None;
+            };
+            let tracks = anim.lights.iter().rfind(|(light, _)| *light == idx);
+            let tracks = tracks.map(|(_, tracks)| Self::light_tracks(tracks));
+            Some(self.with_animate(node, tracks))
+        });
+        res.extend(lights);
+
+        let geometry = scene.geometry.iter().filter_map(|geo| {
+            let node = geo.to_sbt(self);
+            if node.is_none() {
+                self.skip(format!("object {:?}", geo.get_name()));
+            }
+            node
+        });
+        res.extend(geometry);
+
+        let objects: Vec<_> = scene
+            .root
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, obj)| {
+                let Some(node) = obj.to_sbt(self) else {
+                    self.skip(format!("object {:?}", obj.get_name()));
+                    return None;
+                };
+                Some(self.animate_object(idx, node))
+            })
+            .collect();
+
+        for tracks in anim.objects.iter().filter(|tracks| tracks.path.len() > 1) {
+            self.skip(format!("animation of nested object {:?}", tracks.path));
+        }
+
+        let defs = self.defs.take().into_iter().enumerate();
+        res.extend(defs.map(|(idx, (_, node))| {
+            SbtNode::block(
+                "define",
+                SbtNode::block(format!("shared{idx}"), SbtNode::Group(vec![node])),
+            )
+        }));
+
+        res.extend(objects);
+        res
+    }
+
+    pub fn write(&self, out: &mut impl Write) -> RResult<()> {
+        writeln!(out, "SBT-raytracer 1.0")?;
+        for block in self.blocks() {
+            writeln!(out)?;
+            writeln!(out, "{block}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{InnerSpace, MetricSpace};
+
+    use super::SbtWriter;
+    use crate::format::sbt2::tests::{load, load_in, save, save_in, TempDir};
+    use crate::geometry::Triangle;
+    use crate::types::{Error, MaterialId, Point, Ray, Vector, Vectorx};
+
+    const SCENE: &str = r"
+SBT-raytracer 1.0
+
+camera { position = (0, 1, 6); look_at = (0, 0.5, 0); fov = 50; }
+ambient_light { color = (0.1, 0.1, 0.1); }
+point_light { position = (2, 4, 2); color = (1, 0.5, 0.5); }
+
+define ball { sphere { material = { diffuse = (0.8, 0.2, 0.2); }; } }
+instance ball { translate = (-2, 0, 0); scale = 0.5; }
+instance ball { translate = (2, 0, 0); }
+
+translate(0, -1, 0, scale(4, box { material = { diffuse = (0.5, 0.5, 0.5); index = 1.5; }; }));
+motion(translate(0, 1, 0), cylinder { capped = false; })
+";
+
+    #[test]
+    fn test_sbt_roundtrip() {
+        let scene = load(SCENE);
+        let text = save(&scene);
+        let copy = load(&text);
+
+        assert_eq!(copy.cameras.len(), 1);
+        assert_eq!(copy.lights.len(), 1);
+        assert_eq!(copy.root.len(), scene.root.len());
+        assert!(copy.ambient.r > 0.09);
+
+        let (cam, orig) = (&copy.cameras[0], &scene.cameras[0]);
+        assert!(cam.position().distance(orig.position()) < 1e-9);
+        assert!(cam.direction().distance(orig.direction()) < 1e-9);
+
+        /* Both scenes are hit in the same places */
+        for x in [-2.0, -0.25, 0.0, 2.0, 3.0] {
+            let ray = Ray::new(Vector::new(x, 0.1, 10.0), -Vector::UNIT_Z);
+            let a = scene.intersect(&ray).map(|mxl| mxl.pos);
+            let b = copy.intersect(&ray).map(|mxl| mxl.pos);
+            assert_eq!(a.is_some(), b.is_some());
+            if let (Some(a), Some(b)) = (a, b) {
+                assert!(a.distance(b) < 1e-9);
+            }
+        }
+
+        /* Saving again gives the same file (except for rounding errors in
+         * the camera up direction, which is recomputed from its matrix) */
+        let lines = |text: &str| {
+            text.lines()
+                .filter(|line| !line.contains("updir"))
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(lines(&save(&copy)), lines(&text));
+    }
+
+    #[test]
+    fn test_sbt_objfile_reference() {
+        let dir = TempDir::new("objref");
+        let dir = dir.path();
+        std::fs::write(dir.join("tri.obj"), "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+
+        let scene = load_in(
+            dir,
+            r#"
+SBT-raytracer 1.0
+polymesh { objfile = "tri.obj"; }
+"#,
+        );
+        let text = save_in(dir, &scene);
+        let copy = load_in(dir, &text);
+
+        /* Saved as a reference to the file, not as a copy of the mesh */
+        assert!(text.contains(r#"objfile = "tri.obj";"#));
+        assert!(!text.contains("points"));
+
+        let ray = Ray::new(
+            Vector::new(-0.2, 0.2, 10.0),
+            Vector::new(0.001, 0.001, -1.0).normalize(),
+        );
+        let hit = scene.intersect(&ray).unwrap();
+        assert!(copy.intersect(&ray).unwrap().pos.distance(hit.pos) < 1e-9);
+    }
+
+    #[test]
+    fn test_sbt_animation_roundtrip() {
+        let scene = load(
+            r#"
+SBT-raytracer 1.0
+camera { position = (0, 1, 6); look_at = (0, 0, 0);
+    animate = { position = (key(0, (0, 1, 6)), key(2, (3, 2, 5))); }; }
+point_light { position = (2, 4, 4); color = (1, 1, 1);
+    animate = { color = (key(0, (1, 1, 1)), key(2, (1, 0.3, 0.3))); }; }
+translate(0, 1, 0, animate({
+    translate = (key(0, (-1, 0, 0)), key(1, (0, 1, 0)), key(2, (1, 0, 0)));
+    rotate = (key(0, (0, 0, 0)), key(2, (0, 0, 90)));
+    interpolation = "bezier";
+}, translate(0, 0.5, 0, scale(0.5, sphere { }))))
+"#,
+        );
+        let mut scene = scene;
+        scene.set_frame(1.5).unwrap();
+
+        /* Saved in the middle of the animation, which still starts from the
+         * same place */
+        let text = save(&scene);
+        let mut copy = load(&text);
+        assert_eq!(copy.animation.cameras.len(), 1);
+        assert_eq!(copy.animation.lights.len(), 1);
+        assert_eq!(copy.animation.objects.len(), 1);
+        assert!(text.contains(r#"interpolation = "bezier";"#));
+
+        for frame in [0.0, 0.7, 2.0] {
+            scene.set_frame(frame).unwrap();
+            copy.set_frame(frame).unwrap();
+            let (a, b) = (&scene.cameras[0], &copy.cameras[0]);
+            assert!(a.position().distance(b.position()) < 1e-9);
+
+            let mut hits = 0;
+            for (x, y) in (0..81).map(|i| (f64::from(i % 9) / 4.0 - 1.0, f64::from(i / 9) / 4.0)) {
+                let ray = Ray::new(Vector::new(x, y + 0.5, 10.0), -Vector::UNIT_Z);
+                let a = scene.intersect(&ray).map(|mxl| mxl.pos);
+                let b = copy.intersect(&ray).map(|mxl| mxl.pos);
+                assert_eq!(a.is_some(), b.is_some());
+                if let (Some(a), Some(b)) = (a, b) {
+                    assert!(a.distance(b) < 1e-6);
+                    hits += 1;
+                }
+            }
+            assert!(hits > 0);
+        }
+        copy.set_frame(1.5).unwrap();
+        assert_eq!(save(&copy), text);
+    }
+
+    #[test]
+    fn test_sbt_plane_and_strings() {
+        let scene = load(
+            r#"
+SBT-raytracer 1.0
+camera { name = "say \"hi\" \\ C:\dir"; }
+plane { position = (0, -1, 0); u = (2, 0, 0); v = (0, 0, -2); }
+"#,
+        );
+        assert_eq!(
+            scene.cameras[0].name.as_deref(),
+            Some(r#"say "hi" \ C:\dir"#)
+        );
+        assert_eq!(scene.geometry.len(), 1);
+
+        let text = save(&scene);
+        let copy = load(&text);
+        assert_eq!(copy.cameras[0].name, scene.cameras[0].name);
+        assert_eq!(copy.geometry.len(), 1);
+        assert_eq!(save(&copy), text);
+
+        let ray = Ray::new(Vector::new(0.1, 5.0, 0.2), -Vector::UNIT_Y);
+        let hit = copy.intersect(&ray).unwrap();
+        assert!((hit.pos.y + 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_sbt_save_incomplete() {
+        let mut scene = load(SCENE);
+        let v = Vector::UNIT_Z;
+        let p = Point::ZERO;
+        scene.add_object(Triangle::new(
+            Vector::ZERO,
+            Vector::UNIT_X,
+            Vector::UNIT_Y,
+            v,
+            v,
+            v,
+            p,
+            p,
+            p,
+            MaterialId::NULL,
+        ));
+
+        /* An incomplete copy only replaces a file when asked to */
+        let dir = TempDir::new("save");
+        let path = &dir.path().join("scene.ray");
+        assert!(matches!(
+            SbtWriter::save(&scene, path, false),
+            Err(Error::IncompleteSave(_))
+        ));
+        assert!(!path.exists());
+        SbtWriter::save(&scene, path, true).unwrap();
+        assert!(path.exists());
+    }
+}
@@ -1,5 +1,5 @@
 use std::fs::File;
-use std::io::Read;
+use std::io::{BufReader, BufWriter, Read};
 use std::str::FromStr;
 
 use camino::{Utf8Path, Utf8PathBuf};
@@ -12,13 +12,19 @@ use pest::Parser;
 #[cfg(feature = "rayon")]
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
-use image::{ColorType, ImageBuffer, Rgb};
+use image::codecs::hdr::HdrEncoder;
+use image::{ImageBuffer, Rgb, Rgb32FImage, RgbImage};
 
+use crate::format::exr;
+use crate::format::ply::PlyParser;
 use crate::format::sbt2::{Rule as Rule2, SbtBuilder, SbtParser2};
 use crate::sampler::Texel;
-use crate::scene::{BoxScene, RayTracer, Scene};
+use crate::scene::{BoxScene, Scene};
 use crate::tracer::Tracer;
-use crate::types::{Float, RResult, TimeSlice};
+use crate::types::{
+    AntiAlias, Aov, Camera, CameraSelector, Color, Float, FrameRange, RResult, TimeSlice,
+    ToneMapper,
+};
 
 mod pbar {
     use indicatif::{ProgressBar, ProgressStyle};
@@ -41,13 +47,34 @@ where
 {
     info!("=={:=<60}==", format!("[ {:50} ]", path));
     let resdir = path.parent().unwrap();
+
+    match path.extension().map(str::to_ascii_lowercase).as_deref() {
+        /* Option 0: Scene from .gltf/.glb file */
+        Some("gltf" | "glb") => {
+            time.set("build");
+            let mut scene = Scene::empty();
+            crate::format::gltf::load(path, &mut scene)?;
+            scene.add_camera_if_missing()?;
+            scene.add_light_if_missing()?;
+            return Ok(scene);
+        }
+        /* Scene from .ply file (ascii or binary) */
+        Some("ply") => {
+            time.set("build");
+            let mut scene = Scene::empty();
+            let mut reader = BufReader::new(File::open(path)?);
+            PlyParser::parse_file(&mut reader, &mut scene)?;
+            scene.add_camera_if_missing()?;
+            scene.add_light_if_missing()?;
+            return Ok(scene);
+        }
+        _ => {}
+    }
+
     let mut file = File::open(path)?;
 
     time.set("read");
 
-    /* Option 1: Scene from .ply file */
-    /* let scene = PlyParser::<F>::parse_file(&mut file, resdir, WIDTH, HEIGHT)?; */
-
     /* Option 2: Scene from .ray file */
 
     let mut data = String::new();
@@ -83,16 +110,82 @@ where
 fn draw_image<F: Float>(
     time: &mut TimeSlice,
     tracer: &Tracer<F>,
+    camera: &Camera<F>,
+    aa: &AntiAlias<F>,
     width: u32,
     height: u32,
-) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
-    let pb = pbar::init(u64::from(height));
+) -> Rgb32FImage {
+    let adaptive = aa.threshold().is_some();
+    let passes = if adaptive { 2 } else { 1 };
+    let pb = pbar::init(u64::from(height * passes));
 
     let mut img = ImageBuffer::new(width, height);
 
     time.set("render");
 
-    let camera = &tracer.scene().cameras[0];
+    let indices = || {
+        let indices = 0..height;
+
+        #[cfg(feature = "rayon")]
+        let indices = indices.into_par_iter();
+
+        indices
+    };
+
+    let lines: Vec<_> = if adaptive {
+        /* Adaptive: render a single sample per pixel first, and then only
+         * supersample pixels that stand out from their neighbours */
+        let first_pass = aa.first_pass();
+        let coarse: Vec<_> = indices()
+            .inspect(|_| pb.inc(1))
+            .map(|y| {
+                tracer
+                    .render_line(camera, &first_pass, width, height, y)
+                    .pixels
+            })
+            .collect();
+
+        let row = |y: Option<u32>| y.and_then(|y| coarse.get(y as usize)).map(Vec::as_slice);
+
+        indices()
+            .progress_with(pb)
+            .map(|y| {
+                let rows = [row(y.checked_sub(1)), row(Some(y)), row(Some(y + 1))];
+                tracer.refine_line(camera, aa, width, height, y, rows)
+            })
+            .collect()
+    } else {
+        indices()
+            .progress_with(pb)
+            .map(|y| tracer.render_line(camera, aa, width, height, y))
+            .collect()
+    };
+
+    time.set("copy");
+
+    for (y, line) in lines.iter().enumerate() {
+        for (x, pixel) in line.pixels.iter().enumerate() {
+            img.put_pixel(x as u32, y as u32, Rgb(pixel.to_f32_array()));
+        }
+    }
+
+    img
+}
+
+/// Render the `aovs` output passes, returning all values for each pass.
+fn draw_aovs<F: Float>(
+    time: &mut TimeSlice,
+    tracer: &Tracer<F>,
+    camera: &Camera<F>,
+    aovs: &[Aov],
+    width: u32,
+    height: u32,
+) -> Vec<(Aov, Vec<Color<F>>)> {
+    if aovs.is_empty() {
+        return vec![];
+    }
+
+    time.set("aov");
 
     let indices = 0..height;
 
@@ -100,28 +193,199 @@ fn draw_image<F: Float>(
     let indices = indices.into_par_iter();
 
     let lines: Vec<_> = indices
-        .progress_with(pb)
-        .map(|y| tracer.render_line(camera, width, y))
+        .map(|y| tracer.render_aov_line(camera, aovs, width, height, y))
         .collect();
 
-    time.set("copy");
+    aovs.iter()
+        .enumerate()
+        .map(|(idx, aov)| {
+            let values = lines.iter().flat_map(|line| &line[idx]).copied().collect();
+            (*aov, values)
+        })
+        .collect()
+}
 
-    for (y, line) in lines.iter().enumerate() {
-        for (x, pixel) in line.pixels.iter().enumerate() {
-            img.put_pixel(x as u32, y as u32, Rgb(pixel.to_array()));
+/// Save a rendered image, and its output passes, to `output`.
+///
+/// For `.exr` files, the passes are stored as extra layers in the same
+/// file. Otherwise, each pass is converted to a viewable image, and written
+/// next to `output` as `<stem>-<pass>.<ext>`.
+fn save_image<F: Float>(
+    output: &Utf8Path,
+    img: &Rgb32FImage,
+    aovs: &[(Aov, Vec<Color<F>>)],
+    tonemap: &ToneMapper<F>,
+) -> RResult<()> {
+    let (width, height) = img.dimensions();
+
+    if is_exr(output) {
+        let mut channels = exr::rgb_channels(img);
+        for (aov, values) in aovs {
+            for (idx, name) in aov.channels().iter().enumerate() {
+                let data = values.iter().map(|c| c.to_f32_array()[idx]).collect();
+                channels.push((format!("{aov}.{name}"), data));
+            }
         }
+        return exr::save_channels(output, width, height, &channels);
     }
 
-    img
+    save_pass(output, img, tonemap)?;
+
+    for (aov, values) in aovs {
+        let pixels = aov.visualize(values);
+        let img = ImageBuffer::from_fn(width, height, |x, y| {
+            Rgb(pixels[(y * width + x) as usize].to_f32_array())
+        });
+
+        let path = suffixed_path(output, &aov.to_string());
+        info!("Writing {path}");
+        /* Passes hold data rather than radiance, so they are not encoded */
+        save_pass(&path, &img, &ToneMapper::<F>::default().with_srgb(false))?;
+    }
+
+    Ok(())
 }
 
-pub fn run<F>(input: &Utf8Path, width: u32, height: u32, output: Utf8PathBuf) -> RResult<()>
+fn is_exr(output: &Utf8Path) -> bool {
+    output
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("exr"))
+}
+
+/// Save a single image to `output`, with the file format chosen by extension.
+///
+/// `.exr` and `.hdr` files store the unclamped linear radiance, while all
+/// other formats are tone mapped to 8 bits per channel.
+fn save_pass<F: Float>(
+    output: &Utf8Path,
+    img: &Rgb32FImage,
+    tonemap: &ToneMapper<F>,
+) -> RResult<()> {
+    match output.extension().map(str::to_ascii_lowercase).as_deref() {
+        Some("exr") => exr::save(output, img),
+        Some("hdr") => {
+            let pixels: Vec<_> = img.pixels().copied().collect();
+            let out = BufWriter::new(File::create(output)?);
+            let (width, height) = (img.width() as usize, img.height() as usize);
+            Ok(HdrEncoder::new(out).encode(&pixels, width, height)?)
+        }
+        _ => {
+            let ldr: RgbImage = ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+                let [r, g, b] = img.get_pixel(x, y).0;
+                let color = Color::new(F::from_f32(r), F::from_f32(g), F::from_f32(b));
+                Rgb(tonemap.apply(color).to_array())
+            });
+            Ok(ldr.save(output)?)
+        }
+    }
+}
+
+/// Pick the output filename for each input scene.
+///
+/// A single scene is written to `output` (or `output.png`), while multiple
+/// scenes are written as `<stem>.png` into the `output` directory (or the
+/// current directory). Scenes that share a file stem are told apart by their
+/// parent directory (`<dir>-<stem>.png`), and failing that, by their position
+/// on the command line.
+fn output_paths(inputs: &[Utf8PathBuf], output: Option<&Utf8Path>) -> Vec<Utf8PathBuf> {
+    if let [_] = inputs {
+        return vec![output.map_or_else(|| Utf8PathBuf::from("output.png"), Utf8Path::to_path_buf)];
+    }
+
+    let duplicates = |names: &[String]| -> Vec<bool> {
+        names
+            .iter()
+            .map(|name| names.iter().filter(|other| *other == name).count() > 1)
+            .collect()
+    };
+
+    let mut names: Vec<String> = inputs
+        .iter()
+        .map(|input| input.file_stem().unwrap_or("output").to_string())
+        .collect();
+
+    let dups = duplicates(&names);
+    for ((name, input), dup) in names.iter_mut().zip(inputs).zip(dups) {
+        if let Some(parent) = input.parent().and_then(Utf8Path::file_name).filter(|_| dup) {
+            *name = format!("{parent}-{name}");
+        }
+    }
+
+    let dups = duplicates(&names);
+    for (idx, (name, dup)) in names.iter_mut().zip(dups).enumerate() {
+        if dup {
+            *name = format!("{name}-{idx}");
+        }
+    }
+
+    let dir = output.unwrap_or_else(|| Utf8Path::new("."));
+    names
+        .into_iter()
+        .map(|name| dir.join(format!("{name}.png")))
+        .collect()
+}
+
+/// Add a camera suffix to an output filename, so `out.png` becomes
+/// `out-<name>.png` for named cameras, or `out-<index>.png` otherwise.
+fn camera_output_path<F: Float>(
+    output: &Utf8Path,
+    camera: &Camera<F>,
+    index: usize,
+) -> Utf8PathBuf {
+    let suffix = camera.name.clone().unwrap_or_else(|| index.to_string());
+    suffixed_path(output, &suffix)
+}
+
+/// Add a suffix to an output filename, so `out.png` becomes `out-<suffix>.png`
+fn suffixed_path(output: &Utf8Path, suffix: &str) -> Utf8PathBuf {
+    let stem = output.file_stem().unwrap_or("output");
+
+    let name = output.extension().map_or_else(
+        || format!("{stem}-{suffix}"),
+        |ext| format!("{stem}-{suffix}.{ext}"),
+    );
+
+    output.with_file_name(name)
+}
+
+/// Settings for rendering scenes with [`run`].
+#[derive(Clone, Debug, Default)]
+pub struct RenderSettings<F: Float> {
+    /// Camera to render, by index or name (default: the first camera)
+    pub camera: Option<CameraSelector>,
+
+    /// Render every camera into its own file, suffixed with the camera name
+    /// (or index)
+    pub all_cameras: bool,
+
+    /// Pixel sampling, see [`AntiAlias`]
+    pub aa: AntiAlias<F>,
+
+    /// Tone mapping for low dynamic range outputs. High dynamic range outputs
+    /// (`.exr`, `.hdr`) are always written as linear radiance.
+    pub tonemap: ToneMapper<F>,
+
+    /// Auxiliary output passes, see [`save_image`]
+    pub aovs: Vec<Aov>,
+
+    /// Render these frames of the scene animation, suffixing output files
+    /// with the (zero padded) frame number
+    pub frames: Option<FrameRange>,
+}
+
+fn render_scene<F>(
+    input: &Utf8Path,
+    width: u32,
+    height: u32,
+    output: &Utf8Path,
+    settings: &RenderSettings<F>,
+) -> RResult<()>
 where
     F: Float + FromStr + From<f32>,
     rand::distributions::Standard: rand::distributions::Distribution<F>,
 {
     let mut time = TimeSlice::new("startup");
-    let scene = load_scene::<F>(&mut time, input)?;
+    let mut scene = load_scene::<F>(&mut time, input)?;
     /* let scene = rustray::demoscene::construct_demo_scene::<F>(&mut time, cli.width, cli.height)?; */
 
     info!(
@@ -131,13 +395,110 @@ where
         scene.lights.len()
     );
 
-    let img = draw_image(&mut time, &Tracer::new(&scene), width, height);
+    /* Scenes without cameras are rendered from a default viewpoint, also
+     * when rendering all cameras */
+    scene.add_camera_if_missing()?;
+
+    let jobs = if settings.all_cameras {
+        scene
+            .cameras
+            .iter()
+            .enumerate()
+            .map(|(idx, cam)| (idx, camera_output_path(output, cam, idx)))
+            .collect()
+    } else {
+        let idx = settings
+            .camera
+            .as_ref()
+            .map_or(Ok(0), |cam| scene.find_camera(cam))?;
+        vec![(idx, output.to_path_buf())]
+    };
+
+    let frames: Vec<Option<u32>> = settings
+        .frames
+        .map_or_else(|| vec![None], |range| range.iter().map(Some).collect());
+
+    for frame in frames {
+        /* The scene is only parsed once, and moved to each frame in turn */
+        if let Some(frame) = frame {
+            time.set("animate");
+            info!("Frame {frame}");
+            scene.set_frame(F::from_u32(frame))?;
+        }
+
+        let tracer = Tracer::new(&scene);
+
+        for (idx, output) in &jobs {
+            let output = frame.map_or_else(
+                || output.clone(),
+                |frame| suffixed_path(output, &format!("{frame:04}")),
+            );
+
+            info!("Rendering camera {idx}");
+            let camera = &scene.cameras[*idx];
+            let img = draw_image(&mut time, &tracer, camera, &settings.aa, width, height);
+            let aovs = draw_aovs(&mut time, &tracer, camera, &settings.aovs, width, height);
 
-    time.set("write");
-    image::save_buffer(output, &img, img.width(), img.height(), ColorType::Rgb8)?;
+            time.set("write");
+            info!("Writing {output}");
+            save_image(&output, &img, &aovs, &settings.tonemap)?;
+        }
+    }
 
     info!("render complete");
     time.stop();
     time.show();
     Ok(())
 }
+
+/// Render each input scene to an image file, according to `settings`.
+pub fn run<F>(
+    inputs: &[Utf8PathBuf],
+    width: u32,
+    height: u32,
+    output: Option<&Utf8Path>,
+    settings: &RenderSettings<F>,
+) -> RResult<()>
+where
+    F: Float + FromStr + From<f32>,
+    rand::distributions::Standard: rand::distributions::Distribution<F>,
+{
+    if let Some(dir) = output.filter(|_| inputs.len() > 1) {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    for (input, output) in inputs.iter().zip(output_paths(inputs, output)) {
+        render_scene::<F>(input, width, height, &output, settings)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use camino::{Utf8Path, Utf8PathBuf};
+
+    use super::output_paths;
+
+    #[test]
+    fn test_output_paths() {
+        let inputs: Vec<Utf8PathBuf> = ["a/scene.ray", "b/scene.ray", "c/other.ray", "b/scene.ray"]
+            .into_iter()
+            .map(Utf8PathBuf::from)
+            .collect();
+
+        let paths = output_paths(&inputs, Some(Utf8Path::new("out")));
+        assert_eq!(
+            paths,
+            [
+                "out/a-scene.png",
+                "out/b-scene-1.png",
+                "out/other.png",
+                "out/b-scene-3.png"
+            ]
+        );
+
+        let paths = output_paths(&inputs[..1], None);
+        assert_eq!(paths, ["output.png"]);
+    }
+}
@@ -6,7 +6,10 @@ use std::{io::BufReader, sync::Arc, time::Duration};
 
 use crate::{
     engine::{RenderEngine, RenderJob},
-    format::sbt2::{Rule as SbtRule, SbtBuilder, SbtParser2},
+    format::{
+        sbt2::{Rule as SbtRule, SbtBuilder, SbtParser2},
+        sbtwriter::SbtWriter,
+    },
     geometry::{FiniteGeometry, Geometry},
     gui::{
         context_menu,
@@ -18,7 +21,7 @@ use crate::{
     point,
     sampler::Texel,
     scene::{BoxScene, Interactive, SceneObject},
-    types::{Error, Float, Point, RResult, RF},
+    types::{AntiAlias, CameraSelector, Error, Float, Point, RResult, SamplePattern, ToneMap, RF},
 };
 
 use parking_lot::RwLock;
@@ -28,7 +31,7 @@ use egui::{
     CentralPanel, Context, KeyboardShortcut, Modifiers, ProgressBar, RichText, ScrollArea, Sense,
     SidePanel, TopBottomPanel, Ui, ViewportBuilder, ViewportCommand, Visuals,
 };
-use egui_file_dialog::FileDialog;
+use egui_file_dialog::{DialogMode, FileDialog};
 use egui_phosphor::regular as icon;
 use pest::Parser;
 
@@ -36,18 +39,49 @@ pub struct RenderModes<F: Float> {
     default: RenderJob<F>,
     preview: RenderJob<F>,
     normals: RenderJob<F>,
+    path: RenderJob<F>,
+}
+
+impl<F: Float> RenderModes<F> {
+    #[must_use]
+    pub const fn new(camera: usize) -> Self {
+        Self {
+            default: RenderJob::new().with_camera(camera),
+            preview: RenderJob::new()
+                .with_mult(3)
+                .with_ray_flags(RF::Preview.into())
+                .with_camera(camera),
+            normals: RenderJob::new()
+                .with_func_debug_normals()
+                .with_camera(camera),
+            /* A single jittered sample per pass anti-aliases over all passes */
+            path: RenderJob::new()
+                .with_func_path_trace()
+                .with_passes(1024)
+                .with_antialias(
+                    AntiAlias::new()
+                        .with_pattern(SamplePattern::Jittered)
+                        .with_samples(1),
+                )
+                .with_camera(camera),
+        }
+    }
 }
 
 pub struct RustRayGui<F: Float> {
     engine: RenderEngine<F>,
     paths: Vec<Utf8PathBuf>,
     pathindex: usize,
+    /// File written by "Save", if the scene was loaded from (or saved to) one
+    save_path: Option<Utf8PathBuf>,
     lock: Arc<RwLock<BoxScene<F>>>,
     file_dialog: FileDialog,
     ray_debugger: VisualTraceWidget,
     bounding_box: VisualTraceWidget,
     canvas: Canvas,
     render_modes: RenderModes<F>,
+    camera: usize,
+    camera_selector: Option<CameraSelector>,
 }
 
 impl<F: Float + Texel + From<f32>> RustRayGui<F>
@@ -56,7 +90,12 @@ where
 {
     /// Called once before the first frame.
     #[must_use]
-    pub fn new(cc: &CreationContext<'_>, engine: RenderEngine<F>, paths: Vec<Utf8PathBuf>) -> Self {
+    pub fn new(
+        cc: &CreationContext<'_>,
+        engine: RenderEngine<F>,
+        paths: Vec<Utf8PathBuf>,
+        camera_selector: Option<CameraSelector>,
+    ) -> Self {
         // load fonts
         let mut fonts = egui::FontDefinitions::default();
         egui_phosphor::add_to_fonts(&mut fonts, egui_phosphor::Variant::Regular);
@@ -71,25 +110,20 @@ where
 
         let lock = Arc::new(RwLock::new(BoxScene::empty()));
 
-        let render_modes = RenderModes {
-            default: RenderJob::new(),
-            preview: RenderJob::new()
-                .with_mult(3)
-                .with_ray_flags(RF::Preview.into()),
-            normals: RenderJob::new().with_func_debug_normals(),
-        };
-
         // construct gui
         Self {
             engine,
             paths,
             pathindex: 0,
+            save_path: None,
             lock,
             file_dialog: FileDialog::new().show_devices(false),
             ray_debugger: VisualTraceWidget::new(),
             bounding_box: VisualTraceWidget::new(),
             canvas: Canvas::new("canvas"),
-            render_modes,
+            render_modes: RenderModes::new(0),
+            camera: 0,
+            camera_selector,
         }
     }
 
@@ -116,7 +150,7 @@ where
         let Some(id) = self_obj else {
             return;
         };
-        scene.root.del_object(id);
+        scene.del_object(id);
         self_obj = None;
         self.bounding_box.clear();
 
@@ -135,6 +169,14 @@ where
                     self.file_dialog.select_file();
                     ui.close_menu();
                 }
+                if ui.button("Save").clicked() {
+                    self.save();
+                    ui.close_menu();
+                }
+                if ui.button("Save as..").clicked() {
+                    self.file_dialog.save_file();
+                    ui.close_menu();
+                }
                 if ui.button("Quit").clicked() {
                     ctx.send_viewport_cmd(ViewportCommand::Close);
                 }
@@ -156,6 +198,12 @@ where
         });
     }
 
+    fn set_camera(&mut self, camera: usize) {
+        self.camera = camera;
+        self.render_modes = RenderModes::new(camera);
+        self.bounding_box.clear();
+    }
+
     fn update_side_panel(&mut self, _ctx: &Context, ui: &mut Ui, scene: &mut BoxScene<F>) {
         ui.label(RichText::new("RustRay").heading().strong());
 
@@ -187,11 +235,33 @@ where
                         }
                     });
                 });
+
+                if let Some(env) = &mut scene.environment {
+                    let name = format!("{} {}", env.get_icon(), env.get_name());
+                    controls::property_list(&name, ui, |ui| {
+                        changed |= env.ui(ui);
+                    });
+                }
             });
 
             controls::collapsing_group("Cameras", icon::VIDEO_CAMERA).show(ui, |ui| {
+                let mut active = self.camera;
+                ui.horizontal(|ui| {
+                    ui.label("Active camera");
+                    for (i, cam) in scene.cameras.iter().enumerate() {
+                        let name = cam.name.clone().unwrap_or_else(|| i.to_string());
+                        ui.selectable_value(&mut active, i, name);
+                    }
+                });
+
+                if active != self.camera {
+                    self.set_camera(active);
+                    self.engine.submit(&self.render_modes.default, &self.lock);
+                }
+
                 scene.cameras.iter_mut().enumerate().for_each(|(i, cam)| {
-                    let name = format!("{} Camera {i}: {}", cam.get_icon(), cam.get_name());
+                    let label = cam.name.as_deref().unwrap_or_else(|| cam.get_name());
+                    let name = format!("{} Camera {i}: {label}", cam.get_icon());
                     controls::property_list(&name, ui, |ui| {
                         if let Some(interactive) = cam.get_interactive() {
                             changed |= interactive.ui(ui);
@@ -202,6 +272,30 @@ where
                 });
             });
 
+            controls::collapsing_group("Output", icon::IMAGE).show(ui, |ui| {
+                let mut tonemap = self.engine.get_tonemap();
+                let mut update = false;
+
+                ui.horizontal(|ui| {
+                    ui.label("Tone mapping");
+                    for op in ToneMap::ALL {
+                        update |= ui
+                            .selectable_value(&mut tonemap.op, op, op.to_string())
+                            .changed();
+                    }
+                });
+
+                let range = -F::from_u32(8)..=F::from_u32(8);
+                update |= ui
+                    .add(egui::Slider::new(&mut tonemap.exposure, range).text("Exposure"))
+                    .changed();
+                update |= ui.checkbox(&mut tonemap.srgb, "sRGB encoding").changed();
+
+                if update {
+                    self.engine.set_tonemap(tonemap);
+                }
+            });
+
             if changed {
                 scene.recompute_bvh().unwrap();
                 self.engine.submit(&self.render_modes.preview, &self.lock);
@@ -301,7 +395,7 @@ where
         if act.clicked() {
             if let Some(pos) = act.interact_pointer_pos {
                 let coord = from_screen.transform_pos(pos);
-                let mut ray = scene.cameras[0].get_ray(point!(coord.x, coord.y));
+                let mut ray = scene.cameras[self.camera].get_ray(point!(coord.x, coord.y));
                 ray.flags |= RF::StopAtGroup;
                 if let Some(maxel) = scene.intersect(&ray) {
                     let id = maxel.obj.get_id();
@@ -330,12 +424,12 @@ where
                 self.ray_debugger.set_coord(coord);
             }
         }
-        self.ray_debugger.update(scene, &to_screen);
-        self.bounding_box.update(scene, &to_screen);
+        let camera = scene.cameras[self.camera].clone();
 
-        act.context_menu(|ui| self.context_menu(ui, scene));
+        self.ray_debugger.update(scene, &camera, &to_screen);
+        self.bounding_box.update(scene, &camera, &to_screen);
 
-        let camera = scene.cameras[0];
+        act.context_menu(|ui| self.context_menu(ui, scene));
 
         let mut aabb: Option<rtbvh::Aabb> = None;
         if let Some(obj) = Self::find_obj(ui, scene) {
@@ -350,7 +444,7 @@ where
 
         // if the selected object has aabb info, render it
         if let Some(aabb) = aabb {
-            self.bounding_box.aabb(scene, &to_screen, &aabb);
+            self.bounding_box.aabb(&camera, &to_screen, &aabb);
         }
 
         let progress = self.engine.progress();
@@ -379,6 +473,11 @@ where
                 scene.add_camera_if_missing()?;
                 scene.add_light_if_missing()?;
             }
+            "gltf" | "glb" => {
+                crate::format::gltf::load(path, scene)?;
+                scene.add_camera_if_missing()?;
+                scene.add_light_if_missing()?;
+            }
             "ply" => {
                 let mut reader = BufReader::new(std::fs::File::open(path)?);
                 crate::format::ply::PlyParser::parse_file(&mut reader, scene)?;
@@ -402,15 +501,57 @@ where
         scene.clear();
         if let Err(e) = Self::load_scene_from_file(path, &mut scene) {
             let _ = scene.add_camera_if_missing();
+            drop(scene);
+            self.save_path = None;
+            self.set_camera(0);
             return Err(e);
         }
+
+        /* Only overwrite scenes which were in SBT format already */
+        self.save_path = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("ray"))
+            .then(|| path.to_path_buf());
+
+        let camera = self.camera_selector.as_ref().map_or(0, |sel| {
+            scene.find_camera(sel).unwrap_or_else(|err| {
+                warn!("{err}, using first camera");
+                0
+            })
+        });
         drop(scene);
 
+        self.set_camera(camera);
+
         self.engine.submit(&self.render_modes.default, &self.lock);
 
         Ok(())
     }
 
+    fn save_file(&mut self, path: &Utf8Path, lossy: bool) -> RResult<()> {
+        SbtWriter::save(&self.lock.read(), path, lossy)?;
+        self.save_path = Some(path.to_path_buf());
+        Ok(())
+    }
+
+    /// Save to the file the scene came from, or ask where to save it.
+    ///
+    /// The file is only overwritten if the whole scene can be saved,
+    /// otherwise this asks for a new file instead.
+    fn save(&mut self) {
+        match self.save_path.clone() {
+            Some(path) => match self.save_file(&path, false) {
+                Ok(()) => {}
+                Err(err @ Error::IncompleteSave(_)) => {
+                    warn!("Not overwriting {path:?}: {err}");
+                    self.file_dialog.save_file();
+                }
+                Err(err) => error!("Could not save {path:?}: {err}"),
+            },
+            None => self.file_dialog.save_file(),
+        }
+    }
+
     fn load_index(&mut self, mut index: usize) -> RResult<()> {
         index %= self.paths.len();
         self.pathindex = index;
@@ -451,6 +592,11 @@ where
             self.engine.submit(&self.render_modes.normals, &self.lock);
         }
 
+        // progressive path tracing
+        if ctx.input(|i| i.key_pressed(Key::P)) {
+            self.engine.submit(&self.render_modes.path, &self.lock);
+        }
+
         //
         if ctx.input(|i| i.key_pressed(Key::F)) {
             ctx.send_viewport_cmd(ViewportCommand::Fullscreen(true));
@@ -461,6 +607,17 @@ where
             self.file_dialog.select_file();
         }
 
+        let kbd_save = KeyboardShortcut::new(Modifiers::COMMAND, Key::S);
+        let kbd_save_as = KeyboardShortcut::new(Modifiers::COMMAND | Modifiers::SHIFT, Key::S);
+
+        if ctx.input_mut(|i| i.consume_shortcut(&kbd_save_as)) {
+            self.file_dialog.save_file();
+        }
+
+        if ctx.input_mut(|i| i.consume_shortcut(&kbd_save)) {
+            self.save();
+        }
+
         if ctx.input(|i| i.key_pressed(Key::PageDown)) {
             let _ = self.load_index(self.pathindex + 1);
         }
@@ -492,7 +649,14 @@ where
         self.file_dialog.update(ctx);
 
         if let Some(path) = self.file_dialog.take_selected() {
-            self.load_file(Utf8Path::from_path(&path).unwrap()).unwrap();
+            let path = Utf8Path::from_path(&path).unwrap();
+            if self.file_dialog.mode() == DialogMode::SaveFile {
+                if let Err(err) = self.save_file(path, true) {
+                    error!("Could not save {path:?}: {err}");
+                }
+            } else {
+                self.load_file(path).unwrap();
+            }
         }
 
         TopBottomPanel::top("top_panel").show(ctx, |ui| self.update_top_panel(ctx, ui));
@@ -513,7 +677,12 @@ where
     }
 }
 
-pub fn run<F>(paths: Vec<Utf8PathBuf>, width: u32, height: u32) -> RResult<()>
+pub fn run<F>(
+    paths: Vec<Utf8PathBuf>,
+    width: u32,
+    height: u32,
+    camera: Option<CameraSelector>,
+) -> RResult<()>
 where
     F: Float + Texel + From<f32>,
     rand::distributions::Standard: rand::distributions::Distribution<F>,
@@ -538,7 +707,7 @@ where
             Box::new({
                 let engine = RenderEngine::new(width, height);
 
-                let mut app = RustRayGui::new(cc, engine, paths);
+                let mut app = RustRayGui::new(cc, engine, paths, camera);
                 app.load_index(0).unwrap();
 
                 app
@@ -2,10 +2,15 @@ use cgmath::{InnerSpace, Matrix4};
 use glam::Vec3;
 use rtbvh::Aabb;
 
-use crate::geometry::{build_aabb_ranged, FiniteGeometry, Geometry};
+use crate::format::sbtwriter::{SbtNode, SbtWriter};
+use crate::geometry::{
+    build_aabb_motion, build_aabb_ranged, cone_uv, cone_uv_derivatives, FiniteGeometry, Geometry,
+};
 use crate::material::HasMaterial;
 use crate::scene::{Interactive, SceneObject};
-use crate::types::{self, Float, HasTransform, MaterialId, Maxel, Ray, Transform, Vector, Vectorx};
+use crate::types::{
+    self, Float, HasTransform, MaterialId, Maxel, Point, Ray, Transform, Vector, Vectorx,
+};
 use crate::vec3;
 
 #[cfg(feature = "gui")]
@@ -19,6 +24,7 @@ pub struct Cone<F: Float> {
     capped: bool,
     mat: MaterialId,
     xfrm: Transform<F>,
+    motion: Option<Transform<F>>,
     aabb: Aabb,
 }
 
@@ -83,7 +89,9 @@ geometry_impl_hasmaterial!(Cone<F>);
 impl<F: Float> FiniteGeometry<F> for Cone<F> {
     fn recompute_aabb(&mut self) {
         let m = self.bot_r.max(self.top_r);
-        self.aabb = build_aabb_ranged(&self.xfrm, [-m, m], [-m, m], [F::ZERO, self.height]);
+        self.aabb = build_aabb_motion(self, |xfrm| {
+            build_aabb_ranged(xfrm, [-m, m], [-m, m], [F::ZERO, self.height])
+        });
     }
 }
 
@@ -91,7 +99,8 @@ impl<F: Float> Geometry<F> for Cone<F> {
     /* Adapted from publicly-available code for University of Washington's course csep557 */
     /* https://courses.cs.washington.edu/courses/csep557/01sp/projects/trace/Cone.cpp */
     fn intersect(&self, ray: &Ray<F>) -> Option<Maxel<F>> {
-        let r = ray.xfrm_inv(&self.xfrm);
+        let xfrm = self.transform_at(ray.time);
+        let r = ray.xfrm_inv(&xfrm);
 
         let bot_r = self.bot_r.abs().max(F::BIAS);
         let top_r = self.top_r.abs().max(F::BIAS);
@@ -180,7 +189,7 @@ impl<F: Float> Geometry<F> for Cone<F> {
             return None;
         }
 
-        let nml = self.xfrm.nml(normal.normalize());
+        let nml = xfrm.nml(normal.normalize());
 
         Some(
             ray.hit_at(r.extend(root), root, self, self.mat)
@@ -191,6 +200,32 @@ impl<F: Float> Geometry<F> for Cone<F> {
     fn material(&mut self) -> Option<&mut dyn HasMaterial> {
         Some(self)
     }
+
+    fn to_sbt(&self, sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        let props = vec![
+            ("height", SbtNode::Float(self.height)),
+            ("top_radius", SbtNode::Float(self.top_r)),
+            ("bottom_radius", SbtNode::Float(self.bot_r)),
+            ("capped", SbtNode::Bool(self.capped)),
+        ];
+        let node = sbt.object("cone", self.mat, props);
+        Some(SbtNode::transform(&self.xfrm, self.motion.as_ref(), node))
+    }
+
+    fn transform(&mut self) -> Option<&mut dyn HasTransform<F>> {
+        Some(self)
+    }
+
+    fn uv(&self, maxel: &mut Maxel<F>) -> Point<F> {
+        cone_uv(maxel.hit, self.bot_r, self.top_r, self.height, self.capped)
+    }
+
+    fn uv_derivatives(&self, maxel: &mut Maxel<F>) -> Option<(Vector<F>, Vector<F>)> {
+        let xfrm = self.transform_at(maxel.time);
+        let (dpdu, dpdv) =
+            cone_uv_derivatives(maxel.hit, self.bot_r, self.top_r, self.height, self.capped);
+        Some((xfrm.dir(dpdu), xfrm.dir(dpdv)))
+    }
 }
 
 impl<F: Float> Cone<F> {
@@ -211,6 +246,7 @@ impl<F: Float> Cone<F> {
             capped,
             mat,
             xfrm: Transform::new(xfrm),
+            motion: None,
             aabb: Aabb::empty(),
         };
         res.recompute_aabb();
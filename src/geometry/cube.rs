@@ -5,7 +5,8 @@ use cgmath::Matrix4;
 use glam::Vec3;
 use rtbvh::Aabb;
 
-use crate::geometry::{build_aabb_symmetric, FiniteGeometry, Geometry};
+use crate::format::sbtwriter::{SbtNode, SbtWriter};
+use crate::geometry::{build_aabb_motion, build_aabb_symmetric, FiniteGeometry, Geometry};
 use crate::material::HasMaterial;
 use crate::point;
 use crate::scene::{Interactive, SceneObject};
@@ -16,6 +17,7 @@ use crate::types::{
 #[derive(Debug)]
 pub struct Cube<F: Float> {
     xfrm: Transform<F>,
+    motion: Option<Transform<F>>,
     mat: MaterialId,
     aabb: Aabb,
 }
@@ -44,7 +46,9 @@ geometry_impl_hasmaterial!(Cube<F>);
 
 impl<F: Float> FiniteGeometry<F> for Cube<F> {
     fn recompute_aabb(&mut self) {
-        self.aabb = build_aabb_symmetric(&self.xfrm, F::HALF, F::HALF, F::HALF);
+        self.aabb = build_aabb_motion(self, |xfrm| {
+            build_aabb_symmetric(xfrm, F::HALF, F::HALF, F::HALF)
+        });
     }
 }
 
@@ -54,7 +58,8 @@ impl<F: Float> Cube<F> {
 
 impl<F: Float> Geometry<F> for Cube<F> {
     fn intersect(&self, ray: &Ray<F>) -> Option<Maxel<F>> {
-        let r = ray.xfrm_inv(&self.xfrm);
+        let xfrm = self.transform_at(ray.time);
+        let r = ray.xfrm_inv(&xfrm);
 
         let p = r.pos;
         let d = r.dir;
@@ -105,7 +110,7 @@ impl<F: Float> Geometry<F> for Cube<F> {
 
         Some(
             ray.hit_at(r.extend(best_t), best_t, self, self.mat)
-                .with_normal(self.xfrm.nml(normal))
+                .with_normal(xfrm.nml(normal))
                 .with_uv(uv),
         )
     }
@@ -113,6 +118,29 @@ impl<F: Float> Geometry<F> for Cube<F> {
     fn material(&mut self) -> Option<&mut dyn HasMaterial> {
         Some(self)
     }
+
+    fn to_sbt(&self, sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        let node = sbt.object("box", self.mat, vec![]);
+        Some(SbtNode::transform(&self.xfrm, self.motion.as_ref(), node))
+    }
+
+    fn transform(&mut self) -> Option<&mut dyn HasTransform<F>> {
+        Some(self)
+    }
+
+    fn uv_derivatives(&self, maxel: &mut Maxel<F>) -> Option<(Vector<F>, Vector<F>)> {
+        /* The face is on the axis furthest out, and uv runs down the others */
+        let (x, y, z) = (maxel.hit.x.abs(), maxel.hit.y.abs(), maxel.hit.z.abs());
+        let (min, max) = if x >= y && x >= z {
+            (1, 2)
+        } else if y >= z {
+            (0, 2)
+        } else {
+            (0, 1)
+        };
+        let xfrm = self.transform_at(maxel.time);
+        Some((xfrm.dir(-Self::NORMALS[min]), xfrm.dir(-Self::NORMALS[max])))
+    }
 }
 
 impl<F: Float> Cube<F> {
@@ -121,6 +149,7 @@ impl<F: Float> Cube<F> {
     pub fn new(xfrm: Matrix4<F>, mat: MaterialId) -> Self {
         let mut res = Self {
             xfrm: Transform::new(xfrm),
+            motion: None,
             mat,
             aabb: Aabb::empty(),
         };
@@ -5,15 +5,21 @@ use cgmath::{InnerSpace, Matrix4};
 use glam::Vec3;
 use rtbvh::Aabb;
 
-use crate::geometry::{build_aabb_ranged, FiniteGeometry, Geometry};
+use crate::format::sbtwriter::{SbtNode, SbtWriter};
+use crate::geometry::{
+    build_aabb_motion, build_aabb_ranged, cone_uv, cone_uv_derivatives, FiniteGeometry, Geometry,
+};
 use crate::material::HasMaterial;
 use crate::scene::{Interactive, SceneObject};
-use crate::types::{self, Float, HasTransform, MaterialId, Maxel, Ray, Transform, Vector, Vectorx};
+use crate::types::{
+    self, Float, HasTransform, MaterialId, Maxel, Point, Ray, Transform, Vector, Vectorx,
+};
 use crate::vec3;
 
 #[derive(Debug)]
 pub struct Cylinder<F: Float> {
     xfrm: Transform<F>,
+    motion: Option<Transform<F>>,
     capped: bool,
     mat: MaterialId,
     aabb: Aabb,
@@ -50,12 +56,14 @@ geometry_impl_hasmaterial!(Cylinder<F>);
 
 impl<F: Float> FiniteGeometry<F> for Cylinder<F> {
     fn recompute_aabb(&mut self) {
-        self.aabb = build_aabb_ranged(
-            &self.xfrm,
-            [-F::ONE, F::ONE],
-            [-F::ONE, F::ONE],
-            [F::ZERO, F::ONE],
-        );
+        self.aabb = build_aabb_motion(self, |xfrm| {
+            build_aabb_ranged(
+                xfrm,
+                [-F::ONE, F::ONE],
+                [-F::ONE, F::ONE],
+                [F::ZERO, F::ONE],
+            )
+        });
     }
 }
 
@@ -63,7 +71,8 @@ impl<F: Float> Geometry<F> for Cylinder<F> {
     /* Adapted from publicly-available code for University of Washington's course csep557 */
     /* https://courses.cs.washington.edu/courses/csep557/01sp/projects/trace/Cylinder.cpp */
     fn intersect(&self, ray: &Ray<F>) -> Option<Maxel<F>> {
-        let r = ray.xfrm_inv(&self.xfrm);
+        let xfrm = self.transform_at(ray.time);
+        let r = ray.xfrm_inv(&xfrm);
 
         let self_height = F::ONE;
         let self_top_r = F::ONE;
@@ -156,7 +165,7 @@ impl<F: Float> Geometry<F> for Cylinder<F> {
             return None;
         }
 
-        let nml = self.xfrm.nml(normal.normalize());
+        let nml = xfrm.nml(normal.normalize());
 
         Some(
             ray.hit_at(r.extend(root), root, self, self.mat)
@@ -167,6 +176,26 @@ impl<F: Float> Geometry<F> for Cylinder<F> {
     fn material(&mut self) -> Option<&mut dyn HasMaterial> {
         Some(self)
     }
+
+    fn to_sbt(&self, sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        let props = vec![("capped", SbtNode::Bool(self.capped))];
+        let node = sbt.object("cylinder", self.mat, props);
+        Some(SbtNode::transform(&self.xfrm, self.motion.as_ref(), node))
+    }
+
+    fn transform(&mut self) -> Option<&mut dyn HasTransform<F>> {
+        Some(self)
+    }
+
+    fn uv(&self, maxel: &mut Maxel<F>) -> Point<F> {
+        cone_uv(maxel.hit, F::ONE, F::ONE, F::ONE, self.capped)
+    }
+
+    fn uv_derivatives(&self, maxel: &mut Maxel<F>) -> Option<(Vector<F>, Vector<F>)> {
+        let xfrm = self.transform_at(maxel.time);
+        let (dpdu, dpdv) = cone_uv_derivatives(maxel.hit, F::ONE, F::ONE, F::ONE, self.capped);
+        Some((xfrm.dir(dpdu), xfrm.dir(dpdv)))
+    }
 }
 
 impl<F: Float> Cylinder<F> {
@@ -175,6 +204,7 @@ impl<F: Float> Cylinder<F> {
     pub fn new(xfrm: Matrix4<F>, capped: bool, mat: MaterialId) -> Self {
         let mut res = Self {
             xfrm: Transform::new(xfrm),
+            motion: None,
             capped,
             mat,
             aabb: Aabb::empty(),
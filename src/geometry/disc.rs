@@ -0,0 +1,134 @@
+#[cfg(feature = "gui")]
+use crate::types::Camera;
+
+use cgmath::Matrix4;
+use glam::Vec3;
+use rtbvh::Aabb;
+
+use crate::format::sbtwriter::{SbtNode, SbtWriter};
+use crate::geometry::{build_aabb_motion, build_aabb_symmetric, FiniteGeometry, Geometry};
+use crate::material::{rand_unit, HasMaterial};
+use crate::point;
+use crate::scene::{Interactive, SceneObject};
+use crate::types::{
+    Float, HasTransform, MaterialId, Maxel, Point, Ray, Transform, Vector, Vectorx,
+};
+
+/// Disc with radius 1 in the xy plane, centered on the origin
+#[derive(Debug)]
+pub struct Disc<F: Float> {
+    xfrm: Transform<F>,
+    motion: Option<Transform<F>>,
+    mat: MaterialId,
+    aabb: Aabb,
+}
+
+aabb_impl_fm!(Disc<F>);
+
+#[cfg(feature = "gui")]
+impl<F: Float> Interactive<F> for Disc<F> {
+    #[cfg(feature = "gui")]
+    fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        Interactive::<F>::ui(&mut self.mat, ui)
+    }
+
+    fn ui_center(&mut self, ui: &mut egui::Ui, camera: &Camera<F>, rect: &egui::Rect) -> bool {
+        crate::gui::gizmo::gizmo_ui(ui, camera, self, rect)
+    }
+
+    #[cfg(feature = "gui")]
+    fn ui_bounding_box(&mut self) -> Option<&Aabb> {
+        Some(&self.aabb)
+    }
+}
+
+geometry_impl_sceneobject!(Disc<F>, "Disc");
+geometry_impl_hastransform!(Disc<F>);
+geometry_impl_hasmaterial!(Disc<F>);
+
+impl<F: Float> FiniteGeometry<F> for Disc<F> {
+    fn recompute_aabb(&mut self) {
+        self.aabb = build_aabb_motion(self, |xfrm| {
+            build_aabb_symmetric(xfrm, F::ONE, F::ONE, F::ZERO)
+        });
+    }
+}
+
+impl<F: Float> Geometry<F> for Disc<F> {
+    fn intersect(&self, ray: &Ray<F>) -> Option<Maxel<F>> {
+        let xfrm = self.transform_at(ray.time);
+        let r = ray.xfrm_inv(&xfrm);
+
+        if r.dir.z.is_zero() {
+            return None;
+        }
+
+        let t = -r.pos.z / r.dir.z;
+
+        if t <= F::BIAS2 {
+            return None;
+        }
+
+        let p = r.extend(t);
+        if p.x * p.x + p.y * p.y > F::ONE {
+            return None;
+        }
+
+        let normal = if r.dir.z.is_positive() {
+            -Vector::UNIT_Z
+        } else {
+            Vector::UNIT_Z
+        };
+
+        Some(
+            ray.hit_at(r.extend(t), t, self, self.mat)
+                .with_normal(xfrm.nml(normal))
+                .with_uv(point!((p.x + F::ONE) / F::TWO, (p.y + F::ONE) / F::TWO)),
+        )
+    }
+
+    fn uv_derivatives(&self, maxel: &mut Maxel<F>) -> Option<(Vector<F>, Vector<F>)> {
+        let xfrm = self.transform_at(maxel.time);
+        Some((
+            xfrm.dir(Vector::UNIT_X * F::TWO),
+            xfrm.dir(Vector::UNIT_Y * F::TWO),
+        ))
+    }
+
+    fn sample_surface(&self, time: F) -> Option<(Vector<F>, Vector<F>, F)> {
+        let xfrm = self.transform_at(time);
+        let r = rand_unit::<F>().sqrt();
+        let (sin, cos) = (F::TWO * F::PI() * rand_unit()).sin_cos();
+        let pos = Vector::new(r * cos, r * sin, F::ZERO);
+        let area = F::PI() * xfrm.area(Vector::UNIT_Z);
+        Some((xfrm.pos(pos), xfrm.nml(Vector::UNIT_Z), area))
+    }
+
+    fn material(&mut self) -> Option<&mut dyn HasMaterial> {
+        Some(self)
+    }
+
+    fn to_sbt(&self, sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        let node = sbt.object("disc", self.mat, vec![]);
+        Some(SbtNode::transform(&self.xfrm, self.motion.as_ref(), node))
+    }
+
+    fn transform(&mut self) -> Option<&mut dyn HasTransform<F>> {
+        Some(self)
+    }
+}
+
+impl<F: Float> Disc<F> {
+    pub const ICON: &'static str = egui_phosphor::regular::CIRCLE;
+
+    pub fn new(xfrm: Matrix4<F>, mat: MaterialId) -> Self {
+        let mut res = Self {
+            mat,
+            xfrm: Transform::new(xfrm),
+            motion: None,
+            aabb: Aabb::empty(),
+        };
+        res.recompute_aabb();
+        res
+    }
+}
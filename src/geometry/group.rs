@@ -1,5 +1,6 @@
 use std::num::NonZeroUsize;
 
+use camino::Utf8PathBuf;
 use cgmath::Matrix4;
 use glam::Vec3;
 use rtbvh::{Aabb, Bounds, Builder, Bvh, Primitive};
@@ -7,7 +8,8 @@ use rtbvh::{Aabb, Bounds, Builder, Bvh, Primitive};
 #[cfg(feature = "gui")]
 use crate::types::Camera;
 
-use crate::geometry::{build_aabb_ranged, FiniteGeometry, Geometry};
+use crate::format::sbtwriter::{SbtNode, SbtWriter};
+use crate::geometry::{build_aabb_motion, build_aabb_ranged, FiniteGeometry, Geometry};
 use crate::material::HasMaterial;
 use crate::scene::{Interactive, SceneObject};
 use crate::types::{
@@ -17,9 +19,21 @@ use crate::types::{
 #[derive(Debug)]
 pub struct Group<F: Float, G: FiniteGeometry<F>> {
     xfrm: Transform<F>,
+    motion: Option<Transform<F>>,
     geo: Vec<G>,
     bvh: Bvh,
     aabb: Aabb,
+    /* File the geometry was loaded from, saved as a reference to it */
+    source: Option<Utf8PathBuf>,
+}
+
+impl<'a, F: Float, G: FiniteGeometry<F>> IntoIterator for &'a Group<F, G> {
+    type Item = &'a G;
+    type IntoIter = std::slice::Iter<'a, G>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.geo.iter()
+    }
 }
 
 #[cfg(feature = "gui")]
@@ -126,6 +140,10 @@ impl<F: Float, G: FiniteGeometry<F>> SceneObject<F> for Group<F, G> {
         }
         None
     }
+
+    fn get_child(&mut self, idx: usize) -> Option<&mut dyn Geometry<F>> {
+        self.geo.get_mut(idx).map(|obj| obj as &mut dyn Geometry<F>)
+    }
 }
 
 impl<F: Float, G: FiniteGeometry<F>> HasTransform<F> for Group<F, G> {
@@ -137,6 +155,15 @@ impl<F: Float, G: FiniteGeometry<F>> HasTransform<F> for Group<F, G> {
         self.xfrm = *xfrm;
         self.recompute_aabb();
     }
+
+    fn get_motion(&self) -> Option<&Transform<F>> {
+        self.motion.as_ref()
+    }
+
+    fn set_motion(&mut self, end: Option<&Transform<F>>) {
+        self.motion = end.copied();
+        self.recompute_aabb();
+    }
 }
 
 impl<F: Float, G: FiniteGeometry<F>> rtbvh::Primitive for Group<F, G> {
@@ -156,29 +183,60 @@ impl<F: Float, G: FiniteGeometry<F>> FiniteGeometry<F> for Group<F, G> {
         let min = Vector::from_vec3(bounds.min);
         let max = Vector::from_vec3(bounds.max);
 
-        self.aabb = build_aabb_ranged(&self.xfrm, [min.x, max.x], [min.y, max.y], [min.z, max.z]);
+        self.aabb = build_aabb_motion(self, |xfrm| {
+            build_aabb_ranged(xfrm, [min.x, max.x], [min.y, max.y], [min.z, max.z])
+        });
     }
 }
 
 impl<F: Float, G: FiniteGeometry<F>> Geometry<F> for Group<F, G> {
     fn intersect(&self, ray: &Ray<F>) -> Option<Maxel<F>> {
         if ray.flags.contains(RF::StopAtGroup) {
-            let center = self.xfrm.pos_inv(Vector::from_vec3(self.center()));
+            let center = self
+                .transform_at(ray.time)
+                .pos_inv(Vector::from_vec3(self.center()));
             return Some(ray.synthetic_hit(center, self));
         }
 
-        let ray = ray.xfrm_inv(&self.xfrm);
+        let xfrm = self.transform_at(ray.time);
+        let ray = ray.xfrm_inv(&xfrm);
 
         let mut dist = F::max_value();
 
         self.bvh
             .nearest_intersection(&ray, &self.geo, &mut dist)
-            .map(|maxel| maxel.xfrm(&self.xfrm))
+            .map(|maxel| maxel.xfrm(&xfrm))
     }
 
     fn material(&mut self) -> Option<&mut dyn HasMaterial> {
         None
     }
+
+    fn update_bounds(&mut self) -> RResult<()> {
+        self.recompute_bvh()?;
+        self.recompute_aabb();
+        Ok(())
+    }
+
+    fn transform(&mut self) -> Option<&mut dyn HasTransform<F>> {
+        Some(self)
+    }
+
+    fn to_sbt(&self, sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        if let Some(path) = &self.source {
+            let file = SbtNode::Str(sbt.path(path));
+            let node = SbtNode::block("polymesh", SbtNode::Dict(vec![("objfile", file)]));
+            return Some(SbtNode::transform(&self.xfrm, self.motion.as_ref(), node));
+        }
+
+        let children: Vec<_> = self.geo.iter().filter_map(|obj| obj.to_sbt(sbt)).collect();
+        if children.is_empty() {
+            return None;
+        }
+
+        let node = SbtNode::Group(children);
+        Some(SbtNode::transform(&self.xfrm, self.motion.as_ref(), node))
+    }
 }
 
 impl<F: Float, G: FiniteGeometry<F>> Group<F, G> {
@@ -189,9 +247,11 @@ impl<F: Float, G: FiniteGeometry<F>> Group<F, G> {
 
         let mut res = Self {
             xfrm: Transform::new(xfrm),
+            motion: None,
             geo,
             bvh: Bvh::default(),
             aabb: Aabb::empty(),
+            source: None,
         };
         res.recompute_bvh().unwrap();
         res.recompute_aabb();
@@ -203,8 +263,20 @@ impl<F: Float, G: FiniteGeometry<F>> Group<F, G> {
         Self {
             geo: vec![],
             xfrm: Transform::identity(),
+            motion: None,
             bvh: Bvh::default(),
             aabb: Aabb::empty(),
+            source: None,
+        }
+    }
+
+    /// Remember that this group holds the contents of the (obj) file at
+    /// `path`, so it is saved as a reference instead of a copy
+    #[must_use]
+    pub fn with_source(self, path: Utf8PathBuf) -> Self {
+        Self {
+            source: Some(path),
+            ..self
         }
     }
 
@@ -214,6 +286,10 @@ impl<F: Float, G: FiniteGeometry<F>> Group<F, G> {
         self.geo.clear();
     }
 
+    pub fn iter(&self) -> std::slice::Iter<'_, G> {
+        self.geo.iter()
+    }
+
     pub fn iter_mut(&mut self) -> std::slice::IterMut<G> {
         self.geo.iter_mut()
     }
@@ -222,6 +298,11 @@ impl<F: Float, G: FiniteGeometry<F>> Group<F, G> {
         self.bvh.nearest_intersection(ray, &self.geo, dist)
     }
 
+    /// Find the nearest intersection, and the index of the intersected object.
+    pub fn nearest_object(&self, ray: &Ray<F>, dist: &mut F) -> Option<(usize, Maxel<F>)> {
+        self.bvh.nearest_primitive(ray, &self.geo, dist)
+    }
+
     pub fn recompute_bvh(&mut self) -> RResult<()> {
         let aabbs = self
             .geo
@@ -258,6 +339,11 @@ impl<F: Float, G: FiniteGeometry<F>> Group<F, G> {
         self.recompute_aabb();
     }
 
+    /// Find a direct child of this group, by [`SceneObject::get_id`]
+    pub fn find_object(&mut self, id: usize) -> Option<&mut G> {
+        self.geo.iter_mut().find(|obj| obj.get_id() == Some(id))
+    }
+
     pub fn del_object(&mut self, id: usize) {
         self.geo.retain(|obj| obj.get_id() != Some(id));
     }
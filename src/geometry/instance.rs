@@ -0,0 +1,148 @@
+use std::sync::Arc;
+
+use cgmath::Matrix4;
+use glam::Vec3;
+use rtbvh::{Aabb, Primitive};
+
+#[cfg(feature = "gui")]
+use crate::types::Camera;
+
+use crate::format::sbtwriter::{SbtNode, SbtWriter};
+use crate::geometry::{build_aabb_motion, build_aabb_ranged, FiniteGeometry, Geometry};
+use crate::material::HasMaterial;
+use crate::scene::{Interactive, SceneObject};
+use crate::types::{Float, HasTransform, Maxel, Ray, Transform, Vector, Vectorx, RF};
+
+/// Placement of shared geometry, with its own transform.
+///
+/// Any number of instances can refer to the same geometry (and its bvh), so
+/// memory use depends on the number of unique objects, not on the number of
+/// copies in the scene.
+#[derive(Debug)]
+pub struct Instance<F: Float> {
+    xfrm: Transform<F>,
+    motion: Option<Transform<F>>,
+    geo: Arc<dyn FiniteGeometry<F>>,
+    aabb: Aabb,
+}
+
+#[cfg(feature = "gui")]
+impl<F: Float> Interactive<F> for Instance<F> {
+    fn ui_center(&mut self, ui: &mut egui::Ui, camera: &Camera<F>, rect: &egui::Rect) -> bool {
+        crate::gui::gizmo::gizmo_ui(ui, camera, self, rect)
+    }
+
+    fn ui_bounding_box(&mut self) -> Option<&Aabb> {
+        Some(&self.aabb)
+    }
+}
+
+geometry_impl_sceneobject!(Instance<F>, "Instance");
+geometry_impl_hastransform!(Instance<F>);
+aabb_impl_fm!(Instance<F>);
+
+impl<F: Float> FiniteGeometry<F> for Instance<F> {
+    fn recompute_aabb(&mut self) {
+        let bounds = self.geo.aabb();
+
+        let min = Vector::from_vec3(bounds.min);
+        let max = Vector::from_vec3(bounds.max);
+
+        self.aabb = build_aabb_motion(self, |xfrm| {
+            build_aabb_ranged(xfrm, [min.x, max.x], [min.y, max.y], [min.z, max.z])
+        });
+    }
+}
+
+impl<F: Float> Geometry<F> for Instance<F> {
+    fn intersect(&self, ray: &Ray<F>) -> Option<Maxel<F>> {
+        if ray.flags.contains(RF::StopAtGroup) {
+            let center = self
+                .transform_at(ray.time)
+                .pos_inv(Vector::from_vec3(self.center()));
+            return Some(ray.synthetic_hit(center, self));
+        }
+
+        let xfrm = self.transform_at(ray.time);
+        let r = ray.xfrm_inv(&xfrm);
+
+        self.geo.intersect(&r).map(|mut mxl| {
+            /* The shared geometry can only calculate these in its own
+             * coordinate system, so cache them before leaving it (see
+             * TriangleMesh::intersect) */
+            mxl.st();
+            mxl.uv();
+            mxl.nml();
+            mxl.tangents();
+            mxl.uv_footprint();
+
+            mxl.xfrm(&xfrm)
+        })
+    }
+
+    fn material(&mut self) -> Option<&mut dyn HasMaterial> {
+        None
+    }
+
+    fn transform(&mut self) -> Option<&mut dyn HasTransform<F>> {
+        Some(self)
+    }
+
+    fn to_sbt(&self, sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        let name = sbt.define(&self.geo)?;
+        let node = SbtNode::block("instance", SbtNode::block(name, SbtNode::Dict(vec![])));
+        Some(SbtNode::transform(&self.xfrm, self.motion.as_ref(), node))
+    }
+}
+
+impl<F: Float> Instance<F> {
+    const ICON: &'static str = egui_phosphor::regular::COPY;
+
+    pub fn new(geo: Arc<dyn FiniteGeometry<F>>, xfrm: Matrix4<F>) -> Self {
+        let mut res = Self {
+            xfrm: Transform::new(xfrm),
+            motion: None,
+            geo,
+            aabb: Aabb::empty(),
+        };
+        res.recompute_aabb();
+        res
+    }
+
+    /// The shared geometry placed by this instance
+    #[must_use]
+    pub fn geometry(&self) -> &Arc<dyn FiniteGeometry<F>> {
+        &self.geo
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use cgmath::{Matrix4, MetricSpace};
+
+    use super::Instance;
+    use crate::geometry::{FiniteGeometry, Geometry, Sphere};
+    use crate::types::{MaterialId, Ray, Vector, Vectorx};
+
+    #[test]
+    fn test_instance_shared() {
+        let sphere: Arc<dyn FiniteGeometry<f64>> =
+            Arc::new(Sphere::new(Matrix4::from_scale(0.5), MaterialId::NULL));
+
+        let left = Instance::new(sphere.clone(), Matrix4::from_translation(-Vector::UNIT_X));
+        let right = Instance::new(sphere.clone(), Matrix4::from_translation(Vector::UNIT_X));
+        assert_eq!(Arc::strong_count(&sphere), 3);
+
+        /* Each copy is hit at its own position */
+        for (inst, x) in [(&left, -1.0), (&right, 1.0)] {
+            let ray = Ray::new(Vector::new(x, 0.0, 5.0), -Vector::UNIT_Z);
+            let hit = inst.intersect(&ray).unwrap();
+            assert!(hit.pos.distance(Vector::new(x, 0.0, 0.5)) < 1e-9);
+        }
+
+        let ray = Ray::new(Vector::new(0.0, 0.0, 5.0), -Vector::UNIT_Z);
+        assert!(left.intersect(&ray).is_none());
+    }
+}
@@ -3,9 +3,12 @@ use std::fmt::Debug;
 use glam::f32::Vec3;
 use rtbvh::Aabb;
 
+use crate::format::sbtwriter::{SbtNode, SbtWriter};
 use crate::material::HasMaterial;
 use crate::scene::{Interactive, SceneObject};
-use crate::types::{Float, Maxel, Point, Ray, Transform, Vector, Vectorx};
+use crate::types::{
+    Color, Float, HasTransform, Maxel, Point, RResult, Ray, Transform, Vector, Vectorx,
+};
 use crate::vec3;
 
 pub trait Geometry<F: Float>: SceneObject<F> + Debug + Sync + Send {
@@ -19,7 +22,46 @@ pub trait Geometry<F: Float>: SceneObject<F> + Debug + Sync + Send {
     fn st(&self, _maxel: &mut Maxel<F>) -> Point<F> {
         Point::ZERO
     }
+    /// Vertex colour at intersection (white for objects without vertex colours)
+    fn color(&self, _maxel: &mut Maxel<F>) -> Color<F> {
+        Color::WHITE
+    }
+    /// Tangent and bitangent at intersection, matching the (x, y) axes of
+    /// normal maps
+    fn tangents(&self, maxel: &mut Maxel<F>) -> (Vector<F>, Vector<F>) {
+        maxel.nml().surface_tangents()
+    }
+    /// Change in position per unit of texture coordinates (dP/du, dP/dv)
+    /// at intersection, in the coordinate system of the intersecting ray.
+    /// Used for texture filtering, which is skipped if this is `None`
+    fn uv_derivatives(&self, _maxel: &mut Maxel<F>) -> Option<(Vector<F>, Vector<F>)> {
+        None
+    }
+    /// Random point on the surface at `time`, the normal of the side it
+    /// faces, and the area it stands for (one over the density of points
+    /// there), all in world coordinates. Objects that return `None` can not
+    /// be used as emitters (see [`crate::light::Emitter`])
+    fn sample_surface(&self, _time: F) -> Option<(Vector<F>, Vector<F>, F)> {
+        None
+    }
+    /// Like [`Self::sample_surface`], but only over the part of the surface
+    /// that can face `pos` (in world coordinates), for objects that know it
+    fn sample_surface_from(&self, time: F, _pos: Vector<F>) -> Option<(Vector<F>, Vector<F>, F)> {
+        self.sample_surface(time)
+    }
     fn material(&mut self) -> Option<&mut dyn HasMaterial>;
+    fn transform(&mut self) -> Option<&mut dyn HasTransform<F>> {
+        None
+    }
+    /// Rebuild the bounds of this object after its children (see
+    /// [`SceneObject::get_child`]) have moved, for objects made of others
+    fn update_bounds(&mut self) -> RResult<()> {
+        Ok(())
+    }
+    /// Description of this object in SBT format, or `None` if it can not be saved
+    fn to_sbt(&self, _sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        None
+    }
 }
 
 pub trait FiniteGeometry<F: Float>: Geometry<F> + SceneObject<F> + rtbvh::Primitive {
@@ -47,9 +89,41 @@ where
         (**self).st(maxel)
     }
 
+    fn color(&self, maxel: &mut Maxel<F>) -> Color<F> {
+        (**self).color(maxel)
+    }
+
+    fn tangents(&self, maxel: &mut Maxel<F>) -> (Vector<F>, Vector<F>) {
+        (**self).tangents(maxel)
+    }
+
+    fn uv_derivatives(&self, maxel: &mut Maxel<F>) -> Option<(Vector<F>, Vector<F>)> {
+        (**self).uv_derivatives(maxel)
+    }
+
+    fn sample_surface(&self, time: F) -> Option<(Vector<F>, Vector<F>, F)> {
+        (**self).sample_surface(time)
+    }
+
+    fn sample_surface_from(&self, time: F, pos: Vector<F>) -> Option<(Vector<F>, Vector<F>, F)> {
+        (**self).sample_surface_from(time, pos)
+    }
+
     fn material(&mut self) -> Option<&mut dyn HasMaterial> {
         (**self).material()
     }
+
+    fn transform(&mut self) -> Option<&mut dyn HasTransform<F>> {
+        (**self).transform()
+    }
+
+    fn update_bounds(&mut self) -> RResult<()> {
+        (**self).update_bounds()
+    }
+
+    fn to_sbt(&self, sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        (**self).to_sbt(sbt)
+    }
 }
 
 impl<F: Float> SceneObject<F> for Box<(dyn FiniteGeometry<F> + 'static)> {
@@ -72,6 +146,10 @@ impl<F: Float> SceneObject<F> for Box<(dyn FiniteGeometry<F> + 'static)> {
     fn get_object(&mut self, id: usize) -> Option<&mut dyn Geometry<F>> {
         (**self).get_object(id)
     }
+
+    fn get_child(&mut self, idx: usize) -> Option<&mut dyn Geometry<F>> {
+        (**self).get_child(idx)
+    }
 }
 
 impl<F: Float> SceneObject<F> for Box<(dyn Geometry<F> + 'static)> {
@@ -94,6 +172,10 @@ impl<F: Float> SceneObject<F> for Box<(dyn Geometry<F> + 'static)> {
     fn get_object(&mut self, id: usize) -> Option<&mut dyn Geometry<F>> {
         (**self).get_object(id)
     }
+
+    fn get_child(&mut self, idx: usize) -> Option<&mut dyn Geometry<F>> {
+        (**self).get_child(idx)
+    }
 }
 
 impl<F: Float> rtbvh::Primitive for Box<dyn FiniteGeometry<F> + 'static> {
@@ -130,6 +212,81 @@ pub fn build_aabb_symmetric<F: Float>(xfrm: &Transform<F>, x: F, y: F, z: F) ->
     build_aabb_ranged(xfrm, [-x, x], [-y, y], [-z, z])
 }
 
+/// Radius of the cap hit at `hit` on a cone along z, or `None` for the side
+fn cone_cap<F: Float>(hit: Vector<F>, bot_r: F, top_r: F, height: F, capped: bool) -> Option<F> {
+    if !capped {
+        None
+    } else if hit.z.abs() < F::BIAS3 {
+        Some(bot_r)
+    } else if (hit.z - height).abs() < F::BIAS3 {
+        Some(top_r)
+    } else {
+        None
+    }
+}
+
+/// Texture coordinates at `hit` (in object coordinates) on a cone along z:
+/// around and up the side, or across the caps like a [`Disc`]
+pub(crate) fn cone_uv<F: Float>(
+    hit: Vector<F>,
+    bot_r: F,
+    top_r: F,
+    height: F,
+    capped: bool,
+) -> Point<F> {
+    match cone_cap(hit, bot_r, top_r, height, capped) {
+        Some(r) => Point::new((hit.x / r + F::ONE) / F::TWO, (hit.y / r + F::ONE) / F::TWO),
+        None => Point::new(
+            hit.y.atan2(hit.x) / (F::TWO * F::PI()) + F::HALF,
+            hit.z / height,
+        ),
+    }
+}
+
+/// Derivatives of position with respect to [`cone_uv`] at `hit` (in object
+/// coordinates)
+pub(crate) fn cone_uv_derivatives<F: Float>(
+    hit: Vector<F>,
+    bot_r: F,
+    top_r: F,
+    height: F,
+    capped: bool,
+) -> (Vector<F>, Vector<F>) {
+    match cone_cap(hit, bot_r, top_r, height, capped) {
+        Some(r) => (Vector::UNIT_X * (r * F::TWO), Vector::UNIT_Y * (r * F::TWO)),
+        None => {
+            let (sin, cos) = hit.y.atan2(hit.x).sin_cos();
+            let slope = top_r - bot_r;
+            (
+                vec3!(-hit.y, hit.x, F::ZERO) * (F::TWO * F::PI()),
+                vec3!(slope * cos, slope * sin, height),
+            )
+        }
+    }
+}
+
+/// Bounding box covering the whole motion of `obj`, where `aabb` builds the
+/// bounding box for a single transform
+pub fn build_aabb_motion<F: Float>(
+    obj: &impl HasTransform<F>,
+    aabb: impl Fn(&Transform<F>) -> Aabb,
+) -> Aabb {
+    /* Rotation can sweep outside the start and end positions, so sample the motion */
+    const STEPS: u32 = 16;
+
+    let start = obj.get_transform();
+    let Some(end) = obj.get_motion() else {
+        return aabb(start);
+    };
+
+    let mut res = Aabb::empty();
+    for i in 0..=STEPS {
+        let t = F::from_u32(i) / F::from_u32(STEPS);
+        res.grow_bb(&aabb(&start.interpolate(end, t)));
+    }
+    res
+}
+
 macro_rules! aabb_impl_fm {
     ( $t:ty ) => {
         impl<F: Float> rtbvh::Primitive for $t {
@@ -171,6 +328,15 @@ macro_rules! geometry_impl_hastransform {
                 self.xfrm = *xfrm;
                 self.recompute_aabb();
             }
+
+            fn get_motion(&self) -> Option<&Transform<F>> {
+                self.motion.as_ref()
+            }
+
+            fn set_motion(&mut self, end: Option<&Transform<F>>) {
+                self.motion = end.copied();
+                self.recompute_aabb();
+            }
         }
     };
 }
@@ -192,7 +358,9 @@ macro_rules! geometry_impl_hasmaterial {
 mod cone;
 mod cube;
 mod cylinder;
+mod disc;
 mod group;
+mod instance;
 mod plane;
 mod sphere;
 mod square;
@@ -202,7 +370,9 @@ mod trianglemesh;
 pub use cone::Cone;
 pub use cube::Cube;
 pub use cylinder::Cylinder;
+pub use disc::Disc;
 pub use group::Group;
+pub use instance::Instance;
 pub use plane::Plane;
 pub use sphere::Sphere;
 pub use square::Square;
@@ -1,5 +1,6 @@
 use cgmath::InnerSpace;
 
+use crate::format::sbtwriter::{SbtNode, SbtWriter};
 use crate::geometry::Geometry;
 use crate::material::HasMaterial;
 use crate::point;
@@ -43,6 +44,10 @@ impl<F: Float> Geometry<F> for Plane<F> {
         self.normal
     }
 
+    fn uv_derivatives(&self, _maxel: &mut Maxel<F>) -> Option<(Vector<F>, Vector<F>)> {
+        Some((self.u / self.u.magnitude2(), self.v / self.v.magnitude2()))
+    }
+
     fn intersect(&self, ray: &Ray<F>) -> Option<Maxel<F>> {
         let t = ray.intersect_plane(&self.pos, &self.dir1, &self.dir2)?;
         Some(ray.hit_at(ray.extend(t), t, self, self.mat))
@@ -51,6 +56,15 @@ impl<F: Float> Geometry<F> for Plane<F> {
     fn material(&mut self) -> Option<&mut dyn HasMaterial> {
         Some(self)
     }
+
+    fn to_sbt(&self, sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        let props = vec![
+            ("position", SbtNode::vector(self.pos)),
+            ("u", SbtNode::vector(self.u)),
+            ("v", SbtNode::vector(self.v)),
+        ];
+        Some(sbt.object("plane", self.mat, props))
+    }
 }
 
 impl<F: Float> Plane<F> {
@@ -1,18 +1,20 @@
 #[cfg(feature = "gui")]
 use crate::types::Camera;
 
-use cgmath::Matrix4;
+use cgmath::{InnerSpace, Matrix4};
 use glam::Vec3;
 use rtbvh::Aabb;
 
-use crate::geometry::{build_aabb_symmetric, FiniteGeometry, Geometry};
-use crate::material::HasMaterial;
+use crate::format::sbtwriter::{SbtNode, SbtWriter};
+use crate::geometry::{build_aabb_motion, build_aabb_symmetric, FiniteGeometry, Geometry};
+use crate::material::{rand_unit, HasMaterial};
 use crate::scene::{Interactive, SceneObject};
 use crate::types::{Float, HasTransform, MaterialId, Maxel, Ray, Transform, Vector, Vectorx};
 
 #[derive(Debug)]
 pub struct Sphere<F: Float> {
     xfrm: Transform<F>,
+    motion: Option<Transform<F>>,
     mat: MaterialId,
     aabb: Aabb,
 }
@@ -42,13 +44,16 @@ geometry_impl_hasmaterial!(Sphere<F>);
 
 impl<F: Float> FiniteGeometry<F> for Sphere<F> {
     fn recompute_aabb(&mut self) {
-        self.aabb = build_aabb_symmetric(&self.xfrm, F::ONE, F::ONE, F::ONE);
+        self.aabb = build_aabb_motion(self, |xfrm| {
+            build_aabb_symmetric(xfrm, F::ONE, F::ONE, F::ONE)
+        });
     }
 }
 
 impl<F: Float> Geometry<F> for Sphere<F> {
     fn intersect(&self, ray: &Ray<F>) -> Option<Maxel<F>> {
-        let r = ray.xfrm_inv(&self.xfrm);
+        let xfrm = self.transform_at(ray.time);
+        let r = ray.xfrm_inv(&xfrm);
 
         let result = r.intersect_unit_sphere()?;
         let intersect = r.extend(result);
@@ -56,17 +61,70 @@ impl<F: Float> Geometry<F> for Sphere<F> {
         Some(ray.hit_at(intersect, result, self, self.mat))
     }
 
+    fn sample_surface(&self, time: F) -> Option<(Vector<F>, Vector<F>, F)> {
+        let xfrm = self.transform_at(time);
+        let z = F::ONE - F::TWO * rand_unit();
+        let r = (F::ONE - z * z).max(F::ZERO).sqrt();
+        let (sin, cos) = (F::TWO * F::PI() * rand_unit()).sin_cos();
+        let pos = Vector::new(r * cos, r * sin, z);
+        let area = F::from_u32(4) * F::PI() * xfrm.area(pos);
+        Some((xfrm.pos(pos), xfrm.nml(pos), area))
+    }
+
+    fn sample_surface_from(&self, time: F, pos: Vector<F>) -> Option<(Vector<F>, Vector<F>, F)> {
+        let xfrm = self.transform_at(time);
+        let axis = xfrm.pos_inv(pos);
+        let dist = axis.magnitude();
+        if dist <= F::ONE {
+            return self.sample_surface(time);
+        }
+
+        /* Uniform over the cap visible from `pos`, which starts where the
+         * tangent lines touch (at 1/dist along the axis), and has an area
+         * of 2 pi (1 - h) on the unit sphere */
+        let h = dist.recip();
+        let z = h + (F::ONE - h) * rand_unit();
+        let r = (F::ONE - z * z).max(F::ZERO).sqrt();
+        let (sin, cos) = (F::TWO * F::PI() * rand_unit()).sin_cos();
+        let (u, v) = (axis / dist).surface_tangents();
+        let pt = u * (r * cos) + v * (r * sin) + axis * (z / dist);
+        let area = F::TWO * F::PI() * (F::ONE - h) * xfrm.area(pt);
+        Some((xfrm.pos(pt), xfrm.nml(pt), area))
+    }
+
     fn material(&mut self) -> Option<&mut dyn HasMaterial> {
         Some(self)
     }
 
+    fn to_sbt(&self, sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        let node = sbt.object("sphere", self.mat, vec![]);
+        Some(SbtNode::transform(&self.xfrm, self.motion.as_ref(), node))
+    }
+
+    fn transform(&mut self) -> Option<&mut dyn HasTransform<F>> {
+        Some(self)
+    }
+
     fn normal(&self, maxel: &mut Maxel<F>) -> Vector<F> {
-        self.xfrm.nml(maxel.hit)
+        self.transform_at(maxel.time).nml(maxel.hit)
     }
 
     fn uv(&self, maxel: &mut Maxel<F>) -> crate::types::Point<F> {
         maxel.hit.polar_uv().into()
     }
+
+    fn uv_derivatives(&self, maxel: &mut Maxel<F>) -> Option<(Vector<F>, Vector<F>)> {
+        /* Longitude around y (u), and latitude along y (v) */
+        let p = maxel.hit;
+        let r = (p.x * p.x + p.z * p.z).sqrt();
+        if r < F::BIAS {
+            return None;
+        }
+        let xfrm = self.transform_at(maxel.time);
+        let dpdu = Vector::new(p.z, F::ZERO, -p.x) * (F::TWO * F::PI());
+        let dpdv = Vector::new(-p.y * p.x / r, r, -p.y * p.z / r) * F::PI();
+        Some((xfrm.dir(dpdu), xfrm.dir(dpdv)))
+    }
 }
 
 impl<F: Float> Sphere<F> {
@@ -75,6 +133,7 @@ impl<F: Float> Sphere<F> {
     pub fn new(xfrm: Matrix4<F>, mat: MaterialId) -> Self {
         let mut res = Self {
             xfrm: Transform::new(xfrm),
+            motion: None,
             mat,
             aabb: Aabb::empty(),
         };
@@ -93,9 +152,10 @@ impl<F: Float> Sphere<F> {
 mod tests {
     extern crate test;
 
+    use std::f64::consts::PI;
     use std::hint::black_box;
 
-    use cgmath::{Deg, Matrix4};
+    use cgmath::{Deg, InnerSpace, Matrix4};
     use rand::Rng;
     use test::Bencher;
 
@@ -189,6 +249,41 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_sample_visible_cap() {
+        let obj = Sphere::place(Vector::UNIT_X, 2.0, MaterialId::NULL);
+        let pos = Vector::new(1.0, 0.0, 5.0);
+        const N: usize = 40000;
+
+        /* Light reaching `pos` from a surface point, as seen by an emitter */
+        let weight = |pt: Vector<F>, nml: Vector<F>| {
+            let dir = pt - pos;
+            (-dir.normalize().dot(nml.normalize())).max(0.0) / dir.magnitude2()
+        };
+
+        /* The cap starts at 1/2.5 of the radius, and covers 0.3 of a
+         * sphere of radius 2 */
+        let mut cap = 0.0;
+        for _ in 0..N {
+            let (pt, nml, area) = obj.sample_surface_from(0.0, pos).unwrap();
+            assert!((pos - pt).dot(nml) >= -1e-9);
+            assert!((area - 0.3 * 16.0 * PI).abs() < 1e-9);
+            cap += weight(pt, nml) * area;
+        }
+
+        let mut all = 0.0;
+        for _ in 0..N {
+            let (pt, nml, area) = obj.sample_surface(0.0).unwrap();
+            assert!((area - 16.0 * PI).abs() < 1e-9);
+            all += weight(pt, nml) * area;
+        }
+
+        /* Both add up to the solid angle of the sphere */
+        let solid = 2.0 * PI * (1.0 - (1.0 - 0.4f64 * 0.4).sqrt());
+        assert!((cap / N as f64 / solid - 1.0).abs() < 0.03, "{cap}");
+        assert!((all / N as f64 / solid - 1.0).abs() < 0.05, "{all}");
+    }
+
     // benchmark methods with a mix of hit or miss rays
 
     #[bench]
@@ -5,8 +5,9 @@ use cgmath::Matrix4;
 use glam::Vec3;
 use rtbvh::Aabb;
 
-use crate::geometry::{build_aabb_symmetric, FiniteGeometry, Geometry};
-use crate::material::HasMaterial;
+use crate::format::sbtwriter::{SbtNode, SbtWriter};
+use crate::geometry::{build_aabb_motion, build_aabb_symmetric, FiniteGeometry, Geometry};
+use crate::material::{rand_unit, HasMaterial};
 use crate::point;
 use crate::scene::{Interactive, SceneObject};
 use crate::types::{
@@ -16,6 +17,7 @@ use crate::types::{
 #[derive(Debug)]
 pub struct Square<F: Float> {
     xfrm: Transform<F>,
+    motion: Option<Transform<F>>,
     mat: MaterialId,
     aabb: Aabb,
 }
@@ -45,13 +47,16 @@ geometry_impl_hasmaterial!(Square<F>);
 
 impl<F: Float> FiniteGeometry<F> for Square<F> {
     fn recompute_aabb(&mut self) {
-        self.aabb = build_aabb_symmetric(&self.xfrm, F::HALF, F::HALF, F::ZERO);
+        self.aabb = build_aabb_motion(self, |xfrm| {
+            build_aabb_symmetric(xfrm, F::HALF, F::HALF, F::ZERO)
+        });
     }
 }
 
 impl<F: Float> Geometry<F> for Square<F> {
     fn intersect(&self, ray: &Ray<F>) -> Option<Maxel<F>> {
-        let r = ray.xfrm_inv(&self.xfrm);
+        let xfrm = self.transform_at(ray.time);
+        let r = ray.xfrm_inv(&xfrm);
 
         if r.dir.z.is_zero() {
             return None;
@@ -83,14 +88,39 @@ impl<F: Float> Geometry<F> for Square<F> {
 
         Some(
             ray.hit_at(r.extend(t), t, self, self.mat)
-                .with_normal(self.xfrm.nml(normal))
+                .with_normal(xfrm.nml(normal))
                 .with_uv(point!(p.x, p.y)),
         )
     }
 
+    fn uv_derivatives(&self, maxel: &mut Maxel<F>) -> Option<(Vector<F>, Vector<F>)> {
+        let xfrm = self.transform_at(maxel.time);
+        Some((xfrm.dir(Vector::UNIT_X), xfrm.dir(Vector::UNIT_Y)))
+    }
+
+    fn sample_surface(&self, time: F) -> Option<(Vector<F>, Vector<F>, F)> {
+        let xfrm = self.transform_at(time);
+        let pos = Vector::new(
+            rand_unit::<F>() - F::HALF,
+            rand_unit::<F>() - F::HALF,
+            F::ZERO,
+        );
+        let area = xfrm.area(Vector::UNIT_Z);
+        Some((xfrm.pos(pos), xfrm.nml(Vector::UNIT_Z), area))
+    }
+
     fn material(&mut self) -> Option<&mut dyn HasMaterial> {
         Some(self)
     }
+
+    fn to_sbt(&self, sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        let node = sbt.object("square", self.mat, vec![]);
+        Some(SbtNode::transform(&self.xfrm, self.motion.as_ref(), node))
+    }
+
+    fn transform(&mut self) -> Option<&mut dyn HasTransform<F>> {
+        Some(self)
+    }
 }
 
 impl<F: Float> Square<F> {
@@ -100,6 +130,7 @@ impl<F: Float> Square<F> {
         let mut res = Self {
             mat,
             xfrm: Transform::new(xfrm),
+            motion: None,
             aabb: Aabb::empty(),
         };
         res.recompute_aabb();
@@ -5,10 +5,10 @@ use glam::Vec3;
 use rtbvh::{Aabb, SpatialTriangle};
 
 use crate::geometry::{FiniteGeometry, Geometry};
-use crate::material::HasMaterial;
+use crate::material::{rand_unit, HasMaterial};
 use crate::point;
 use crate::scene::{Interactive, SceneObject};
-use crate::types::{Float, MaterialId, Maxel, Point, Ray, Vector, Vectorx};
+use crate::types::{Color, Float, MaterialId, Maxel, Point, Ray, Vector, Vectorx};
 
 #[derive(Clone, Debug)]
 pub struct Triangle<F: Float> {
@@ -20,9 +20,16 @@ pub struct Triangle<F: Float> {
     pub(crate) nb: Vector<F>,
     pub(crate) nc: Vector<F>,
 
-    ta: Point<F>,
-    tb: Point<F>,
-    tc: Point<F>,
+    pub(crate) ta: Point<F>,
+    pub(crate) tb: Point<F>,
+    pub(crate) tc: Point<F>,
+
+    /// Optional per-vertex colours
+    pub(crate) colors: Option<[Color<F>; 3]>,
+
+    /// Per-vertex tangents (along texture u) and bitangent handedness, if
+    /// the texture coordinates are not degenerate
+    pub(crate) tangents: Option<[(Vector<F>, F); 3]>,
 
     pub(crate) edge1: Vector<F>,
     pub(crate) edge2: Vector<F>,
@@ -30,7 +37,7 @@ pub struct Triangle<F: Float> {
 
     aabb: Aabb,
 
-    mat: MaterialId,
+    pub(crate) mat: MaterialId,
 }
 
 #[cfg(feature = "gui")]
@@ -102,6 +109,38 @@ impl<F: Float> Triangle<F> {
         let w = F::ONE - u - v;
         (self.ta * w) + (self.tb * u) + (self.tc * v)
     }
+
+    fn interpolate_tangents(&self, u: F, v: F, nml: Vector<F>) -> Option<(Vector<F>, Vector<F>)> {
+        let [(ta, sa), (tb, sb), (tc, sc)] = self.tangents?;
+        let w = F::ONE - u - v;
+        let sign = sa * w + sb * u + sc * v;
+        let tng = ta * w + tb * u + tc * v;
+        let (tng, sign) = crate::mesh::vertex_tangent(nml, tng, nml.cross(tng) * sign)?;
+
+        /* Texture v points down in image space, while normal maps point up */
+        Some((tng, -nml.cross(tng) * sign))
+    }
+
+    /// Tangent and bitangent of the face, along the texture (u, v)
+    /// directions, or `None` if the texture coordinates are degenerate
+    pub(crate) fn face_tangents(&self) -> Option<(Vector<F>, Vector<F>)> {
+        let d1 = self.tb - self.ta;
+        let d2 = self.tc - self.ta;
+        let det = d1.x * d2.y - d2.x * d1.y;
+        if det.is_zero() {
+            return None;
+        }
+        let t = (self.edge1 * d2.y - self.edge2 * d1.y) / det;
+        let b = (self.edge2 * d1.x - self.edge1 * d2.x) / det;
+        Some((t, b))
+    }
+
+    fn interpolate_color(&self, u: F, v: F) -> Color<F> {
+        self.colors.map_or(Color::WHITE, |[ca, cb, cc]| {
+            let w = F::ONE - u - v;
+            ca * w + cb * u + cc * v
+        })
+    }
 }
 
 impl<F: Float> Geometry<F> for Triangle<F> {
@@ -124,11 +163,37 @@ impl<F: Float> Geometry<F> for Triangle<F> {
         self.interpolate_uv(st.x, st.y)
     }
 
+    fn color(&self, hit: &mut Maxel<F>) -> Color<F> {
+        let st = hit.st();
+        self.interpolate_color(st.x, st.y)
+    }
+
+    fn uv_derivatives(&self, _hit: &mut Maxel<F>) -> Option<(Vector<F>, Vector<F>)> {
+        self.face_tangents()
+    }
+
+    fn tangents(&self, hit: &mut Maxel<F>) -> (Vector<F>, Vector<F>) {
+        let st = hit.st();
+        let nml = hit.nml();
+        self.interpolate_tangents(st.x, st.y, nml)
+            .unwrap_or_else(|| nml.surface_tangents())
+    }
+
     fn intersect(&self, ray: &Ray<F>) -> Option<Maxel<F>> {
         let t = ray.intersect_triangle4(&self.edge1, &self.edge2, &self.a)?;
         Some(ray.hit_at(ray.extend(t), t, self, self.mat))
     }
 
+    fn sample_surface(&self, _time: F) -> Option<(Vector<F>, Vector<F>, F)> {
+        let (mut u, mut v) = (rand_unit::<F>(), rand_unit::<F>());
+        if u + v > F::ONE {
+            (u, v) = (F::ONE - u, F::ONE - v);
+        }
+        let pos = self.a + self.edge1 * u + self.edge2 * v;
+        let area = self.edge1.cross(self.edge2).magnitude() / F::TWO;
+        Some((pos, self.interpolate_normal(u, v), area))
+    }
+
     fn material(&mut self) -> Option<&mut dyn HasMaterial> {
         Some(self)
     }
@@ -164,6 +229,8 @@ impl<F: Float> Triangle<F> {
             ta,
             tb,
             tc,
+            colors: None,
+            tangents: None,
             edge1,
             edge2,
             area2,
@@ -171,8 +238,21 @@ impl<F: Float> Triangle<F> {
             aabb: Aabb::empty(),
         };
         res.recompute_aabb();
+        res.tangents = res.face_tangents().and_then(|(t, b)| {
+            let tangent = |n| crate::mesh::vertex_tangent(n, t, b);
+            Some([tangent(na)?, tangent(nb)?, tangent(nc)?])
+        });
         res
     }
+
+    /// Set per-vertex colours, interpolated across the triangle
+    #[must_use]
+    pub const fn with_colors(self, ca: Color<F>, cb: Color<F>, cc: Color<F>) -> Self {
+        Self {
+            colors: Some([ca, cb, cc]),
+            ..self
+        }
+    }
 }
 
 #[cfg(test)]
@@ -209,6 +289,32 @@ mod tests {
         black_box(tri)
     }
 
+    #[test]
+    fn test_triangle_tangents() {
+        use crate::geometry::Geometry;
+        use cgmath::MetricSpace;
+
+        /* Texture u along -y, v (pointing down in the image) along -x */
+        let tri = Triangle::<F>::new(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(-1.0, 0.0, 0.0),
+            Vector::new(0.0, -1.0, 0.0),
+            Vector::UNIT_Z,
+            Vector::UNIT_Z,
+            Vector::UNIT_Z,
+            Point::new(0.0, 0.0),
+            Point::new(0.0, 1.0),
+            Point::new(1.0, 0.0),
+            MaterialId::NULL,
+        );
+
+        let ray = Ray::new(Vector::new(-0.25, -0.25, 1.0), -Vector::UNIT_Z);
+        let mut hit = tri.intersect(&ray).unwrap();
+        let (u, v) = hit.tangents();
+        assert!(u.distance(-Vector::UNIT_Y) < 1e-9);
+        assert!(v.distance(Vector::UNIT_X) < 1e-9);
+    }
+
     fn ray() -> Ray<F> {
         let ray = Ray::<F>::new(-Vector::UNIT_Z * F::TWO, Vector::UNIT_Z);
         black_box(ray)
@@ -1,21 +1,27 @@
 use std::num::NonZeroUsize;
 
-use cgmath::Matrix4;
+use cgmath::{InnerSpace, Matrix4};
 use glam::Vec3;
 use rtbvh::{Aabb, Bounds, Builder, Bvh, Primitive};
 
 #[cfg(feature = "gui")]
 use crate::types::Camera;
 
-use crate::geometry::{build_aabb_ranged, FiniteGeometry, Geometry, Triangle};
-use crate::material::HasMaterial;
+use crate::format::sbtwriter::{SbtNode, SbtWriter};
+use crate::geometry::{build_aabb_motion, build_aabb_ranged, FiniteGeometry, Geometry, Triangle};
+use crate::material::{rand_unit, HasMaterial};
 use crate::scene::{Interactive, SceneObject};
-use crate::types::{BvhExt, Float, HasTransform, Maxel, Ray, Transform, Vector, Vectorx, RF};
+use crate::types::{
+    BvhExt, Color, Float, HasTransform, MaterialId, Maxel, Ray, Transform, Vector, Vectorx, RF,
+};
 
 #[derive(Debug)]
 pub struct TriangleMesh<F: Float> {
     xfrm: Transform<F>,
+    motion: Option<Transform<F>>,
     pub tris: Vec<Triangle<F>>,
+    /// Running total of triangle areas, for sampling the surface
+    areas: Vec<F>,
     bvh: Bvh,
     aabb: Aabb,
 }
@@ -26,10 +32,12 @@ impl<F: Float> Interactive<F> for TriangleMesh<F> {
         let mut res = false;
         if ui.button("Face normals").clicked() {
             crate::mesh::face_normals(&mut self.tris);
+            crate::mesh::smooth_tangents(&mut self.tris);
             res |= true;
         }
         if ui.button("Smooth normals").clicked() {
             crate::mesh::smooth_normals(&mut self.tris);
+            crate::mesh::smooth_tangents(&mut self.tris);
             res |= true;
         }
         res
@@ -56,18 +64,23 @@ impl<F: Float> FiniteGeometry<F> for TriangleMesh<F> {
         let min = Vector::from_vec3(bounds.min);
         let max = Vector::from_vec3(bounds.max);
 
-        self.aabb = build_aabb_ranged(&self.xfrm, [min.x, max.x], [min.y, max.y], [min.z, max.z]);
+        self.aabb = build_aabb_motion(self, |xfrm| {
+            build_aabb_ranged(xfrm, [min.x, max.x], [min.y, max.y], [min.z, max.z])
+        });
     }
 }
 
 impl<F: Float> Geometry<F> for TriangleMesh<F> {
     fn intersect(&self, ray: &Ray<F>) -> Option<Maxel<F>> {
         if ray.flags.contains(RF::StopAtGroup) {
-            let center = self.xfrm.pos_inv(Vector::from_vec3(self.center()));
+            let center = self
+                .transform_at(ray.time)
+                .pos_inv(Vector::from_vec3(self.center()));
             return Some(ray.synthetic_hit(center, self));
         }
 
-        let r = ray.xfrm_inv(&self.xfrm);
+        let xfrm = self.transform_at(ray.time);
+        let r = ray.xfrm_inv(&xfrm);
 
         self.bvh
             .nearest_intersection(&r, &self.tris, &mut F::max_value())
@@ -79,21 +92,88 @@ impl<F: Float> Geometry<F> for TriangleMesh<F> {
                 mxl.st();
                 mxl.uv();
                 mxl.nml();
+                mxl.tangents();
+                mxl.uv_footprint();
 
                 /* Transform maxel from object space */
-                mxl.xfrm(&self.xfrm)
+                mxl.xfrm(&xfrm)
             })
     }
 
+    fn sample_surface(&self, time: F) -> Option<(Vector<F>, Vector<F>, F)> {
+        /* Pick a triangle with probability proportional to its area */
+        let total = *self.areas.last()?;
+        let target = rand_unit::<F>() * total;
+        let idx = self.areas.partition_point(|area| *area < target);
+        let tri = self.tris.get(idx)?;
+        let (pos, nml, _) = tri.sample_surface(time)?;
+
+        let xfrm = self.transform_at(time);
+        let face = tri.edge1.cross(tri.edge2).normalize();
+        Some((xfrm.pos(pos), xfrm.nml(nml), total * xfrm.area(face)))
+    }
+
     fn material(&mut self) -> Option<&mut dyn HasMaterial> {
         None
     }
+
+    fn to_sbt(&self, sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        /* A polymesh has a single material, so split the mesh by material */
+        let mut meshes: Vec<(MaterialId, Vec<&Triangle<F>>)> = vec![];
+        for tri in &self.tris {
+            match meshes.iter_mut().find(|(mat, _)| *mat == tri.mat) {
+                Some((_, tris)) => tris.push(tri),
+                None => meshes.push((tri.mat, vec![tri])),
+            }
+        }
+
+        let polymeshes = meshes.into_iter().map(|(mat, tris)| {
+            let vertices = |func: fn(&Triangle<F>) -> [SbtNode<F>; 3]| {
+                SbtNode::Tuple(tris.iter().flat_map(|tri| func(tri)).collect())
+            };
+            let faces = (0..)
+                .step_by(3)
+                .take(tris.len())
+                .map(|idx: i64| SbtNode::Tuple([idx, idx + 1, idx + 2].map(SbtNode::Int).to_vec()))
+                .collect();
+
+            let colors = tris
+                .iter()
+                .any(|tri| tri.colors.is_some())
+                .then(|| vertices(|t| t.colors.unwrap_or([Color::WHITE; 3]).map(SbtNode::color)));
+            let mut props = vec![
+                ("points", vertices(|t| [t.a, t.b, t.c].map(SbtNode::vector))),
+                (
+                    "normals",
+                    vertices(|t| [t.na, t.nb, t.nc].map(SbtNode::vector)),
+                ),
+                (
+                    "texture_uv",
+                    vertices(|t| [t.ta, t.tb, t.tc].map(SbtNode::point)),
+                ),
+                ("faces", SbtNode::Tuple(faces)),
+            ];
+            if let Some(colors) = colors {
+                props.push(("colors", colors));
+            }
+            sbt.object("polymesh", mat, props)
+        });
+
+        let node = SbtNode::Group(polymeshes.collect());
+        Some(SbtNode::transform(&self.xfrm, self.motion.as_ref(), node))
+    }
+
+    fn transform(&mut self) -> Option<&mut dyn HasTransform<F>> {
+        Some(self)
+    }
 }
 
 impl<F: Float> TriangleMesh<F> {
     const ICON: &'static str = egui_phosphor::regular::POLYGON;
 
-    pub fn new(tris: Vec<Triangle<F>>, xfrm: Matrix4<F>) -> Self {
+    pub fn new(mut tris: Vec<Triangle<F>>, xfrm: Matrix4<F>) -> Self {
+        crate::mesh::smooth_tangents(&mut tris);
+
         debug!("building bvh for {} triangles..", tris.len());
 
         let aabbs: Vec<Aabb> = tris.iter().map(rtbvh::Primitive::aabb).collect();
@@ -108,9 +188,19 @@ impl<F: Float> TriangleMesh<F> {
         .unwrap();
         /* .construct_locally_ordered_clustered().unwrap(); */
 
+        let areas = tris
+            .iter()
+            .scan(F::ZERO, |total, tri| {
+                *total += tri.area2 / F::TWO;
+                Some(*total)
+            })
+            .collect();
+
         let mut res = Self {
             xfrm: Transform::new(xfrm),
+            motion: None,
             tris,
+            areas,
             bvh,
             aabb: Aabb::empty(),
         };
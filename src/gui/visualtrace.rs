@@ -120,11 +120,10 @@ impl VisualTraceWidget {
         self.coord = Some(coord);
     }
 
-    pub fn aabb<F>(&mut self, scene: &BoxScene<F>, to_screen: &RectTransform, aabb: &rtbvh::Aabb)
+    pub fn aabb<F>(&mut self, cam: &Camera<F>, to_screen: &RectTransform, aabb: &rtbvh::Aabb)
     where
         F: Float,
     {
-        let cam = &scene.cameras[0];
         let mut vt = VisualTracer::new(to_screen, cam);
 
         let corners = aabb.all_corners().map(Vector::from_vec3);
@@ -164,7 +163,7 @@ impl VisualTraceWidget {
         self.shapes = vt.into_inner();
     }
 
-    pub fn update<F>(&mut self, scene: &BoxScene<F>, to_screen: &RectTransform)
+    pub fn update<F>(&mut self, scene: &BoxScene<F>, cam: &Camera<F>, to_screen: &RectTransform)
     where
         F: Float + From<f32>,
     {
@@ -172,8 +171,6 @@ impl VisualTraceWidget {
 
         let Some(coord) = self.coord else { return };
 
-        let cam = &scene.cameras[0];
-
         let ray = cam.get_ray(point!(coord.x, coord.y)).with_debug();
 
         let steps = DebugTracer::trace_single(scene, TRACE_STEPS, &ray);
@@ -41,6 +41,7 @@ pub mod geometry;
 pub mod light;
 pub mod material;
 pub mod mesh;
+pub mod pathtracer;
 pub mod sampler;
 pub mod scene;
 pub mod tracer;
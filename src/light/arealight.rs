@@ -1,17 +1,25 @@
 use cgmath::InnerSpace;
+use rand::seq::SliceRandom;
+use rand::Rng;
 
+use crate::format::sbtwriter::{SbtNode, SbtWriter};
 use crate::light::{Attenuation, Light, Lixel};
 use crate::scene::{Interactive, RayTracer, SceneObject};
 use crate::sceneobject_impl_body;
-use crate::types::{Color, Float, GridSamples, Maxel, Vector, Vectorx, RF};
+use crate::types::{Color, Float, Maxel, Point, SamplePattern, Vector, Vectorx, RF};
 
+/// Rectangular light, spanning `width` x `height` from `pos`, and emitting
+/// towards `dir`.
+///
+/// Soft shadows are computed by tracing `xres` x `yres` shadow rays to points
+/// on the light, distributed according to `pattern`. With a `threshold`, a
+/// first batch of rays is traced, and sampling stops early if they all agree
+/// on the light's visibility.
 #[derive(Debug)]
 pub struct AreaLight<F: Float> {
     pub attn: Attenuation<F>,
     pos: Vector<F>,
-    #[allow(dead_code)]
     dir: Vector<F>,
-    #[allow(dead_code)]
     upd: Vector<F>,
     pub color: Color<F>,
     pub width: F,
@@ -20,6 +28,8 @@ pub struct AreaLight<F: Float> {
     dir2: Vector<F>,
     xres: u32,
     yres: u32,
+    pattern: SamplePattern,
+    threshold: Option<F>,
 }
 
 impl<F: Float> AreaLight<F> {
@@ -50,9 +60,63 @@ impl<F: Float> AreaLight<F> {
             color,
             xres: 8,
             yres: 8,
+            pattern: SamplePattern::Jittered,
+            threshold: None,
         }
     }
 
+    /// Use `xres` x `yres` shadow rays (for primary rays; halved per bounce)
+    #[must_use]
+    pub fn with_samples(self, xres: u32, yres: u32) -> Self {
+        Self {
+            xres: xres.max(1),
+            yres: yres.max(1),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub const fn with_pattern(self, pattern: SamplePattern) -> Self {
+        Self { pattern, ..self }
+    }
+
+    /// Stop sampling early when the first shadow rays differ in visibility
+    /// by at most `threshold`
+    #[must_use]
+    pub const fn with_threshold(self, threshold: Option<F>) -> Self {
+        Self { threshold, ..self }
+    }
+
+    /// Center of the light surface
+    fn center(&self) -> Vector<F> {
+        self.pos + self.dir1 * (self.width / F::TWO) + self.dir2 * (self.height / F::TWO)
+    }
+
+    /// Sample points on the light, in the unit square. Grid patterns are
+    /// shuffled for adaptive sampling, so the first batch covers the whole
+    /// light, and Halton points get a random shift, so neighbouring pixels
+    /// do not share the same pattern.
+    fn points(&self, xres: u32, yres: u32) -> Vec<Point<F>> {
+        let mut rng = rand::thread_rng();
+        let mut points = self.pattern.points(xres, yres);
+
+        match self.pattern {
+            SamplePattern::Halton if xres * yres > 1 => {
+                let shift = Point::new(F::from_f32(rng.gen()), F::from_f32(rng.gen()));
+                for point in &mut points {
+                    let p = *point + shift;
+                    *point = Point::new(p.x.fract(), p.y.fract());
+                }
+            }
+            SamplePattern::Stratified | SamplePattern::Jittered if self.threshold.is_some() => {
+                points.shuffle(&mut rng);
+            }
+            _ => {}
+        }
+
+        points
+    }
+
     fn compute_dirs(dir: Vector<F>, upd: Vector<F>) -> (Vector<F>, Vector<F>) {
         let dir1 = dir.cross(upd);
         let dir2 = dir.cross(dir1);
@@ -79,6 +143,34 @@ impl<F: Float> Interactive<F> for AreaLight<F> {
         res |= controls::position(ui, &mut self.dir, "Direction");
         res |= controls::position(ui, &mut self.upd, "Up direction");
 
+        ui.label("Pattern");
+        let pattern = self.pattern;
+        egui::ComboBox::from_id_source("area-light-pattern")
+            .selected_text(self.pattern.to_string())
+            .show_ui(ui, |ui| {
+                for kind in SamplePattern::ALL {
+                    ui.selectable_value(&mut self.pattern, kind, kind.to_string());
+                }
+            });
+        ui.end_row();
+        res |= pattern != self.pattern;
+
+        ui.label("Adaptive");
+        let mut adaptive = self.threshold.is_some();
+        if ui.checkbox(&mut adaptive, "").changed() {
+            self.threshold = adaptive.then(|| F::from_f32(0.05));
+            res = true;
+        }
+        ui.end_row();
+
+        if let Some(threshold) = &mut self.threshold {
+            ui.label("Threshold");
+            res |= ui
+                .add(Slider::new(threshold, F::ZERO..=F::HALF).logarithmic(true))
+                .changed();
+            ui.end_row();
+        }
+
         ui.label("X resolution");
         res |= ui.add(Slider::new(&mut self.xres, 1..=32)).changed();
         ui.end_row();
@@ -113,10 +205,6 @@ where
     rand::distributions::Standard: rand::distributions::Distribution<F>,
 {
     fn contribution(&self, maxel: &mut Maxel<F>, rt: &dyn RayTracer<F>) -> Lixel<F> {
-        let mut color = Color::BLACK;
-
-        /* let mut rng = rand::thread_rng(); */
-
         let (xres, yres) = if maxel.flags.contains(RF::Preview) {
             (1, 1)
         } else {
@@ -126,28 +214,74 @@ where
             )
         };
 
-        let dist = GridSamples::new(self.width, self.height, xres, yres);
+        let points = if xres * yres == 1 {
+            vec![Point::new(F::HALF, F::HALF)]
+        } else {
+            self.points(xres, yres)
+        };
+
+        /* With a threshold, trace a quarter of the rays (but at least 4)
+         * before checking if they agree */
+        let probes = if self.threshold.is_some() {
+            (points.len() / 4).max(4)
+        } else {
+            points.len()
+        };
+
+        let mut color = Color::BLACK;
+        let mut count = 0;
+        let (mut vis_min, mut vis_max) = (F::ONE, F::ZERO);
+
+        for point in &points {
+            if count == probes {
+                if let Some(threshold) = self.threshold {
+                    if vis_max - vis_min <= threshold {
+                        break;
+                    }
+                }
+            }
+            count += 1;
 
-        for (rx, ry) in dist.iter() {
-            let pos = self.pos + self.dir1 * rx + self.dir2 * ry;
+            let pos =
+                self.pos + self.dir1 * (point.x * self.width) + self.dir2 * (point.y * self.height);
 
             let dir = maxel.pos.vector_to(pos);
             let len2 = dir.magnitude2();
             let len = len2.sqrt();
-            let col = self.attn.attenuate(self.color, len, len2);
+            let dir = dir / len;
+
+            /* The light only emits from its front side, with the usual
+             * cosine falloff of a lambertian emitter */
+            let cos = -dir.dot(self.dir);
+            if cos <= F::ZERO {
+                vis_min = F::ZERO;
+                continue;
+            }
 
+            let col = self.attn.attenuate(self.color, len, len2) * cos;
             let lixel = Lixel {
                 color: col,
-                dir: dir / len,
+                dir,
                 len2,
             };
 
-            color += rt.ray_shadow(maxel, &lixel).unwrap_or(lixel.color);
+            let res = rt.ray_shadow(maxel, &lixel).unwrap_or(lixel.color);
+
+            let total = col.max_channel();
+            let vis = if total > F::ZERO {
+                res.max_channel() / total
+            } else {
+                F::ZERO
+            };
+            vis_min = vis_min.min(vis);
+            vis_max = vis_max.max(vis);
+
+            color += res;
         }
 
-        color = color / F::from_u32(xres * yres);
+        color = color / F::from_usize(count);
 
-        let dir = maxel.pos.vector_to(self.pos);
+        let dir = maxel.pos.vector_to(self.center());
         let len2 = dir.magnitude2();
 
         Lixel {
@@ -156,4 +290,35 @@ where
             len2,
         }
     }
+
+    fn position(&mut self) -> Option<&mut Vector<F>> {
+        Some(&mut self.pos)
+    }
+
+    fn color(&mut self) -> Option<&mut Color<F>> {
+        Some(&mut self.color)
+    }
+
+    fn to_sbt(&self, _sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        let mut props = vec![
+            ("position", SbtNode::vector(self.pos)),
+            ("direction", SbtNode::vector(self.dir)),
+            ("updir", SbtNode::vector(self.upd)),
+            ("color", SbtNode::color(self.color)),
+            ("width", SbtNode::Float(self.width)),
+            ("height", SbtNode::Float(self.height)),
+            ("pattern", SbtNode::Str(self.pattern.to_string())),
+        ];
+        if self.xres == self.yres {
+            props.push(("samples", SbtNode::Int(self.xres.into())));
+        } else {
+            props.push(("xres", SbtNode::Int(self.xres.into())));
+            props.push(("yres", SbtNode::Int(self.yres.into())));
+        }
+        if let Some(threshold) = self.threshold {
+            props.push(("threshold", SbtNode::Float(threshold)));
+        }
+        props.extend(self.attn.to_sbt());
+        Some(SbtNode::block("area_light", SbtNode::Dict(props)))
+    }
 }
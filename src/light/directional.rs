@@ -1,5 +1,6 @@
 use cgmath::InnerSpace;
 
+use crate::format::sbtwriter::{SbtNode, SbtWriter};
 use crate::light::{Light, Lixel};
 use crate::scene::{Interactive, RayTracer, SceneObject};
 use crate::sceneobject_impl_body;
@@ -51,4 +52,16 @@ impl<F: Float> Light<F> for DirectionalLight<F> {
         }
         lixel
     }
+
+    fn color(&mut self) -> Option<&mut Color<F>> {
+        Some(&mut self.color)
+    }
+
+    fn to_sbt(&self, _sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        let props = vec![
+            ("direction", SbtNode::vector(self.dir)),
+            ("color", SbtNode::color(self.color)),
+        ];
+        Some(SbtNode::block("directional_light", SbtNode::Dict(props)))
+    }
 }
@@ -0,0 +1,263 @@
+use cgmath::InnerSpace;
+use rtbvh::Primitive;
+
+use crate::format::sbtwriter::{SbtNode, SbtWriter};
+use crate::geometry::FiniteGeometry;
+use crate::light::{Attenuation, Light, Lixel};
+use crate::scene::{Interactive, RayTracer, SceneObject};
+use crate::sceneobject_impl_body;
+use crate::types::{Color, Float, MaterialId, Maxel, Ray, Transform, Vector, Vectorx, RF};
+
+/// Light emitted by the surface of an object, like a sphere, disc or mesh.
+///
+/// Shadow rays are traced to `samples` random points on the part of the
+/// surface facing the lit point (see
+/// [`crate::geometry::Geometry::sample_surface_from`]), which only emits from
+/// the side its normals face. The object is not part of the scene geometry, so
+/// it does not cast shadows, but it is visible to camera and reflection rays,
+/// drawn with the material `mat` (usually a plain emissive colour).
+///
+/// Each sample is weighed by the solid angle of the area it stands for, so
+/// light already falls off with the square of the distance; `attn` adds to
+/// that (and is none by default in SBT files).
+#[derive(Debug)]
+pub struct Emitter<F: Float> {
+    pub attn: Attenuation<F>,
+    pub color: Color<F>,
+    geo: Box<dyn FiniteGeometry<F>>,
+    mat: MaterialId,
+    samples: u32,
+}
+
+impl<F: Float> Emitter<F> {
+    pub const ICON: &'static str = egui_phosphor::regular::LAMP;
+
+    pub fn new(
+        attn: Attenuation<F>,
+        color: Color<F>,
+        geo: Box<dyn FiniteGeometry<F>>,
+        mat: MaterialId,
+    ) -> Self {
+        Self {
+            attn,
+            color,
+            geo,
+            mat,
+            samples: 16,
+        }
+    }
+
+    /// Use `samples` shadow rays (for primary rays; halved per bounce)
+    #[must_use]
+    pub fn with_samples(self, samples: u32) -> Self {
+        Self {
+            samples: samples.max(1),
+            ..self
+        }
+    }
+}
+
+impl<F: Float> SceneObject<F> for Emitter<F> {
+    sceneobject_impl_body!("Emitter", Self::ICON);
+}
+
+impl<F: Float> Interactive<F> for Emitter<F> {
+    #[cfg(feature = "gui")]
+    fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        use crate::gui::controls;
+
+        let mut res = false;
+
+        res |= controls::color(ui, &mut self.color, "Color");
+        res |= controls::attenuation(ui, &mut self.attn);
+
+        ui.label("Samples");
+        res |= ui
+            .add(egui::Slider::new(&mut self.samples, 1..=256).logarithmic(true))
+            .changed();
+        ui.end_row();
+
+        if let Some(interactive) = self.geo.get_interactive() {
+            res |= interactive.ui(ui);
+        }
+
+        res
+    }
+}
+
+impl<F: Float> Light<F> for Emitter<F> {
+    fn contribution(&self, maxel: &mut Maxel<F>, rt: &dyn RayTracer<F>) -> Lixel<F> {
+        let samples = if maxel.flags.contains(RF::Preview) {
+            1
+        } else {
+            (self.samples >> maxel.lvl).max(1)
+        };
+
+        let mut color = Color::BLACK;
+        let mut target = Vector::ZERO;
+        let mut hits = 0;
+
+        for _ in 0..samples {
+            let Some((pos, nml, area)) = self.geo.sample_surface_from(maxel.time, maxel.pos) else {
+                break;
+            };
+
+            let dir = maxel.pos.vector_to(pos);
+            let len2 = dir.magnitude2();
+            let len = len2.sqrt();
+            let dir = dir / len;
+
+            /* Lambertian emitter: light falls off with the cosine to the normal */
+            let cos = -dir.dot(nml.normalize());
+            if cos <= F::ZERO {
+                continue;
+            }
+
+            /* Solid angle of the sampled area, over pi to match the
+             * lighting convention of Material::bsdf */
+            let lixel = Lixel {
+                color: self.attn.attenuate(self.color, len, len2) * (cos * area / (len2 * F::PI())),
+                dir,
+                len2,
+            };
+
+            color += rt.ray_shadow(maxel, &lixel).unwrap_or(lixel.color);
+            target += pos;
+            hits += 1;
+        }
+
+        /* Shade from the middle of the sampled points */
+        let target = if hits > 0 {
+            target / F::from_u32(hits)
+        } else {
+            Vector::from_vec3(self.geo.center())
+        };
+        let dir = maxel.pos.vector_to(target);
+        let len2 = dir.magnitude2();
+
+        Lixel {
+            color: color / F::from_u32(samples),
+            dir: dir.normalize(),
+            len2,
+        }
+    }
+
+    fn color(&mut self) -> Option<&mut Color<F>> {
+        Some(&mut self.color)
+    }
+
+    /// Emitters are placed by their shape, so this moves its origin
+    fn set_position(&mut self, pos: Vector<F>) {
+        if let Some(obj) = self.geo.transform() {
+            let mut xfrm = *obj.get_transform().matrix();
+            xfrm.w = pos.extend(F::ONE);
+            obj.set_transform(&Transform::new(xfrm));
+        }
+    }
+
+    fn intersect(&self, ray: &Ray<F>) -> Option<Maxel<F>> {
+        let mut maxel = self.geo.intersect(ray)?;
+        maxel.mat = self.mat;
+        Some(maxel)
+    }
+
+    fn to_sbt(&self, sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        let mut props = vec![
+            ("color", SbtNode::color(self.color)),
+            ("samples", SbtNode::Int(self.samples.into())),
+            ("shape", self.geo.to_sbt(sbt)?),
+        ];
+        props.extend(self.attn.to_sbt());
+        Some(SbtNode::block("emitter", SbtNode::Dict(props)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{InnerSpace, MetricSpace};
+
+    use crate::format::sbt2::tests::{load, save};
+    use crate::light::Light;
+    use crate::tracer::Tracer;
+    use crate::types::{Ray, Vector, Vectorx};
+
+    #[test]
+    fn test_sbt_emitters() {
+        let scene = load(
+            r"
+SBT-raytracer 1.0
+sphere_light { position = (0, 2, 0); radius = 0.5; color = (4, 4, 4); }
+disc_light { position = (2, 2, 0); direction = (0, -1, 0); color = (1, 1, 2); samples = 4; }
+emitter { color = (1, 1, 1); shape = translate(0, 0, -2, square { }); }
+",
+        );
+        assert_eq!(scene.lights.len(), 3);
+        assert_eq!(scene.root.len(), 0);
+
+        /* Emitters are visible to camera rays, drawn in their own colour */
+        let ray = Ray::new(Vector::new(0.0, 2.0, 10.0), -Vector::UNIT_Z);
+        let hit = scene.intersect(&ray).unwrap();
+        assert!(hit.pos.distance(Vector::new(0.0, 2.0, 0.5)) < 1e-9);
+        assert!(scene.intersect_emitter(&ray).unwrap().1);
+
+        let text = save(&scene);
+        let copy = load(&text);
+        assert_eq!(copy.lights.len(), 3);
+        assert_eq!(save(&copy), text);
+    }
+
+    #[test]
+    fn test_sbt_animate_emitter() {
+        let mut scene = load(
+            r"
+SBT-raytracer 1.0
+sphere_light { position = (0, 0, 0); radius = 0.5; color = (1, 1, 1);
+    animate = { position = (key(0, (0, 0, 0)), key(2, (0, 4, 0))); }; }
+",
+        );
+        assert_eq!(scene.animation.lights.len(), 1);
+
+        /* The shape of the light moves with it */
+        let ray = Ray::new(Vector::new(0.01, 2.0, 10.0), -Vector::UNIT_Z);
+        assert!(scene.lights[0].intersect(&ray).is_none());
+        scene.set_frame(1.0).unwrap();
+        let hit = scene.lights[0].intersect(&ray).unwrap();
+        assert!(hit.pos.distance(Vector::new(0.01, 2.0, 0.5)) < 1e-3);
+
+        let copy = load(&save(&scene));
+        assert_eq!(copy.animation.lights.len(), 1);
+    }
+
+    #[test]
+    fn test_emitter_solid_angle() {
+        let scene = load(
+            r"
+SBT-raytracer 1.0
+disc_light { position = (0, 10, 0); direction = (0, -1, 0); radius = 0.1; color = (1, 1, 1); }
+sphere_light { position = (0, 10, 0); radius = 1; color = (1, 1, 1); samples = 4096; }
+scale(20, rotate(1, 0, 0, -1.5708, square { }))
+",
+        );
+        let tracer = Tracer::new(&scene);
+        let ray = Ray::new(
+            Vector::new(0.0, 5.0, 0.0),
+            Vector::new(0.001, -1.0, 0.002).normalize(),
+        );
+
+        /* Lights as bright as their colour cast the solid angle they
+         * cover (over pi, like all lights) */
+        for (light, radius) in scene.lights.iter().zip([0.1f64, 1.0]) {
+            let mut maxel = scene.intersect(&ray).unwrap();
+            let lixel = light.contribution(&mut maxel, &tracer);
+            let sin2 = radius * radius / 100.0;
+            let solid = 2.0 * std::f64::consts::PI * (1.0 - (1.0 - sin2).sqrt());
+            let expected = solid / std::f64::consts::PI;
+            assert!(
+                (lixel.color.g / expected - 1.0).abs() < 0.05,
+                "{radius}: {:?}",
+                lixel.color
+            );
+            assert!(lixel.dir.dot(Vector::UNIT_Y) > 0.99);
+        }
+    }
+}
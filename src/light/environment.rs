@@ -0,0 +1,402 @@
+use std::fmt::{self, Debug};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use cgmath::InnerSpace;
+use image::Rgb32FImage;
+
+use crate::format::sbtwriter::{SbtNode, SbtWriter};
+use crate::light::{Light, Lixel};
+use crate::material::rand_unit;
+use crate::scene::{Interactive, RayTracer, SceneObject};
+use crate::sceneobject_impl_body;
+use crate::types::{Color, Float, Lerp, Maxel, SamplePattern, Vector, RF};
+use crate::vec3;
+
+/// Image based light, from an equirectangular (latitude-longitude) map
+/// surrounding the scene at infinite distance.
+///
+/// Rays escaping the scene see the map. For the (deterministic) Whitted
+/// tracer, [`Light::contribution`] gathers a fixed set of directions spread
+/// over the map, while path tracers use [`EnvironmentLight::sample_contribution`],
+/// which picks a single random direction, importance sampled by the brightness
+/// of the map.
+pub struct EnvironmentLight<F: Float> {
+    img: Rgb32FImage,
+    pub intensity: F,
+    /// Rotation around the Y axis, in degrees
+    pub rotation: F,
+    /// Image file the map was loaded from, if any
+    source: Option<Utf8PathBuf>,
+    /* Cumulative distribution of the rows, and of the pixels within each row */
+    marginal: Vec<F>,
+    conditional: Vec<F>,
+    /* Fixed directions (map coordinates), and their light divided by pdf */
+    fixed: Vec<(F, F, Color<F>)>,
+}
+
+impl<F: Float> EnvironmentLight<F> {
+    pub const ICON: &'static str = egui_phosphor::regular::GLOBE_HEMISPHERE_WEST;
+
+    /// Number of fixed directions gathered by [`Light::contribution`] (for
+    /// primary rays, halved for each reflection level)
+    pub const FIXED_SAMPLES: u32 = 64;
+
+    #[must_use]
+    pub fn new(img: Rgb32FImage) -> Self {
+        let (w, h) = img.dimensions();
+
+        let mut marginal = Vec::with_capacity(h as usize);
+        let mut conditional = Vec::with_capacity((w * h) as usize);
+        let mut total = F::ZERO;
+
+        for y in 0..h {
+            /* Rows near the poles cover a smaller solid angle */
+            let sin_theta = Self::row_theta(y, h).sin();
+            let start = conditional.len();
+            let mut sum = F::ZERO;
+
+            for x in 0..w {
+                sum += Self::texel(&img, x, y).luminance() * sin_theta;
+                conditional.push(sum);
+            }
+
+            for (x, c) in conditional[start..].iter_mut().enumerate() {
+                *c = if sum > F::ZERO {
+                    *c / sum
+                } else {
+                    F::from_usize(x + 1) / F::from_u32(w)
+                };
+            }
+
+            total += sum;
+            marginal.push(total);
+        }
+
+        for m in &mut marginal {
+            *m = if total > F::ZERO { *m / total } else { F::ZERO };
+        }
+
+        let mut env = Self {
+            img,
+            intensity: F::ONE,
+            rotation: F::ZERO,
+            source: None,
+            marginal,
+            conditional,
+            fixed: vec![],
+        };
+
+        /* Halton points are progressive, so every prefix of the fixed
+         * directions is spread over the whole map */
+        let n = Self::FIXED_SAMPLES;
+        env.fixed = SamplePattern::Halton
+            .points::<F>(n, 1)
+            .into_iter()
+            .filter_map(|point| {
+                let (x, y, prob) = env.pick_pixel(point.x, point.y)?;
+                let u = (F::from_u32(x) + F::HALF) / F::from_u32(w);
+                let v = (F::from_u32(y) + F::HALF) / F::from_u32(h);
+                let color = Self::texel(&env.img, x, y) / env.pdf(prob, v);
+                Some((u, v, color))
+            })
+            .collect();
+
+        env
+    }
+
+    #[must_use]
+    pub fn with_intensity(self, intensity: F) -> Self {
+        Self { intensity, ..self }
+    }
+
+    #[must_use]
+    pub fn with_rotation(self, rotation: F) -> Self {
+        Self { rotation, ..self }
+    }
+
+    #[must_use]
+    pub fn with_source(self, source: impl Into<Utf8PathBuf>) -> Self {
+        Self {
+            source: Some(source.into()),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn source(&self) -> Option<&Utf8Path> {
+        self.source.as_deref()
+    }
+
+    fn row_theta(y: u32, h: u32) -> F {
+        (F::from_u32(y) + F::HALF) / F::from_u32(h) * F::PI()
+    }
+
+    fn texel(img: &Rgb32FImage, x: u32, y: u32) -> Color<F> {
+        let [r, g, b] = img.get_pixel(x, y).0;
+        Color::new(F::from_f32(r), F::from_f32(g), F::from_f32(b))
+    }
+
+    /// Map coordinates (in `[0, 1]`) of the normalized direction `dir`
+    fn dir_to_uv(&self, dir: Vector<F>) -> (F, F) {
+        let lon = dir.x.atan2(-dir.z) - self.rotation.to_radians();
+        let theta = dir.y.clamp(-F::ONE, F::ONE).acos();
+        let u = lon / (F::TWO * F::PI()) + F::HALF;
+        (u - u.floor(), theta / F::PI())
+    }
+
+    /// Inverse of [`EnvironmentLight::dir_to_uv`]
+    fn uv_to_dir(&self, u: F, v: F) -> Vector<F> {
+        let lon = (u * F::TWO - F::ONE) * F::PI() + self.rotation.to_radians();
+        let theta = v * F::PI();
+        vec3![
+            theta.sin() * lon.sin(),
+            theta.cos(),
+            -theta.sin() * lon.cos()
+        ]
+    }
+
+    /// Light arriving from the normalized direction `dir`
+    pub fn radiance(&self, dir: Vector<F>) -> Color<F> {
+        let (w, h) = self.img.dimensions();
+        let (u, v) = self.dir_to_uv(dir);
+
+        /* Bilinear interpolation, wrapping around horizontally */
+        let rx = u * F::from_u32(w) - F::HALF;
+        let ry = (v * F::from_u32(h) - F::HALF).clamp(F::ZERO, F::from_u32(h - 1));
+        let fx = rx - rx.floor();
+        let fy = ry.fract();
+
+        let x0 = (rx.floor().to_i64().unwrap_or(0)).rem_euclid(i64::from(w)) as u32;
+        let x1 = (x0 + 1) % w;
+        let y0 = ry.to_u32().unwrap_or(0);
+        let y1 = (y0 + 1).min(h - 1);
+
+        let top = Self::texel(&self.img, x0, y0).lerp(Self::texel(&self.img, x1, y0), fx);
+        let bot = Self::texel(&self.img, x0, y1).lerp(Self::texel(&self.img, x1, y1), fx);
+
+        top.lerp(bot, fy) * self.intensity
+    }
+
+    /// Pixel picked by `a` and `b` (in `[0, 1)`), with a probability
+    /// proportional to its brightness. Returns the pixel, and its probability.
+    fn pick_pixel(&self, a: F, b: F) -> Option<(u32, u32, F)> {
+        let w = self.img.width() as usize;
+
+        let pick = |cdf: &[F], u: F| cdf.partition_point(|&c| c <= u).min(cdf.len() - 1);
+
+        let y = pick(&self.marginal, a);
+        let row = &self.conditional[(y * w)..((y + 1) * w)];
+        let x = pick(row, b);
+
+        let prev = |cdf: &[F], i: usize| if i == 0 { F::ZERO } else { cdf[i - 1] };
+        let prob = (self.marginal[y] - prev(&self.marginal, y)) * (row[x] - prev(row, x));
+
+        (prob > F::ZERO).then_some((x as u32, y as u32, prob))
+    }
+
+    /// Probability density (per solid angle) of directions at map height `v`,
+    /// in a pixel picked with probability `prob`
+    fn pdf(&self, prob: F, v: F) -> F {
+        let (w, h) = self.img.dimensions();
+        /* Uniform within the pixel, which covers 2 pi^2 sin(theta) / (w * h) steradians */
+        let sin_theta = (v * F::PI()).sin().max(F::BIAS);
+        prob * F::from_u32(w * h) / (F::TWO * F::PI() * F::PI() * sin_theta)
+    }
+
+    /// Pick a random direction, proportional to the brightness of the map.
+    /// Returns the direction, the light from it, and its probability density
+    /// (per solid angle).
+    pub fn sample(&self) -> Option<(Vector<F>, Color<F>, F)> {
+        let (w, h) = self.img.dimensions();
+
+        let (x, y, prob) = self.pick_pixel(rand_unit(), rand_unit())?;
+
+        let u = (F::from_u32(x) + rand_unit()) / F::from_u32(w);
+        let v = (F::from_u32(y) + rand_unit()) / F::from_u32(h);
+        if (v * F::PI()).sin() <= F::ZERO {
+            return None;
+        }
+
+        let color = Self::texel(&self.img, x, y) * self.intensity;
+
+        Some((self.uv_to_dir(u, v), color, self.pdf(prob, v)))
+    }
+
+    /// Light from a single random direction, importance sampled by the
+    /// brightness of the map. This is noisy, so it should be averaged over
+    /// many samples, like the passes of a path tracer.
+    pub fn sample_contribution(&self, maxel: &mut Maxel<F>, rt: &dyn RayTracer<F>) -> Lixel<F> {
+        let Some((dir, color, pdf)) = self.sample() else {
+            return Lixel {
+                dir: maxel.nml(),
+                color: Color::BLACK,
+                len2: F::max_value(),
+            };
+        };
+
+        /* Monte-Carlo estimate, scaled to match the lighting convention of Material::bsdf */
+        let lixel = Lixel {
+            dir,
+            color: color / (pdf * F::PI()),
+            len2: F::max_value(),
+        };
+
+        Lixel {
+            color: rt.ray_shadow(maxel, &lixel).unwrap_or(lixel.color),
+            ..lixel
+        }
+    }
+}
+
+impl<F: Float> Debug for EnvironmentLight<F> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("EnvironmentLight")
+            .field("source", &self.source)
+            .field("size", &self.img.dimensions())
+            .field("intensity", &self.intensity)
+            .field("rotation", &self.rotation)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F: Float> Interactive<F> for EnvironmentLight<F> {
+    #[cfg(feature = "gui")]
+    fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        use egui::Slider;
+
+        let mut res = false;
+
+        ui.label("Intensity");
+        res |= ui
+            .add(Slider::new(&mut self.intensity, F::ZERO..=F::from_u32(10)).clamp_to_range(false))
+            .changed();
+        ui.end_row();
+
+        ui.label("Rotation");
+        res |= ui
+            .add(Slider::new(
+                &mut self.rotation,
+                -F::from_u32(180)..=F::from_u32(180),
+            ))
+            .changed();
+        ui.end_row();
+
+        res
+    }
+}
+
+impl<F: Float> SceneObject<F> for EnvironmentLight<F> {
+    sceneobject_impl_body!("Environment Light", Self::ICON);
+}
+
+impl<F: Float> Light<F> for EnvironmentLight<F> {
+    /* Irradiance from the fixed directions, each shadowed and weighted by
+     * its cosine. The result arrives along the normal, so materials apply no
+     * further cosine. */
+    fn contribution(&self, maxel: &mut Maxel<F>, rt: &dyn RayTracer<F>) -> Lixel<F> {
+        let nml = maxel.nml();
+
+        let count = if maxel.flags.contains(RF::Preview) {
+            4
+        } else {
+            (Self::FIXED_SAMPLES as usize >> maxel.lvl).max(4)
+        };
+        let fixed = &self.fixed[..count.min(self.fixed.len())];
+        let scale = self.intensity / (F::PI() * F::from_usize(fixed.len().max(1)));
+
+        let mut color = Color::BLACK;
+        for (u, v, weight) in fixed {
+            let dir = self.uv_to_dir(*u, *v);
+            let cos = dir.dot(nml);
+            if cos <= F::ZERO {
+                continue;
+            }
+
+            let lixel = Lixel {
+                dir,
+                color: *weight * scale,
+                len2: F::max_value(),
+            };
+            color += rt.ray_shadow(maxel, &lixel).unwrap_or(lixel.color) * cos;
+        }
+
+        Lixel {
+            dir: nml,
+            color,
+            len2: F::max_value(),
+        }
+    }
+
+    fn to_sbt(&self, sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        let props = vec![
+            ("map", sbt.map(self.source()?)),
+            ("intensity", SbtNode::Float(self.intensity)),
+            ("rotation", SbtNode::Float(self.rotation)),
+        ];
+        Some(SbtNode::block("environment", SbtNode::Dict(props)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::InnerSpace;
+    use image::Rgb32FImage;
+
+    use std::f64::consts::PI;
+
+    use super::EnvironmentLight;
+    use crate::types::{Vector, Vectorx};
+
+    #[test]
+    fn test_environment_uv_roundtrip() {
+        let env = EnvironmentLight::<f64>::new(Rgb32FImage::new(8, 4)).with_rotation(30.0);
+
+        for dir in [
+            Vector::new(1.0, 0.0, 0.0),
+            Vector::new(0.0, 0.3, -1.0),
+            Vector::new(-0.5, -0.7, 0.2),
+        ] {
+            let dir = dir.normalize();
+            let (u, v) = env.dir_to_uv(dir);
+            assert!((env.uv_to_dir(u, v) - dir).magnitude() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_environment_pdf() {
+        /* A single bright pixel must always be sampled, with a pdf matching its solid angle */
+        let mut img = Rgb32FImage::new(8, 4);
+        img.put_pixel(5, 1, image::Rgb([4.0, 4.0, 4.0]));
+        let env = EnvironmentLight::<f64>::new(img);
+
+        for _ in 0..16 {
+            let (dir, color, pdf) = env.sample().unwrap();
+            let (u, v) = env.dir_to_uv(dir);
+            assert!((5.0..6.0).contains(&(u * 8.0)));
+            assert!((1.0..2.0).contains(&(v * 4.0)));
+            assert!((color.r - 4.0).abs() < 1e-9);
+
+            let sin_theta = (v * PI).sin();
+            assert!((pdf * 2.0 * PI * PI * sin_theta / 32.0 - 1.0).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_environment_fixed_irradiance() {
+        /* A uniform white map gives unit (cosine weighted) irradiance, seen
+         * from any side, through the fixed directions alone */
+        let mut img = Rgb32FImage::new(16, 8);
+        img.pixels_mut().for_each(|p| *p = image::Rgb([1.0; 3]));
+        let env = EnvironmentLight::<f64>::new(img);
+
+        for nml in [Vector::UNIT_Y, -Vector::UNIT_X, Vector::UNIT_Z] {
+            let sum: f64 = env
+                .fixed
+                .iter()
+                .map(|(u, v, weight)| weight.r * env.uv_to_dir(*u, *v).dot(nml).max(0.0))
+                .sum();
+            let irradiance = sum / (PI * env.fixed.len() as f64);
+            assert!((irradiance - 1.0).abs() < 0.1, "{nml:?}: {irradiance}");
+        }
+    }
+}
@@ -1,18 +1,53 @@
 mod arealight;
 mod directional;
+mod emitter;
+mod environment;
+mod photometric;
 mod pointlight;
 mod spotlight;
 
 pub use arealight::AreaLight;
 pub use directional::DirectionalLight;
+pub use emitter::Emitter;
+pub use environment::EnvironmentLight;
+pub use photometric::Photometric;
 pub use pointlight::PointLight;
 pub use spotlight::SpotLight;
 
+use crate::format::sbtwriter::{SbtNode, SbtWriter};
 use crate::scene::{Interactive, RayTracer, SceneObject};
-use crate::types::{Color, Float, Maxel, Vector};
+use crate::types::{Color, Float, Maxel, Ray, Vector};
 
 pub trait Light<F: Float>: SceneObject<F> + Sync + Send {
     fn contribution(&self, _maxel: &mut Maxel<F>, _rt: &dyn RayTracer<F>) -> Lixel<F>;
+
+    /// Position of the light, for lights that have one (used for animation)
+    fn position(&mut self) -> Option<&mut Vector<F>> {
+        None
+    }
+
+    /// Move the light to `pos`, if it has a position (used for animation)
+    fn set_position(&mut self, pos: Vector<F>) {
+        if let Some(res) = self.position() {
+            *res = pos;
+        }
+    }
+
+    /// Color of the light, if it can be changed (used for animation)
+    fn color(&mut self) -> Option<&mut Color<F>> {
+        None
+    }
+
+    /// Description of this light in SBT format, or `None` if it can not be saved
+    fn to_sbt(&self, _sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        None
+    }
+
+    /// Intersection with the surface of the light itself, for lights that
+    /// are visible to camera and reflection rays
+    fn intersect(&self, _ray: &Ray<F>) -> Option<Maxel<F>> {
+        None
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
@@ -23,9 +58,29 @@ pub struct Attenuation<F: Float> {
 }
 
 impl<F: Float> Attenuation<F> {
+    /// Physical `1 / d²` falloff (the constant term cancels the 1 that
+    /// [`Self::attenuate`] always adds)
+    #[must_use]
+    pub fn inverse_square() -> Self {
+        Self {
+            a: -F::ONE,
+            b: F::ZERO,
+            c: F::ONE,
+        }
+    }
+
     pub fn attenuate(&self, color: Color<F>, len: F, len2: F) -> Color<F> {
         color / (F::ONE + self.a + (self.b * len) + (self.c * len2))
     }
+
+    /// Attenuation keys for SBT light blocks
+    pub const fn to_sbt(&self) -> [(&'static str, SbtNode<F>); 3] {
+        [
+            ("constant_attenuation_coeff", SbtNode::Float(self.a)),
+            ("linear_attenuation_coeff", SbtNode::Float(self.b)),
+            ("quadratic_attenuation_coeff", SbtNode::Float(self.c)),
+        ]
+    }
 }
 
 pub struct Lixel<F: Float> {
@@ -38,6 +93,26 @@ impl<F: Float> Light<F> for Box<dyn Light<F> + 'static> {
     fn contribution(&self, maxel: &mut Maxel<F>, rt: &dyn RayTracer<F>) -> Lixel<F> {
         (**self).contribution(maxel, rt)
     }
+
+    fn position(&mut self) -> Option<&mut Vector<F>> {
+        (**self).position()
+    }
+
+    fn set_position(&mut self, pos: Vector<F>) {
+        (**self).set_position(pos);
+    }
+
+    fn color(&mut self) -> Option<&mut Color<F>> {
+        (**self).color()
+    }
+
+    fn to_sbt(&self, sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        (**self).to_sbt(sbt)
+    }
+
+    fn intersect(&self, ray: &Ray<F>) -> Option<Maxel<F>> {
+        (**self).intersect(ray)
+    }
 }
 
 impl<F: Float> SceneObject<F> for Box<dyn Light<F> + 'static> {
@@ -0,0 +1,126 @@
+use std::fmt::Debug;
+
+use cgmath::{Deg, InnerSpace, Rad};
+
+use crate::format::ies::IesProfile;
+use crate::format::sbtwriter::{SbtNode, SbtWriter};
+use crate::light::{Light, Lixel};
+use crate::scene::{Interactive, RayTracer, SceneObject};
+use crate::sceneobject_impl_body;
+use crate::types::{Color, Float, Maxel, Vector, Vectorx};
+
+/// Light shaped by a measured photometric profile (from an IES file).
+///
+/// Wraps a point or spot light, and scales its contribution by the relative
+/// intensity of the profile in the direction of the lit point. Vertical
+/// angles are measured from `dir`, the aim of the fixture, and horizontal
+/// angles go around it, starting from `upd`.
+#[derive(Debug)]
+pub struct Photometric<F: Float, L: Light<F>> {
+    light: L,
+    profile: IesProfile<F>,
+    dir: Vector<F>,
+    upd: Vector<F>,
+}
+
+impl<F: Float, L: Light<F> + Debug> Photometric<F, L> {
+    pub const ICON: &'static str = egui_phosphor::regular::LIGHTBULB_FILAMENT;
+
+    pub fn new(light: L, profile: IesProfile<F>, dir: Vector<F>) -> Self {
+        let dir = dir.normalize();
+        Self {
+            light,
+            profile,
+            dir,
+            upd: dir.surface_tangents().0,
+        }
+    }
+
+    /// Measure horizontal angles from `upd` (projected onto the plane
+    /// perpendicular to the aim)
+    #[must_use]
+    pub fn with_updir(self, upd: Vector<F>) -> Self {
+        Self { upd, ..self }
+    }
+
+    /// Relative intensity of the profile, in direction `out` from the light
+    fn intensity(&self, out: Vector<F>) -> F {
+        let out = out.normalize();
+
+        let up = self.upd - self.dir * self.upd.dot(self.dir);
+        let up = if up.magnitude2() > F::BIAS {
+            up.normalize()
+        } else {
+            self.dir.surface_tangents().0
+        };
+        let side = self.dir.cross(up);
+
+        let theta = Rad(self.dir.dot(out).clamp(-F::ONE, F::ONE).acos());
+        let phi = Rad(out.dot(side).atan2(out.dot(up)));
+
+        self.profile.intensity(Deg::from(theta).0, Deg::from(phi).0)
+    }
+}
+
+impl<F: Float, L: Light<F> + Debug> SceneObject<F> for Photometric<F, L> {
+    sceneobject_impl_body!("Photometric Light", Self::ICON);
+}
+
+impl<F: Float, L: Light<F> + Debug> Interactive<F> for Photometric<F, L> {
+    #[cfg(feature = "gui")]
+    fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        use crate::gui::controls;
+
+        let mut res = false;
+
+        if let Some(source) = self.profile.source().and_then(|path| path.file_name()) {
+            ui.label("Profile");
+            ui.label(source);
+            ui.end_row();
+        }
+
+        if let Some(interactive) = self.light.get_interactive() {
+            res |= interactive.ui(ui);
+        }
+
+        res |= controls::position(ui, &mut self.dir, "Aim");
+        res |= controls::position(ui, &mut self.upd, "Up");
+
+        res
+    }
+}
+
+impl<F: Float, L: Light<F> + Debug> Light<F> for Photometric<F, L> {
+    fn contribution(&self, maxel: &mut Maxel<F>, rt: &dyn RayTracer<F>) -> Lixel<F> {
+        let mut lixel = self.light.contribution(maxel, rt);
+        lixel.color = lixel.color * self.intensity(-lixel.dir);
+        lixel
+    }
+
+    fn position(&mut self) -> Option<&mut Vector<F>> {
+        self.light.position()
+    }
+
+    fn color(&mut self) -> Option<&mut Color<F>> {
+        self.light.color()
+    }
+
+    fn to_sbt(&self, sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        let SbtNode::Block(name, props) = self.light.to_sbt(sbt)? else {
+            return None;
+        };
+        let SbtNode::Dict(mut props) = *props else {
+            return None;
+        };
+
+        if let Some(source) = self.profile.source() {
+            props.push(("ies", SbtNode::Str(sbt.path(source))));
+        }
+        if !props.iter().any(|(key, _)| *key == "direction") {
+            props.push(("direction", SbtNode::vector(self.dir)));
+        }
+        props.push(("updir", SbtNode::vector(self.upd)));
+
+        Some(SbtNode::block(name, SbtNode::Dict(props)))
+    }
+}
@@ -1,5 +1,6 @@
 use cgmath::InnerSpace;
 
+use crate::format::sbtwriter::{SbtNode, SbtWriter};
 use crate::light::{Attenuation, Light, Lixel};
 use crate::scene::{Interactive, RayTracer, SceneObject};
 use crate::sceneobject_impl_body;
@@ -55,4 +56,21 @@ impl<F: Float> Light<F> for PointLight<F> {
         }
         lixel
     }
+
+    fn position(&mut self) -> Option<&mut Vector<F>> {
+        Some(&mut self.pos)
+    }
+
+    fn color(&mut self) -> Option<&mut Color<F>> {
+        Some(&mut self.color)
+    }
+
+    fn to_sbt(&self, _sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        let mut props = vec![
+            ("position", SbtNode::vector(self.pos)),
+            ("color", SbtNode::color(self.color)),
+        ];
+        props.extend(self.attn.to_sbt());
+        Some(SbtNode::block("point_light", SbtNode::Dict(props)))
+    }
 }
@@ -1,5 +1,6 @@
-use cgmath::{InnerSpace, Rad};
+use cgmath::{Deg, InnerSpace, Rad};
 
+use crate::format::sbtwriter::{SbtNode, SbtWriter};
 use crate::light::{Attenuation, Light, Lixel};
 use crate::scene::{Interactive, RayTracer, SceneObject};
 use crate::sceneobject_impl_body;
@@ -63,6 +64,26 @@ impl<F: Float> Light<F> for SpotLight<F> {
         }
         lixel
     }
+
+    fn position(&mut self) -> Option<&mut Vector<F>> {
+        Some(&mut self.pos)
+    }
+
+    fn color(&mut self) -> Option<&mut Color<F>> {
+        Some(&mut self.color)
+    }
+
+    fn to_sbt(&self, _sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        let mut props = vec![
+            ("position", SbtNode::vector(self.pos)),
+            ("direction", SbtNode::vector(self.dir)),
+            ("color", SbtNode::color(self.color)),
+            ("umbra", SbtNode::Float(Deg::from(self.umbra).0)),
+            ("penumbra", SbtNode::Float(Deg::from(self.penumbra).0)),
+        ];
+        props.extend(self.attn.to_sbt());
+        Some(SbtNode::block("spot_light", SbtNode::Dict(props)))
+    }
 }
 
 impl<F: Float> Interactive<F> for SpotLight<F> {
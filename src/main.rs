@@ -1,27 +1,137 @@
-#[cfg(not(feature = "gui"))]
 use std::str::FromStr;
 
 use camino::Utf8PathBuf;
 use log::LevelFilter;
 
-use rustray::types::RResult;
+use rustray::frontend::cli::RenderSettings;
+use rustray::types::{
+    AntiAlias, Aov, CameraSelector, Error, FrameRange, RResult, SamplePattern, ToneMap, ToneMapper,
+};
 
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
+#[command(args_conflicts_with_subcommands = true)]
 struct Cli {
-    #[arg(short, long, default_value_t = 1440)]
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[arg(short, long, default_value_t = 1440, global = true)]
     width: u32,
 
-    #[arg(short, long, default_value_t = 1200)]
+    #[arg(short = 'H', long, default_value_t = 1200, global = true)]
     height: u32,
 
     #[arg(value_name = "input")]
     input: Vec<Utf8PathBuf>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Open scenes in the interactive viewer
+    #[cfg(feature = "gui")]
+    Gui {
+        #[arg(value_name = "input")]
+        input: Vec<Utf8PathBuf>,
 
-    #[arg(short, long, value_name = "output")]
-    output: Option<Utf8PathBuf>,
+        /// Camera to view through, by index or name
+        #[arg(short, long)]
+        camera: Option<CameraSelector>,
+    },
+
+    /// Render scenes to image files, without opening a window
+    Render {
+        #[arg(value_name = "input", required = true)]
+        input: Vec<Utf8PathBuf>,
+
+        /// Output file (single input), or output directory (multiple inputs).
+        /// Use .exr or .hdr for linear, high dynamic range output.
+        #[arg(short, long, value_name = "output")]
+        output: Option<Utf8PathBuf>,
+
+        #[command(flatten)]
+        settings: RenderArgs,
+    },
+}
+
+#[derive(Args)]
+struct RenderArgs {
+    /// Camera to render, by index or name
+    #[arg(short, long)]
+    camera: Option<CameraSelector>,
+
+    /// Render every camera, suffixing output files with the camera name (or index)
+    #[arg(short, long, conflicts_with = "camera")]
+    all_cameras: bool,
+
+    /// Anti-aliasing sample pattern [stratified, jittered, halton]
+    #[arg(long, value_name = "pattern", value_parser = parse_arg::<SamplePattern>)]
+    aa: Option<SamplePattern>,
+
+    /// Anti-aliasing samples per pixel, along each axis
+    #[arg(long, value_name = "n", default_value_t = 4)]
+    aa_samples: u32,
+
+    /// Only supersample pixels that differ from their neighbours by more than this contrast
+    #[arg(long, value_name = "contrast")]
+    aa_threshold: Option<f64>,
+
+    /// Tone mapping for low dynamic range output [clamp, reinhard, aces]
+    #[arg(long, value_name = "operator", default_value_t, value_parser = parse_arg::<ToneMap>)]
+    tonemap: ToneMap,
+
+    /// Exposure adjustment in stops, applied before tone mapping
+    #[arg(
+        long,
+        value_name = "stops",
+        default_value_t = 0.0,
+        allow_negative_numbers = true
+    )]
+    exposure: f64,
+
+    /// Encode low dynamic range output with the sRGB transfer function,
+    /// instead of writing linear values
+    #[arg(long)]
+    srgb: bool,
+
+    /// Extra output passes [depth, normal, albedo, matid, objid], stored as
+    /// layers in .exr output, or as separate images otherwise
+    #[arg(long, value_name = "pass", value_delimiter = ',', value_parser = parse_arg::<Aov>)]
+    aov: Vec<Aov>,
+
+    /// Render frames N..M (inclusive) of the scene animation, suffixing
+    /// output files with the frame number
+    #[arg(long, value_name = "N..M", value_parser = parse_arg::<FrameRange>)]
+    frames: Option<FrameRange>,
+}
+
+fn parse_arg<T: FromStr<Err = Error>>(s: &str) -> Result<T, String> {
+    s.parse().map_err(|err: Error| err.to_string())
+}
+
+impl RenderArgs {
+    fn settings(self) -> RenderSettings<f64> {
+        let aa = if self.aa.is_none() && self.aa_threshold.is_none() {
+            AntiAlias::new()
+        } else {
+            AntiAlias::new()
+                .with_pattern(self.aa.unwrap_or_default())
+                .with_samples(self.aa_samples)
+                .with_threshold(self.aa_threshold)
+        };
+
+        RenderSettings {
+            camera: self.camera,
+            all_cameras: self.all_cameras,
+            aa,
+            tonemap: ToneMapper::new(self.tonemap)
+                .with_exposure(self.exposure)
+                .with_srgb(self.srgb),
+            aovs: self.aov,
+            frames: self.frames,
+        }
+    }
 }
 
 fn main() -> RResult<()> {
@@ -33,17 +143,36 @@ fn main() -> RResult<()> {
 
     type F = f64;
 
-    #[cfg(feature = "gui")]
-    return rustray::frontend::gui::run::<F>(cli.input, cli.width, cli.height);
-
-    #[cfg(not(feature = "gui"))]
-    return rustray::frontend::cli::run::<F>(
-        &cli.input,
-        cli.width,
-        cli.height,
-        cli.output
-            .unwrap_or_else(|| PathBuf::from_str("output.png").unwrap()),
-    );
+    match cli.command {
+        #[cfg(feature = "gui")]
+        Some(Command::Gui { input, camera }) => {
+            rustray::frontend::gui::run::<F>(input, cli.width, cli.height, camera)
+        }
+
+        Some(Command::Render {
+            input,
+            output,
+            settings,
+        }) => rustray::frontend::cli::run::<F>(
+            &input,
+            cli.width,
+            cli.height,
+            output.as_deref(),
+            &settings.settings(),
+        ),
+
+        #[cfg(feature = "gui")]
+        None => rustray::frontend::gui::run::<F>(cli.input, cli.width, cli.height, None),
+
+        #[cfg(not(feature = "gui"))]
+        None => rustray::frontend::cli::run::<F>(
+            &cli.input,
+            cli.width,
+            cli.height,
+            None,
+            &RenderSettings::default(),
+        ),
+    }
 }
 
 #[cfg(test)]
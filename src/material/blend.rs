@@ -1,10 +1,10 @@
 use cgmath::VectorSpace;
 
 use crate::light::Lixel;
-use crate::material::Material;
+use crate::material::{rand_unit, BsdfSample, Material};
 use crate::scene::{Interactive, RayTracer, SceneObject};
 use crate::sceneobject_impl_body;
-use crate::types::{Color, Float, Maxel};
+use crate::types::{Color, Float, Maxel, Medium, Vector};
 
 #[derive(Copy, Clone, Debug)]
 pub struct Blend<F: Float, A: Material<F>, B: Material<F>> {
@@ -31,6 +31,42 @@ impl<F: Float, A: Material<F>, B: Material<F>> Material<F> for Blend<F, A, B> {
         let b = self.b.shadow(maxel, rt, lixel);
         a.lerp(b, self.pct)
     }
+
+    fn emission(&self, maxel: &mut Maxel<F>) -> Color<F> {
+        let a = self.a.emission(maxel);
+        let b = self.b.emission(maxel);
+        a.lerp(b, self.pct)
+    }
+
+    fn medium(&self, maxel: &mut Maxel<F>) -> Option<Medium<F>> {
+        match (self.a.medium(maxel), self.b.medium(maxel)) {
+            (None, None) => None,
+            (a, b) => {
+                let (a, b) = (a.unwrap_or(Medium::VACUUM), b.unwrap_or(Medium::VACUUM));
+                Some(crate::types::Lerp::lerp(a, b, self.pct))
+            }
+        }
+    }
+
+    fn albedo(&self, maxel: &mut Maxel<F>) -> Color<F> {
+        let a = self.a.albedo(maxel);
+        let b = self.b.albedo(maxel);
+        a.lerp(b, self.pct)
+    }
+
+    fn bsdf(&self, maxel: &mut Maxel<F>, dir: Vector<F>) -> Color<F> {
+        let a = self.a.bsdf(maxel, dir);
+        let b = self.b.bsdf(maxel, dir);
+        a.lerp(b, self.pct)
+    }
+
+    fn scatter(&self, maxel: &mut Maxel<F>) -> Option<BsdfSample<F>> {
+        if rand_unit::<F>() < self.pct {
+            self.b.scatter(maxel)
+        } else {
+            self.a.scatter(maxel)
+        }
+    }
 }
 
 impl<F: Float, A: Material<F>, B: Material<F>> Interactive<F> for Blend<F, A, B> {}
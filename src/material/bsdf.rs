@@ -0,0 +1,89 @@
+use cgmath::InnerSpace;
+use rand::Rng;
+
+use crate::types::{Color, Float, Maxel, Ray, Vector, Vectorx};
+
+/// Scattered ray sampled from a material, for use by path tracers.
+#[derive(Copy, Clone, Debug)]
+pub struct BsdfSample<F: Float> {
+    /// Outgoing ray, continuing the path
+    pub ray: Ray<F>,
+    /// Path throughput factor (BSDF times cosine, divided by sample pdf)
+    pub weight: Color<F>,
+}
+
+impl<F: Float> BsdfSample<F> {
+    #[must_use]
+    pub fn scaled(self, factor: F) -> Self {
+        Self {
+            weight: self.weight * factor,
+            ..self
+        }
+    }
+}
+
+/// Uniformly distributed random number in `[0, 1)`
+#[must_use]
+pub fn rand_unit<F: Float>() -> F {
+    F::from_f32(rand::thread_rng().gen())
+}
+
+/// Surface normal at `maxel`, flipped to face the incoming ray
+pub fn facing_normal<F: Float>(maxel: &mut Maxel<F>) -> Vector<F> {
+    let nml = maxel.nml();
+    if nml.dot(maxel.dir).is_positive() {
+        -nml
+    } else {
+        nml
+    }
+}
+
+/// Cosine-weighted random direction in the hemisphere around `nml`.
+///
+/// The pdf of the returned direction is `cos(theta) / pi`.
+#[must_use]
+pub fn cosine_hemisphere<F: Float>(nml: Vector<F>) -> Vector<F> {
+    let (u, v) = nml.surface_tangents();
+    let phi = F::TWO * F::PI() * rand_unit();
+    let r2: F = rand_unit();
+    let r = r2.sqrt();
+
+    (u * (r * phi.cos()) + v * (r * phi.sin()) + nml * (F::ONE - r2).sqrt()).normalize()
+}
+
+/// Ideal diffuse reflection of `color`, for light arriving from `dir`
+///
+/// Follows the convention of [`Material::bsdf`](crate::material::Material::bsdf).
+pub fn lambert_bsdf<F: Float>(maxel: &mut Maxel<F>, color: Color<F>, dir: Vector<F>) -> Color<F> {
+    let lambert = maxel.nml().dot(dir);
+    if lambert < F::BIAS {
+        return Color::BLACK;
+    }
+    color * lambert
+}
+
+/// Sample an ideal diffuse reflection of `color`, or `None` if it is black
+pub fn lambert_scatter<F: Float>(maxel: &mut Maxel<F>, color: Color<F>) -> Option<BsdfSample<F>> {
+    if color.max_channel() <= F::ZERO {
+        return None;
+    }
+
+    /* The normalized BSDF is color / pi, and the pdf of the direction is
+     * cos / pi, so the weight (BSDF times cosine, over pdf) is the colour */
+    let nml = facing_normal(maxel);
+    let ray = maxel.ray(maxel.pos + nml * F::BIAS2, cosine_hemisphere(nml));
+    Some(BsdfSample { ray, weight: color })
+}
+
+/// Random direction around `dir`, distributed after a Phong lobe of power `pow`.
+///
+/// The pdf of the returned direction is `(pow + 1) / (2 pi) * cos(alpha)^pow`.
+#[must_use]
+pub fn phong_lobe<F: Float>(dir: Vector<F>, pow: F) -> Vector<F> {
+    let (u, v) = dir.surface_tangents();
+    let phi = F::TWO * F::PI() * rand_unit();
+    let cos_t = rand_unit::<F>().powf(F::ONE / (pow + F::ONE));
+    let sin_t = (F::ONE - cos_t * cos_t).max(F::ZERO).sqrt();
+
+    (u * (sin_t * phi.cos()) + v * (sin_t * phi.sin()) + dir * cos_t).normalize()
+}
@@ -2,12 +2,13 @@ use std::marker::PhantomData;
 
 use cgmath::InnerSpace;
 
+use crate::format::sbtwriter::{SbtNode, SbtWriter};
 use crate::light::Lixel;
-use crate::material::Material;
+use crate::material::{BsdfSample, Material};
 use crate::sampler::{Sampler, Texel};
 use crate::scene::{Interactive, RayTracer, SceneObject};
 use crate::sceneobject_impl_body;
-use crate::types::{Color, Float, Maxel, Point, Vector, Vectorx};
+use crate::types::{Color, Float, Maxel, Medium, Point, Vector};
 
 #[derive(Copy, Clone, Debug)]
 pub struct BumpPower<F: Float>(pub F);
@@ -61,6 +62,19 @@ where
             _p: PhantomData,
         }
     }
+
+    fn bump<'a>(&self, maxel: &mut Maxel<'a, F>) -> Maxel<'a, F> {
+        let n = maxel.lookup(&self.img);
+        let pow = maxel.lookup(&self.pow);
+
+        let mxl = *maxel;
+
+        let normal = maxel.nml();
+        let (normalu, normalv) = maxel.tangents();
+        let nx = normalu * n.x + normalv * n.y + normal * n.z / (pow + F::BIAS);
+
+        mxl.with_normal(nx.normalize())
+    }
 }
 
 impl<F, S1, S2, M> Material<F> for Bumpmap<F, S1, S2, M>
@@ -71,23 +85,42 @@ where
     M: Material<F>,
 {
     fn render(&self, maxel: &mut Maxel<F>, rt: &dyn RayTracer<F>) -> Color<F> {
-        let uv = maxel.uv();
-        let n = self.img.sample(uv);
-        let pow = self.pow.sample(uv);
+        let mut mxl = self.bump(maxel);
+        self.mat.render(&mut mxl, rt)
+    }
 
-        let mut mxl = *maxel;
+    fn shadow(&self, maxel: &mut Maxel<F>, rt: &dyn RayTracer<F>, lixel: &Lixel<F>) -> Color<F> {
+        self.mat.shadow(maxel, rt, lixel)
+    }
 
-        let normal = mxl.nml();
-        let (normalu, normalv) = normal.surface_tangents();
-        let nx = normalu * n.x + normalv * n.y + normal * n.z / (pow + F::BIAS);
+    fn emission(&self, maxel: &mut Maxel<F>) -> Color<F> {
+        self.mat.emission(maxel)
+    }
 
-        mxl = mxl.with_normal(nx.normalize());
+    fn medium(&self, maxel: &mut Maxel<F>) -> Option<Medium<F>> {
+        self.mat.medium(maxel)
+    }
 
-        self.mat.render(&mut mxl, rt)
+    fn albedo(&self, maxel: &mut Maxel<F>) -> Color<F> {
+        self.mat.albedo(maxel)
     }
 
-    fn shadow(&self, maxel: &mut Maxel<F>, rt: &dyn RayTracer<F>, lixel: &Lixel<F>) -> Color<F> {
-        self.mat.shadow(maxel, rt, lixel)
+    fn bsdf(&self, maxel: &mut Maxel<F>, dir: Vector<F>) -> Color<F> {
+        let mut mxl = self.bump(maxel);
+        self.mat.bsdf(&mut mxl, dir)
+    }
+
+    fn scatter(&self, maxel: &mut Maxel<F>) -> Option<BsdfSample<F>> {
+        let mut mxl = self.bump(maxel);
+        self.mat.scatter(&mut mxl)
+    }
+
+    fn to_sbt(&self, sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        let SbtNode::Dict(mut props) = self.mat.to_sbt(sbt)? else {
+            return None;
+        };
+        props.push(("bump", sbt.map_sampler(&self.img, SbtNode::vector)?));
+        Some(SbtNode::Dict(props))
     }
 }
 
@@ -1,10 +1,10 @@
 use std::marker::PhantomData;
 
 use crate::light::Lixel;
-use crate::material::Material;
+use crate::material::{BsdfSample, Material};
 use crate::scene::{Interactive, RayTracer, SceneObject};
 use crate::sceneobject_impl_body;
-use crate::types::{Color, Float, Maxel};
+use crate::types::{Color, Float, Maxel, Medium, Vector};
 
 #[derive(Copy, Clone, Debug)]
 pub enum ChessBoardMode {
@@ -66,6 +66,46 @@ impl<F: Float, A: Material<F>, B: Material<F>> Material<F> for ChessBoard<F, A,
             self.b.shadow(maxel, rt, lixel)
         }
     }
+
+    fn emission(&self, maxel: &mut Maxel<F>) -> Color<F> {
+        if self.select(maxel) {
+            self.a.emission(maxel)
+        } else {
+            self.b.emission(maxel)
+        }
+    }
+
+    fn medium(&self, maxel: &mut Maxel<F>) -> Option<Medium<F>> {
+        if self.select(maxel) {
+            self.a.medium(maxel)
+        } else {
+            self.b.medium(maxel)
+        }
+    }
+
+    fn albedo(&self, maxel: &mut Maxel<F>) -> Color<F> {
+        if self.select(maxel) {
+            self.a.albedo(maxel)
+        } else {
+            self.b.albedo(maxel)
+        }
+    }
+
+    fn bsdf(&self, maxel: &mut Maxel<F>, dir: Vector<F>) -> Color<F> {
+        if self.select(maxel) {
+            self.a.bsdf(maxel, dir)
+        } else {
+            self.b.bsdf(maxel, dir)
+        }
+    }
+
+    fn scatter(&self, maxel: &mut Maxel<F>) -> Option<BsdfSample<F>> {
+        if self.select(maxel) {
+            self.a.scatter(maxel)
+        } else {
+            self.b.scatter(maxel)
+        }
+    }
 }
 
 impl<F: Float, A: Material<F>, B: Material<F>> Interactive<F> for ChessBoard<F, A, B> {}
@@ -41,6 +41,14 @@ impl<F: Float> ColorDebug<F> {
 
 impl<F: Float> Material<F> for ColorDebug<F> {
     fn render(&self, maxel: &mut Maxel<F>, _rt: &dyn RayTracer<F>) -> Color<F> {
+        self.emission(maxel)
+    }
+
+    fn albedo(&self, maxel: &mut Maxel<F>) -> Color<F> {
+        self.emission(maxel)
+    }
+
+    fn emission(&self, maxel: &mut Maxel<F>) -> Color<F> {
         let res = match self.dt {
             DebugType::ColorPos => {
                 let mut n = maxel.pos / F::from_f32(32.0);
@@ -1,12 +1,13 @@
-use cgmath::{InnerSpace, VectorSpace};
+use cgmath::VectorSpace;
 use num::Zero;
 
+use crate::format::sbtwriter::{SbtNode, SbtWriter};
 use crate::light::Lixel;
-use crate::material::{Material, Mirror};
+use crate::material::{rand_unit, BsdfSample, Material, Mirror};
 use crate::sampler::{Sampler, Texel};
 use crate::scene::{Interactive, RayTracer, SceneObject};
 use crate::sceneobject_impl_body;
-use crate::types::{Color, Float, Maxel};
+use crate::types::{Color, Float, Maxel, Medium};
 
 #[derive(Copy, Clone, Debug)]
 pub struct Fresnel<F, SI, ST, SR>
@@ -19,6 +20,8 @@ where
     ior: SI,
     refr: ST,
     refl: Mirror<F, SR>,
+    /// Medium inside the object (see [`Material::medium`])
+    medium: Option<Medium<F>>,
 }
 
 impl<F, SI, ST, SR> Fresnel<F, SI, ST, SR>
@@ -33,8 +36,14 @@ where
             ior,
             refl: Mirror::new(refl),
             refr,
+            medium: None,
         }
     }
+
+    #[must_use]
+    pub fn with_medium(self, medium: Option<Medium<F>>) -> Self {
+        Self { medium, ..self }
+    }
 }
 
 impl<F, SI, ST, SR> Material<F> for Fresnel<F, SI, ST, SR>
@@ -45,12 +54,11 @@ where
     SR: Sampler<F, Color<F>>,
 {
     fn render(&self, maxel: &mut Maxel<F>, rt: &dyn RayTracer<F>) -> Color<F> {
-        let uv = maxel.uv();
-        let ior = self.ior.sample(uv);
+        let ior = maxel.lookup(&self.ior);
 
         let refl_term = self.refl.render(maxel, rt);
 
-        let tran_color = self.refr.sample(uv);
+        let tran_color = maxel.lookup(&self.refr);
         let refr_term = if !tran_color.is_zero() {
             rt.ray_trace(&maxel.refracted_ray(ior))
                 .map_or(Color::BLACK, |c| c * tran_color)
@@ -62,11 +70,52 @@ where
     }
 
     fn shadow(&self, maxel: &mut Maxel<F>, _rt: &dyn RayTracer<F>, lixel: &Lixel<F>) -> Color<F> {
-        let uv = maxel.uv();
-        let sha = self.refr.sample(uv);
-        let lambert = lixel.dir.dot(maxel.nml());
+        /* Light passing through is filtered, but not spread out like light
+         * landing on the surface, so there is no cosine term */
+        maxel.lookup(&self.refr) * lixel.color
+    }
+
+    fn medium(&self, _maxel: &mut Maxel<F>) -> Option<Medium<F>> {
+        self.medium
+    }
+
+    fn albedo(&self, maxel: &mut Maxel<F>) -> Color<F> {
+        let ior = maxel.lookup(&self.ior);
+
+        let refl = self.refl.albedo(maxel);
+        maxel.lookup(&self.refr).lerp(refl, maxel.fresnel(ior))
+    }
+
+    fn scatter(&self, maxel: &mut Maxel<F>) -> Option<BsdfSample<F>> {
+        let ior = maxel.lookup(&self.ior);
+        let fresnel = maxel.fresnel(ior);
+
+        let tran_color = maxel.lookup(&self.refr);
+        if tran_color.is_zero() {
+            return self.refl.scatter(maxel).map(|s| s.scaled(fresnel));
+        }
+
+        /* Choose reflection or refraction, according to the fresnel term */
+        if rand_unit::<F>() < fresnel {
+            self.refl.scatter(maxel)
+        } else {
+            Some(BsdfSample {
+                ray: maxel.refracted_ray(ior),
+                weight: tran_color,
+            })
+        }
+    }
 
-        sha * lixel.color * lambert
+    fn to_sbt(&self, sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        let SbtNode::Dict(mut props) = self.refl.to_sbt(sbt)? else {
+            return None;
+        };
+        props.push(("index", sbt.sampler(&self.ior, SbtNode::Float)?));
+        props.push(("transmissive", sbt.sampler(&self.refr, SbtNode::color)?));
+        if let Some(medium) = &self.medium {
+            props.extend(medium.to_sbt());
+        }
+        Some(SbtNode::Dict(props))
     }
 }
 
@@ -86,6 +135,16 @@ where
         res |= self.ior.ui(ui, "Index of refraction");
         res |= self.refl.ui(ui);
         res |= self.refr.ui(ui, "Refraction");
+
+        let mut enabled = self.medium.is_some();
+        ui.label("Medium");
+        res |= ui.checkbox(&mut enabled, "").changed();
+        ui.end_row();
+        match (enabled, &mut self.medium) {
+            (true, Some(medium)) => res |= medium.ui(ui),
+            (true, None) => self.medium = Some(Medium::VACUUM),
+            (false, _) => self.medium = None,
+        }
         res
     }
 }
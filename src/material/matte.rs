@@ -4,11 +4,11 @@ use cgmath::InnerSpace;
 use rand::Rng;
 
 use crate::light::Lixel;
-use crate::material::Material;
+use crate::material::{BsdfSample, Material};
 use crate::sampler::{Sampler, Texel};
 use crate::scene::{Interactive, RayTracer, SceneObject};
 use crate::sceneobject_impl_body;
-use crate::types::{Color, Float, Maxel, Vectorx};
+use crate::types::{Color, Float, Maxel, Medium, Vector, Vectorx};
 
 #[derive(Copy, Clone, Debug)]
 pub struct Matte<F: Float + Texel, S: Sampler<F, F>, M: Material<F>> {
@@ -46,9 +46,8 @@ where
         let mut col = Color::BLACK;
         let mut mxl = *maxel;
 
-        let uv = maxel.uv();
         let normal = mxl.nml();
-        let src = self.src.sample(uv);
+        let src = maxel.lookup(&self.src);
         for _n in 0..self.rays {
             let rx = (rng.gen() - F::HALF) * src;
             let ry = (rng.gen() - F::HALF) * src;
@@ -64,6 +63,38 @@ where
     fn shadow(&self, maxel: &mut Maxel<F>, rt: &dyn RayTracer<F>, lixel: &Lixel<F>) -> Color<F> {
         self.mat.shadow(maxel, rt, lixel)
     }
+
+    fn emission(&self, maxel: &mut Maxel<F>) -> Color<F> {
+        self.mat.emission(maxel)
+    }
+
+    fn medium(&self, maxel: &mut Maxel<F>) -> Option<Medium<F>> {
+        self.mat.medium(maxel)
+    }
+
+    fn albedo(&self, maxel: &mut Maxel<F>) -> Color<F> {
+        self.mat.albedo(maxel)
+    }
+
+    fn bsdf(&self, maxel: &mut Maxel<F>, dir: Vector<F>) -> Color<F> {
+        self.mat.bsdf(maxel, dir)
+    }
+
+    fn scatter(&self, maxel: &mut Maxel<F>) -> Option<BsdfSample<F>> {
+        /* Path tracing averages over many samples, so a single jittered normal suffices */
+        let mut rng = rand::thread_rng();
+
+        let normal = maxel.nml();
+        let src = maxel.lookup(&self.src);
+
+        let rx = (rng.gen() - F::HALF) * src;
+        let ry = (rng.gen() - F::HALF) * src;
+        let rz = (rng.gen() / F::TWO) * (F::ONE - src) + src;
+        let (normalu, normalv) = normal.surface_tangents();
+        let mut mxl = maxel.with_normal((normal * rz + normalu * rx + normalv * ry).normalize());
+
+        self.mat.scatter(&mut mxl)
+    }
 }
 
 impl<F, S, M> Interactive<F> for Matte<F, S, M>
@@ -2,7 +2,8 @@ use std::marker::PhantomData;
 
 use num::Zero;
 
-use crate::material::Material;
+use crate::format::sbtwriter::{SbtNode, SbtWriter};
+use crate::material::{BsdfSample, Material};
 use crate::sampler::Sampler;
 use crate::scene::{Interactive, RayTracer, SceneObject};
 use crate::sceneobject_impl_body;
@@ -29,7 +30,7 @@ impl<F: Float, T: Sampler<F, Color<F>>> Mirror<F, T> {
 
 impl<F: Float, T: Sampler<F, Color<F>>> Material<F> for Mirror<F, T> {
     fn render(&self, maxel: &mut Maxel<F>, rt: &dyn RayTracer<F>) -> Color<F> {
-        let refl_color = self.refl.sample(maxel.uv());
+        let refl_color = maxel.lookup(&self.refl);
 
         if !refl_color.is_zero() {
             rt.ray_trace(&maxel.reflected_ray())
@@ -38,6 +39,28 @@ impl<F: Float, T: Sampler<F, Color<F>>> Material<F> for Mirror<F, T> {
             Color::BLACK
         }
     }
+
+    fn albedo(&self, maxel: &mut Maxel<F>) -> Color<F> {
+        maxel.lookup(&self.refl)
+    }
+
+    fn scatter(&self, maxel: &mut Maxel<F>) -> Option<BsdfSample<F>> {
+        let refl_color = maxel.lookup(&self.refl);
+
+        if refl_color.is_zero() {
+            return None;
+        }
+
+        Some(BsdfSample {
+            ray: maxel.reflected_ray(),
+            weight: refl_color,
+        })
+    }
+
+    fn to_sbt(&self, sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        let refl = sbt.sampler(&self.refl, SbtNode::color)?;
+        Some(SbtNode::Dict(vec![("reflective", refl)]))
+    }
 }
 
 impl<F: Float, T: Sampler<F, Color<F>>> Interactive<F> for Mirror<F, T> {
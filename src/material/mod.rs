@@ -1,10 +1,11 @@
 use std::fmt::Debug;
 use std::sync::Arc;
 
+use crate::format::sbtwriter::{SbtNode, SbtWriter};
 use crate::light::Lixel;
 use crate::scene::{Interactive, RayTracer, SceneObject};
 use crate::sceneobject_impl_body;
-use crate::types::{Color, Float, MaterialId, Maxel};
+use crate::types::{Color, Float, MaterialId, Maxel, Medium, Vector};
 
 pub trait Material<F: Float>: SceneObject<F> + Interactive<F> + Debug + Send + Sync {
     fn render(&self, maxel: &mut Maxel<F>, rt: &dyn RayTracer<F>) -> Color<F>;
@@ -13,12 +14,47 @@ pub trait Material<F: Float>: SceneObject<F> + Interactive<F> + Debug + Send + S
         Color::BLACK
     }
 
+    /// Light emitted by the surface itself, used by path tracers.
+    fn emission(&self, _maxel: &mut Maxel<F>) -> Color<F> {
+        Color::BLACK
+    }
+
+    /// Participating medium inside the object, for closed surfaces that
+    /// light passes through (like coloured glass or murky water).
+    fn medium(&self, _maxel: &mut Maxel<F>) -> Option<Medium<F>> {
+        None
+    }
+
+    /// Light reflected towards the viewer, for light arriving from `dir`.
+    ///
+    /// This uses the same convention as [`Material::render`], so that direct
+    /// lighting from a [`Lixel`] is `bsdf(maxel, lixel.dir) * lixel.color`.
+    fn bsdf(&self, _maxel: &mut Maxel<F>, _dir: Vector<F>) -> Color<F> {
+        Color::BLACK
+    }
+
+    /// Sample a scattered ray for path tracing, or `None` to terminate the path.
+    fn scatter(&self, _maxel: &mut Maxel<F>) -> Option<BsdfSample<F>> {
+        None
+    }
+
+    /// Surface color, independent of lighting (used for albedo passes).
+    fn albedo(&self, _maxel: &mut Maxel<F>) -> Color<F> {
+        Color::BLACK
+    }
+
     fn dynamic(self) -> DynMaterial<F>
     where
         Self: Sized + 'static,
     {
         Arc::new(self)
     }
+
+    /// Properties of this material as an SBT dictionary, or `None` if it can
+    /// not be saved
+    fn to_sbt(&self, _sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        None
+    }
 }
 
 pub trait HasMaterial {
@@ -33,6 +69,27 @@ impl<F: Float> Material<F> for Color<F> {
     fn render(&self, _maxel: &mut Maxel<F>, _rt: &dyn RayTracer<F>) -> Self {
         *self
     }
+
+    fn albedo(&self, _maxel: &mut Maxel<F>) -> Self {
+        *self
+    }
+
+    /* Path tracers treat flat colours as diffuse surfaces */
+    fn bsdf(&self, maxel: &mut Maxel<F>, dir: Vector<F>) -> Self {
+        lambert_bsdf(maxel, *self, dir)
+    }
+
+    fn scatter(&self, maxel: &mut Maxel<F>) -> Option<BsdfSample<F>> {
+        lambert_scatter(maxel, *self)
+    }
+
+    /* Written as a diffuse-only material, like path tracers render it */
+    fn to_sbt(&self, _sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        Some(SbtNode::Dict(vec![
+            ("diffuse", SbtNode::color(*self)),
+            ("specular", SbtNode::color(Self::BLACK)),
+        ]))
+    }
 }
 
 impl<F: Float> SceneObject<F> for BoxMaterial<F> {
@@ -81,6 +138,30 @@ impl<F: Float> Material<F> for BoxMaterial<F> {
     fn shadow(&self, maxel: &mut Maxel<F>, rt: &dyn RayTracer<F>, lixel: &Lixel<F>) -> Color<F> {
         (**self).shadow(maxel, rt, lixel)
     }
+
+    fn emission(&self, maxel: &mut Maxel<F>) -> Color<F> {
+        (**self).emission(maxel)
+    }
+
+    fn medium(&self, maxel: &mut Maxel<F>) -> Option<Medium<F>> {
+        (**self).medium(maxel)
+    }
+
+    fn bsdf(&self, maxel: &mut Maxel<F>, dir: Vector<F>) -> Color<F> {
+        (**self).bsdf(maxel, dir)
+    }
+
+    fn scatter(&self, maxel: &mut Maxel<F>) -> Option<BsdfSample<F>> {
+        (**self).scatter(maxel)
+    }
+
+    fn albedo(&self, maxel: &mut Maxel<F>) -> Color<F> {
+        (**self).albedo(maxel)
+    }
+
+    fn to_sbt(&self, sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        (**self).to_sbt(sbt)
+    }
 }
 
 impl<F: Float> Interactive<F> for BoxMaterial<F> {
@@ -113,6 +194,30 @@ impl<F: Float> Material<F> for DynMaterial<F> {
     fn shadow(&self, maxel: &mut Maxel<F>, rt: &dyn RayTracer<F>, lixel: &Lixel<F>) -> Color<F> {
         (**self).shadow(maxel, rt, lixel)
     }
+
+    fn emission(&self, maxel: &mut Maxel<F>) -> Color<F> {
+        (**self).emission(maxel)
+    }
+
+    fn medium(&self, maxel: &mut Maxel<F>) -> Option<Medium<F>> {
+        (**self).medium(maxel)
+    }
+
+    fn bsdf(&self, maxel: &mut Maxel<F>, dir: Vector<F>) -> Color<F> {
+        (**self).bsdf(maxel, dir)
+    }
+
+    fn scatter(&self, maxel: &mut Maxel<F>) -> Option<BsdfSample<F>> {
+        (**self).scatter(maxel)
+    }
+
+    fn albedo(&self, maxel: &mut Maxel<F>) -> Color<F> {
+        (**self).albedo(maxel)
+    }
+
+    fn to_sbt(&self, sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        (**self).to_sbt(sbt)
+    }
 }
 
 impl<F: Float> SceneObject<F> for DynMaterial<F> {
@@ -150,12 +255,14 @@ impl<F: Float> Interactive<F> for DynMaterial<F> {
 }
 
 mod blend;
+mod bsdf;
 mod bumpmap;
 mod chessboard;
 mod debug;
 mod fresnel;
 mod matte;
 mod mirror;
+mod pbr;
 mod phong;
 mod scaleuv;
 mod smart;
@@ -163,12 +270,17 @@ mod texture;
 mod triblend;
 
 pub use blend::Blend;
+pub use bsdf::{
+    cosine_hemisphere, facing_normal, lambert_bsdf, lambert_scatter, phong_lobe, rand_unit,
+    BsdfSample,
+};
 pub use bumpmap::{BumpPower, Bumpmap};
 pub use chessboard::{ChessBoard, ChessBoardMode};
 pub use debug::ColorDebug;
 pub use fresnel::Fresnel;
 pub use matte::Matte;
 pub use mirror::Mirror;
+pub use pbr::Pbr;
 pub use phong::Phong;
 pub use scaleuv::ScaleUV;
 pub use smart::Smart;
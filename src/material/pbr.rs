@@ -0,0 +1,316 @@
+use std::marker::PhantomData;
+
+use cgmath::InnerSpace;
+
+use crate::format::sbtwriter::{SbtNode, SbtWriter};
+use crate::material::{cosine_hemisphere, facing_normal, rand_unit, BsdfSample, Material};
+use crate::sampler::{Sampler, Texel};
+use crate::scene::{Interactive, RayTracer, SceneObject};
+use crate::sceneobject_impl_body;
+use crate::types::{Color, Float, Lerp, Maxel, Vector, Vectorx};
+
+/// Physically based metallic/roughness material.
+///
+/// Implements a Cook-Torrance microfacet BRDF, with a GGX normal
+/// distribution, Smith-Schlick geometry term and Schlick fresnel, on top of a
+/// Lambertian diffuse base. This is the material model used by most PBR
+/// texture sets (base color, metalness, roughness and normal maps).
+///
+/// As with [`crate::material::Phong`], the diffuse term is `base * cos`, so
+/// the BRDF is scaled by `pi` compared to its textbook definition.
+#[derive(Copy, Clone, Debug)]
+pub struct Pbr<F, SC, SM, SR, SN>
+where
+    F: Float + Texel,
+    SC: Sampler<F, Color<F>>,
+    SM: Sampler<F, F>,
+    SR: Sampler<F, F>,
+    SN: Sampler<F, Vector<F>>,
+{
+    /// Base color (albedo for dielectrics, specular color for metals)
+    color: SC,
+
+    /// Metalness, from 0 (dielectric) to 1 (metal)
+    metallic: SM,
+
+    /// Perceptual roughness, from 0 (smooth) to 1 (rough)
+    roughness: SR,
+
+    /// Tangent-space normal map
+    normal: Option<SN>,
+
+    /// Emissive color
+    ke: Color<F>,
+
+    /// Ambient color
+    ambient: Color<F>,
+
+    _p: PhantomData<F>,
+}
+
+/// Material parameters, sampled at a single surface point
+struct PbrSample<F: Float> {
+    nml: Vector<F>,
+    color: Color<F>,
+    metallic: F,
+    alpha: F,
+}
+
+impl<F: Float> PbrSample<F> {
+    /// Reflectance at normal incidence
+    fn f0(&self) -> Color<F> {
+        Color::gray(F::from_f32(0.04)).lerp(self.color, self.metallic)
+    }
+
+    /// Schlick approximation of the fresnel term
+    fn fresnel(&self, cos: F) -> Color<F> {
+        let f0 = self.f0();
+        f0 + (Color::WHITE - f0) * (F::ONE - cos).max(F::ZERO).powi(5)
+    }
+
+    /// GGX (Trowbridge-Reitz) normal distribution
+    fn ggx(&self, ndoth: F) -> F {
+        let a2 = self.alpha * self.alpha;
+        let d = ndoth * ndoth * (a2 - F::ONE) + F::ONE;
+        a2 / (F::PI() * d * d)
+    }
+
+    /// Smith-Schlick masking/shadowing term for both directions
+    fn smith(&self, ndotv: F, ndotl: F) -> F {
+        let k = self.alpha / F::TWO;
+        let g1 = |x: F| x / (x * (F::ONE - k) + k);
+        g1(ndotv) * g1(ndotl)
+    }
+
+    /// BRDF times cosine, for light arriving from `l`, viewed from `v`
+    fn eval(&self, v: Vector<F>, l: Vector<F>) -> Color<F> {
+        let ndotl = self.nml.dot(l);
+        let ndotv = self.nml.dot(v);
+        if ndotl < F::BIAS || ndotv <= F::ZERO {
+            return Color::BLACK;
+        }
+
+        let h = (v + l).normalize();
+        let ndoth = self.nml.dot(h).max(F::ZERO);
+        let vdoth = v.dot(h).max(F::ZERO);
+
+        let fresnel = self.fresnel(vdoth);
+        let spec =
+            fresnel * (self.ggx(ndoth) * self.smith(ndotv, ndotl) * F::PI() / (F::FOUR * ndotv));
+        let diff = (Color::WHITE - fresnel) * self.color * ((F::ONE - self.metallic) * ndotl);
+
+        diff + spec
+    }
+
+    /// Probability of choosing the specular lobe when sampling
+    fn spec_weight(&self, ndotv: F) -> F {
+        let spec = self.fresnel(ndotv).max_channel();
+        let diff = (self.color * (F::ONE - self.metallic)).max_channel();
+        (spec / (spec + diff + F::BIAS)).clamp(F::from_f32(0.1), F::from_f32(0.9))
+    }
+
+    /// Sample a microfacet normal from the GGX distribution
+    fn sample_half(&self) -> Vector<F> {
+        let (u, v) = self.nml.surface_tangents();
+        let a2 = self.alpha * self.alpha;
+        let phi = F::TWO * F::PI() * rand_unit();
+        let r: F = rand_unit();
+        let cos2_t = (F::ONE - r) / (F::ONE + (a2 - F::ONE) * r);
+        let cos_t = cos2_t.sqrt();
+        let sin_t = (F::ONE - cos2_t).max(F::ZERO).sqrt();
+
+        (u * (sin_t * phi.cos()) + v * (sin_t * phi.sin()) + self.nml * cos_t).normalize()
+    }
+
+    /// Combined pdf of sampling `l`, from either lobe
+    fn pdf(&self, v: Vector<F>, l: Vector<F>, spec_weight: F) -> F {
+        let h = (v + l).normalize();
+        let ndoth = self.nml.dot(h).max(F::ZERO);
+        let vdoth = v.dot(h).max(F::BIAS);
+
+        let pdf_diff = self.nml.dot(l).max(F::ZERO) / F::PI();
+        let pdf_spec = self.ggx(ndoth) * ndoth / (F::FOUR * vdoth);
+
+        (F::ONE - spec_weight) * pdf_diff + spec_weight * pdf_spec
+    }
+}
+
+impl<F, SC, SM, SR, SN> Pbr<F, SC, SM, SR, SN>
+where
+    F: Float + Texel,
+    SC: Sampler<F, Color<F>>,
+    SM: Sampler<F, F>,
+    SR: Sampler<F, F>,
+    SN: Sampler<F, Vector<F>>,
+{
+    #[must_use]
+    pub const fn new(color: SC, metallic: SM, roughness: SR, normal: Option<SN>) -> Self {
+        Self {
+            color,
+            metallic,
+            roughness,
+            normal,
+            ke: Color::BLACK,
+            ambient: Color::BLACK,
+            _p: PhantomData,
+        }
+    }
+
+    #[must_use]
+    pub fn with_ke(self, ke: Color<F>) -> Self {
+        Self { ke, ..self }
+    }
+
+    #[must_use]
+    pub fn with_ambient(self, ambient: Color<F>) -> Self {
+        Self { ambient, ..self }
+    }
+
+    fn sample(&self, maxel: &mut Maxel<F>) -> PbrSample<F> {
+        let mut nml = facing_normal(maxel);
+        if let Some(normal) = &self.normal {
+            let n = maxel.lookup(normal);
+            let (nu, nv) = maxel.tangents();
+            nml = (nu * n.x + nv * n.y + nml * n.z).normalize();
+        }
+
+        /* Clamp roughness, to avoid singularities for perfectly smooth surfaces */
+        let roughness = maxel
+            .lookup(&self.roughness)
+            .clamp(F::from_f32(0.03), F::ONE);
+
+        PbrSample {
+            nml,
+            color: maxel.lookup(&self.color) * maxel.color(),
+            metallic: maxel.lookup(&self.metallic).clamp(F::ZERO, F::ONE),
+            alpha: roughness * roughness,
+        }
+    }
+}
+
+impl<F, SC, SM, SR, SN> Material<F> for Pbr<F, SC, SM, SR, SN>
+where
+    F: Float + Texel,
+    SC: Sampler<F, Color<F>>,
+    SM: Sampler<F, F>,
+    SR: Sampler<F, F>,
+    SN: Sampler<F, Vector<F>>,
+{
+    fn render(&self, maxel: &mut Maxel<F>, rt: &dyn RayTracer<F>) -> Color<F> {
+        let smp = self.sample(maxel);
+        let view = -maxel.dir;
+
+        let mut res = self.ke + self.ambient * rt.scene().ambient;
+
+        for light in rt.scene().all_lights() {
+            let lixel = light.contribution(maxel, rt);
+            res += smp.eval(view, lixel.dir) * lixel.color;
+        }
+
+        /* Approximate glossy reflection of the surroundings, fading out with roughness */
+        let gloss = F::ONE - smp.alpha.sqrt();
+        let refl = smp.fresnel(smp.nml.dot(view).max(F::ZERO)) * (gloss * gloss);
+
+        if refl.max_channel() > F::from_f32(0.01) {
+            let dir = maxel.dir.reflect(&smp.nml);
+            let ray = maxel.ray(maxel.pos + smp.nml * F::BIAS3, dir);
+            res += rt.ray_trace(&ray).map_or(Color::BLACK, |c| c * refl);
+        }
+
+        res
+    }
+
+    fn emission(&self, _maxel: &mut Maxel<F>) -> Color<F> {
+        self.ke
+    }
+
+    fn albedo(&self, maxel: &mut Maxel<F>) -> Color<F> {
+        maxel.lookup(&self.color) * maxel.color()
+    }
+
+    fn bsdf(&self, maxel: &mut Maxel<F>, dir: Vector<F>) -> Color<F> {
+        self.sample(maxel).eval(-maxel.dir, dir)
+    }
+
+    fn scatter(&self, maxel: &mut Maxel<F>) -> Option<BsdfSample<F>> {
+        let smp = self.sample(maxel);
+        let view = -maxel.dir;
+
+        let ndotv = smp.nml.dot(view);
+        if ndotv <= F::ZERO {
+            return None;
+        }
+
+        let spec_weight = smp.spec_weight(ndotv);
+
+        let dir = if rand_unit::<F>() < spec_weight {
+            (-view).reflect(&smp.sample_half())
+        } else {
+            cosine_hemisphere(smp.nml)
+        };
+
+        let pdf = smp.pdf(view, dir, spec_weight);
+        if smp.nml.dot(dir) <= F::ZERO || pdf <= F::ZERO {
+            return None;
+        }
+
+        let ray = maxel.ray(maxel.pos + smp.nml * F::BIAS2, dir);
+
+        Some(BsdfSample {
+            ray,
+            weight: smp.eval(view, dir) / (pdf * F::PI()),
+        })
+    }
+
+    fn to_sbt(&self, sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        let mut props = vec![
+            ("base_color", sbt.sampler(&self.color, SbtNode::color)?),
+            ("metallic", sbt.sampler(&self.metallic, SbtNode::Float)?),
+            ("roughness", sbt.sampler(&self.roughness, SbtNode::Float)?),
+            ("emissive", SbtNode::color(self.ke)),
+            ("ambient", SbtNode::color(self.ambient)),
+        ];
+        if let Some(normal) = &self.normal {
+            props.push(("normal", sbt.map_sampler(normal, SbtNode::vector)?));
+        }
+        Some(SbtNode::Dict(props))
+    }
+}
+
+impl<F, SC, SM, SR, SN> Interactive<F> for Pbr<F, SC, SM, SR, SN>
+where
+    F: Float + Texel,
+    SC: Sampler<F, Color<F>>,
+    SM: Sampler<F, F>,
+    SR: Sampler<F, F>,
+    SN: Sampler<F, Vector<F>>,
+{
+    #[cfg(feature = "gui")]
+    fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        use crate::gui::controls;
+
+        ui.strong("Pbr");
+        ui.end_row();
+
+        let mut res = false;
+        res |= controls::color(ui, &mut self.ambient, "Ambient");
+        res |= controls::color(ui, &mut self.ke, "Emissive");
+        res |= self.color.ui(ui, "Base color");
+        res |= self.metallic.ui(ui, "Metallic");
+        res |= self.roughness.ui(ui, "Roughness");
+        res |= self.normal.as_mut().is_some_and(|m| m.ui(ui, "Normal map"));
+
+        res
+    }
+}
+
+impl<F, SC, SM, SR, SN> SceneObject<F> for Pbr<F, SC, SM, SR, SN>
+where
+    F: Float + Texel,
+    SC: Sampler<F, Color<F>>,
+    SM: Sampler<F, F>,
+    SR: Sampler<F, F>,
+    SN: Sampler<F, Vector<F>>,
+{
+    sceneobject_impl_body!("Pbr", egui_phosphor::regular::DIAMOND);
+}
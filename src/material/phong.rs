@@ -3,11 +3,14 @@ use std::ops::Mul;
 use cgmath::InnerSpace;
 use num::Zero;
 
-use crate::material::Material;
+use crate::format::sbtwriter::{SbtNode, SbtWriter};
+use crate::material::{
+    cosine_hemisphere, facing_normal, phong_lobe, rand_unit, BsdfSample, Material,
+};
 use crate::sampler::{Sampler, Texel};
 use crate::scene::{Interactive, RayTracer, SceneObject};
 use crate::sceneobject_impl_body;
-use crate::types::{Color, Float, Maxel, Point, Vectorx};
+use crate::types::{Color, Float, Maxel, Vector, Vectorx};
 
 #[derive(Copy, Clone, Debug)]
 pub struct Phong<F, SE, SD, SS, SP>
@@ -114,12 +117,26 @@ where
         Self { ambient, ..self }
     }
 
-    fn sample_map<T, S>(color: T, sampler: &Option<S>, uv: Point<F>) -> T
+    fn sample_map<T, S>(color: T, sampler: &Option<S>, maxel: &mut Maxel<F>) -> T
     where
         T: Texel + Mul<T, Output = T>,
         S: Sampler<F, T>,
     {
-        sampler.as_ref().map_or(color, |s| s.sample(uv) * color)
+        sampler.as_ref().map_or(color, |s| maxel.lookup(s) * color)
+    }
+
+    /* Maps are written as they are, without the constant they are multiplied
+     * with, since that is how the SBT loader reads them */
+    fn sbt_map<T: Texel, S: Sampler<F, T>>(
+        sbt: &SbtWriter<F>,
+        value: T,
+        map: Option<&S>,
+        node: impl Fn(T) -> SbtNode<F>,
+    ) -> Option<SbtNode<F>> {
+        match map {
+            Some(map) => sbt.sampler(map, node),
+            None => Some(node(value)),
+        }
     }
 }
 
@@ -150,17 +167,15 @@ where
     SP: Sampler<F, F>,
 {
     fn render(&self, maxel: &mut Maxel<F>, rt: &dyn RayTracer<F>) -> Color<F> {
-        let uv = maxel.uv();
-
-        let emis_color = Self::sample_map(self.ke, &self.ke_map, uv);
-        let diff_color = Self::sample_map(self.kd, &self.kd_map, uv);
-        let spec_color = Self::sample_map(self.ks, &self.ks_map, uv);
-        let spec_pow = Self::sample_map(self.pow, &self.pow_map, uv);
+        let emis_color = Self::sample_map(self.ke, &self.ke_map, maxel);
+        let diff_color = Self::sample_map(self.kd, &self.kd_map, maxel) * maxel.color();
+        let spec_color = Self::sample_map(self.ks, &self.ks_map, maxel);
+        let spec_pow = Self::sample_map(self.pow, &self.pow_map, maxel);
 
         let ambi_color = self.ambient * rt.scene().ambient;
         let mut res = emis_color + ambi_color;
 
-        for light in &rt.scene().lights {
+        for light in rt.scene().all_lights() {
             let lixel = light.contribution(maxel, rt);
 
             let lambert = maxel.nml().dot(lixel.dir);
@@ -183,6 +198,102 @@ where
         }
         res
     }
+
+    fn emission(&self, maxel: &mut Maxel<F>) -> Color<F> {
+        Self::sample_map(self.ke, &self.ke_map, maxel)
+    }
+
+    fn albedo(&self, maxel: &mut Maxel<F>) -> Color<F> {
+        Self::sample_map(self.kd, &self.kd_map, maxel) * maxel.color()
+    }
+
+    fn bsdf(&self, maxel: &mut Maxel<F>, dir: Vector<F>) -> Color<F> {
+        let lambert = maxel.nml().dot(dir);
+        if lambert < F::BIAS {
+            return Color::BLACK;
+        }
+
+        let diff_color = Self::sample_map(self.kd, &self.kd_map, maxel) * maxel.color();
+        let spec_color = Self::sample_map(self.ks, &self.ks_map, maxel);
+        let spec_pow = Self::sample_map(self.pow, &self.pow_map, maxel);
+
+        let mut res = diff_color * lambert;
+
+        if !spec_color.is_zero() && !spec_pow.is_zero() {
+            let refl_dir = dir.reflect(&maxel.nml());
+            let spec_angle = refl_dir.dot(maxel.dir).max(F::ZERO);
+            res += spec_color * spec_angle.pow(spec_pow);
+        }
+
+        res
+    }
+
+    fn scatter(&self, maxel: &mut Maxel<F>) -> Option<BsdfSample<F>> {
+        let diff_color = Self::sample_map(self.kd, &self.kd_map, maxel) * maxel.color();
+        let mut spec_color = Self::sample_map(self.ks, &self.ks_map, maxel);
+        let spec_pow = Self::sample_map(self.pow, &self.pow_map, maxel);
+
+        if spec_pow.is_zero() {
+            spec_color = Color::BLACK;
+        }
+
+        /* Pick diffuse or specular lobe, proportional to their strength */
+        let diff_weight = diff_color.max_channel();
+        let spec_weight = spec_color.max_channel();
+        let total = diff_weight + spec_weight;
+        if total <= F::ZERO {
+            return None;
+        }
+
+        let nml = facing_normal(maxel);
+
+        if rand_unit::<F>() * total < diff_weight {
+            let dir = cosine_hemisphere(nml);
+            let ray = maxel.ray(maxel.pos + nml * F::BIAS2, dir);
+            Some(BsdfSample {
+                ray,
+                weight: diff_color * (total / diff_weight),
+            })
+        } else {
+            let dir = phong_lobe(maxel.dir.reflect(&nml), spec_pow);
+            let cos = dir.dot(nml);
+            if cos <= F::ZERO {
+                return None;
+            }
+            let ray = maxel.ray(maxel.pos + nml * F::BIAS2, dir);
+            let norm = (spec_pow + F::TWO) / (spec_pow + F::ONE);
+            Some(BsdfSample {
+                ray,
+                weight: spec_color * (norm * cos * total / spec_weight),
+            })
+        }
+    }
+
+    fn to_sbt(&self, sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        let props = [
+            (
+                "emissive",
+                Self::sbt_map(sbt, self.ke, self.ke_map.as_ref(), SbtNode::color),
+            ),
+            (
+                "diffuse",
+                Self::sbt_map(sbt, self.kd, self.kd_map.as_ref(), SbtNode::color),
+            ),
+            (
+                "specular",
+                Self::sbt_map(sbt, self.ks, self.ks_map.as_ref(), SbtNode::color),
+            ),
+            (
+                "shininess",
+                Self::sbt_map(sbt, self.pow, self.pow_map.as_ref(), SbtNode::Float),
+            ),
+            ("ambient", Some(SbtNode::color(self.ambient))),
+        ];
+        let props = props
+            .into_iter()
+            .filter_map(|(key, value)| Some((key, value?)));
+        Some(SbtNode::Dict(props.collect()))
+    }
 }
 
 impl<F, SE, SD, SS, SP> Interactive<F> for Phong<F, SE, SD, SS, SP>
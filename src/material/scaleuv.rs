@@ -1,9 +1,9 @@
 use crate::light::Lixel;
-use crate::material::Material;
+use crate::material::{BsdfSample, Material};
 use crate::point;
 use crate::scene::{Interactive, RayTracer, SceneObject};
 use crate::sceneobject_impl_body;
-use crate::types::{Color, Float, Maxel, Point};
+use crate::types::{Color, Float, Maxel, Medium, Point, Vector};
 
 /// Proxy material that scales UV coordinates, before rendering backing material.
 #[derive(Copy, Clone, Debug)]
@@ -33,6 +33,36 @@ impl<F: Float, M: Material<F>> Material<F> for ScaleUV<F, M> {
         let mut smaxel = maxel.with_uv(self.uv.dot(uv));
         self.mat.shadow(&mut smaxel, rt, lixel)
     }
+
+    fn emission(&self, maxel: &mut Maxel<F>) -> Color<F> {
+        let uv = maxel.uv();
+        let mut smaxel = maxel.with_uv(self.uv.dot(uv));
+        self.mat.emission(&mut smaxel)
+    }
+
+    fn medium(&self, maxel: &mut Maxel<F>) -> Option<Medium<F>> {
+        let uv = maxel.uv();
+        let mut smaxel = maxel.with_uv(self.uv.dot(uv));
+        self.mat.medium(&mut smaxel)
+    }
+
+    fn albedo(&self, maxel: &mut Maxel<F>) -> Color<F> {
+        let uv = maxel.uv();
+        let mut smaxel = maxel.with_uv(self.uv.dot(uv));
+        self.mat.albedo(&mut smaxel)
+    }
+
+    fn bsdf(&self, maxel: &mut Maxel<F>, dir: Vector<F>) -> Color<F> {
+        let uv = maxel.uv();
+        let mut smaxel = maxel.with_uv(self.uv.dot(uv));
+        self.mat.bsdf(&mut smaxel, dir)
+    }
+
+    fn scatter(&self, maxel: &mut Maxel<F>) -> Option<BsdfSample<F>> {
+        let uv = maxel.uv();
+        let mut smaxel = maxel.with_uv(self.uv.dot(uv));
+        self.mat.scatter(&mut smaxel)
+    }
 }
 
 impl<F: Float, M: Material<F>> Interactive<F> for ScaleUV<F, M> {
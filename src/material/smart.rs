@@ -1,9 +1,10 @@
+use crate::format::sbtwriter::{SbtNode, SbtWriter};
 use crate::light::Lixel;
-use crate::material::{Fresnel, Material, Phong};
+use crate::material::{rand_unit, BsdfSample, Fresnel, Material, Phong};
 use crate::sampler::{Sampler, Texel};
 use crate::scene::{Interactive, RayTracer, SceneObject};
 use crate::sceneobject_impl_body;
-use crate::types::{Color, Float, Maxel};
+use crate::types::{Color, Float, Maxel, Medium, Vector};
 
 /// Smart material shader that supports ambient, diffuse, specular, translucent,
 /// and reflective light. Implements the Phong shader model for light transport.
@@ -56,6 +57,14 @@ where
             ..self
         }
     }
+
+    #[must_use]
+    pub fn with_medium(self, medium: Option<Medium<F>>) -> Self {
+        Self {
+            fresnel: self.fresnel.with_medium(medium),
+            ..self
+        }
+    }
 }
 
 impl<F, SE, SD, SS, SP, ST, SR> Material<F> for Smart<F, SE, SD, SS, SP, ST, SR>
@@ -75,6 +84,50 @@ where
     fn shadow(&self, maxel: &mut Maxel<F>, rt: &dyn RayTracer<F>, lixel: &Lixel<F>) -> Color<F> {
         self.fresnel.shadow(maxel, rt, lixel)
     }
+
+    fn emission(&self, maxel: &mut Maxel<F>) -> Color<F> {
+        self.phong.emission(maxel)
+    }
+
+    fn medium(&self, maxel: &mut Maxel<F>) -> Option<Medium<F>> {
+        self.fresnel.medium(maxel)
+    }
+
+    fn albedo(&self, maxel: &mut Maxel<F>) -> Color<F> {
+        self.phong.albedo(maxel) + self.fresnel.albedo(maxel)
+    }
+
+    fn bsdf(&self, maxel: &mut Maxel<F>, dir: Vector<F>) -> Color<F> {
+        self.phong.bsdf(maxel, dir)
+    }
+
+    fn scatter(&self, maxel: &mut Maxel<F>) -> Option<BsdfSample<F>> {
+        /* Pick one lobe, proportional to its albedo */
+        let phong = self.phong.albedo(maxel).max_channel();
+        let fresnel = self.fresnel.albedo(maxel).max_channel();
+        let total = phong + fresnel;
+        if total <= F::ZERO {
+            return self.phong.scatter(maxel);
+        }
+
+        if rand_unit::<F>() * total < phong {
+            self.phong.scatter(maxel).map(|s| s.scaled(total / phong))
+        } else {
+            self.fresnel
+                .scatter(maxel)
+                .map(|s| s.scaled(total / fresnel))
+        }
+    }
+
+    fn to_sbt(&self, sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        let (SbtNode::Dict(mut props), SbtNode::Dict(more)) =
+            (self.phong.to_sbt(sbt)?, self.fresnel.to_sbt(sbt)?)
+        else {
+            return None;
+        };
+        props.extend(more);
+        Some(SbtNode::Dict(props))
+    }
 }
 
 impl<F, SE, SD, SS, SP, ST, SR> Interactive<F> for Smart<F, SE, SD, SS, SP, ST, SR>
@@ -1,10 +1,10 @@
 use std::marker::PhantomData;
 
-use crate::material::Material;
+use crate::material::{lambert_bsdf, lambert_scatter, BsdfSample, Material};
 use crate::sampler::Sampler;
 use crate::scene::{Interactive, RayTracer, SceneObject};
 use crate::sceneobject_impl_body;
-use crate::types::{Color, Float, Maxel};
+use crate::types::{Color, Float, Maxel, Vector};
 
 pub trait TextureSampler<F: Float>
 where
@@ -39,7 +39,21 @@ impl<F: Float, S: Sampler<F, Color<F>>> Texture<F, S> {
 
 impl<F: Float, S: Sampler<F, Color<F>>> Material<F> for Texture<F, S> {
     fn render(&self, maxel: &mut Maxel<F>, _rt: &dyn RayTracer<F>) -> Color<F> {
-        self.img.sample(maxel.uv())
+        maxel.lookup(&self.img)
+    }
+
+    fn albedo(&self, maxel: &mut Maxel<F>) -> Color<F> {
+        maxel.lookup(&self.img)
+    }
+
+    fn bsdf(&self, maxel: &mut Maxel<F>, dir: Vector<F>) -> Color<F> {
+        let color = maxel.lookup(&self.img);
+        lambert_bsdf(maxel, color, dir)
+    }
+
+    fn scatter(&self, maxel: &mut Maxel<F>) -> Option<BsdfSample<F>> {
+        let color = maxel.lookup(&self.img);
+        lambert_scatter(maxel, color)
     }
 }
 
@@ -1,9 +1,9 @@
 use std::marker::PhantomData;
 
-use crate::material::Material;
+use crate::material::{rand_unit, BsdfSample, Material};
 use crate::scene::{Interactive, RayTracer, SceneObject};
 use crate::sceneobject_impl_body;
-use crate::types::{Color, Float, Maxel};
+use crate::types::{Color, Float, Maxel, Medium, Vector};
 
 /// Material blender, that interpolates between three materials.
 ///
@@ -50,6 +50,70 @@ where
 
         (a * w) + (b * u) + (c * v)
     }
+
+    fn emission(&self, maxel: &mut Maxel<F>) -> Color<F> {
+        let a = self.a.emission(maxel);
+        let b = self.b.emission(maxel);
+        let c = self.c.emission(maxel);
+
+        let st = maxel.st();
+        let w = F::ONE - st.x - st.y;
+
+        (a * w) + (b * st.x) + (c * st.y)
+    }
+
+    fn medium(&self, maxel: &mut Maxel<F>) -> Option<Medium<F>> {
+        let media = [
+            self.a.medium(maxel),
+            self.b.medium(maxel),
+            self.c.medium(maxel),
+        ];
+        if media.iter().all(Option::is_none) {
+            return None;
+        }
+        let [a, b, c] = media.map(|medium| medium.unwrap_or(Medium::VACUUM));
+
+        let st = maxel.st();
+        let w = F::ONE - st.x - st.y;
+
+        Some((a * w) + (b * st.x) + (c * st.y))
+    }
+
+    fn albedo(&self, maxel: &mut Maxel<F>) -> Color<F> {
+        let a = self.a.albedo(maxel);
+        let b = self.b.albedo(maxel);
+        let c = self.c.albedo(maxel);
+
+        let st = maxel.st();
+        let w = F::ONE - st.x - st.y;
+
+        (a * w) + (b * st.x) + (c * st.y)
+    }
+
+    fn bsdf(&self, maxel: &mut Maxel<F>, dir: Vector<F>) -> Color<F> {
+        let a = self.a.bsdf(maxel, dir);
+        let b = self.b.bsdf(maxel, dir);
+        let c = self.c.bsdf(maxel, dir);
+
+        let st = maxel.st();
+        let w = F::ONE - st.x - st.y;
+
+        (a * w) + (b * st.x) + (c * st.y)
+    }
+
+    fn scatter(&self, maxel: &mut Maxel<F>) -> Option<BsdfSample<F>> {
+        /* Pick one material, with probability equal to its blend weight */
+        let st = maxel.st();
+        let r = rand_unit::<F>();
+
+        if r < st.x {
+            self.b.scatter(maxel)
+        } else if r < st.x + st.y {
+            self.c.scatter(maxel)
+        } else {
+            self.a.scatter(maxel)
+        }
+    }
 }
 
 impl<F, A, B, C> Interactive<F> for Triblend<F, A, B, C>
@@ -1,3 +1,6 @@
 mod normals;
+mod tangents;
 
 pub use normals::{face_normals, smooth_normals};
+pub use tangents::smooth_tangents;
+pub(crate) use tangents::vertex_tangent;
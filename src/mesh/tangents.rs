@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+
+use cgmath::InnerSpace;
+
+use crate::{
+    geometry::Triangle,
+    types::{Float, Vector, Vectorx},
+};
+
+/// Tangent orthogonal to normal `n`, with the handedness of bitangent `b`
+pub(crate) fn vertex_tangent<F: Float>(
+    n: Vector<F>,
+    t: Vector<F>,
+    b: Vector<F>,
+) -> Option<(Vector<F>, F)> {
+    let n = n.normalize();
+    let tng = (t - n * n.dot(t)).normalize();
+    if !tng.magnitude2().is_finite() {
+        return None;
+    }
+    let sign = if n.cross(tng).dot(b) < F::ZERO {
+        -F::ONE
+    } else {
+        F::ONE
+    };
+    Some((tng, sign))
+}
+
+pub fn smooth_tangents<F: Float>(tris: &mut [Triangle<F>]) {
+    /* Vertex-smoothed tangents, in the style of MikkTSpace: face tangents
+     * are weighted by corner angle, and only vertices that share position,
+     * normal and texture coordinates are merged */
+    let mut tngs: HashMap<[u64; 3], (Vector<F>, Vector<F>)> = HashMap::new();
+
+    let keys = |tri: &Triangle<F>| {
+        [
+            (tri.a, tri.na, tri.ta),
+            (tri.b, tri.nb, tri.tb),
+            (tri.c, tri.nc, tri.tc),
+        ]
+        .map(|(p, n, t)| [p.hash(), n.hash(), Vector::new(t.x, t.y, F::ZERO).hash()])
+    };
+
+    for tri in tris.iter() {
+        let Some((t, b)) = tri.face_tangents() else {
+            continue;
+        };
+        let angles = [
+            tri.edge1.angle(tri.edge2).0,
+            (tri.a - tri.b).angle(tri.c - tri.b).0,
+            (tri.a - tri.c).angle(tri.b - tri.c).0,
+        ];
+        for (key, angle) in keys(tri).into_iter().zip(angles) {
+            if !angle.is_finite() {
+                continue;
+            }
+            let acc = tngs.entry(key).or_insert((Vector::ZERO, Vector::ZERO));
+            acc.0 += t * angle;
+            acc.1 += b * angle;
+        }
+    }
+
+    for tri in tris.iter_mut() {
+        let [ka, kb, kc] = keys(tri);
+        let tangent = |key: &[u64; 3], n: Vector<F>| {
+            tngs.get(key).and_then(|&(t, b)| vertex_tangent(n, t, b))
+        };
+        if let (Some(ta), Some(tb), Some(tc)) = (
+            tangent(&ka, tri.na),
+            tangent(&kb, tri.nb),
+            tangent(&kc, tri.nc),
+        ) {
+            tri.tangents = Some([ta, tb, tc]);
+        }
+    }
+}
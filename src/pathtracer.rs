@@ -0,0 +1,219 @@
+use std::fmt::{self, Debug};
+
+use num::Zero;
+
+use crate::light::Lixel;
+use crate::material::{rand_unit, Material};
+use crate::scene::{BoxScene, Interactive, RayTracer, SceneObject};
+use crate::sceneobject_impl_body;
+use crate::types::{Color, Float, Maxel, Ray};
+
+/// Unbiased Monte-Carlo path tracer.
+///
+/// Each call to [`RayTracer::ray_trace`] follows a single random path through
+/// the scene, so the result is noisy, and should be averaged over many
+/// samples (see [`crate::engine::RenderJob::with_passes`]).
+///
+/// Direct light is gathered at every bounce by next-event estimation against
+/// the scene lights (including the environment), while indirect light is sampled from the material
+/// [`Material::scatter`] distribution. Paths are terminated by Russian
+/// roulette, once they have reached `rrlvl` bounces.
+pub struct PathTracer<'a, F: Float> {
+    scene: &'a BoxScene<F>,
+    maxlvl: u16,
+    rrlvl: u16,
+}
+
+impl<'a, F: Float> PathTracer<'a, F> {
+    #[must_use]
+    pub const fn new(scene: &'a BoxScene<F>) -> Self {
+        Self {
+            scene,
+            maxlvl: 32,
+            rrlvl: 3,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_maxlvl(self, maxlvl: u16) -> Self {
+        Self { maxlvl, ..self }
+    }
+
+    /// Add the light scattered towards the viewer by the medium along `ray`
+    /// (up to `hit`) to `res`, and filter `throughput` by it. Returns `false`
+    /// if the ray is not inside any medium.
+    fn march(
+        &self,
+        ray: &Ray<F>,
+        hit: Option<&mut Maxel<F>>,
+        res: &mut Color<F>,
+        throughput: &mut Color<F>,
+    ) -> bool {
+        let Some((medium, dist)) = self.scene.medium_along(ray, hit) else {
+            return false;
+        };
+        let (trans, light) = self.scene.march(ray, &medium, dist, self);
+        *res += *throughput * light;
+        *throughput = *throughput * trans;
+        true
+    }
+
+    fn direct_light(&self, maxel: &mut Maxel<F>, mat: &dyn Material<F>) -> Color<F> {
+        let mut res = Color::BLACK;
+
+        for light in &self.scene.lights {
+            let lixel = light.contribution(maxel, self);
+            res += mat.bsdf(maxel, lixel.dir) * lixel.color;
+        }
+
+        /* A single importance sampled direction, rather than the fixed
+         * directions used by the Whitted tracer */
+        if let Some(env) = &self.scene.environment {
+            let lixel = env.sample_contribution(maxel, self);
+            res += mat.bsdf(maxel, lixel.dir) * lixel.color;
+        }
+
+        res
+    }
+}
+
+impl<'a, F: Float> RayTracer<F> for PathTracer<'a, F> {
+    fn ray_shadow(&self, maxel: &mut Maxel<F>, lixel: &Lixel<F>) -> Option<Color<F>> {
+        self.scene.ray_shadow(maxel, lixel, self)
+    }
+
+    fn ray_trace(&self, ray: &Ray<F>) -> Option<Color<F>> {
+        let mut res = Color::BLACK;
+        let mut throughput = Color::WHITE;
+
+        let mut hit = self.scene.intersect(ray);
+        if !self.march(ray, hit.as_mut(), &mut res, &mut throughput) && hit.is_none() {
+            return self.scene.environment_light(ray);
+        }
+        let Some(mut maxel) = hit else {
+            /* Background, seen through the fog */
+            let env = self.scene.environment_light(ray);
+            return Some(res + throughput * env.unwrap_or(self.scene.background));
+        };
+
+        /* Set when the path hits a light that was already sampled by
+         * direct_light() at the previous bounce */
+        let mut sampled = false;
+
+        loop {
+            let mat = &self.scene.materials.mats[&maxel.mat];
+
+            if !sampled {
+                res += throughput * mat.emission(&mut maxel);
+            }
+            res += throughput * self.direct_light(&mut maxel, mat);
+
+            if maxel.lvl >= self.maxlvl {
+                break;
+            }
+
+            let Some(sample) = mat.scatter(&mut maxel) else {
+                break;
+            };
+
+            throughput = throughput * sample.weight;
+
+            /* Russian roulette: randomly terminate low-contribution paths */
+            if maxel.lvl >= self.rrlvl {
+                let survive = throughput.max_channel().min(F::from_f32(0.95));
+                if rand_unit::<F>() >= survive {
+                    break;
+                }
+                throughput = throughput / survive;
+            }
+
+            let mut hit = self.scene.intersect_emitter(&sample.ray);
+            let next = hit.as_mut().map(|(next, _)| next);
+            self.march(&sample.ray, next, &mut res, &mut throughput);
+
+            let Some((next, emitter)) = hit else {
+                /* The environment was already sampled by direct_light(), unless
+                 * this is a specular bounce that bsdf() cannot represent */
+                if let Some(env) = self.scene.environment_light(&sample.ray) {
+                    if mat.bsdf(&mut maxel, sample.ray.dir).is_zero() {
+                        res += throughput * env;
+                    }
+                }
+                break;
+            };
+
+            sampled = emitter && !mat.bsdf(&mut maxel, sample.ray.dir).is_zero();
+            maxel = next;
+        }
+
+        Some(res)
+    }
+
+    fn scene(&self) -> &BoxScene<F> {
+        self.scene
+    }
+}
+
+impl<'a, F: Float> Debug for PathTracer<'a, F> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("PathTracer")
+            .field("scene", &"<scene>")
+            .field("maxlvl", &self.maxlvl)
+            .field("rrlvl", &self.rrlvl)
+            .finish()
+    }
+}
+
+impl<'a, F: Float> Interactive<F> for PathTracer<'a, F> {
+    #[cfg(feature = "gui")]
+    fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        let mut res = false;
+        res |= ui
+            .add(egui::Slider::new(&mut self.maxlvl, 1..=64).text("Max bounces"))
+            .changed();
+        res |= ui
+            .add(egui::Slider::new(&mut self.rrlvl, 1..=16).text("Russian roulette depth"))
+            .changed();
+        res
+    }
+}
+
+impl<'a, F: Float> SceneObject<F> for PathTracer<'a, F> {
+    sceneobject_impl_body!("Path tracer", egui_phosphor::regular::PATH);
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{InnerSpace, Matrix4};
+
+    use super::PathTracer;
+    use crate::geometry::Sphere;
+    use crate::material::Phong;
+    use crate::scene::{BoxScene, RayTracer};
+    use crate::types::{Color, Ray, Vector};
+
+    #[test]
+    fn test_path_furnace() {
+        /* Inside a closed sphere that emits 0.2 and reflects half of the
+         * light, every bounce adds half of the previous one, whichever way
+         * the path goes. Three bounces stay clear of Russian roulette, and
+         * add up to 0.2 * (1 + 1/2 + 1/4 + 1/8). */
+        let mut scene = BoxScene::<f64>::empty();
+        let mat = Phong::<f64, Color<f64>, Color<f64>, Color<f64>, f64>::new()
+            .with_ke(Color::gray(0.2))
+            .with_kd(Color::gray(0.5));
+        let mat = scene.materials.insert(Box::new(mat));
+        scene.add_object(Sphere::new(Matrix4::from_scale(10.0), mat));
+        scene.recompute_bvh().unwrap();
+
+        let tracer = PathTracer::new(&scene).with_maxlvl(3);
+        let ray = Ray::new(
+            Vector::new(0.0, 0.0, 0.0),
+            Vector::new(0.1, 0.2, 1.0).normalize(),
+        );
+        for _ in 0..16 {
+            let color = tracer.ray_trace(&ray).unwrap();
+            assert!((color.g - 0.375).abs() < 1e-9, "{color:?}");
+        }
+    }
+}
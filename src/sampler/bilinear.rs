@@ -1,29 +1,54 @@
 use std::fmt::{self, Debug};
 use std::marker::PhantomData;
 
+use crate::format::sbtwriter::SbtNode;
 use crate::point;
-use crate::sampler::{Sampler, Texel};
+use crate::sampler::{Sampler, Texel, Wrap};
 use crate::types::{Float, Point};
 
 #[derive(Copy, Clone)]
 pub struct Bilinear<P: Texel, S: Sampler<u32, P>> {
     samp: S,
+    pub(super) wrap: Wrap,
+    border: P,
     _p0: PhantomData<P>,
 }
 
-impl<P: Texel, S: Sampler<u32, P>> Bilinear<P, S> {
-    pub const fn new(samp: S) -> Self {
+impl<P: Texel + Copy, S: Sampler<u32, P>> Bilinear<P, S> {
+    pub fn new(samp: S) -> Self {
         Self {
             samp,
+            wrap: Wrap::default(),
+            border: P::zero(),
             _p0: PhantomData {},
         }
     }
+
+    #[must_use]
+    pub fn with_wrap(self, wrap: Wrap) -> Self {
+        Self { wrap, ..self }
+    }
+
+    /// Value outside of the image, for [`Wrap::Border`]
+    #[must_use]
+    pub fn with_border(self, border: P) -> Self {
+        Self { border, ..self }
+    }
+
+    fn texel(&self, x: i64, y: i64) -> P {
+        let (w, h) = self.samp.dimensions();
+        match (self.wrap.texel(x, w), self.wrap.texel(y, h)) {
+            (Some(x), Some(y)) => self.samp.sample(point!(x, y)),
+            _ => self.border,
+        }
+    }
 }
 
 impl<P: Texel, S: Sampler<u32, P>> Debug for Bilinear<P, S> {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt.debug_struct("Bilinear")
             .field("<type>", &self._p0)
+            .field("wrap", &self.wrap)
             .finish_non_exhaustive()
     }
 }
@@ -31,32 +56,28 @@ impl<P: Texel, S: Sampler<u32, P>> Debug for Bilinear<P, S> {
 impl<F, P, S> Sampler<F, P> for Bilinear<P, S>
 where
     F: Float,
-    P: Texel<Ratio = F>,
+    P: Texel<Ratio = F> + Copy,
     S: Sampler<u32, P>,
 {
     fn sample(&self, uv: Point<F>) -> P {
         let (w, h) = self.dimensions();
 
         /* Raw (x, y) coordinates */
-        let rx = (uv.x * F::from_u32(w) - F::ONE / F::from_u32(w / 2))
-            .max(F::ZERO)
-            .min(F::from_u32(w - 1));
-        let ry = (uv.y * F::from_u32(h) - F::ONE / F::from_u32(h / 2))
-            .max(F::ZERO)
-            .min(F::from_u32(h - 1));
+        let rx = uv.x * F::from_u32(w) - F::ONE / F::from_u32((w / 2).max(1));
+        let ry = uv.y * F::from_u32(h) - F::ONE / F::from_u32((h / 2).max(1));
 
         /* Integer coordinate part */
-        let x = rx.trunc().to_u32().unwrap_or(0);
-        let y = ry.trunc().to_u32().unwrap_or(0);
+        let x = rx.floor().to_i64().unwrap_or(0);
+        let y = ry.floor().to_i64().unwrap_or(0);
 
         /* Fractional coordinate part */
-        let fx = rx.fract();
-        let fy = ry.fract();
+        let fx = rx - rx.floor();
+        let fy = ry - ry.floor();
 
-        let n1 = self.samp.sample(point!(x, y));
-        let n2 = self.samp.sample(point!(x + 1, y));
-        let n3 = self.samp.sample(point!(x, y + 1));
-        let n4 = self.samp.sample(point!(x + 1, y + 1));
+        let n1 = self.texel(x, y);
+        let n2 = self.texel(x + 1, y);
+        let n3 = self.texel(x, y + 1);
+        let n4 = self.texel(x + 1, y + 1);
 
         let x1 = n1.lerp(n2, fx);
         let x2 = n3.lerp(n4, fx);
@@ -68,8 +89,44 @@ where
         self.samp.dimensions()
     }
 
+    fn sbt_args(&self, value: &dyn Fn(P) -> SbtNode<F>) -> Vec<(&'static str, SbtNode<F>)> {
+        wrap_args(self.wrap, self.border, value)
+    }
+
     #[cfg(feature = "gui")]
     fn ui(&mut self, ui: &mut egui::Ui, name: &str) -> bool {
-        self.samp.ui(ui, &format!("{name} (bilinear)"))
+        let res = self.samp.ui(ui, &format!("{name} (bilinear)"));
+        res | wrap_ui(ui, name, &mut self.wrap)
     }
 }
+
+/// Arguments for `map(..)` describing `wrap` (see [`Sampler::sbt_args`])
+pub(crate) fn wrap_args<F, P>(
+    wrap: Wrap,
+    border: P,
+    value: &dyn Fn(P) -> SbtNode<F>,
+) -> Vec<(&'static str, SbtNode<F>)> {
+    match wrap {
+        Wrap::Clamp => vec![],
+        Wrap::Border => vec![
+            ("wrap", SbtNode::Ident(wrap.name().into())),
+            ("border", value(border)),
+        ],
+        _ => vec![("wrap", SbtNode::Ident(wrap.name().into()))],
+    }
+}
+
+#[cfg(feature = "gui")]
+pub(crate) fn wrap_ui(ui: &mut egui::Ui, name: &str, wrap: &mut Wrap) -> bool {
+    let old = *wrap;
+    ui.label("Wrap mode");
+    egui::ComboBox::from_id_source(format!("{name}-wrap"))
+        .selected_text(wrap.to_string())
+        .show_ui(ui, |ui| {
+            for mode in Wrap::ALL {
+                ui.selectable_value(wrap, mode, mode.to_string());
+            }
+        });
+    ui.end_row();
+    old != *wrap
+}
@@ -22,10 +22,25 @@ impl<F: Float, T: Texel, A: Sampler<F, T>, B: Sampler<F, T>> ChessBoardSampler<F
     }
 }
 
+/// Fraction of `[x0, x1]` covered by cells where `|x|.fract() > 0.5`
+fn coverage<F: Float>(x0: F, x1: F) -> F {
+    /* Integral of the cell function from 0 to x (which is symmetric around 0) */
+    let integral = |x: F| {
+        let a = x.abs();
+        let res = a.floor() * F::HALF + (a.fract() - F::HALF).max(F::ZERO);
+        if x.is_negative() {
+            -res
+        } else {
+            res
+        }
+    };
+    (integral(x1) - integral(x0)) / (x1 - x0)
+}
+
 impl<F, T, A, B> Sampler<F, T> for ChessBoardSampler<F, T, A, B>
 where
     F: Float,
-    T: Texel,
+    T: Texel<Ratio = F>,
     A: Sampler<F, T>,
     B: Sampler<F, T>,
 {
@@ -40,6 +55,25 @@ where
         }
     }
 
+    fn sample_grad(&self, uv: Point<F>, dx: Point<F>, dy: Point<F>) -> T {
+        let du = dx.x.abs().max(dy.x.abs());
+        let dv = dx.y.abs().max(dy.y.abs());
+
+        if du.is_zero() || dv.is_zero() {
+            return self.sample(uv);
+        }
+
+        /* Box-filter the pattern over the footprint, as the chance of
+         * (u ^ v) for independent u and v */
+        let pu = coverage(uv.x - du, uv.x + du);
+        let pv = coverage(uv.y - dv, uv.y + dv);
+        let w = pu * (F::ONE - pv) + pv * (F::ONE - pu);
+
+        let a = self.a.sample_grad(uv, dx, dy);
+        let b = self.b.sample_grad(uv, dx, dy);
+        b.lerp(a, w)
+    }
+
     fn dimensions(&self) -> (u32, u32) {
         self.a.dimensions()
     }
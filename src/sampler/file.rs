@@ -0,0 +1,54 @@
+use camino::{Utf8Path, Utf8PathBuf};
+use num_traits::Num;
+
+use crate::format::sbtwriter::SbtNode;
+use crate::sampler::{Sampler, Texel};
+use crate::types::Point;
+
+/// Sampler loaded from an image file, which remembers where it came from (so
+/// scenes can be saved again, see [`crate::format::sbtwriter`])
+#[derive(Clone, Debug)]
+pub struct FileSampler<S> {
+    path: Utf8PathBuf,
+    samp: S,
+}
+
+impl<S> FileSampler<S> {
+    pub fn new(path: impl Into<Utf8PathBuf>, samp: S) -> Self {
+        Self {
+            path: path.into(),
+            samp,
+        }
+    }
+}
+
+impl<F: Num, T: Texel, S: Sampler<F, T>> Sampler<F, T> for FileSampler<S> {
+    fn sample(&self, uv: Point<F>) -> T {
+        self.samp.sample(uv)
+    }
+
+    fn sample_grad(&self, uv: Point<F>, dx: Point<F>, dy: Point<F>) -> T {
+        self.samp.sample_grad(uv, dx, dy)
+    }
+
+    fn dimensions(&self) -> (u32, u32) {
+        self.samp.dimensions()
+    }
+
+    fn source(&self) -> Option<&Utf8Path> {
+        Some(&self.path)
+    }
+
+    fn sbt_args(&self, value: &dyn Fn(T) -> SbtNode<F>) -> Vec<(&'static str, SbtNode<F>)> {
+        self.samp.sbt_args(value)
+    }
+
+    #[cfg(feature = "gui")]
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str) -> bool {
+        let res = self.samp.ui(ui, name);
+        ui.label("");
+        ui.monospace(self.path.file_name().unwrap_or_default());
+        ui.end_row();
+        res
+    }
+}
@@ -0,0 +1,244 @@
+use std::fmt::{self, Debug};
+
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView};
+
+use crate::format::sbtwriter::SbtNode;
+use crate::point;
+use crate::sampler::{Bilinear, Sampler, Texel, Wrap};
+use crate::types::{Float, Point};
+
+/// Images that can be reduced to half their size, to build mip pyramids
+pub trait Downsample: Sized {
+    /// Copy at half the size (rounded down, but at least 1x1)
+    #[must_use]
+    fn downsample(&self) -> Self;
+}
+
+impl Downsample for DynamicImage {
+    fn downsample(&self) -> Self {
+        let (w, h) = GenericImageView::dimensions(self);
+        self.resize_exact((w / 2).max(1), (h / 2).max(1), FilterType::Triangle)
+    }
+}
+
+/// Mipmapped image sampler.
+///
+/// Keeps a pyramid of downsampled copies of an image, and picks the level
+/// matching the texture footprint (see [`Sampler::sample_grad`]). Levels
+/// are blended trilinearly, and elongated footprints are covered by up to
+/// `anisotropy` probes along their long axis.
+#[derive(Clone)]
+pub struct Mipmap<P: Texel, S: Sampler<u32, P>> {
+    levels: Vec<Bilinear<P, S>>,
+    anisotropy: u32,
+}
+
+impl<P: Texel + Copy, S: Sampler<u32, P>> Mipmap<P, S> {
+    pub fn new(img: S) -> Self
+    where
+        S: Downsample,
+    {
+        let mut levels = vec![];
+        let mut img = img;
+        loop {
+            let (w, h) = img.dimensions();
+            let next = (w > 1 || h > 1).then(|| img.downsample());
+            levels.push(Bilinear::new(img));
+            match next {
+                Some(next) => img = next,
+                None => break,
+            }
+        }
+
+        Self {
+            levels,
+            anisotropy: 8,
+        }
+    }
+
+    #[must_use]
+    pub fn with_wrap(self, wrap: Wrap) -> Self {
+        Self {
+            levels: self
+                .levels
+                .into_iter()
+                .map(|level| level.with_wrap(wrap))
+                .collect(),
+            ..self
+        }
+    }
+
+    /// Value outside of the image, for [`Wrap::Border`]
+    #[must_use]
+    pub fn with_border(self, border: P) -> Self {
+        Self {
+            levels: self
+                .levels
+                .into_iter()
+                .map(|level| level.with_border(border))
+                .collect(),
+            ..self
+        }
+    }
+
+    /// Maximum number of probes for elongated footprints (1 for plain
+    /// trilinear filtering)
+    #[must_use]
+    pub fn with_anisotropy(self, anisotropy: u32) -> Self {
+        Self {
+            anisotropy: anisotropy.max(1),
+            ..self
+        }
+    }
+
+    /// Number of levels in the pyramid, including the full size image
+    #[must_use]
+    pub fn levels(&self) -> usize {
+        self.levels.len()
+    }
+}
+
+impl<P: Texel, S: Sampler<u32, P>> Debug for Mipmap<P, S> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("Mipmap")
+            .field("levels", &self.levels.len())
+            .field("anisotropy", &self.anisotropy)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F, P, S> Mipmap<P, S>
+where
+    F: Float,
+    P: Texel<Ratio = F> + Copy,
+    S: Sampler<u32, P>,
+{
+    /// Sample at fractional pyramid level `lod`, blending the two nearest
+    /// levels
+    fn trilinear(&self, uv: Point<F>, lod: F) -> P {
+        let last = self.levels.len() - 1;
+        let lod = lod.clamp(F::ZERO, F::from_usize(last));
+        let level = lod.floor().to_usize().unwrap_or(0).min(last);
+        let frac = lod - lod.floor();
+
+        let res = self.levels[level].sample(uv);
+        if level == last || frac.is_zero() {
+            return res;
+        }
+        res.lerp(self.levels[level + 1].sample(uv), frac)
+    }
+}
+
+impl<F, P, S> Sampler<F, P> for Mipmap<P, S>
+where
+    F: Float,
+    P: Texel<Ratio = F> + Copy,
+    S: Sampler<u32, P>,
+{
+    fn sample(&self, uv: Point<F>) -> P {
+        self.levels[0].sample(uv)
+    }
+
+    fn sample_grad(&self, uv: Point<F>, dx: Point<F>, dy: Point<F>) -> P {
+        let (w, h) = self.dimensions();
+        let size = point!(F::from_u32(w), F::from_u32(h));
+
+        /* Footprint axes, in texels of the full size image */
+        let len = |d: Point<F>| {
+            let d = d.dot(size);
+            d.x.hypot(d.y)
+        };
+        let (major, minor) = if len(dx) >= len(dy) {
+            (dx, dy)
+        } else {
+            (dy, dx)
+        };
+        let (lmajor, lminor) = (len(major), len(minor));
+
+        /* Split elongated footprints into several probes along the major
+         * axis, each covering a roughly square part of it */
+        let ratio = if lminor > F::ZERO {
+            (lmajor / lminor).ceil()
+        } else {
+            F::from_u32(self.anisotropy)
+        };
+        let probes = ratio.to_u32().unwrap_or(1).clamp(1, self.anisotropy);
+        let width = (lmajor / F::from_u32(probes)).max(lminor);
+
+        if width <= F::ONE {
+            return self.levels[0].sample(uv);
+        }
+
+        let lod = width.log2();
+        if probes == 1 {
+            return self.trilinear(uv, lod);
+        }
+
+        let count = F::from_u32(probes);
+        (0..probes)
+            .map(|i| {
+                let t = (F::from_u32(i) + F::HALF) / count - F::HALF;
+                self.trilinear(uv + major * t, lod)
+            })
+            .fold(P::zero(), |acc, texel| acc + texel)
+            * (F::ONE / count)
+    }
+
+    fn dimensions(&self) -> (u32, u32) {
+        self.levels[0].dimensions()
+    }
+
+    fn sbt_args(&self, value: &dyn Fn(P) -> SbtNode<F>) -> Vec<(&'static str, SbtNode<F>)> {
+        self.levels[0].sbt_args(value)
+    }
+
+    #[cfg(feature = "gui")]
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str) -> bool {
+        let mut res = self.levels[0].ui(ui, name);
+
+        /* Keep the wrap mode of all levels in sync */
+        let wrap = self.levels[0].wrap;
+        for level in &mut self.levels[1..] {
+            level.wrap = wrap;
+        }
+
+        ui.label("Anisotropy");
+        res |= ui
+            .add(egui::Slider::new(&mut self.anisotropy, 1..=16))
+            .changed();
+        ui.end_row();
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, GrayImage, Luma};
+
+    use super::Mipmap;
+    use crate::point;
+    use crate::sampler::Sampler;
+    use crate::types::Point;
+
+    #[test]
+    fn test_mipmap_footprint() {
+        /* 16x16 checkerboard of single-pixel cells */
+        let img = GrayImage::from_fn(16, 16, |x, y| {
+            Luma([if (x + y) % 2 == 0 { 255 } else { 0 }])
+        });
+        let mip = Mipmap::<f64, _>::new(DynamicImage::ImageLuma8(img));
+        assert_eq!(mip.levels(), 5);
+
+        /* Footprints smaller than a texel sample the full size image */
+        let uv = point!(0.5 / 16.0, 0.5 / 16.0);
+        let tiny = point!(0.01 / 16.0, 0.0);
+        let value: f64 = mip.sample_grad(uv, tiny, point!(0.0, 0.01 / 16.0));
+        assert_eq!(value, mip.sample(uv));
+
+        /* Footprints covering many texels average them out */
+        let value = mip.sample_grad(uv, point!(0.5, 0.0), point!(0.0, 0.5));
+        assert!((value - 0.5).abs() < 0.05, "{value}");
+    }
+}
@@ -2,9 +2,11 @@ use std::ops::Sub;
 use std::sync::Arc;
 use std::{fmt::Debug, ops::Add};
 
+use camino::Utf8Path;
 use num::Zero;
 use num_traits::Num;
 
+use crate::format::sbtwriter::SbtNode;
 use crate::types::{Color, Float, Lerp, Point};
 
 /** Trait for sampling values from datasource (textures, etc)
@@ -18,6 +20,12 @@ where
     /** Sample a single value at position `uv` */
     fn sample(&self, uv: Point<F>) -> T;
 
+    /** Sample a value at position `uv`, filtered over the footprint spanned
+     * by `dx` and `dy` (the change in `uv` between neighbouring pixels) */
+    fn sample_grad(&self, uv: Point<F>, _dx: Point<F>, _dy: Point<F>) -> T {
+        self.sample(uv)
+    }
+
     /** Return (`width`, `height`) dimensions of sampler */
     fn dimensions(&self) -> (u32, u32);
 
@@ -28,6 +36,17 @@ where
         Arc::new(self)
     }
 
+    /** Image file this sampler was loaded from, if any */
+    fn source(&self) -> Option<&Utf8Path> {
+        None
+    }
+
+    /** Extra named arguments (wrap mode, uv transform, ..) for writing this
+     * sampler as an sbt `map(..)`, using `value` to format texels */
+    fn sbt_args(&self, _value: &dyn Fn(T) -> SbtNode<F>) -> Vec<(&'static str, SbtNode<F>)> {
+        vec![]
+    }
+
     #[cfg(feature = "gui")]
     fn ui(&mut self, ui: &mut egui::Ui, name: &str) -> bool;
 }
@@ -40,10 +59,22 @@ impl<F: Num, T: Texel> Sampler<F, T> for DynSampler<F, T> {
         (**self).sample(uv)
     }
 
+    fn sample_grad(&self, uv: Point<F>, dx: Point<F>, dy: Point<F>) -> T {
+        (**self).sample_grad(uv, dx, dy)
+    }
+
     fn dimensions(&self) -> (u32, u32) {
         (**self).dimensions()
     }
 
+    fn source(&self) -> Option<&Utf8Path> {
+        (**self).source()
+    }
+
+    fn sbt_args(&self, value: &dyn Fn(T) -> SbtNode<F>) -> Vec<(&'static str, SbtNode<F>)> {
+        (**self).sbt_args(value)
+    }
+
     #[cfg(feature = "gui")]
     fn ui(&mut self, ui: &mut egui::Ui, name: &str) -> bool {
         if let Some(samp) = Arc::get_mut(self) {
@@ -111,7 +142,9 @@ impl<F: Float> Sampler<F, Self> for Color<F> {
 
 mod bilinear;
 mod chessboard;
+mod file;
 mod heightnormal;
+mod mipmap;
 mod nearest;
 mod normalmap;
 mod perlin;
@@ -119,13 +152,19 @@ mod samplerext;
 mod shinemap;
 mod texture;
 mod transform;
+mod uvtransform;
+mod wrap;
 
 pub use bilinear::Bilinear;
 pub use chessboard::ChessBoardSampler;
+pub use file::FileSampler;
 pub use heightnormal::HeightNormal;
+pub use mipmap::{Downsample, Mipmap};
 pub use nearest::Nearest;
 pub use normalmap::NormalMap;
 pub use perlin::Perlin;
 pub use samplerext::SamplerExt;
 pub use shinemap::ShineMap;
 pub use transform::Adjust;
+pub use uvtransform::UvTransform;
+pub use wrap::Wrap;
@@ -1,54 +1,75 @@
 use std::marker::PhantomData;
 
+use crate::format::sbtwriter::SbtNode;
 use crate::point;
-use crate::sampler::{Sampler, Texel};
+use crate::sampler::bilinear::wrap_args;
+use crate::sampler::{Sampler, Texel, Wrap};
 use crate::types::{Float, Point};
 
 #[derive(Copy, Clone, Debug)]
 pub struct Nearest<P: Texel, S: Sampler<u32, P>> {
     samp: S,
+    wrap: Wrap,
+    border: P,
     _p0: PhantomData<P>,
 }
 
-impl<P: Texel, S: Sampler<u32, P>> Nearest<P, S> {
-    pub const fn new(samp: S) -> Self {
+impl<P: Texel + Copy, S: Sampler<u32, P>> Nearest<P, S> {
+    pub fn new(samp: S) -> Self {
         Self {
             samp,
+            wrap: Wrap::default(),
+            border: P::zero(),
             _p0: PhantomData {},
         }
     }
+
+    #[must_use]
+    pub fn with_wrap(self, wrap: Wrap) -> Self {
+        Self { wrap, ..self }
+    }
+
+    /// Value outside of the image, for [`Wrap::Border`]
+    #[must_use]
+    pub fn with_border(self, border: P) -> Self {
+        Self { border, ..self }
+    }
 }
 
 impl<F, P, S> Sampler<F, P> for Nearest<P, S>
 where
     F: Float,
-    P: Texel,
+    P: Texel + Copy,
     S: Sampler<u32, P>,
 {
     fn sample(&self, uv: Point<F>) -> P {
         let (w, h) = self.samp.dimensions();
 
         /* Raw (x, y) coordinates */
-        let rx = (uv.x * F::from_u32(w) - F::ONE / F::from_u32(w / 2))
-            .max(F::ZERO)
-            .min(F::from_u32(w - 1));
-        let ry = (uv.y * F::from_u32(h) - F::ONE / F::from_u32(h / 2))
-            .max(F::ZERO)
-            .min(F::from_u32(h - 1));
+        let rx = uv.x * F::from_u32(w) - F::ONE / F::from_u32((w / 2).max(1));
+        let ry = uv.y * F::from_u32(h) - F::ONE / F::from_u32((h / 2).max(1));
 
         /* Integer coordinate part */
-        let x = rx.trunc().to_u32().unwrap_or(0);
-        let y = ry.trunc().to_u32().unwrap_or(0);
+        let x = rx.floor().to_i64().unwrap_or(0);
+        let y = ry.floor().to_i64().unwrap_or(0);
 
-        self.samp.sample(point!(x, y))
+        match (self.wrap.texel(x, w), self.wrap.texel(y, h)) {
+            (Some(x), Some(y)) => self.samp.sample(point!(x, y)),
+            _ => self.border,
+        }
     }
 
     fn dimensions(&self) -> (u32, u32) {
         self.samp.dimensions()
     }
 
+    fn sbt_args(&self, value: &dyn Fn(P) -> SbtNode<F>) -> Vec<(&'static str, SbtNode<F>)> {
+        wrap_args(self.wrap, self.border, value)
+    }
+
     #[cfg(feature = "gui")]
     fn ui(&mut self, ui: &mut egui::Ui, name: &str) -> bool {
-        self.samp.ui(ui, &format!("{name} (nearest)"))
+        let res = self.samp.ui(ui, &format!("{name} (nearest)"));
+        res | crate::sampler::bilinear::wrap_ui(ui, name, &mut self.wrap)
     }
 }
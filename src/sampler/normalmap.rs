@@ -1,7 +1,9 @@
 use std::marker::PhantomData;
 
+use camino::Utf8Path;
 use cgmath::InnerSpace;
 
+use crate::format::sbtwriter::SbtNode;
 use crate::sampler::Sampler;
 use crate::types::{Color, Float, Point, Vector};
 
@@ -34,10 +36,26 @@ impl<F: Float, S: Sampler<F, Color<F>>> Sampler<F, Vector<F>> for NormalMap<F, S
         Self::color_to_vector(&self.sampler.sample(uv))
     }
 
+    fn sample_grad(&self, uv: Point<F>, dx: Point<F>, dy: Point<F>) -> Vector<F> {
+        Self::color_to_vector(&self.sampler.sample_grad(uv, dx, dy))
+    }
+
     fn dimensions(&self) -> (u32, u32) {
         self.sampler.dimensions()
     }
 
+    fn source(&self) -> Option<&Utf8Path> {
+        self.sampler.source()
+    }
+
+    fn sbt_args(
+        &self,
+        _value: &dyn Fn(Vector<F>) -> SbtNode<F>,
+    ) -> Vec<(&'static str, SbtNode<F>)> {
+        /* Border values are stored as raw colours */
+        self.sampler.sbt_args(&SbtNode::color)
+    }
+
     #[cfg(feature = "gui")]
     fn ui(&mut self, ui: &mut egui::Ui, name: &str) -> bool {
         ui.strong("Normal map");
@@ -1,4 +1,4 @@
-use crate::sampler::{Adjust, Bilinear, Nearest, Sampler, Texel};
+use crate::sampler::{Adjust, Bilinear, Downsample, Mipmap, Nearest, Sampler, Texel};
 use crate::types::Float;
 
 pub trait SamplerExt<T: Texel> {
@@ -9,6 +9,14 @@ pub trait SamplerExt<T: Texel> {
         Bilinear::new(self)
     }
 
+    fn mipmap(self) -> Mipmap<T, Self>
+    where
+        Self: Sampler<u32, T> + Downsample + Sized,
+        T: Copy,
+    {
+        Mipmap::new(self)
+    }
+
     fn nearest(self) -> Nearest<T, Self>
     where
         Self: Sampler<u32, T> + Sized,
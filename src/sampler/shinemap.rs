@@ -1,3 +1,6 @@
+use camino::Utf8Path;
+
+use crate::format::sbtwriter::SbtNode;
 use crate::sampler::Sampler;
 use crate::types::{Float, Point};
 
@@ -18,10 +21,22 @@ impl<F: Float, S: Sampler<F, F>> Sampler<F, F> for ShineMap<F, S> {
         self.sampler.sample(uv) * self.scale
     }
 
+    fn sample_grad(&self, uv: Point<F>, dx: Point<F>, dy: Point<F>) -> F {
+        self.sampler.sample_grad(uv, dx, dy) * self.scale
+    }
+
     fn dimensions(&self) -> (u32, u32) {
         self.sampler.dimensions()
     }
 
+    fn source(&self) -> Option<&Utf8Path> {
+        self.sampler.source()
+    }
+
+    fn sbt_args(&self, value: &dyn Fn(F) -> SbtNode<F>) -> Vec<(&'static str, SbtNode<F>)> {
+        self.sampler.sbt_args(value)
+    }
+
     #[cfg(feature = "gui")]
     fn ui(&mut self, ui: &mut egui::Ui, name: &str) -> bool {
         ui.strong("Shine map");
@@ -33,6 +33,10 @@ where
         self.samp.sample(uv) * self.scale + self.offset
     }
 
+    fn sample_grad(&self, uv: Point<F>, dx: Point<F>, dy: Point<F>) -> T {
+        self.samp.sample_grad(uv, dx, dy) * self.scale + self.offset
+    }
+
     fn dimensions(&self) -> (u32, u32) {
         self.samp.dimensions()
     }
@@ -0,0 +1,135 @@
+use std::marker::PhantomData;
+
+use camino::Utf8Path;
+use cgmath::Rad;
+
+use crate::format::sbtwriter::SbtNode;
+use crate::point;
+use crate::sampler::{Sampler, Texel};
+use crate::types::{Float, Point};
+
+/// Sampler adaptor that transforms texture coordinates, before sampling the
+/// backing sampler.
+///
+/// Coordinates are scaled first, then rotated around the origin, and finally
+/// offset.
+#[derive(Copy, Clone, Debug)]
+pub struct UvTransform<F: Float, T: Texel, S: Sampler<F, T>> {
+    scale: Point<F>,
+    rotation: Rad<F>,
+    offset: Point<F>,
+    samp: S,
+    _p: PhantomData<T>,
+}
+
+impl<F: Float, T: Texel, S: Sampler<F, T>> UvTransform<F, T, S> {
+    pub const fn new(samp: S) -> Self {
+        Self {
+            scale: Point::new(F::ONE, F::ONE),
+            rotation: Rad(F::ZERO),
+            offset: Point::ZERO,
+            samp,
+            _p: PhantomData {},
+        }
+    }
+
+    #[must_use]
+    pub fn with_scale(self, scale: Point<F>) -> Self {
+        Self { scale, ..self }
+    }
+
+    #[must_use]
+    pub fn with_rotation(self, rotation: Rad<F>) -> Self {
+        Self { rotation, ..self }
+    }
+
+    #[must_use]
+    pub fn with_offset(self, offset: Point<F>) -> Self {
+        Self { offset, ..self }
+    }
+
+    /// Scale and rotate `uv`, without the offset (for footprints)
+    fn turn(&self, uv: Point<F>) -> Point<F> {
+        let (sin, cos) = self.rotation.0.sin_cos();
+        let x = uv.x * self.scale.x;
+        let y = uv.y * self.scale.y;
+        point!(x * cos - y * sin, x * sin + y * cos)
+    }
+
+    fn apply(&self, uv: Point<F>) -> Point<F> {
+        self.turn(uv) + self.offset
+    }
+}
+
+impl<F: Float, T: Texel, S: Sampler<F, T>> Sampler<F, T> for UvTransform<F, T, S> {
+    fn sample(&self, uv: Point<F>) -> T {
+        self.samp.sample(self.apply(uv))
+    }
+
+    fn sample_grad(&self, uv: Point<F>, dx: Point<F>, dy: Point<F>) -> T {
+        self.samp
+            .sample_grad(self.apply(uv), self.turn(dx), self.turn(dy))
+    }
+
+    fn dimensions(&self) -> (u32, u32) {
+        self.samp.dimensions()
+    }
+
+    fn source(&self) -> Option<&Utf8Path> {
+        self.samp.source()
+    }
+
+    fn sbt_args(&self, value: &dyn Fn(T) -> SbtNode<F>) -> Vec<(&'static str, SbtNode<F>)> {
+        let mut res = self.samp.sbt_args(value);
+        if self.scale.x != F::ONE || self.scale.y != F::ONE {
+            res.push(("scale", SbtNode::point(self.scale)));
+        }
+        if self.rotation.0 != F::ZERO {
+            res.push(("rotate", SbtNode::Float(self.rotation.0)));
+        }
+        if self.offset.x != F::ZERO || self.offset.y != F::ZERO {
+            res.push(("offset", SbtNode::point(self.offset)));
+        }
+        res
+    }
+
+    #[cfg(feature = "gui")]
+    fn ui(&mut self, ui: &mut egui::Ui, name: &str) -> bool {
+        use egui::Slider;
+        use num_traits::FloatConst;
+
+        let mut res = self.samp.ui(ui, name);
+
+        ui.label("UV scale");
+        ui.horizontal(|ui| {
+            for value in [&mut self.scale.x, &mut self.scale.y] {
+                res |= ui
+                    .add(
+                        Slider::new(value, F::from_f32(0.01)..=F::from_u32(100))
+                            .logarithmic(true)
+                            .clamp_to_range(false),
+                    )
+                    .changed();
+            }
+        });
+        ui.end_row();
+
+        ui.label("UV rotation");
+        res |= ui
+            .add(Slider::new(&mut self.rotation.0, -F::PI()..=F::PI()).step_by(f64::PI() / 180.0))
+            .changed();
+        ui.end_row();
+
+        ui.label("UV offset");
+        ui.horizontal(|ui| {
+            for value in [&mut self.offset.x, &mut self.offset.y] {
+                res |= ui
+                    .add(Slider::new(value, F::ZERO..=F::ONE).clamp_to_range(false))
+                    .changed();
+            }
+        });
+        ui.end_row();
+
+        res
+    }
+}
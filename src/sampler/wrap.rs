@@ -0,0 +1,109 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::types::Error;
+
+/// How image samplers handle texture coordinates outside of the image
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Wrap {
+    /// Tile the image
+    Repeat,
+    /// Tile the image, mirroring every other copy
+    Mirror,
+    /// Extend the edge texels
+    #[default]
+    Clamp,
+    /// Use a fixed border value
+    Border,
+}
+
+impl Wrap {
+    pub const ALL: [Self; 4] = [Self::Repeat, Self::Mirror, Self::Clamp, Self::Border];
+
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Repeat => "repeat",
+            Self::Mirror => "mirror",
+            Self::Clamp => "clamp",
+            Self::Border => "border",
+        }
+    }
+
+    /// Texel index for coordinate `x` in an image `size` texels wide, or
+    /// `None` if it falls on the border
+    #[must_use]
+    pub fn texel(self, x: i64, size: u32) -> Option<u32> {
+        let size = i64::from(size.max(1));
+        let idx = match self {
+            Self::Repeat => x.rem_euclid(size),
+            Self::Mirror => {
+                let idx = x.rem_euclid(size * 2);
+                if idx < size {
+                    idx
+                } else {
+                    size * 2 - 1 - idx
+                }
+            }
+            Self::Clamp => x.clamp(0, size - 1),
+            Self::Border if (0..size).contains(&x) => x,
+            Self::Border => return None,
+        };
+        u32::try_from(idx).ok()
+    }
+}
+
+impl Display for Wrap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Wrap {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|wrap| wrap.name() == s)
+            .ok_or_else(|| Error::ParseUnsupported(format!("wrap mode {s}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Wrap;
+
+    #[test]
+    fn test_wrap_modes() {
+        let texels = |wrap: Wrap| (-3..7).map(|x| wrap.texel(x, 3)).collect::<Vec<_>>();
+
+        assert_eq!(
+            texels(Wrap::Repeat),
+            [0, 1, 2, 0, 1, 2, 0, 1, 2, 0].map(Some)
+        );
+        assert_eq!(
+            texels(Wrap::Mirror),
+            [2, 1, 0, 0, 1, 2, 2, 1, 0, 0].map(Some)
+        );
+        assert_eq!(
+            texels(Wrap::Clamp),
+            [0, 0, 0, 0, 1, 2, 2, 2, 2, 2].map(Some)
+        );
+        assert_eq!(
+            texels(Wrap::Border),
+            [
+                None,
+                None,
+                None,
+                Some(0),
+                Some(1),
+                Some(2),
+                None,
+                None,
+                None,
+                None
+            ]
+        );
+    }
+}
@@ -1,15 +1,25 @@
 use crate::geometry::{FiniteGeometry, Geometry, Group};
-use crate::light::{DirectionalLight, Light, Lixel};
+use crate::light::{DirectionalLight, EnvironmentLight, Light, Lixel};
+use crate::material::{rand_unit, Material};
 use crate::types::{
-    Camera, Color, Float, MaterialLib, Maxel, RResult, Ray, TextureLib, Vector, Vectorx,
+    Animation, Camera, CameraSelector, Color, Error, Float, MaterialLib, Maxel, Medium, RResult,
+    Ray, TextureLib, Vector, Vectorx,
 };
 use crate::vec3;
 
 use cgmath::{InnerSpace, Matrix4, MetricSpace, SquareMatrix};
 
+use num_traits::Zero;
 use rtbvh::Primitive;
 use std::fmt::Debug;
 
+/// Maximum number of translucent surfaces a shadow ray passes through, before
+/// the light counts as blocked
+const MAX_SHADOW_HITS: usize = 16;
+
+/// Number of ray marching steps through participating media
+const MAX_MEDIUM_STEPS: u32 = 256;
+
 pub trait SceneObject<F: Float> {
     fn get_name(&self) -> &str;
     fn get_icon(&self) -> &str;
@@ -18,6 +28,10 @@ pub trait SceneObject<F: Float> {
     fn get_object(&mut self, _id: usize) -> Option<&mut dyn Geometry<F>> {
         None
     }
+    /// Child number `idx`, for objects made of (unshared) other objects
+    fn get_child(&mut self, _idx: usize) -> Option<&mut dyn Geometry<F>> {
+        None
+    }
 }
 
 #[macro_export]
@@ -77,6 +91,10 @@ pub struct Scene<F: Float, B: FiniteGeometry<F>, G: Geometry<F>, L: Light<F>> {
     pub lights: Vec<L>,
     pub ambient: Color<F>,
     pub background: Color<F>,
+    pub environment: Option<EnvironmentLight<F>>,
+    /// Fog filling the scene (see [`Scene::medium_along`])
+    pub medium: Option<Medium<F>>,
+    pub animation: Animation<F>,
 }
 
 pub type BoxScene<F> = Scene<
@@ -103,6 +121,9 @@ impl<F: Float, B: FiniteGeometry<F>, G: Geometry<F>, L: Light<F>> Scene<F, B, G,
             lights,
             background: Color::new(F::ZERO, F::ZERO, F::from_f32(0.2)),
             ambient: Color::BLACK,
+            environment: None,
+            medium: None,
+            animation: Animation::default(),
         };
 
         res.root.recompute_bvh()?;
@@ -121,6 +142,9 @@ impl<F: Float, B: FiniteGeometry<F>, G: Geometry<F>, L: Light<F>> Scene<F, B, G,
             lights: vec![],
             ambient: Color::BLACK,
             background: Color::new(F::ZERO, F::ZERO, F::from_f32(0.2)),
+            environment: None,
+            medium: None,
+            animation: Animation::default(),
         }
     }
 
@@ -131,6 +155,184 @@ impl<F: Float, B: FiniteGeometry<F>, G: Geometry<F>, L: Light<F>> Scene<F, B, G,
         self.textures.texs.clear();
         self.materials.mats.clear();
         self.lights.clear();
+        self.environment = None;
+        self.medium = None;
+        self.animation.clear();
+    }
+
+    /// Move all animated cameras, lights and objects to `frame`
+    pub fn set_frame(&mut self, frame: F) -> RResult<()> {
+        self.animation
+            .apply(frame, &mut self.cameras, &mut self.lights, &mut self.root)
+    }
+
+    /// All lights in the scene, including the environment light (if any)
+    pub fn all_lights(&self) -> impl Iterator<Item = &dyn Light<F>> {
+        let env = self.environment.iter().map(|env| env as &dyn Light<F>);
+        self.lights
+            .iter()
+            .map(|light| light as &dyn Light<F>)
+            .chain(env)
+    }
+
+    /// Light from the environment, for a ray that escaped the scene
+    pub fn environment_light(&self, ray: &Ray<F>) -> Option<Color<F>> {
+        self.environment.as_ref().map(|env| env.radiance(ray.dir))
+    }
+
+    /// Light from `lixel` that reaches `maxel` through the scene, or `None`
+    /// if nothing is in the way.
+    ///
+    /// Shadow rays continue through translucent surfaces, so the light is
+    /// filtered by every surface (see [`Material::shadow`]) and medium (see
+    /// [`Scene::medium_along`]) on the way, and stop at the first opaque one.
+    /// Light that would pass more than [`MAX_SHADOW_HITS`] surfaces is
+    /// blocked.
+    pub fn ray_shadow(
+        &self,
+        maxel: &mut Maxel<F>,
+        lixel: &Lixel<F>,
+        rt: &dyn RayTracer<F>,
+    ) -> Option<Color<F>> {
+        let mut ray = maxel.shadow_ray(lixel);
+        let mut len2 = lixel.len2;
+        let mut res = None;
+
+        for n in 0..=MAX_SHADOW_HITS {
+            let mut dist2 = len2;
+            let Some(mut hit) = self.root.nearest_intersection(&ray, &mut dist2) else {
+                break;
+            };
+            if n == MAX_SHADOW_HITS {
+                return Some(Color::BLACK);
+            }
+
+            let mut color = res.unwrap_or(lixel.color);
+            if let Some((medium, dist)) = self.medium_along(&ray, Some(&mut hit)) {
+                color = color * medium.transmittance(dist);
+            }
+
+            len2 = (len2.sqrt() - dist2.sqrt()).powi(2);
+            let mat = &self.materials.mats[&hit.mat];
+            let color = mat.shadow(
+                &mut hit,
+                rt,
+                &Lixel {
+                    color,
+                    len2,
+                    ..*lixel
+                },
+            );
+            if color.max_channel() <= F::ZERO {
+                return Some(Color::BLACK);
+            }
+
+            res = Some(color);
+            ray = hit.ray(hit.pos + ray.dir * F::BIAS2, ray.dir);
+        }
+
+        /* Fog between the last surface and the light */
+        if let Some(fog) = &self.medium {
+            let dist = self.fog_distance(&ray, len2.sqrt());
+            res = Some(res.unwrap_or(lixel.color) * fog.transmittance(dist));
+        }
+
+        res
+    }
+
+    /// Medium that `ray` travels through to `hit` (or out of the scene, if
+    /// `None`), and the distance travelled in it.
+    ///
+    /// Rays that leave a closed object through its surface are inside the
+    /// medium of its material (see [`Material::medium`]), and all others are
+    /// in the fog of the scene, which fills its bounding box.
+    pub fn medium_along(&self, ray: &Ray<F>, hit: Option<&mut Maxel<F>>) -> Option<(Medium<F>, F)> {
+        let Some(hit) = hit else {
+            let fog = self.medium?;
+            return Some((fog, self.fog_distance(ray, F::max_value())));
+        };
+
+        let dist = ray.pos.distance(hit.pos);
+        if ray.dir.dot(hit.nml()) > F::ZERO {
+            if let Some(medium) = self.materials.mats[&hit.mat].medium(hit) {
+                return Some((medium, dist));
+            }
+        }
+        self.medium.map(|fog| (fog, dist))
+    }
+
+    /// Distance that `ray` travels inside the bounding box of the scene,
+    /// before reaching `max`. Rays that reach the background (or a light)
+    /// only pass through fog inside this box.
+    fn fog_distance(&self, ray: &Ray<F>, max: F) -> F {
+        let aabb = self.root.aabb();
+        if !aabb.is_valid() {
+            return F::ZERO;
+        }
+        let (lo, hi) = aabb.points();
+        let (lo, hi): (Vector<F>, Vector<F>) = (Vector::from_vec3(lo), Vector::from_vec3(hi));
+
+        /* The ray is inside the box from where it has entered all three
+         * slabs, to where it leaves the first one */
+        let (mut near, mut far) = (F::ZERO, max);
+        for axis in 0..3 {
+            let inv = ray.dir[axis].recip();
+            let t0 = (lo[axis] - ray.pos[axis]) * inv;
+            let t1 = (hi[axis] - ray.pos[axis]) * inv;
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+        }
+        (far - near).max(F::ZERO)
+    }
+
+    /// Light lost and gained along the first `dist` units of `ray`, through
+    /// `medium`: the fraction of light that passes through, and the light
+    /// from the scene lights that is scattered back along the ray.
+    ///
+    /// The in-scattered light is found by ray marching, every
+    /// [`Medium::step`] units (up to a limit, after which the steps grow),
+    /// with a random offset for the sample points. Each sample point is lit
+    /// by one randomly chosen light, so the noise averages out over samples.
+    pub fn march(
+        &self,
+        ray: &Ray<F>,
+        medium: &Medium<F>,
+        dist: F,
+        rt: &dyn RayTracer<F>,
+    ) -> (Color<F>, Color<F>) {
+        let trans = medium.transmittance(dist);
+        if medium.scattering.is_zero() || dist <= F::ZERO {
+            return (trans, Color::BLACK);
+        }
+
+        if self.lights.is_empty() {
+            return (trans, Color::BLACK);
+        }
+
+        let steps = (dist / medium.step)
+            .ceil()
+            .to_u32()
+            .unwrap_or(MAX_MEDIUM_STEPS)
+            .clamp(1, MAX_MEDIUM_STEPS);
+        let step = dist / F::from_u32(steps);
+        let offset = rand_unit::<F>();
+        let lights = F::from_usize(self.lights.len());
+        let mut res = Color::BLACK;
+
+        for i in 0..steps {
+            let t = (F::from_u32(i) + offset) * step;
+            let mut probe = ray
+                .synthetic_hit(ray.extend(t), &self.root)
+                .with_normal(Vector::ZERO);
+
+            let idx = (rand_unit::<F>() * lights).to_usize().unwrap_or(0);
+            let lgt = &self.lights[idx.min(self.lights.len() - 1)];
+            let lixel = lgt.contribution(&mut probe, rt);
+            let light = lixel.color * (medium.phase(lixel.dir.dot(ray.dir)) * lights);
+            res += light * medium.transmittance(t);
+        }
+
+        (trans, res * medium.scattering * step)
     }
 
     pub fn recompute_bvh(&mut self) -> RResult<()> {
@@ -138,20 +340,59 @@ impl<F: Float, B: FiniteGeometry<F>, G: Geometry<F>, L: Light<F>> Scene<F, B, G,
     }
 
     pub fn intersect(&self, ray: &Ray<F>) -> Option<Maxel<F>> {
+        self.intersect_emitter(ray).map(|(maxel, _)| maxel)
+    }
+
+    /// Like [`Scene::intersect`], but also tells if the hit is on the
+    /// surface of a light (see [`Light::intersect`]).
+    pub fn intersect_emitter(&self, ray: &Ray<F>) -> Option<(Maxel<F>, bool)> {
         let mut dist = F::max_value();
-        let mut hit: Option<Maxel<F>> = None;
+        let mut hit: Option<(Maxel<F>, bool)> = None;
 
         for g in &self.geometry {
             if let Some(curhit) = g.intersect(ray) {
                 let curdist = ray.pos.distance2(curhit.pos);
                 if curdist > F::BIAS2 && curdist < dist {
                     dist = curdist;
-                    hit = Some(curhit);
+                    hit = Some((curhit, false));
+                }
+            }
+        }
+
+        for light in &self.lights {
+            if let Some(curhit) = light.intersect(ray) {
+                let curdist = ray.pos.distance2(curhit.pos);
+                if curdist > F::BIAS2 && curdist < dist {
+                    dist = curdist;
+                    hit = Some((curhit, true));
+                }
+            }
+        }
+
+        self.root
+            .nearest_intersection(ray, &mut dist)
+            .map(|maxel| (maxel, false))
+            .or(hit)
+    }
+
+    /// Like [`Scene::intersect`], but also returns the index of the
+    /// intersected top-level object (objects in `root` first, followed by
+    /// `geometry`).
+    pub fn intersect_object(&self, ray: &Ray<F>) -> Option<(usize, Maxel<F>)> {
+        let mut dist = F::max_value();
+        let mut hit: Option<(usize, Maxel<F>)> = None;
+
+        for (idx, g) in self.geometry.iter().enumerate() {
+            if let Some(curhit) = g.intersect(ray) {
+                let curdist = ray.pos.distance2(curhit.pos);
+                if curdist > F::BIAS2 && curdist < dist {
+                    dist = curdist;
+                    hit = Some((self.root.len() + idx, curhit));
                 }
             }
         }
 
-        self.root.nearest_intersection(ray, &mut dist).or(hit)
+        self.root.nearest_object(ray, &mut dist).or(hit)
     }
 
     pub fn set_ambient(&mut self, ambient: Color<F>) {
@@ -173,6 +414,12 @@ impl<F: Float, B: FiniteGeometry<F>, G: Geometry<F>, L: Light<F>> Scene<F, B, G,
     pub fn add_camera(&mut self, camera: Camera<F>) {
         self.cameras.push(camera);
     }
+
+    pub fn find_camera(&self, camera: &CameraSelector) -> RResult<usize> {
+        camera
+            .find(&self.cameras)
+            .ok_or_else(|| Error::UnknownCamera(camera.to_string()))
+    }
 }
 
 impl<F: Float> BoxScene<F> {
@@ -188,6 +435,14 @@ impl<F: Float> BoxScene<F> {
         self.root.add_object(Box::new(geometry));
     }
 
+    /// Remove a top-level object, and the animation tracks that refer to it
+    pub fn del_object(&mut self, id: usize) {
+        if let Some(idx) = self.root.iter().position(|obj| obj.get_id() == Some(id)) {
+            self.animation.remove_object(idx);
+        }
+        self.root.del_object(id);
+    }
+
     pub fn add_camera_if_missing(&mut self) -> RResult<()> {
         if !self.cameras.is_empty() {
             return Ok(());
@@ -223,3 +478,219 @@ impl<F: Float> BoxScene<F> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write;
+
+    use cgmath::{InnerSpace, MetricSpace};
+
+    use super::RayTracer;
+    use crate::format::sbt2::tests::{load, save};
+    use crate::light::Lixel;
+    use crate::tracer::Tracer;
+    use crate::types::{Color, Ray, Vector, Vectorx};
+
+    #[test]
+    fn test_shadow_transmission() {
+        let scene = load(
+            r"
+SBT-raytracer 1.0
+material = { transmissive = (0.5, 0.5, 0.5); }
+translate(0, 1, 0, rotate(1, 0, 0, -1.5708, square { }))
+translate(0, 2, 0, rotate(1, 0, 0, -1.5708, square { }))
+translate(0, 3.5, 0, scale(0.5, sphere { material = {
+    transmissive = (1, 1, 1); absorption = (1, 0, 0);
+}; }))
+scale(20, rotate(1, 0, 0, -1.5708, square { material = { transmissive = (0, 0, 0); }; }))
+",
+        );
+
+        let ray = Ray::new(
+            Vector::new(5.0, 5.0, 0.0),
+            Vector::new(-1.0, -1.0, 0.0).normalize(),
+        );
+        let mut maxel = scene.intersect(&ray).unwrap();
+        assert!(maxel.pos.distance(Vector::ZERO) < 1e-6);
+
+        /* Two panes, and one unit of distance through the absorbing ball */
+        let lixel = Lixel {
+            dir: Vector::UNIT_Y,
+            color: Color::WHITE,
+            len2: 25.0,
+        };
+        let tracer = Tracer::new(&scene);
+        let color = scene.ray_shadow(&mut maxel, &lixel, &tracer).unwrap();
+        assert!((color.r - 0.25 * (-1.0f64).exp()).abs() < 1e-3);
+        assert!((color.g - 0.25).abs() < 1e-3);
+        assert!((color.b - 0.25).abs() < 1e-3);
+
+        assert!(save(&scene).contains("absorption = (1.0, 0.0, 0.0);"));
+    }
+
+    #[test]
+    fn test_shadow_oblique() {
+        let scene = load(
+            r"
+SBT-raytracer 1.0
+material = { transmissive = (0.5, 0.5, 0.5); }
+translate(0, 1, 0, rotate(1, 0, 0, -1.5708, scale(8, square { })))
+translate(0, 2, 0, rotate(1, 0, 0, -1.5708, scale(8, square { })))
+scale(20, rotate(1, 0, 0, -1.5708, square { material = { transmissive = (0, 0, 0); }; }))
+",
+        );
+        let tracer = Tracer::new(&scene);
+
+        /* Panes filter light by their colour alone, at any angle */
+        for slope in [0.0, 0.5, 1.0, 1.5] {
+            let ray = Ray::new(
+                Vector::new(0.0, 0.5, 0.0),
+                Vector::new(0.01, -1.0, 0.01).normalize(),
+            );
+            let mut maxel = scene.intersect(&ray).unwrap();
+            let lixel = Lixel {
+                dir: Vector::new(slope, 1.0, 0.0).normalize(),
+                color: Color::WHITE,
+                len2: 100.0,
+            };
+            let color = scene.ray_shadow(&mut maxel, &lixel, &tracer).unwrap();
+            assert!((color.g - 0.25).abs() < 1e-6, "{slope}: {color:?}");
+        }
+    }
+
+    #[test]
+    fn test_shadow_many_panes() {
+        let mut panes = String::new();
+        for n in 1..=20 {
+            writeln!(
+                panes,
+                "translate(0, {n}, 0, rotate(1, 0, 0, -1.5708, scale(8, square {{ }})))"
+            )
+            .unwrap();
+        }
+        let scene = load(&format!(
+            "SBT-raytracer 1.0
+material = {{ transmissive = (1, 1, 1); }}
+{panes}
+scale(20, rotate(1, 0, 0, -1.5708, square {{ material = {{ transmissive = (0, 0, 0); }}; }}))
+"
+        ));
+        let tracer = Tracer::new(&scene);
+
+        /* Light behind more clear panes than a shadow ray follows is blocked */
+        let ray = Ray::new(
+            Vector::new(0.0, 0.5, 0.0),
+            Vector::new(0.01, -1.0, 0.01).normalize(),
+        );
+        let mut maxel = scene.intersect(&ray).unwrap();
+        let lixel = Lixel {
+            dir: Vector::UNIT_Y,
+            color: Color::WHITE,
+            len2: 900.0,
+        };
+        let color = scene.ray_shadow(&mut maxel, &lixel, &tracer).unwrap();
+        assert_eq!(color, Color::BLACK);
+
+        let lixel = Lixel {
+            len2: 100.0,
+            ..lixel
+        };
+        let color = scene.ray_shadow(&mut maxel, &lixel, &tracer).unwrap();
+        assert!((color.g - 1.0).abs() < 1e-6, "{color:?}");
+    }
+
+    #[test]
+    fn test_fog() {
+        let scene = load(
+            r"
+SBT-raytracer 1.0
+fog { absorption = (0.01, 0.01, 0.01); scattering = (0.1, 0.1, 0.1); anisotropy = 0.5; }
+point_light { position = (0, 1, 0); color = (1, 1, 1); }
+scale(4, sphere { material = { transmissive = (1, 1, 1); index = 1; scattering = (1, 0, 0); }; })
+",
+        );
+        let fog = scene.medium.unwrap();
+        assert_eq!(fog.scattering.g, 0.1);
+        assert_eq!(fog.anisotropy, 0.5);
+
+        /* Camera rays passing the light pick up light scattered by the fog,
+         * and rays through the ball pick up the red light scattered inside */
+        let tracer = Tracer::new(&scene);
+        let ray = Ray::new(Vector::new(-20.0, 0.0, 0.0), Vector::UNIT_X);
+        let color = tracer.ray_trace(&ray).unwrap();
+        assert!(color.r > color.g && color.g > 0.0);
+
+        let text = save(&scene);
+        assert!(text.contains("fog {"));
+        assert_eq!(save(&load(&text)), text);
+    }
+
+    #[test]
+    fn test_fog_transmittance() {
+        let ray = Ray::new(
+            Vector::new(0.0, 0.0, 10.0),
+            Vector::new(0.001, 0.002, -1.0).normalize(),
+        );
+
+        /* Light from a lit ball 19 units away, through absorbing fog */
+        let scene = load(
+            r"
+SBT-raytracer 1.0
+fog { absorption = (0.01, 0.02, 0.05); }
+ambient_light { color = (1, 1, 1); }
+translate(0, 0, -10, sphere { material = { ambient = (1, 1, 1); diffuse = (0, 0, 0); specular = (0, 0, 0); }; })
+",
+        );
+        let color = Tracer::new(&scene).ray_trace(&ray).unwrap();
+        for (found, sigma) in [(color.r, 0.01), (color.g, 0.02), (color.b, 0.05)] {
+            let expected = (-sigma * 19.0f64).exp();
+            assert!((found - expected).abs() < 1e-3, "{found} {expected}");
+        }
+
+        /* Rays that miss everything only pass the fog in the bounding box
+         * of the ball, not in the sphere around that box */
+        assert!((scene.fog_distance(&ray, f64::MAX) - 2.0).abs() < 1e-3);
+        assert!((scene.fog_distance(&ray, 19.5) - 0.5).abs() < 1e-3);
+        let ray = Ray::new(Vector::new(1.5, 0.0, 10.0), -Vector::UNIT_Z);
+        assert!(scene.fog_distance(&ray, f64::MAX) <= 0.0);
+    }
+
+    #[test]
+    fn test_fog_march() {
+        let ray = Ray::new(Vector::new(0.0, 0.0, 10.0), -Vector::UNIT_Z);
+
+        /* Light scattered towards the camera over 19 units, integral of
+         * sigma / 4pi * exp(-sigma * t). The sample points are offset at
+         * random, but always fall between the left and right sums */
+        let density = |t: f64| 0.02 * (-0.02 * t).exp() / (4.0 * std::f64::consts::PI);
+        for step in [0.1, 5.0] {
+            let scene = load(&format!(
+                r"
+SBT-raytracer 1.0
+fog {{ scattering = (0.02, 0.02, 0.02); step = {step}; }}
+directional_light {{ direction = (0, -1, 0); color = (1, 1, 1); }}
+translate(0, 0, -10, sphere {{ }})
+",
+            ));
+            let medium = scene.medium.unwrap();
+            assert_eq!(medium.step, step);
+
+            let tracer = Tracer::new(&scene);
+            let (trans, light) = scene.march(&ray, &medium, 19.0, &tracer);
+            assert!((trans.g - (-0.02f64 * 19.0).exp()).abs() < 1e-9);
+
+            let steps = (19.0 / step).ceil();
+            let h = 19.0 / steps;
+            let left: f64 = (0..steps as u32)
+                .map(|i| density(f64::from(i) * h) * h)
+                .sum();
+            let right: f64 = (1..=steps as u32)
+                .map(|i| density(f64::from(i) * h) * h)
+                .sum();
+            assert!(
+                right - 1e-9 <= light.g && light.g <= left + 1e-9,
+                "{step}: {light:?}"
+            );
+        }
+    }
+}
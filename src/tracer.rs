@@ -5,7 +5,7 @@ use crate::light::Lixel;
 use crate::material::Material;
 use crate::scene::{BoxScene, Interactive, RayTracer, SceneObject};
 use crate::sceneobject_impl_body;
-use crate::types::{Camera, Color, Float, Maxel, Point, Ray};
+use crate::types::{AntiAlias, Aov, Camera, Color, Float, Maxel, Point, Ray};
 
 pub struct Tracer<'a, F: Float> {
     scene: &'a BoxScene<F>,
@@ -27,8 +27,7 @@ impl<'a, F: Float> Tracer<'a, F> {
 
     pub fn render_pixel_single(&self, camera: &Camera<F>, point: Point<F>) -> Color<F> {
         let ray = camera.get_ray(point);
-        self.ray_trace(&ray)
-            .map_or_else(|| self.scene.background, Color::clamped)
+        self.ray_trace(&ray).unwrap_or(self.scene.background)
     }
 
     pub fn render_pixel(&self, camera: &Camera<F>, point: Point<F>, size: Point<F>) -> Color<F> {
@@ -45,18 +44,83 @@ impl<'a, F: Float> Tracer<'a, F> {
         colors / F::from_u32(self.sx * self.sy)
     }
 
-    pub fn render_line(&self, camera: &Camera<F>, width: u32, y: u32) -> RenderSpan<F> {
-        let pixels = (0..width)
-            .map(|x| self.render_pixel_single(camera, (x, y).into()))
-            .collect();
+    /// Render line `y` of a `width` x `height` image, anti-aliased according to `aa`.
+    pub fn render_line(
+        &self,
+        camera: &Camera<F>,
+        aa: &AntiAlias<F>,
+        width: u32,
+        height: u32,
+        y: u32,
+    ) -> RenderSpan<F> {
+        let pixel = aa.footprint(width, height, (1, 1));
+        let pixels = aa.render_line(width, height, (1, 1), y, |point| {
+            let ray = camera.get_pixel_ray(point, pixel);
+            self.ray_trace(&ray).unwrap_or(self.scene.background)
+        });
 
         RenderSpan {
             line: y,
             mult_x: 1,
             mult_y: 1,
             pixels,
+            generation: 0,
         }
     }
+
+    /// Second pass of adaptive anti-aliasing for line `y`, given the first
+    /// pass of the surrounding lines (see [`AntiAlias::refine_line`]).
+    pub fn refine_line(
+        &self,
+        camera: &Camera<F>,
+        aa: &AntiAlias<F>,
+        width: u32,
+        height: u32,
+        y: u32,
+        rows: [Option<&[Color<F>]>; 3],
+    ) -> RenderSpan<F> {
+        let pixel = aa.footprint(width, height, (1, 1));
+        let pixels = aa.refine_line(width, height, (1, 1), y, rows, |point| {
+            let ray = camera.get_pixel_ray(point, pixel);
+            self.ray_trace(&ray).unwrap_or(self.scene.background)
+        });
+
+        RenderSpan {
+            line: y,
+            mult_x: 1,
+            mult_y: 1,
+            pixels,
+            generation: 0,
+        }
+    }
+
+    /// Render line `y` of each of the `aovs` passes, with a single sample in
+    /// the center of each pixel, through the center of the lens (see
+    /// [`Camera::get_center_ray`]). Returns one line of values per pass.
+    pub fn render_aov_line(
+        &self,
+        camera: &Camera<F>,
+        aovs: &[Aov],
+        width: u32,
+        height: u32,
+        y: u32,
+    ) -> Vec<Vec<Color<F>>> {
+        let mut res = vec![Vec::with_capacity(width as usize); aovs.len()];
+        let size = Point::from((width, height));
+        let pixel = Point::from((F::ONE, F::ONE)) / size;
+
+        for x in 0..width {
+            let point = (Point::from((x, y)) + Point::from((F::HALF, F::HALF))) / size;
+            let ray = camera.get_center_ray(point, pixel);
+            let mut hit = self.scene.intersect_object(&ray);
+
+            for (aov, line) in aovs.iter().zip(&mut res) {
+                line.push(aov.value(self.scene, &ray, &mut hit));
+            }
+        }
+
+        res
+    }
 }
 
 impl<'a, F: Float> RayTracer<F> for Tracer<'a, F> {
@@ -65,17 +129,7 @@ impl<'a, F: Float> RayTracer<F> for Tracer<'a, F> {
             return None;
         }
 
-        let hitray = maxel.shadow_ray(lixel);
-
-        let mut len2 = lixel.len2;
-
-        self.scene
-            .root
-            .nearest_intersection(&hitray, &mut len2)
-            .map(|mut maxel| {
-                let mat = &self.scene.materials.mats[&maxel.mat];
-                mat.shadow(&mut maxel, self, lixel)
-            })
+        self.scene.ray_shadow(maxel, lixel, self)
     }
 
     fn ray_trace(&self, ray: &Ray<F>) -> Option<Color<F>> {
@@ -83,10 +137,25 @@ impl<'a, F: Float> RayTracer<F> for Tracer<'a, F> {
             return None;
         }
 
-        let mut maxel = self.scene.intersect(ray)?;
+        let Some(mut maxel) = self.scene.intersect(ray) else {
+            let env = self.scene.environment_light(ray);
+            let Some((fog, dist)) = self.scene.medium_along(ray, None) else {
+                return env;
+            };
+            /* Background, seen through the fog */
+            let (trans, light) = self.scene.march(ray, &fog, dist, self);
+            return Some(env.unwrap_or(self.scene.background) * trans + light);
+        };
 
         let mat = &self.scene.materials.mats[&maxel.mat];
-        Some(mat.render(&mut maxel, self))
+        let color = mat.render(&mut maxel, self);
+
+        /* Light lost and gained in the medium, on the way to the surface */
+        if let Some((medium, dist)) = self.scene.medium_along(ray, Some(&mut maxel)) {
+            let (trans, light) = self.scene.march(ray, &medium, dist, self);
+            return Some(color * trans + light);
+        }
+        Some(color)
     }
 
     fn scene(&self) -> &BoxScene<F> {
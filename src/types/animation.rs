@@ -0,0 +1,466 @@
+use std::cmp::Ordering;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use cgmath::{Deg, Matrix4, SquareMatrix};
+
+use crate::geometry::{FiniteGeometry, Geometry, Group};
+use crate::light::Light;
+use crate::types::{Camera, Color, Error, Float, Lerp, RResult, Transform, Vector};
+
+/// How to move between the keyframes of a [`Track`]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Straight lines between keyframes
+    #[default]
+    Linear,
+    /// Smooth (Catmull-Rom style) Bezier curves through the keyframes
+    Bezier,
+}
+
+impl FromStr for Interpolation {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "linear" => Ok(Self::Linear),
+            "bezier" | "smooth" => Ok(Self::Bezier),
+            _ => Err(Error::ParseError(format!("unknown interpolation {s:?}"))),
+        }
+    }
+}
+
+impl Display for Interpolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Linear => write!(f, "linear"),
+            Self::Bezier => write!(f, "bezier"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Keyframe<F: Float, T> {
+    pub frame: F,
+    pub value: T,
+}
+
+/// Animated value, given by keyframes at (not necessarily whole) frame numbers.
+///
+/// Before the first and after the last keyframe, the value stays constant.
+#[derive(Clone, Debug)]
+pub struct Track<F: Float, T: Lerp<Ratio = F>> {
+    keys: Vec<Keyframe<F, T>>,
+    pub interp: Interpolation,
+}
+
+impl<F: Float, T: Lerp<Ratio = F>> Track<F, T> {
+    #[must_use]
+    pub fn new(mut keys: Vec<Keyframe<F, T>>, interp: Interpolation) -> Self {
+        keys.sort_by(|a, b| a.frame.partial_cmp(&b.frame).unwrap_or(Ordering::Equal));
+        Self { keys, interp }
+    }
+
+    #[must_use]
+    pub fn keys(&self) -> &[Keyframe<F, T>] {
+        &self.keys
+    }
+
+    /// Value at `frame`, or `None` for a track without keyframes
+    pub fn sample(&self, frame: F) -> Option<T> {
+        let first = self.keys.first()?;
+        let idx = self.keys.partition_point(|key| key.frame <= frame);
+
+        if idx == 0 {
+            return Some(first.value);
+        }
+        if idx == self.keys.len() {
+            return self.keys.last().map(|key| key.value);
+        }
+
+        let (k1, k2) = (&self.keys[idx - 1], &self.keys[idx]);
+        let t = (frame - k1.frame) / (k2.frame - k1.frame);
+
+        match self.interp {
+            Interpolation::Linear => Some(k1.value.lerp(k2.value, t)),
+            Interpolation::Bezier => {
+                /* Control points from the neighbouring keyframes, so the
+                 * curve passes smoothly through every keyframe */
+                let p0 = self.keys[idx.saturating_sub(2)].value;
+                let p3 = self.keys[(idx + 1).min(self.keys.len() - 1)].value;
+                let (p1, p2) = (k1.value, k2.value);
+                let sixth = F::ONE / F::from_u32(6);
+                let c1 = p1 + (p2 - p0) * sixth;
+                let c2 = p2 - (p3 - p1) * sixth;
+
+                /* de Casteljau */
+                let (a, b, c) = (p1.lerp(c1, t), c1.lerp(c2, t), c2.lerp(p2, t));
+                let (d, e) = (a.lerp(b, t), b.lerp(c, t));
+                Some(d.lerp(e, t))
+            }
+        }
+    }
+}
+
+/// Keyframe tracks for a camera. Without a `look_at` track, the camera keeps
+/// looking in the same direction.
+#[derive(Clone, Debug, Default)]
+pub struct CameraTracks<F: Float> {
+    pub position: Option<Track<F, Vector<F>>>,
+    pub look_at: Option<Track<F, Vector<F>>>,
+}
+
+/// Keyframe tracks for a light
+#[derive(Clone, Debug, Default)]
+pub struct LightTracks<F: Float> {
+    pub position: Option<Track<F, Vector<F>>>,
+    pub color: Option<Track<F, Color<F>>>,
+}
+
+/// Keyframe tracks for an object, applied on top of its transform at load time.
+///
+/// The object is found by its path of child indices from the root group, so
+/// it can be nested in other groups. The animated transform is `translate *
+/// rotate * scale`, in the coordinate system of `parent`. Rotations are euler
+/// angles in degrees, applied in X, Y, Z order.
+#[derive(Clone, Debug)]
+pub struct ObjectTracks<F: Float> {
+    /// Object, by child index at each level below the root group
+    pub path: Vec<usize>,
+    pub parent: Matrix4<F>,
+    pub base: Transform<F>,
+    pub motion: Option<Transform<F>>,
+    pub translate: Option<Track<F, Vector<F>>>,
+    pub rotate: Option<Track<F, Vector<F>>>,
+    pub scale: Option<Track<F, Vector<F>>>,
+}
+
+impl<F: Float> ObjectTracks<F> {
+    /// Animated transform at `frame`, relative to `parent`
+    pub fn matrix(&self, frame: F) -> Matrix4<F> {
+        let mut res = Matrix4::identity();
+
+        if let Some(pos) = self.translate.as_ref().and_then(|t| t.sample(frame)) {
+            res = res * Matrix4::from_translation(pos);
+        }
+        if let Some(rot) = self.rotate.as_ref().and_then(|t| t.sample(frame)) {
+            res = res
+                * Matrix4::from_angle_z(Deg(rot.z))
+                * Matrix4::from_angle_y(Deg(rot.y))
+                * Matrix4::from_angle_x(Deg(rot.x));
+        }
+        if let Some(scale) = self.scale.as_ref().and_then(|t| t.sample(frame)) {
+            res = res * Matrix4::from_nonuniform_scale(scale.x, scale.y, scale.z);
+        }
+
+        res
+    }
+}
+
+/// Path of child indices from `obj` down to the object with `id` (by
+/// [`crate::scene::SceneObject::get_id`]), if it is below `obj`
+fn find_path<F: Float>(obj: &mut dyn Geometry<F>, id: usize) -> Option<Vec<usize>> {
+    let mut idx = 0;
+    while let Some(child) = obj.get_child(idx) {
+        if child.get_id() == Some(id) {
+            return Some(vec![idx]);
+        }
+        if let Some(mut path) = find_path(child, id) {
+            path.insert(0, idx);
+            return Some(path);
+        }
+        idx += 1;
+    }
+    None
+}
+
+/// The object at `path` below `obj`
+fn object_at<'a, F: Float>(
+    obj: &'a mut dyn Geometry<F>,
+    path: &[usize],
+) -> Option<&'a mut dyn Geometry<F>> {
+    match path {
+        [] => Some(obj),
+        [idx, rest @ ..] => object_at(obj.get_child(*idx)?, rest),
+    }
+}
+
+/// All animated properties of a scene, see [`crate::scene::Scene::set_frame`]
+#[derive(Clone, Debug, Default)]
+pub struct Animation<F: Float> {
+    /// Tracks for cameras, by index
+    pub cameras: Vec<(usize, CameraTracks<F>)>,
+    /// Tracks for lights, by index
+    pub lights: Vec<(usize, LightTracks<F>)>,
+    pub objects: Vec<ObjectTracks<F>>,
+    /// Frame everything was last moved to
+    pub frame: F,
+}
+
+impl<F: Float> Animation<F> {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.cameras.is_empty() && self.lights.is_empty() && self.objects.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.cameras.clear();
+        self.lights.clear();
+        self.objects.clear();
+        self.frame = F::ZERO;
+    }
+
+    /// Animate the object with `id` (by [`crate::scene::SceneObject::get_id`])
+    /// somewhere below `root` by `tracks`, keyed on its current position in
+    /// the hierarchy.
+    ///
+    /// Objects inside shared geometry (see [`crate::geometry::Instance`])
+    /// cannot be moved on their own, and are not found.
+    pub fn add_object<B: FiniteGeometry<F>>(
+        &mut self,
+        root: &mut Group<F, B>,
+        id: usize,
+        mut tracks: ObjectTracks<F>,
+    ) -> RResult<()> {
+        tracks.path = find_path(root, id).ok_or_else(|| {
+            Error::UnknownObject("inside shared geometry (animate the instance instead)".into())
+        })?;
+        self.objects.push(tracks);
+        Ok(())
+    }
+
+    /// Forget the tracks for top-level object number `idx`, which is about to
+    /// be removed, and renumber the ones after it
+    pub fn remove_object(&mut self, idx: usize) {
+        self.objects
+            .retain(|tracks| tracks.path.first() != Some(&idx));
+        for tracks in &mut self.objects {
+            if tracks.path[0] > idx {
+                tracks.path[0] -= 1;
+            }
+        }
+    }
+
+    /// Move everything to its state at `frame`.
+    ///
+    /// Objects are moved by their transform, so the BVHs inside them (for
+    /// meshes and groups) stay valid. Only the groups that contain objects
+    /// that moved since the last frame are rebuilt, from the innermost out to
+    /// `root` (see [`Geometry::update_bounds`]).
+    pub fn apply<L: Light<F>, B: FiniteGeometry<F>>(
+        &mut self,
+        frame: F,
+        cameras: &mut [Camera<F>],
+        lights: &mut [L],
+        root: &mut Group<F, B>,
+    ) -> RResult<()> {
+        self.frame = frame;
+
+        for (idx, tracks) in &self.cameras {
+            let Some(camera) = cameras.get_mut(*idx) else {
+                continue;
+            };
+            let sample = |track: &Option<Track<F, Vector<F>>>| track.as_ref()?.sample(frame);
+
+            let pos = sample(&tracks.position).unwrap_or_else(|| camera.position());
+            let look_at = sample(&tracks.look_at).unwrap_or_else(|| pos + camera.direction());
+            camera.set_view(pos, look_at);
+        }
+
+        for (idx, tracks) in &self.lights {
+            let Some(light) = lights.get_mut(*idx) else {
+                continue;
+            };
+            if let Some(pos) = tracks.position.as_ref().and_then(|t| t.sample(frame)) {
+                light.set_position(pos);
+            }
+            if let (Some(color), Some(track)) = (light.color(), &tracks.color) {
+                *color = track.sample(frame).unwrap_or(*color);
+            }
+        }
+
+        let mut moved = vec![];
+        for tracks in &self.objects {
+            let obj = object_at(root, &tracks.path)
+                .and_then(|obj| obj.transform())
+                .ok_or_else(|| Error::UnknownObject(format!("at {:?}", tracks.path)))?;
+
+            /* Express the animation in world space, like the parent transform */
+            let Some(inv) = tracks.parent.invert() else {
+                continue;
+            };
+            let delta = tracks.parent * tracks.matrix(frame) * inv;
+
+            let xfrm = delta * tracks.base.matrix();
+            let motion = tracks.motion.as_ref().map(|motion| delta * motion.matrix());
+            if *obj.get_transform().matrix() == xfrm
+                && obj.get_motion().map(|motion| *motion.matrix()) == motion
+            {
+                continue;
+            }
+
+            obj.set_transform(&Transform::new(xfrm));
+            if let Some(motion) = motion {
+                obj.set_motion(Some(&Transform::new(motion)));
+            }
+            moved.push(tracks.path.as_slice());
+        }
+
+        /* Every group on the way to a moved object, innermost first */
+        let mut groups: Vec<&[usize]> = moved
+            .iter()
+            .flat_map(|path| (0..path.len()).map(|len| &path[..len]))
+            .collect();
+        groups.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+        groups.dedup();
+
+        for path in groups {
+            if let Some(group) = object_at(root, path) {
+                group.update_bounds()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Inclusive range of frames to render, parsed from `N..M` (or just `N`)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FrameRange {
+    pub first: u32,
+    pub last: u32,
+}
+
+impl FrameRange {
+    pub fn iter(&self) -> impl Iterator<Item = u32> {
+        self.first..=self.last
+    }
+}
+
+impl FromStr for FrameRange {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (first, last) = s.split_once("..").unwrap_or((s, s));
+        let first = first.trim().parse()?;
+        let last = last.trim_start_matches('=').trim().parse()?;
+        if last < first {
+            return Err(Error::ParseError(format!("empty frame range {s:?}")));
+        }
+        Ok(Self { first, last })
+    }
+}
+
+impl Display for FrameRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..{}", self.first, self.last)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{InnerSpace, Matrix4, SquareMatrix};
+
+    use super::{Animation, FrameRange, Interpolation, Keyframe, ObjectTracks, Track};
+    use crate::geometry::{FiniteGeometry, Group, Sphere};
+    use crate::light::PointLight;
+    use crate::scene::SceneObject;
+    use crate::types::{MaterialId, Ray, Transform, Vector, Vectorx};
+
+    fn track(interp: Interpolation) -> Track<f64, f64> {
+        let keys =
+            [(10.0, 1.0), (0.0, 0.0), (20.0, 4.0)].map(|(frame, value)| Keyframe { frame, value });
+        Track::new(keys.to_vec(), interp)
+    }
+
+    #[test]
+    fn test_track_linear() {
+        let track = track(Interpolation::Linear);
+        for (frame, value) in [
+            (-5.0, 0.0),
+            (0.0, 0.0),
+            (5.0, 0.5),
+            (15.0, 2.5),
+            (30.0, 4.0),
+        ] {
+            assert!((track.sample(frame).unwrap() - value).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_track_bezier() {
+        let track = track(Interpolation::Bezier);
+
+        /* Passes through the keyframes, and stays between them */
+        for (frame, value) in [(0.0, 0.0), (10.0, 1.0), (20.0, 4.0)] {
+            assert!((track.sample(frame).unwrap() - value).abs() < 1e-9);
+        }
+        let mid = track.sample(15.0).unwrap();
+        assert!((1.0..4.0).contains(&mid));
+
+        assert!(Track::<f64, f64>::new(vec![], Interpolation::Bezier)
+            .sample(1.0)
+            .is_none());
+    }
+
+    #[test]
+    fn test_frame_range() {
+        let range: FrameRange = "3..5".parse().unwrap();
+        assert_eq!(range.iter().collect::<Vec<_>>(), [3, 4, 5]);
+        assert_eq!("7".parse::<FrameRange>().unwrap().iter().count(), 1);
+        assert!("5..3".parse::<FrameRange>().is_err());
+    }
+
+    #[test]
+    fn test_animate_nested() {
+        type Geo = Box<dyn FiniteGeometry<f64>>;
+        let sphere = || -> Geo { Box::new(Sphere::new(Matrix4::identity(), MaterialId::NULL)) };
+
+        let inner: Geo = Box::new(Group::new(vec![sphere(), sphere()], Matrix4::identity()));
+        let mut root = Group::new(vec![sphere(), inner], Matrix4::identity());
+        let nested = {
+            let mut geo = root.iter_mut();
+            let inner = geo.nth(1).unwrap();
+            inner.get_child(1).unwrap().get_id().unwrap()
+        };
+
+        let keys = [(0.0, Vector::ZERO), (10.0, Vector::new(0.0, 10.0, 0.0))]
+            .map(|(frame, value)| Keyframe { frame, value });
+        let tracks = ObjectTracks {
+            path: vec![],
+            parent: Matrix4::identity(),
+            base: Transform::identity(),
+            motion: None,
+            translate: Some(Track::new(keys.to_vec(), Interpolation::Linear)),
+            rotate: None,
+            scale: None,
+        };
+
+        let mut anim = Animation::default();
+        anim.add_object(&mut root, nested, tracks.clone()).unwrap();
+        assert_eq!(anim.objects[0].path, [1, 1]);
+        assert!(anim.add_object(&mut root, 1, tracks).is_err());
+
+        anim.apply(5.0, &mut [], &mut [] as &mut [PointLight<f64>], &mut root)
+            .unwrap();
+        let mut geo = root.iter_mut();
+        let inner = geo.nth(1).unwrap();
+        let moved = inner.get_child(1).unwrap().transform().unwrap();
+        let pos = moved.get_transform().pos(Vector::ZERO);
+        assert!((pos - Vector::new(0.0, 5.0, 0.0)).magnitude() < 1e-9);
+
+        /* The groups around it know where it went */
+        let ray = Ray::new(
+            Vector::new(0.0, 5.0, -10.0),
+            Vector::new(0.001, 0.002, 1.0).normalize(),
+        );
+        let mut dist = f64::MAX;
+        let hit = root.nearest_intersection(&ray, &mut dist).unwrap();
+        assert!((hit.pos.z + 1.0).abs() < 0.01, "{:?}", hit.pos);
+
+        /* Removing an object before the animated one keeps the track */
+        anim.remove_object(0);
+        assert_eq!(anim.objects[0].path, [0, 1]);
+        anim.remove_object(0);
+        assert!(anim.objects.is_empty());
+    }
+}
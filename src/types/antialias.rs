@@ -0,0 +1,348 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use rand::Rng;
+
+use crate::types::{Color, Error, Float, GridSamples, Point};
+
+/// Distribution of sub-pixel samples used for anti-aliasing.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SamplePattern {
+    /// Regular grid, with one sample in the center of each cell
+    #[default]
+    Stratified,
+    /// Regular grid, with one sample at a random position in each cell
+    Jittered,
+    /// Low-discrepancy Halton sequence (bases 2 and 3)
+    Halton,
+}
+
+impl SamplePattern {
+    pub const ALL: [Self; 3] = [Self::Stratified, Self::Jittered, Self::Halton];
+
+    /// `xres` x `yres` sample points in the unit square, in the range `[0, 1)`.
+    ///
+    /// Halton points are ordered progressively, so any prefix of them covers
+    /// the whole square. Jittered points are drawn again on every call.
+    #[must_use]
+    pub fn points<F: Float>(self, xres: u32, yres: u32) -> Vec<Point<F>> {
+        let (xres, yres) = (xres.max(1), yres.max(1));
+
+        match self {
+            Self::Stratified => GridSamples::new(F::ONE, F::ONE, xres, yres)
+                .iter()
+                .map(Point::from)
+                .collect(),
+
+            Self::Jittered => {
+                let mut rng = rand::thread_rng();
+                let cell = Point::new(F::ONE / F::from_u32(xres), F::ONE / F::from_u32(yres));
+                (0..xres * yres)
+                    .map(|i| {
+                        let x = F::from_u32(i % xres) + F::from_f32(rng.gen());
+                        let y = F::from_u32(i / xres) + F::from_f32(rng.gen());
+                        Point::new(x, y).dot(cell)
+                    })
+                    .collect()
+            }
+
+            /* Skip index 0, which would place a sample exactly on the corner */
+            Self::Halton => (1..=xres * yres)
+                .map(|i| Point::new(radical_inverse(i, 2), radical_inverse(i, 3)))
+                .collect(),
+        }
+    }
+}
+
+impl FromStr for SamplePattern {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stratified" | "grid" => Ok(Self::Stratified),
+            "jittered" | "jitter" => Ok(Self::Jittered),
+            "halton" => Ok(Self::Halton),
+            _ => Err(Error::ParseError(format!("unknown sample pattern {s:?}"))),
+        }
+    }
+}
+
+impl Display for SamplePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stratified => write!(f, "stratified"),
+            Self::Jittered => write!(f, "jittered"),
+            Self::Halton => write!(f, "halton"),
+        }
+    }
+}
+
+/// Radical inverse of `index` in `base`, i.e. the `index`th element of the
+/// van der Corput sequence.
+fn radical_inverse<F: Float>(mut index: u32, base: u32) -> F {
+    let inv = F::ONE / F::from_u32(base);
+    let mut scale = inv;
+    let mut res = F::ZERO;
+
+    while index > 0 {
+        res += F::from_u32(index % base) * scale;
+        index /= base;
+        scale *= inv;
+    }
+
+    res
+}
+
+/// Largest per-channel difference between two colors
+fn contrast<F: Float>(a: Color<F>, b: Color<F>) -> F {
+    (a.r - b.r)
+        .abs()
+        .max((a.g - b.g).abs())
+        .max((a.b - b.b).abs())
+}
+
+/// Anti-aliasing settings: a sample pattern, the number of samples, and an
+/// optional contrast threshold for adaptive supersampling.
+///
+/// With a threshold, the image is rendered in two passes: first with a single
+/// sample per pixel (see [`AntiAlias::first_pass`]), after which only pixels
+/// that differ from one of their neighbours by more than the threshold are
+/// supersampled (see [`AntiAlias::refine_line`]).
+#[derive(Copy, Clone, Debug)]
+pub struct AntiAlias<F: Float> {
+    pattern: SamplePattern,
+    samples: u32,
+    threshold: Option<F>,
+}
+
+impl<F: Float> Default for AntiAlias<F> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<F: Float> AntiAlias<F> {
+    /// A single sample in the center of each pixel (no anti-aliasing)
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            pattern: SamplePattern::Stratified,
+            samples: 1,
+            threshold: None,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_pattern(self, pattern: SamplePattern) -> Self {
+        Self { pattern, ..self }
+    }
+
+    /// Use `samples` x `samples` samples per (supersampled) pixel.
+    #[must_use]
+    pub const fn with_samples(self, samples: u32) -> Self {
+        Self { samples, ..self }
+    }
+
+    /// Only supersample pixels with a contrast above `threshold`.
+    #[must_use]
+    pub const fn with_threshold(self, threshold: Option<F>) -> Self {
+        Self { threshold, ..self }
+    }
+
+    #[must_use]
+    pub const fn pattern(&self) -> SamplePattern {
+        self.pattern
+    }
+
+    #[must_use]
+    pub const fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    #[must_use]
+    pub const fn threshold(&self) -> Option<F> {
+        self.threshold
+    }
+
+    /// Area covered by each sample in a `width` x `height` image (with
+    /// pixels `mult` units wide and tall), in normalized image coordinates.
+    /// Used for ray differentials, see [`crate::types::Camera::get_pixel_ray`]
+    #[must_use]
+    pub fn footprint(&self, width: u32, height: u32, mult: (u32, u32)) -> Point<F> {
+        let pixel = Point::from(mult) / Point::from((width, height));
+        pixel / F::from_u32(self.samples.max(1))
+    }
+
+    /// Settings for the first pass of adaptive anti-aliasing: a single
+    /// sample in the center of each pixel
+    #[must_use]
+    pub const fn first_pass(&self) -> Self {
+        Self::new()
+    }
+
+    /// Sample offsets within a pixel, in the range `[0, 1)`.
+    #[must_use]
+    pub fn offsets(&self) -> Vec<Point<F>> {
+        let n = self.samples.max(1);
+        self.pattern.points(n, n)
+    }
+
+    /// Average of `sample` over all sample offsets in the pixel at (`x`, `y`)
+    /// (jittered offsets are drawn again for every pixel)
+    fn supersample(
+        &self,
+        size: Point<F>,
+        cell: Point<F>,
+        x: u32,
+        y: u32,
+        sample: &impl Fn(Point<F>) -> Color<F>,
+    ) -> Color<F> {
+        let base = Point::from((x, y));
+        let offsets = self.offsets();
+        let sum: Color<F> = offsets
+            .iter()
+            .map(|ofs| sample((base + ofs.dot(cell)) / size))
+            .sum();
+        sum / F::from_usize(offsets.len())
+    }
+
+    /// Render a line of pixels, using `sample` to trace a single ray.
+    ///
+    /// Pixels are `mult` units wide and tall (for low-resolution previews),
+    /// and `sample` is called with image coordinates normalized to `[0, 1]`.
+    ///
+    /// This supersamples every pixel, since the contrast threshold needs the
+    /// neighbouring lines: adaptive renderers should use [`Self::first_pass`]
+    /// followed by [`Self::refine_line`] instead.
+    pub fn render_line(
+        &self,
+        width: u32,
+        height: u32,
+        mult: (u32, u32),
+        y: u32,
+        sample: impl Fn(Point<F>) -> Color<F>,
+    ) -> Vec<Color<F>> {
+        let size = Point::from((width, height));
+        let cell = Point::from(mult);
+        let xs = (0..width).step_by(mult.0 as usize);
+
+        if self.samples <= 1 && self.pattern == SamplePattern::Stratified {
+            /* Single sample in the center of each pixel */
+            return xs
+                .map(|x| sample((Point::from((x, y)) + cell / F::TWO) / size))
+                .collect();
+        }
+
+        xs.map(|x| self.supersample(size, cell, x, y, &sample))
+            .collect()
+    }
+
+    /// Second pass of adaptive anti-aliasing for line `y`, supersampling the
+    /// pixels that differ from their neighbours by more than the threshold.
+    ///
+    /// `rows` holds the first pass (see [`Self::first_pass`]) of the lines
+    /// above, at and below `y`, where the outer lines are `None` at the
+    /// image border. Other arguments are as for [`Self::render_line`].
+    pub fn refine_line(
+        &self,
+        width: u32,
+        height: u32,
+        mult: (u32, u32),
+        y: u32,
+        rows: [Option<&[Color<F>]>; 3],
+        sample: impl Fn(Point<F>) -> Color<F>,
+    ) -> Vec<Color<F>> {
+        let size = Point::from((width, height));
+        let cell = Point::from(mult);
+        let [above, here, below] = rows;
+        let here = here.unwrap_or_default();
+
+        here.iter()
+            .enumerate()
+            .zip((0..width).step_by(mult.0 as usize))
+            .map(|((idx, pix), x)| {
+                let neighbours = [
+                    idx.checked_sub(1).map(|i| here[i]),
+                    here.get(idx + 1).copied(),
+                    above.and_then(|r| r.get(idx).copied()),
+                    below.and_then(|r| r.get(idx).copied()),
+                ];
+
+                let edge = self.threshold.map_or(true, |threshold| {
+                    neighbours
+                        .into_iter()
+                        .flatten()
+                        .any(|n| contrast(*pix, n) > threshold)
+                });
+
+                if edge {
+                    self.supersample(size, cell, x, y, &sample)
+                } else {
+                    *pix
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{radical_inverse, AntiAlias, SamplePattern};
+    use crate::types::Color;
+
+    #[test]
+    fn test_radical_inverse() {
+        let seq: Vec<f64> = (1..5).map(|i| radical_inverse(i, 2)).collect();
+        assert_eq!(seq, [0.5, 0.25, 0.75, 0.125]);
+    }
+
+    #[test]
+    fn test_offsets_in_unit_square() {
+        for pattern in SamplePattern::ALL {
+            let aa = AntiAlias::<f64>::new()
+                .with_pattern(pattern)
+                .with_samples(3);
+            let offsets = aa.offsets();
+            assert_eq!(offsets.len(), 9);
+            assert!(offsets.iter().all(|p| (0.0..1.0).contains(&p.x)));
+            assert!(offsets.iter().all(|p| (0.0..1.0).contains(&p.y)));
+        }
+    }
+
+    #[test]
+    fn test_points_rectangular() {
+        for pattern in SamplePattern::ALL {
+            let points = pattern.points::<f64>(4, 2);
+            assert_eq!(points.len(), 8);
+            assert!(points.iter().all(|p| (0.0..1.0).contains(&p.x)));
+            assert!(points.iter().all(|p| (0.0..1.0).contains(&p.y)));
+        }
+    }
+
+    #[test]
+    fn test_adaptive_refine() {
+        let aa = AntiAlias::<f64>::new()
+            .with_samples(4)
+            .with_threshold(Some(0.1));
+
+        /* A flat image never needs supersampling */
+        let flat = [Color::WHITE; 8];
+        let line = aa.refine_line(8, 8, (1, 1), 4, [Some(&flat[..]); 3], |_| {
+            panic!("flat pixels should not be supersampled")
+        });
+        assert_eq!(line, flat);
+
+        /* Only the pixels on either side of an edge are supersampled */
+        let edge: Vec<_> = (0..8)
+            .map(|x| if x < 4 { Color::BLACK } else { Color::WHITE })
+            .collect();
+        let count = std::cell::Cell::new(0);
+        let line = aa.refine_line(8, 8, (1, 1), 0, [None, Some(&edge[..]), None], |_| {
+            count.set(count.get() + 1);
+            Color::gray(0.5)
+        });
+        assert_eq!(count.get(), 2 * 16);
+        assert_eq!(line[3], Color::gray(0.5));
+        assert_eq!(line[0], Color::BLACK);
+    }
+}
@@ -0,0 +1,175 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use cgmath::MetricSpace;
+
+use crate::scene::BoxScene;
+use crate::types::{hash, Color, Error, Float, Maxel, Ray};
+
+/// Auxiliary output pass ("arbitrary output variable"), rendered alongside
+/// the main image for compositing and denoising.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Aov {
+    /// Distance from the camera to the first hit
+    Depth,
+    /// World space normal at the first hit
+    Normal,
+    /// Surface color at the first hit, without lighting
+    Albedo,
+    /// Material id at the first hit
+    MatId,
+    /// Index of the (top-level) object at the first hit
+    ObjId,
+}
+
+impl Aov {
+    pub const ALL: [Self; 5] = [
+        Self::Depth,
+        Self::Normal,
+        Self::Albedo,
+        Self::MatId,
+        Self::ObjId,
+    ];
+
+    /// Channel names, when stored as a layer in a multi-channel image. Single
+    /// channel passes are stored in the first (red) channel.
+    #[must_use]
+    pub const fn channels(self) -> &'static [&'static str] {
+        match self {
+            Self::Depth => &["Z"],
+            Self::Normal => &["X", "Y", "Z"],
+            Self::Albedo => &["R", "G", "B"],
+            Self::MatId | Self::ObjId => &["id"],
+        }
+    }
+
+    /// Value of this pass for a camera ray, given its first hit (as returned
+    /// by [`crate::scene::Scene::intersect_object`]). Missed rays give a depth
+    /// of infinity, and 0 for all other passes.
+    ///
+    /// Ids are offset by one, so that 0 always means "no hit".
+    pub fn value<F: Float>(
+        self,
+        scene: &BoxScene<F>,
+        ray: &Ray<F>,
+        hit: &mut Option<(usize, Maxel<F>)>,
+    ) -> Color<F> {
+        let Some((idx, maxel)) = hit else {
+            return match self {
+                Self::Depth => Color::gray(F::infinity()),
+                _ => Color::BLACK,
+            };
+        };
+
+        match self {
+            Self::Depth => Color::gray(ray.pos.distance(maxel.pos)),
+            Self::Normal => {
+                let n = maxel.nml();
+                Color::new(n.x, n.y, n.z)
+            }
+            Self::Albedo => scene.materials.mats[&maxel.mat].albedo(maxel),
+            Self::MatId => Color::gray(F::from_u32(maxel.mat.0 + 1)),
+            Self::ObjId => Color::gray(F::from_usize(*idx + 1)),
+        }
+    }
+
+    /// Map the values of this pass to displayable colors in `[0, 1]`.
+    ///
+    /// Depth is normalized to the largest finite depth in `values`, normals
+    /// are mapped from `[-1, 1]`, and ids are given random (but stable) colors.
+    #[must_use]
+    pub fn visualize<F: Float>(self, values: &[Color<F>]) -> Vec<Color<F>> {
+        match self {
+            Self::Depth => {
+                let max = values
+                    .iter()
+                    .map(|c| c.r)
+                    .filter(|d| d.is_finite())
+                    .fold(F::ZERO, F::max);
+
+                values
+                    .iter()
+                    .map(|c| {
+                        if c.r.is_finite() && max > F::ZERO {
+                            Color::gray(c.r / max)
+                        } else {
+                            Color::WHITE
+                        }
+                    })
+                    .collect()
+            }
+            Self::Normal => values
+                .iter()
+                .map(|n| (*n + Color::WHITE) / F::TWO)
+                .collect(),
+            Self::Albedo => values.iter().map(|c| c.clamped()).collect(),
+            Self::MatId | Self::ObjId => values.iter().map(|c| id_color(c.r)).collect(),
+        }
+    }
+}
+
+/// Pseudo-random color for an id, with 0 (no hit) mapped to black
+fn id_color<F: Float>(id: F) -> Color<F> {
+    let id = id.to_u64().unwrap_or_default();
+    if id == 0 {
+        return Color::BLACK;
+    }
+
+    let [r, g, b, ..] = hash(&id).to_le_bytes();
+    Color::from([r, g, b].map(|c| f32::from(c) / 255.0))
+}
+
+impl FromStr for Aov {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "depth" | "z" => Ok(Self::Depth),
+            "normal" => Ok(Self::Normal),
+            "albedo" => Ok(Self::Albedo),
+            "matid" | "material" => Ok(Self::MatId),
+            "objid" | "object" => Ok(Self::ObjId),
+            _ => Err(Error::ParseError(format!("unknown output pass {s:?}"))),
+        }
+    }
+}
+
+impl Display for Aov {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Depth => write!(f, "depth"),
+            Self::Normal => write!(f, "normal"),
+            Self::Albedo => write!(f, "albedo"),
+            Self::MatId => write!(f, "matid"),
+            Self::ObjId => write!(f, "objid"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_float_eq::{afe_is_f64_near, afe_near_error_msg, assert_f64_near};
+
+    use super::Aov;
+    use crate::types::Color;
+
+    #[test]
+    fn test_aov_names() {
+        for aov in Aov::ALL {
+            assert_eq!(aov.to_string().parse::<Aov>().unwrap(), aov);
+        }
+    }
+
+    #[test]
+    fn test_visualize_depth() {
+        let values = [
+            Color::gray(1.0),
+            Color::gray(4.0),
+            Color::gray(f64::INFINITY),
+        ];
+        let res = Aov::Depth.visualize(&values);
+        assert_f64_near!(res[0].r, 0.25);
+        assert_f64_near!(res[1].r, 1.0);
+        assert_f64_near!(res[2].r, 1.0);
+    }
+}
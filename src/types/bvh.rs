@@ -14,6 +14,18 @@ pub trait BvhExt {
     where
         F: Float,
         T: Primitive + Geometry<F> + 'a;
+
+    /// Like [`BvhExt::nearest_intersection`], but also returns the index of
+    /// the intersected primitive in `prims`.
+    fn nearest_primitive<'a, F, T>(
+        &'a self,
+        ray: &Ray<F>,
+        prims: &'a [T],
+        dist: &mut F,
+    ) -> Option<(usize, Maxel<'a, F>)>
+    where
+        F: Float,
+        T: Primitive + Geometry<F> + 'a;
 }
 
 impl BvhExt for rtbvh::Bvh {
@@ -41,4 +53,30 @@ impl BvhExt for rtbvh::Bvh {
         }
         hit
     }
+
+    fn nearest_primitive<'a, F, T>(
+        &'a self,
+        ray: &Ray<F>,
+        prims: &'a [T],
+        dist: &mut F,
+    ) -> Option<(usize, Maxel<'a, F>)>
+    where
+        F: Float,
+        T: Primitive + Geometry<F> + 'a,
+    {
+        let mut r: rtbvh::Ray = ray.into();
+
+        let mut hit: Option<(usize, Maxel<F>)> = None;
+        for (idx, _) in self.traverse_iter_indices(&mut r) {
+            let idx = idx as usize;
+            if let Some(curhit) = prims[idx].intersect(ray) {
+                let curdist = ray.pos.distance2(curhit.pos);
+                if curdist > F::BIAS2 && curdist < *dist {
+                    *dist = curdist;
+                    hit = Some((idx, curhit));
+                }
+            }
+        }
+        hit
+    }
 }
@@ -1,16 +1,155 @@
+use std::convert::Infallible;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
 use cgmath::{Angle, Deg, EuclideanSpace, InnerSpace, Matrix4, Point3};
+use rand::Rng;
 
 use crate::scene::{Interactive, SceneObject};
-use crate::types::{Float, Point, Ray, Transform, Vector};
-use crate::{sceneobject_impl_body, vec3};
+use crate::types::{Error, Float, Point, Ray, RayDiff, Transform, Vector, Vectorx};
+use crate::{point, sceneobject_impl_body, vec3};
+
+/// Thin lens model, for depth of field.
+///
+/// Rays start at a random point on the aperture, and all rays through a
+/// pixel converge on the plane at `focal_distance` in front of the camera.
+#[derive(Copy, Clone, Debug)]
+pub struct Lens<F: Float> {
+    /// Aperture radius, or 0 for a pinhole camera (everything in focus)
+    pub aperture: F,
+    /// Distance from the camera to the plane in focus
+    pub focal_distance: F,
+    /// Number of aperture blades, or 0 for a circular aperture
+    pub blades: u32,
+}
+
+impl<F: Float> Default for Lens<F> {
+    fn default() -> Self {
+        Self::new(F::ZERO, F::ONE)
+    }
+}
 
-#[derive(Clone, Copy, Debug)]
+impl<F: Float> Lens<F> {
+    #[must_use]
+    pub const fn new(aperture: F, focal_distance: F) -> Self {
+        Self {
+            aperture,
+            focal_distance,
+            blades: 0,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_blades(self, blades: u32) -> Self {
+        Self { blades, ..self }
+    }
+
+    #[must_use]
+    pub fn is_pinhole(&self) -> bool {
+        self.aperture <= F::ZERO
+    }
+
+    /// Uniformly sample a point on the aperture, relative to its center.
+    #[must_use]
+    pub fn sample(&self) -> Point<F> {
+        let mut rng = rand::thread_rng();
+        let mut rand = || F::from_f32(rng.gen());
+
+        if self.blades < 3 {
+            let r = rand().sqrt() * self.aperture;
+            let phi = F::TWO * F::PI() * rand();
+            return point!(r * phi.cos(), r * phi.sin());
+        }
+
+        /* Pick one of the triangles between the center and two neighbouring
+         * blade corners, then sample uniformly within that triangle */
+        let n = F::from_u32(self.blades);
+        let sector = (rand() * n).floor();
+        let corner = |i: F| {
+            let phi = F::TWO * F::PI() * i / n;
+            point!(phi.cos(), phi.sin()) * self.aperture
+        };
+
+        let (mut u, mut v) = (rand(), rand());
+        if u + v > F::ONE {
+            u = F::ONE - u;
+            v = F::ONE - v;
+        }
+
+        corner(sector) * u + corner(sector + F::ONE) * v
+    }
+}
+
+/// Mapping from image coordinates to camera rays.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Projection {
+    /// Pinhole camera, with straight lines staying straight
+    #[default]
+    Perspective,
+    /// Parallel rays, with no foreshortening. The view covers the area that
+    /// the perspective view would show at the lens focal distance.
+    Orthographic,
+    /// Equidistant fisheye, with `fov` degrees across the image height
+    Fisheye,
+    /// Equirectangular 360 degree panorama
+    Equirect,
+}
+
+impl Projection {
+    pub const ALL: [Self; 4] = [
+        Self::Perspective,
+        Self::Orthographic,
+        Self::Fisheye,
+        Self::Equirect,
+    ];
+
+    /// True for projections which can not be expressed as a matrix
+    #[must_use]
+    pub const fn is_spherical(self) -> bool {
+        matches!(self, Self::Fisheye | Self::Equirect)
+    }
+}
+
+impl FromStr for Projection {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "perspective" => Ok(Self::Perspective),
+            "orthographic" | "ortho" => Ok(Self::Orthographic),
+            "fisheye" => Ok(Self::Fisheye),
+            "equirect" | "equirectangular" | "panorama" => Ok(Self::Equirect),
+            _ => Err(Error::ParseError(format!("unknown projection {s:?}"))),
+        }
+    }
+}
+
+impl Display for Projection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Perspective => write!(f, "perspective"),
+            Self::Orthographic => write!(f, "orthographic"),
+            Self::Fisheye => write!(f, "fisheye"),
+            Self::Equirect => write!(f, "equirect"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Camera<F: Float> {
+    pub name: Option<String>,
     pub model: Transform<F>,
     pub projection: Transform<F>,
     pub ndc: Transform<F>,
+    pub lens: Lens<F>,
+    /// Time at which the shutter opens, for motion blur (see [`Ray::time`])
+    pub shutter_open: F,
+    /// Time at which the shutter closes
+    pub shutter_close: F,
+    kind: Projection,
+    fov: F,
+    aspect_ratio: F,
     pos: Vector<F>,
-    #[allow(dead_code)]
     dir: Vector<F>,
 }
 
@@ -33,12 +172,7 @@ impl<F: Float> Camera<F> {
         info!("vp_width: {:.4}", viewplane_width);
         info!("vp_height: {:.4}", viewplane_height);
 
-        let mat1 = cgmath::perspective(
-            Deg(fov),
-            aspect_ratio,
-            F::from_f32(1.0),
-            F::from_u32(10_000),
-        );
+        let mat1 = Self::perspective(fov, aspect_ratio);
 
         let mat2 = Matrix4::from_translation(vec3![F::HALF, F::HALF, F::ZERO]);
         let mat3 = Matrix4::from_nonuniform_scale(F::HALF, -F::HALF, F::ONE);
@@ -48,29 +182,332 @@ impl<F: Float> Camera<F> {
         let ndc = Transform::new(mat2 * mat3);
 
         Self {
+            name: None,
             model,
             projection,
             ndc,
+            lens: Lens::default(),
+            shutter_open: F::ZERO,
+            shutter_close: F::ZERO,
+            kind: Projection::Perspective,
+            fov,
+            aspect_ratio,
             pos,
             dir,
         }
     }
 
-    pub fn get_ray(self, point: Point<F>) -> Ray<F> {
+    fn perspective(fov: F, aspect_ratio: F) -> Matrix4<F> {
+        /* Fisheye cameras can see 180 degrees or more, which a perspective
+         * matrix can not, so clamp it (it's only an approximation for those) */
+        let fov = fov.min(F::from_u32(170));
+
+        cgmath::perspective(
+            Deg(fov),
+            aspect_ratio,
+            F::from_f32(1.0),
+            F::from_u32(10_000),
+        )
+    }
+
+    /// Rebuild the projection matrix, after changing the projection kind or lens.
+    ///
+    /// Spherical projections keep the perspective matrix, which is used as
+    /// an approximation by the gizmo overlay.
+    fn update_projection(&mut self) {
+        let mat = if self.kind == Projection::Orthographic {
+            let height = Deg(self.fov / F::TWO).tan() * self.lens.focal_distance;
+            let width = height * self.aspect_ratio;
+            cgmath::ortho(
+                -width,
+                width,
+                -height,
+                height,
+                F::from_f32(1.0),
+                F::from_u32(10_000),
+            )
+        } else {
+            Self::perspective(self.fov, self.aspect_ratio)
+        };
+
+        self.projection = Transform::new(mat);
+    }
+
+    #[must_use]
+    pub fn with_lens(mut self, lens: Lens<F>) -> Self {
+        self.lens = lens;
+        self.update_projection();
+        self
+    }
+
+    #[must_use]
+    pub fn with_projection(mut self, kind: Projection) -> Self {
+        self.kind = kind;
+        self.update_projection();
+        self
+    }
+
+    /// Keep the shutter open from time `open` to `close`, so that moving
+    /// objects are blurred along their motion
+    #[must_use]
+    pub const fn with_shutter(mut self, open: F, close: F) -> Self {
+        self.shutter_open = open;
+        self.shutter_close = close;
+        self
+    }
+
+    /// Random point in time, while the shutter is open
+    fn shutter_time(&self) -> F {
+        if self.shutter_close <= self.shutter_open {
+            return self.shutter_open;
+        }
+
+        let t = F::from_f32(rand::thread_rng().gen());
+        self.shutter_open + (self.shutter_close - self.shutter_open) * t
+    }
+
+    #[must_use]
+    pub const fn position(&self) -> Vector<F> {
+        self.pos
+    }
+
+    /// Normalized viewing direction
+    #[must_use]
+    pub const fn direction(&self) -> Vector<F> {
+        self.dir
+    }
+
+    /// Normalized up direction
+    #[must_use]
+    pub fn updir(&self) -> Vector<F> {
+        self.model.dir_inv(Vector::UNIT_Y).normalize()
+    }
+
+    /// Vertical field of view, in degrees
+    #[must_use]
+    pub const fn fov(&self) -> F {
+        self.fov
+    }
+
+    #[must_use]
+    pub const fn aspect_ratio(&self) -> F {
+        self.aspect_ratio
+    }
+
+    /// Move the camera to `pos`, looking at `look_at`, keeping the current
+    /// up direction
+    pub fn set_view(&mut self, pos: Vector<F>, look_at: Vector<F>) {
+        let viewdir = look_at - pos;
+        let updir = self.model.dir_inv(Vector::UNIT_Y);
+
+        self.model = Transform::new(Matrix4::look_to_rh(Point3::from_vec(pos), viewdir, updir));
+        self.pos = pos;
+        self.dir = viewdir.normalize();
+    }
+
+    #[must_use]
+    pub const fn get_projection(&self) -> Projection {
+        self.kind
+    }
+
+    #[must_use]
+    pub fn with_name(self, name: impl Into<String>) -> Self {
+        Self {
+            name: Some(name.into()),
+            ..self
+        }
+    }
+
+    /// Direction of a spherical projection ray in camera space, for image
+    /// coordinates `u`, `v` in `[-1, 1]` (with `v` pointing up).
+    fn spherical_dir(&self, u: F, v: F) -> Vector<F> {
+        if self.kind == Projection::Equirect {
+            let lon = u * F::PI();
+            let lat = v * F::FRAC_PI_2();
+            return vec3![lat.cos() * lon.sin(), lat.sin(), -lat.cos() * lon.cos()];
+        }
+
+        let u = u * self.aspect_ratio;
+        let theta = u.hypot(v) * (self.fov / F::TWO).to_radians();
+        let phi = v.atan2(u);
+        vec3![
+            theta.sin() * phi.cos(),
+            theta.sin() * phi.sin(),
+            -theta.cos()
+        ]
+    }
+
+    /// Inverse of [`Camera::spherical_dir`], for a normalized direction `dir`
+    fn spherical_uv(&self, dir: Vector<F>) -> (F, F) {
+        if self.kind == Projection::Equirect {
+            let lon = dir.x.atan2(-dir.z);
+            let lat = dir.y.clamp(-F::ONE, F::ONE).asin();
+            return (lon / F::PI(), lat / F::FRAC_PI_2());
+        }
+
+        let theta = (-dir.z).clamp(-F::ONE, F::ONE).acos();
+        let phi = dir.y.atan2(dir.x);
+        let r = theta / (self.fov / F::TWO).to_radians();
+        (r * phi.cos() / self.aspect_ratio, r * phi.sin())
+    }
+
+    /// Ray origin and direction for a pinhole camera with this projection
+    fn pinhole_ray(&self, point: Point<F>) -> (Vector<F>, Vector<F>) {
         let pos = self.model.pos_inv(vec3![F::ZERO, F::ZERO, F::ZERO]);
 
-        let vpp = self
-            .model
-            .dir_inv(
-                self.projection
-                    .pos_inv(self.ndc.pos_inv(vec3![point.x, point.y, F::ONE])),
-            );
+        match self.kind {
+            Projection::Perspective => {
+                let vpp = self
+                    .model
+                    .dir_inv(self.projection.pos_inv(self.ndc.pos_inv(vec3![
+                        point.x,
+                        point.y,
+                        F::ONE
+                    ])));
+                (pos, vpp.normalize())
+            }
+
+            Projection::Orthographic => {
+                /* Start rays on the camera plane, rather than the near plane */
+                let mut origin =
+                    self.projection
+                        .pos_inv(self.ndc.pos_inv(vec3![point.x, point.y, -F::ONE]));
+                origin.z = F::ZERO;
+
+                let dir = self.model.dir_inv(-Vector::UNIT_Z).normalize();
+                (self.model.pos_inv(origin), dir)
+            }
+
+            Projection::Fisheye | Projection::Equirect => {
+                let u = point.x * F::TWO - F::ONE;
+                let v = F::ONE - point.y * F::TWO;
+                let dir = self.model.dir_inv(self.spherical_dir(u, v));
+                (pos, dir.normalize())
+            }
+        }
+    }
+
+    pub fn get_ray(&self, point: Point<F>) -> Ray<F> {
+        let (pos, dir) = self.pinhole_ray(point);
+        let time = self.shutter_time();
+
+        if self.lens.is_pinhole() {
+            return Ray::new(pos, dir).with_time(time);
+        }
+
+        /* Find where the pinhole ray meets the focal plane (or sphere, for
+         * spherical projections), and aim a ray from a random point on the
+         * aperture at it */
+        let (focus, right, up) = if self.kind.is_spherical() {
+            let (right, up) = dir.surface_tangents();
+            (pos + dir * self.lens.focal_distance, right, up)
+        } else {
+            let fwd = self.model.dir_inv(-Vector::UNIT_Z).normalize();
+            let right = self.model.dir_inv(Vector::UNIT_X).normalize();
+            let up = self.model.dir_inv(Vector::UNIT_Y).normalize();
+            (
+                pos + dir * (self.lens.focal_distance / dir.dot(fwd)),
+                right,
+                up,
+            )
+        };
+
+        let ofs = self.lens.sample();
+        let origin = pos + right * ofs.x + up * ofs.y;
 
-        Ray::new(pos, vpp.normalize())
+        Ray::new(origin, (focus - origin).normalize()).with_time(time)
+    }
+
+    /// Ray through `point` (see [`Self::get_ray`]), with ray differentials
+    /// for a pixel of size `pixel` (in the same normalized units as `point`)
+    pub fn get_pixel_ray(&self, point: Point<F>, pixel: Point<F>) -> Ray<F> {
+        /* Lens rays use the differentials of the pinhole ray, which is
+         * close enough for texture filtering */
+        self.get_ray(point)
+            .with_diff(Some(self.pinhole_diff(point, pixel)))
+    }
+
+    /// Like [`Self::get_pixel_ray`], but always through the center of the
+    /// lens, in the middle of the shutter interval, so that data passes (like
+    /// depth and normals) are free of noise
+    pub fn get_center_ray(&self, point: Point<F>, pixel: Point<F>) -> Ray<F> {
+        let (pos, dir) = self.pinhole_ray(point);
+        let time = (self.shutter_open + self.shutter_close.max(self.shutter_open)) / F::TWO;
+
+        Ray::new(pos, dir)
+            .with_time(time)
+            .with_diff(Some(self.pinhole_diff(point, pixel)))
+    }
+
+    /// Ray differentials of the pinhole ray through `point`, for a pixel of
+    /// size `pixel`
+    fn pinhole_diff(&self, point: Point<F>, pixel: Point<F>) -> RayDiff<F> {
+        let (pos, dir) = self.pinhole_ray(point);
+        let (px, dx) = self.pinhole_ray(point + point!(pixel.x, F::ZERO));
+        let (py, dy) = self.pinhole_ray(point + point!(F::ZERO, pixel.y));
+
+        RayDiff {
+            dpdx: px - pos,
+            dpdy: py - pos,
+            dddx: dx - dir,
+            dddy: dy - dir,
+        }
     }
 
     pub fn world_to_ndc(&self, pos: Vector<F>) -> Vector<F> {
-        self.ndc.pos(self.projection.pos(self.model.pos(pos)))
+        if !self.kind.is_spherical() {
+            return self.ndc.pos(self.projection.pos(self.model.pos(pos)));
+        }
+
+        /* Spherical projections have no matrix form, so map the direction
+         * to the point directly, and use the distance as depth */
+        let pos = self.model.pos(pos);
+        let (u, v) = self.spherical_uv(pos.normalize());
+        vec3![
+            (u + F::ONE) / F::TWO,
+            (F::ONE - v) / F::TWO,
+            pos.magnitude()
+        ]
+    }
+}
+
+/// Selects a camera in a scene, either by position or by name.
+///
+/// Parsing a string never fails: anything that is a valid index selects by
+/// index, everything else selects by name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CameraSelector {
+    Index(usize),
+    Name(String),
+}
+
+impl CameraSelector {
+    #[must_use]
+    pub fn find<F: Float>(&self, cameras: &[Camera<F>]) -> Option<usize> {
+        match self {
+            Self::Index(idx) => Some(*idx).filter(|idx| *idx < cameras.len()),
+            Self::Name(name) => cameras
+                .iter()
+                .position(|cam| cam.name.as_deref() == Some(name.as_str())),
+        }
+    }
+}
+
+impl FromStr for CameraSelector {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(s.parse()
+            .map_or_else(|_| Self::Name(s.to_string()), Self::Index))
+    }
+}
+
+impl Display for CameraSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Index(idx) => write!(f, "{idx}"),
+            Self::Name(name) => write!(f, "{name:?}"),
+        }
     }
 }
 
@@ -85,6 +522,52 @@ impl<F: Float> Interactive<F> for Camera<F> {
         res |= controls::position(ui, &mut self.dir, "Direction");
         self.dir = self.dir.normalize();
 
+        let kind = self.kind;
+        ui.label("Projection");
+        egui::ComboBox::from_id_source("projection")
+            .selected_text(self.kind.to_string())
+            .show_ui(ui, |ui| {
+                for kind in Projection::ALL {
+                    ui.selectable_value(&mut self.kind, kind, kind.to_string());
+                }
+            });
+        ui.end_row();
+        res |= kind != self.kind;
+
+        ui.label("Aperture");
+        res |= ui
+            .add(egui::Slider::new(&mut self.lens.aperture, F::ZERO..=F::ONE).logarithmic(true))
+            .changed();
+        ui.end_row();
+
+        ui.label("Focal distance");
+        res |= ui
+            .add(egui::DragValue::new(&mut self.lens.focal_distance).speed(0.1))
+            .changed();
+        ui.end_row();
+
+        ui.label("Aperture blades");
+        res |= ui
+            .add(egui::Slider::new(&mut self.lens.blades, 0..=12))
+            .changed();
+        ui.end_row();
+
+        ui.label("Shutter open");
+        res |= ui
+            .add(egui::Slider::new(&mut self.shutter_open, F::ZERO..=F::ONE))
+            .changed();
+        ui.end_row();
+
+        ui.label("Shutter close");
+        res |= ui
+            .add(egui::Slider::new(&mut self.shutter_close, F::ZERO..=F::ONE))
+            .changed();
+        ui.end_row();
+
+        if res {
+            self.update_projection();
+        }
+
         res
     }
 }
@@ -95,7 +578,9 @@ impl<F: Float> SceneObject<F> for Camera<F> {
 
 #[cfg(test)]
 mod test {
-    use crate::types::{Camera, Point, Vector, Vectorx};
+    use cgmath::InnerSpace;
+
+    use crate::types::{Camera, CameraSelector, Lens, Point, Projection, Vector, Vectorx};
     use crate::{point, vec3};
 
     #[test]
@@ -123,4 +608,96 @@ mod test {
             /* info!("Point [{point:?}] | {:7.4?} | {:7.4?}", ray1.dir, ray2.dir); */
         }
     }
+
+    #[test]
+    fn test_camera_selector() {
+        let camera = Camera::build(Vector::ZERO, -Vector::UNIT_Z, Vector::UNIT_Y, 50.0, 1.0);
+        let cameras = [camera.clone(), camera.with_name("front")];
+
+        let by_index: CameraSelector = "1".parse().unwrap();
+        let by_name: CameraSelector = "front".parse().unwrap();
+        let missing: CameraSelector = "2".parse().unwrap();
+
+        assert_eq!(by_index, CameraSelector::Index(1));
+        assert_eq!(by_name, CameraSelector::Name("front".into()));
+        assert_eq!(by_index.find(&cameras), Some(1));
+        assert_eq!(by_name.find(&cameras), Some(1));
+        assert_eq!(missing.find(&cameras), None);
+    }
+
+    #[test]
+    fn test_thin_lens_focus() {
+        let camera = Camera::build(Vector::ZERO, -Vector::UNIT_Z, Vector::UNIT_Y, 50.0, 1.0);
+        let lens = Lens::<f64>::new(0.5, 10.0).with_blades(6);
+
+        /* All rays through a pixel must meet the pinhole ray on the focal plane */
+        let point = point!(0.3, 0.6);
+        let pinhole = camera.get_ray(point);
+        let expected = pinhole.extend(-10.0 / pinhole.dir.z);
+
+        let camera = camera.with_lens(lens);
+        for _ in 0..16 {
+            let ray = camera.get_ray(point);
+            assert!(ray.pos.magnitude() <= 0.5 + 1e-9);
+
+            let hit = ray.extend((-10.0 - ray.pos.z) / ray.dir.z);
+            assert!((hit.x - expected.x).abs() < 1e-9);
+            assert!((hit.y - expected.y).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_ortho_extent() {
+        /* The view is as high as the perspective view at the focal distance */
+        let camera = Camera::build(Vector::ZERO, -Vector::UNIT_Z, Vector::UNIT_Y, 90.0, 1.0)
+            .with_lens(Lens::<f64>::new(0.0, 5.0))
+            .with_projection(Projection::Orthographic);
+
+        let top = camera.get_ray(point!(0.5, 0.0)).pos.y;
+        let bottom = camera.get_ray(point!(0.5, 1.0)).pos.y;
+        assert!(((top - bottom).abs() - 10.0).abs() < 1e-9, "{top} {bottom}");
+    }
+
+    #[test]
+    fn test_center_ray() {
+        let camera = Camera::build(Vector::ZERO, -Vector::UNIT_Z, Vector::UNIT_Y, 50.0, 1.0)
+            .with_lens(Lens::<f64>::new(0.5, 10.0))
+            .with_shutter(1.0, 2.0);
+
+        /* Data passes see the same ray every time, through the pinhole */
+        let (point, pixel) = (point!(0.3, 0.6), point!(0.01, 0.01));
+        let ray = camera.get_center_ray(point, pixel);
+        assert_eq!(ray.pos, Vector::ZERO);
+        assert_eq!(ray.time, 1.5);
+        assert_eq!(camera.get_center_ray(point, pixel).dir, ray.dir);
+    }
+
+    #[test]
+    fn test_projection_roundtrip() {
+        for kind in Projection::ALL {
+            let camera = Camera::<f64>::build(
+                vec3![1.0, 2.0, 3.0],
+                -Vector::UNIT_Z,
+                Vector::UNIT_Y,
+                60.0,
+                1.5,
+            )
+            .with_lens(Lens::new(0.0, 5.0))
+            .with_projection(kind);
+
+            /* Points along a camera ray must map back to the same image point */
+            for point in [point!(0.5, 0.5), point!(0.2, 0.7), point!(0.9, 0.1)] {
+                let ray = camera.get_ray(point);
+                let ndc = camera.world_to_ndc(ray.extend(4.0));
+                assert!(
+                    (ndc.x - point.x).abs() < 1e-9,
+                    "{kind}: {ndc:?} != {point:?}"
+                );
+                assert!(
+                    (ndc.y - point.y).abs() < 1e-9,
+                    "{kind}: {ndc:?} != {point:?}"
+                );
+            }
+        }
+    }
 }
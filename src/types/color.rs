@@ -120,6 +120,28 @@ impl<F: Float> Color<F> {
         Self { r, g, b }
     }
 
+    #[must_use]
+    pub fn max_channel(&self) -> F {
+        self.r.max(self.g).max(self.b)
+    }
+
+    /// Fraction of light that passes through `dist` units of a medium with
+    /// absorption coefficients `self` (Beer-Lambert law)
+    #[must_use]
+    pub fn transmittance(&self, dist: F) -> Self {
+        Self::new(
+            (-self.r * dist).exp(),
+            (-self.g * dist).exp(),
+            (-self.b * dist).exp(),
+        )
+    }
+
+    /// Relative luminance (Rec. 709 weights)
+    #[must_use]
+    pub fn luminance(&self) -> F {
+        self.r * F::from_f32(0.2126) + self.g * F::from_f32(0.7152) + self.b * F::from_f32(0.0722)
+    }
+
     pub fn mixed(input: &[Self]) -> Self {
         match input.len() {
             0 => Self::BLACK,
@@ -137,6 +159,15 @@ impl<F: Float> Color<F> {
         ]
     }
 
+    /// Unclamped linear color channels, for high dynamic range output
+    pub fn to_f32_array(&self) -> [f32; 3] {
+        [
+            self.r.to_f32().unwrap_or_default(),
+            self.g.to_f32().unwrap_or_default(),
+            self.b.to_f32().unwrap_or_default(),
+        ]
+    }
+
     pub fn to_array4(&self) -> [u8; 4] {
         let clamped = self.clamped();
         let max = F::from_u32(u8::MAX.into());
@@ -1,8 +1,15 @@
 use core::fmt::{self, Debug};
 
+use cgmath::InnerSpace;
+use num_traits::Zero;
+
 use crate::geometry::Geometry;
 use crate::light::Lixel;
-use crate::types::{Float, MaterialId, Point, Ray, RayFlags, Transform, Vector, Vectorx, RF};
+use crate::point;
+use crate::sampler::{Sampler, Texel};
+use crate::types::{
+    Color, Float, MaterialId, Point, Ray, RayDiff, RayFlags, Transform, Vector, Vectorx, RF,
+};
 
 #[derive(Copy, Clone)]
 pub struct Maxel<'a, F: Float> {
@@ -22,10 +29,20 @@ pub struct Maxel<'a, F: Float> {
     uv: Option<Point<F>>,
     /// Object (s, t) coordinates at intersection
     st: Option<Point<F>>,
+    /// Vertex colour at intersection
+    color: Option<Color<F>>,
+    /// Surface tangent and bitangent at intersection
+    tng: Option<(Vector<F>, Vector<F>)>,
+    /// Ray differentials at intersection (see [`RayDiff`])
+    diff: Option<RayDiff<F>>,
+    /// Texture coordinate footprint at intersection
+    duv: Option<(Point<F>, Point<F>)>,
     /// Ray nesting level
     pub lvl: u16,
     /// Ray flags from intersecting ray
     pub flags: RayFlags,
+    /// Point in time of intersecting ray
+    pub time: F,
 }
 
 impl<'a, F: Float> Debug for Maxel<'a, F> {
@@ -39,6 +56,9 @@ impl<'a, F: Float> Debug for Maxel<'a, F> {
             .field("nml", &self.nml)
             .field("uv", &self.uv)
             .field("st", &self.st)
+            .field("color", &self.color)
+            .field("tng", &self.tng)
+            .field("duv", &self.duv)
             .finish()
     }
 }
@@ -64,7 +84,12 @@ impl<'a, F: Float> Maxel<'a, F> {
             nml: None,
             uv: None,
             st: None,
+            color: None,
+            tng: None,
+            diff: None,
+            duv: None,
             flags,
+            time: F::ZERO,
         }
     }
 
@@ -73,11 +98,15 @@ impl<'a, F: Float> Maxel<'a, F> {
         self.pos = xfrm.pos(self.pos);
         self.dir = xfrm.dir(self.dir);
         self.nml = self.nml.map(|nml| xfrm.nml(nml));
+        self.tng = self
+            .tng
+            .map(|(u, v)| (xfrm.dir(u).normalize(), xfrm.dir(v).normalize()));
+        self.diff = self.diff.map(|diff| diff.map(|d| xfrm.dir(d)));
         self
     }
 
     pub fn ray(&self, pos: Vector<F>, dir: Vector<F>) -> Ray<F> {
-        let mut ray = Ray::new(pos, dir);
+        let mut ray = Ray::new(pos, dir).with_time(self.time);
         ray.lvl = self.lvl + 1;
         if self.flags.contains(RF::Debug) {
             ray = ray.with_debug();
@@ -86,15 +115,48 @@ impl<'a, F: Float> Maxel<'a, F> {
     }
 
     pub fn reflected_ray(&mut self) -> Ray<F> {
-        let refl = self.dir.reflect(&self.nml());
         let nml = self.nml();
-        self.ray(self.pos + nml * F::BIAS4, refl)
+        let refl = self.dir.reflect(&nml);
+        let diff = self.bounce_diff(refl, |dir| dir.reflect(&nml));
+        self.ray(self.pos + nml * F::BIAS4, refl).with_diff(diff)
     }
 
     pub fn refracted_ray(&mut self, ior: F) -> Ray<F> {
-        let refr = self.dir.refract(&self.nml(), ior);
         let nml = self.nml();
-        self.ray(self.pos - nml * F::BIAS4, refr)
+        let refr = self.dir.refract(&nml, ior);
+        let diff = self.bounce_diff(refr, |dir| dir.refract(&nml, ior));
+        self.ray(self.pos - nml * F::BIAS4, refr).with_diff(diff)
+    }
+
+    /// Differentials for a ray leaving the surface in direction `out`, where
+    /// `bounce` maps incoming directions to outgoing ones.
+    ///
+    /// The surface is treated as locally flat, so curvature does not widen
+    /// or narrow the footprint.
+    fn bounce_diff(
+        &mut self,
+        out: Vector<F>,
+        bounce: impl Fn(Vector<F>) -> Vector<F>,
+    ) -> Option<RayDiff<F>> {
+        let diff = self.diff?;
+        let nml = self.nml();
+        let ndir = nml.dot(self.dir);
+        if ndir.is_zero() || out.is_zero() {
+            return None;
+        }
+
+        let project = |dp: Vector<F>| dp - self.dir * (nml.dot(dp) / ndir);
+        let turn = |dd: Vector<F>| {
+            let dir = bounce((self.dir + dd).normalize());
+            (!dir.is_zero()).then(|| dir - out)
+        };
+
+        Some(RayDiff {
+            dpdx: project(diff.dpdx),
+            dpdy: project(diff.dpdy),
+            dddx: turn(diff.dddx)?,
+            dddy: turn(diff.dddy)?,
+        })
     }
 
     pub fn fresnel(&mut self, ior: F) -> F {
@@ -114,6 +176,16 @@ impl<'a, F: Float> Maxel<'a, F> {
         }
     }
 
+    #[must_use]
+    pub const fn with_time(self, time: F) -> Self {
+        Self { time, ..self }
+    }
+
+    #[must_use]
+    pub const fn with_diff(self, diff: Option<RayDiff<F>>) -> Self {
+        Self { diff, ..self }
+    }
+
     #[must_use]
     pub const fn with_uv(self, uv: Point<F>) -> Self {
         Self {
@@ -150,6 +222,77 @@ impl<'a, F: Float> Maxel<'a, F> {
         }
     }
 
+    /// Vertex colour at intersection, for use as a sampler input by
+    /// materials (white if the object has no vertex colours)
+    pub fn color(&mut self) -> Color<F> {
+        match self.color {
+            None => {
+                let color = self.obj.color(self);
+                *self.color.insert(color)
+            }
+            Some(c) => c,
+        }
+    }
+
+    /// Surface tangent and bitangent, used as the (x, y) axes for normal
+    /// maps
+    pub fn tangents(&mut self) -> (Vector<F>, Vector<F>) {
+        match self.tng {
+            None => {
+                let tng = self.obj.tangents(self);
+                *self.tng.insert(tng)
+            }
+            Some(p) => p,
+        }
+    }
+
+    /// Change in texture coordinates between neighbouring pixels, in x and
+    /// y (zero if unknown, which disables texture filtering)
+    pub fn uv_footprint(&mut self) -> (Point<F>, Point<F>) {
+        match self.duv {
+            None => {
+                let duv = self.footprint().unwrap_or((Point::ZERO, Point::ZERO));
+                *self.duv.insert(duv)
+            }
+            Some(p) => p,
+        }
+    }
+
+    fn footprint(&mut self) -> Option<(Point<F>, Point<F>)> {
+        let diff = self.diff?;
+        let (dpdu, dpdv) = self.obj.uv_derivatives(self)?;
+        let nml = dpdu.cross(dpdv);
+        let ndir = nml.dot(self.dir);
+
+        /* Least-squares solution of dp = dpdu * du + dpdv * dv, for the
+         * footprint projected onto the tangent plane along the ray */
+        let (a, b, c) = (dpdu.magnitude2(), dpdu.dot(dpdv), dpdv.magnitude2());
+        let det = a * c - b * b;
+        if ndir.is_zero() || det.is_zero() {
+            return None;
+        }
+
+        let solve = |dp: Vector<F>| {
+            let dp = dp - self.dir * (nml.dot(dp) / ndir);
+            let (pu, pv) = (dpdu.dot(dp), dpdv.dot(dp));
+            point!((c * pu - b * pv) / det, (a * pv - b * pu) / det)
+        };
+
+        let (dx, dy) = (solve(diff.dpdx), solve(diff.dpdy));
+        [dx.x, dx.y, dy.x, dy.y]
+            .iter()
+            .all(|v: &F| v.is_finite())
+            .then_some((dx, dy))
+    }
+
+    /// Sample `samp` at the texture coordinates of this maxel, filtered over
+    /// its footprint (see [`Self::uv_footprint`])
+    pub fn lookup<T: Texel>(&mut self, samp: &impl Sampler<F, T>) -> T {
+        let uv = self.uv();
+        let (dx, dy) = self.uv_footprint();
+        samp.sample_grad(uv, dx, dy)
+    }
+
     pub fn nml(&mut self) -> Vector<F> {
         match self.nml {
             None => {
@@ -164,9 +307,35 @@ impl<'a, F: Float> Maxel<'a, F> {
 #[cfg(test)]
 mod tests {
     use assert_float_eq::{afe_is_f64_near, afe_near_error_msg, assert_f64_near};
-    use cgmath::InnerSpace;
+    use cgmath::{Deg, InnerSpace, Matrix4};
+
+    use crate::geometry::{Cone, Cube, Cylinder, Geometry, Sphere};
+    use crate::sampler::Sampler;
+    use crate::types::{MaterialId, Point, Ray, RayDiff, Vector, Vectorx};
 
-    use crate::types::{Vector, Vectorx};
+    /// Sampler returning the uv footprint it is sampled with, in x (or y)
+    #[derive(Debug)]
+    struct Footprint(bool);
+
+    impl Sampler<f64, Vector<f64>> for Footprint {
+        fn sample(&self, _uv: Point<f64>) -> Vector<f64> {
+            Vector::ZERO
+        }
+
+        fn sample_grad(&self, _uv: Point<f64>, dx: Point<f64>, dy: Point<f64>) -> Vector<f64> {
+            let d = if self.0 { dy } else { dx };
+            Vector::new(d.x, d.y, 0.0)
+        }
+
+        fn dimensions(&self) -> (u32, u32) {
+            (1, 1)
+        }
+
+        #[cfg(feature = "gui")]
+        fn ui(&mut self, _ui: &mut egui::Ui, _name: &str) -> bool {
+            false
+        }
+    }
 
     macro_rules! assert_vec {
         ($val:expr, $x:expr, $y:expr, $z:expr) => {
@@ -176,6 +345,84 @@ mod tests {
         };
     }
 
+    /// Check that the footprint from [`super::Maxel::lookup`] matches the
+    /// change in uv between neighbouring rays, for hits away from seams
+    fn check_footprint(geo: &dyn Geometry<f64>) {
+        const EPS: f64 = 1e-5;
+        let diff = RayDiff {
+            dpdx: Vector::UNIT_X * EPS,
+            dpdy: Vector::UNIT_Y * EPS,
+            dddx: Vector::ZERO,
+            dddy: Vector::ZERO,
+        };
+
+        let mut checked = 0;
+        for i in 0..49 {
+            let pos = Vector::new(
+                f64::from(i % 7) * 0.2 - 0.6,
+                f64::from(i / 7) * 0.2 - 0.6,
+                5.0,
+            );
+            let ray = Ray::new(pos, Vector::new(0.03, -0.02, -1.0)).with_diff(Some(diff));
+            let Some(mut hit) = geo.intersect(&ray) else {
+                continue;
+            };
+            let (uv, nml) = (hit.uv(), hit.nml());
+
+            for (dp, y) in [(diff.dpdx, false), (diff.dpdy, true)] {
+                let Some(mut next) = geo.intersect(&Ray::new(pos + dp, ray.dir)) else {
+                    continue;
+                };
+                let duv = next.uv() - uv;
+                if next.nml().dot(nml) < 0.999 || duv.x.abs() > 0.5 {
+                    continue;
+                }
+
+                let found = hit.lookup(&Footprint(y));
+                let scale = duv.x.abs().max(duv.y.abs()).max(EPS);
+                assert!((found.x - duv.x).abs() < scale * 0.05, "{found:?} {duv:?}");
+                assert!((found.y - duv.y).abs() < scale * 0.05, "{found:?} {duv:?}");
+                checked += 1;
+            }
+        }
+        assert!(checked > 10, "only {checked} footprints checked");
+    }
+
+    #[test]
+    fn test_uv_footprint() {
+        let xfrm = Matrix4::from_translation(Vector::new(0.1, -0.1, 0.0))
+            * Matrix4::from_angle_x(Deg(-60.0))
+            * Matrix4::from_angle_z(Deg(20.0))
+            * Matrix4::from_nonuniform_scale(1.0, 0.8, 0.9);
+        let mat = MaterialId::NULL;
+
+        check_footprint(&Sphere::new(xfrm, mat));
+        check_footprint(&Cube::new(xfrm * Matrix4::from_scale(1.5), mat));
+        check_footprint(&Cylinder::new(
+            xfrm * Matrix4::from_translation(-Vector::UNIT_Z * 0.5),
+            true,
+            mat,
+        ));
+        check_footprint(&Cone::new(
+            1.0,
+            0.3,
+            0.8,
+            true,
+            xfrm * Matrix4::from_translation(-Vector::UNIT_Z * 0.5),
+            mat,
+        ));
+
+        /* Rays without differentials have no footprint, so are unfiltered */
+        let sphere = Sphere::new(Matrix4::from_scale(2.0), mat);
+        let mut hit = sphere
+            .intersect(&Ray::new(Vector::UNIT_Z * 5.0, -Vector::UNIT_Z))
+            .unwrap();
+        let uv = hit.uv();
+        assert_f64_near!(uv.x, 0.5);
+        assert_f64_near!(uv.y, 0.0);
+        assert_eq!(hit.lookup(&Footprint(false)), Vector::ZERO);
+    }
+
     #[test]
     fn test_reflect() {
         let dir = Vector::new(1.0, -1.0, 0.0).normalize();
@@ -0,0 +1,178 @@
+use std::ops::{Add, Mul, Sub};
+
+use crate::format::sbtwriter::SbtNode;
+use crate::types::{Color, Float, Lerp};
+
+/// Homogeneous participating medium, like fog, smoke or murky water.
+///
+/// Light travelling through the medium is absorbed and scattered away (per
+/// unit of distance, for each colour channel), while light from the scene
+/// lights is scattered towards the viewer, making beams of light visible.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Medium<F: Float> {
+    pub absorption: Color<F>,
+    pub scattering: Color<F>,
+    /// Henyey-Greenstein asymmetry, from -1 (backward) through 0 (uniform)
+    /// to 1 (forward scattering)
+    pub anisotropy: F,
+    /// Distance between samples of scattered light (see
+    /// [`crate::scene::Scene::march`])
+    pub step: F,
+}
+
+impl<F: Float> Medium<F> {
+    pub const VACUUM: Self = Self::new(Color::BLACK, Color::BLACK);
+
+    #[must_use]
+    pub const fn new(absorption: Color<F>, scattering: Color<F>) -> Self {
+        Self {
+            absorption,
+            scattering,
+            anisotropy: F::ZERO,
+            step: F::ONE,
+        }
+    }
+
+    #[must_use]
+    pub fn with_anisotropy(self, anisotropy: F) -> Self {
+        Self {
+            anisotropy: anisotropy.clamp(-F::ONE, F::ONE),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn with_step(self, step: F) -> Self {
+        Self {
+            step: step.max(F::BIAS3),
+            ..self
+        }
+    }
+
+    /// Total loss of light per unit of distance
+    #[must_use]
+    pub fn extinction(&self) -> Color<F> {
+        self.absorption + self.scattering
+    }
+
+    /// Fraction of light that passes through `dist` units of the medium
+    #[must_use]
+    pub fn transmittance(&self, dist: F) -> Color<F> {
+        self.extinction().transmittance(dist)
+    }
+
+    /// Fraction of scattered light that leaves at angle `cos` (cosine)
+    /// relative to its direction of travel, per steradian
+    #[must_use]
+    pub fn phase(&self, cos: F) -> F {
+        let g = self.anisotropy;
+        let denom = F::ONE + g * g - F::TWO * g * cos;
+        (F::ONE - g * g) / (F::from_u32(4) * F::PI() * denom * denom.sqrt())
+    }
+
+    /// Medium keys for SBT blocks
+    pub fn to_sbt(&self) -> [(&'static str, SbtNode<F>); 4] {
+        [
+            ("absorption", SbtNode::color(self.absorption)),
+            ("scattering", SbtNode::color(self.scattering)),
+            ("anisotropy", SbtNode::Float(self.anisotropy)),
+            ("step", SbtNode::Float(self.step)),
+        ]
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+        use crate::gui::controls;
+
+        let mut res = false;
+        res |= controls::color(ui, &mut self.absorption, "Absorption");
+        res |= controls::color(ui, &mut self.scattering, "Scattering");
+
+        ui.label("Anisotropy");
+        res |= ui
+            .add(egui::Slider::new(&mut self.anisotropy, -F::ONE..=F::ONE))
+            .changed();
+        ui.end_row();
+
+        ui.label("Step");
+        res |= ui
+            .add(
+                egui::Slider::new(&mut self.step, F::from_f32(0.01)..=F::from_u32(100))
+                    .logarithmic(true),
+            )
+            .changed();
+        ui.end_row();
+
+        res
+    }
+}
+
+impl<F: Float> Add for Medium<F> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            absorption: self.absorption + other.absorption,
+            scattering: self.scattering + other.scattering,
+            anisotropy: self.anisotropy + other.anisotropy,
+            step: self.step + other.step,
+        }
+    }
+}
+
+impl<F: Float> Sub for Medium<F> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self {
+            absorption: self.absorption - other.absorption,
+            scattering: self.scattering - other.scattering,
+            anisotropy: self.anisotropy - other.anisotropy,
+            step: self.step - other.step,
+        }
+    }
+}
+
+impl<F: Float> Mul<F> for Medium<F> {
+    type Output = Self;
+
+    fn mul(self, other: F) -> Self {
+        Self {
+            absorption: self.absorption * other,
+            scattering: self.scattering * other,
+            anisotropy: self.anisotropy * other,
+            step: self.step * other,
+        }
+    }
+}
+
+impl<F: Float> Lerp for Medium<F> {
+    type Ratio = F;
+}
+
+#[cfg(test)]
+mod tests {
+    use num_traits::FloatConst;
+
+    use super::Medium;
+    use crate::types::Color;
+
+    #[test]
+    fn test_medium_phase() {
+        /* The phase function integrates to 1 over the sphere */
+        for g in [-0.5, 0.0, 0.3, 0.8] {
+            let medium = Medium::new(Color::BLACK, Color::WHITE).with_anisotropy(g);
+            let steps = 10_000;
+            let sum: f64 = (0..steps)
+                .map(|i| {
+                    let cos = -1.0 + (f64::from(i) + 0.5) * 2.0 / f64::from(steps);
+                    medium.phase(cos) * 2.0 / f64::from(steps)
+                })
+                .sum();
+            assert!((sum * 2.0 * f64::PI() - 1.0).abs() < 1e-3, "g = {g}: {sum}");
+        }
+
+        let fog = Medium::new(Color::gray(0.5), Color::gray(0.5));
+        assert!((fog.transmittance(2.0).r - (-2.0f64).exp()).abs() < 1e-12);
+    }
+}
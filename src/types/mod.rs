@@ -1,3 +1,6 @@
+mod animation;
+mod antialias;
+mod aov;
 mod bvh;
 mod camera;
 mod color;
@@ -6,28 +9,37 @@ mod hash;
 mod iter;
 mod matlib;
 mod maxel;
+mod medium;
 mod object;
 mod point;
 mod ray;
 mod result;
 mod texlib;
 mod timeslice;
+mod tonemap;
 mod transform;
 mod vector;
 
+pub use animation::{
+    Animation, CameraTracks, FrameRange, Interpolation, Keyframe, LightTracks, ObjectTracks, Track,
+};
+pub use antialias::{AntiAlias, SamplePattern};
+pub use aov::Aov;
 pub use bvh::BvhExt;
-pub use camera::Camera;
+pub use camera::{Camera, CameraSelector, Lens, Projection};
 pub use color::Color;
 pub use float::{quadratic, quadratic2, Float, Lerp};
 pub use hash::hash;
 pub use iter::GridSamples;
 pub use matlib::{MaterialId, MaterialLib};
 pub use maxel::Maxel;
+pub use medium::Medium;
 pub use object::NamedObject;
 pub use point::Point;
-pub use ray::{Ray, RayFlags, RF};
+pub use ray::{Ray, RayDiff, RayFlags, RF};
 pub use result::{Error, RResult};
 pub use texlib::{TextureId, TextureLib};
 pub use timeslice::TimeSlice;
+pub use tonemap::{ToneMap, ToneMapper};
 pub use transform::{HasTransform, Transform};
 pub use vector::{Vector, Vector4x, Vectorx};
@@ -1,11 +1,12 @@
 use glam::Vec3;
 use rtbvh::Aabb;
 
+use crate::format::sbtwriter::{SbtNode, SbtWriter};
 use crate::geometry::{FiniteGeometry, Geometry};
 use crate::light::Lixel;
 use crate::material::{DynMaterial, HasMaterial, Material};
 use crate::scene::{Interactive, RayTracer, SceneObject};
-use crate::types::{Color, Float, Maxel, Point, Ray, Vector};
+use crate::types::{Color, Float, HasTransform, Maxel, Point, Ray, Vector};
 
 #[derive(Debug)]
 pub struct NamedObject<S> {
@@ -41,6 +42,14 @@ where
     fn material(&mut self) -> Option<&mut dyn HasMaterial> {
         self.obj.material()
     }
+
+    fn transform(&mut self) -> Option<&mut dyn HasTransform<F>> {
+        self.obj.transform()
+    }
+
+    fn to_sbt(&self, sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        self.obj.to_sbt(sbt)
+    }
 }
 
 impl<T> rtbvh::Primitive for NamedObject<T>
@@ -99,6 +108,10 @@ impl<F: Float, M: Material<F>> Material<F> for NamedObject<M> {
     {
         self.obj.dynamic()
     }
+
+    fn to_sbt(&self, sbt: &SbtWriter<F>) -> Option<SbtNode<F>> {
+        self.obj.to_sbt(sbt)
+    }
 }
 
 impl<F: Float, S: SceneObject<F>> SceneObject<F> for NamedObject<S> {
@@ -121,4 +134,8 @@ impl<F: Float, S: SceneObject<F>> SceneObject<F> for NamedObject<S> {
     fn get_object(&mut self, id: usize) -> Option<&mut dyn Geometry<F>> {
         self.obj.get_object(id)
     }
+
+    fn get_child(&mut self, idx: usize) -> Option<&mut dyn Geometry<F>> {
+        self.obj.get_child(idx)
+    }
 }
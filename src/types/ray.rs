@@ -14,12 +14,61 @@ flags! {
 
 pub type RayFlags = FlagSet<RF>;
 
+/// Ray differentials: the change in ray origin and direction between
+/// neighbouring pixels (in x and y), used to estimate texture footprints
+#[derive(Clone, Copy, Debug)]
+pub struct RayDiff<F: Float> {
+    pub dpdx: Vector<F>,
+    pub dpdy: Vector<F>,
+    pub dddx: Vector<F>,
+    pub dddy: Vector<F>,
+}
+
+impl<F: Float> RayDiff<F> {
+    /// Origin differentials after travelling `t` along the ray
+    #[must_use]
+    pub fn advance(self, t: F) -> Self {
+        Self {
+            dpdx: self.dpdx + self.dddx * t,
+            dpdy: self.dpdy + self.dddy * t,
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn scale(self, scale: F) -> Self {
+        Self {
+            dpdx: self.dpdx * scale,
+            dpdy: self.dpdy * scale,
+            dddx: self.dddx * scale,
+            dddy: self.dddy * scale,
+        }
+    }
+
+    /// Differentials in a new coordinate system, where `dir` maps
+    /// directions into it
+    #[must_use]
+    pub fn map(self, dir: impl Fn(Vector<F>) -> Vector<F>) -> Self {
+        Self {
+            dpdx: dir(self.dpdx),
+            dpdy: dir(self.dpdy),
+            dddx: dir(self.dddx),
+            dddy: dir(self.dddy),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct Ray<F: Float> {
     pub pos: Vector<F>,
     pub dir: Vector<F>,
     pub lvl: u16,
     pub flags: RayFlags,
+    /// Point in time (for motion blur), where 0 and 1 are the start and end
+    /// of object motion
+    pub time: F,
+    /// Ray differentials, if known (see [`RayDiff`])
+    pub diff: Option<RayDiff<F>>,
 }
 
 impl<'a, F: Float> Ray<F> {
@@ -29,9 +78,21 @@ impl<'a, F: Float> Ray<F> {
             dir,
             lvl: 0,
             flags: RayFlags::default(),
+            time: F::ZERO,
+            diff: None,
         }
     }
 
+    #[must_use]
+    pub const fn with_time(self, time: F) -> Self {
+        Self { time, ..self }
+    }
+
+    #[must_use]
+    pub const fn with_diff(self, diff: Option<RayDiff<F>>) -> Self {
+        Self { diff, ..self }
+    }
+
     #[must_use]
     pub const fn with_debug(self) -> Self {
         self.with_flags(RF::Debug.into())
@@ -65,6 +126,8 @@ impl<'a, F: Float> Ray<F> {
             mat,
             self.flags,
         )
+        .with_time(self.time)
+        .with_diff(self.diff.map(|diff| diff.advance(ext)))
     }
 
     pub fn synthetic_hit<G: Geometry<F>>(self, center: Vector<F>, obj: &'a G) -> Maxel<'a, F> {
@@ -77,6 +140,7 @@ impl<'a, F: Float> Ray<F> {
             MaterialId::NULL,
             self.flags,
         )
+        .with_time(self.time)
     }
 
     pub fn enter_group(self) -> Option<Self> {
@@ -92,6 +156,7 @@ impl<'a, F: Float> Ray<F> {
         Self {
             pos: xfrm.pos_inv(self.pos),
             dir: xfrm.dir_inv(self.dir),
+            diff: self.diff.map(|diff| diff.map(|d| xfrm.dir_inv(d))),
             ..*self
         }
     }
@@ -101,6 +166,7 @@ impl<'a, F: Float> Ray<F> {
         Self {
             pos: xfrm.pos(self.pos),
             dir: xfrm.dir(self.dir),
+            diff: self.diff.map(|diff| diff.map(|d| xfrm.dir(d))),
             ..*self
         }
     }
@@ -34,6 +34,9 @@ pub enum Error {
     #[error(transparent)]
     MtlLibsLoadError(#[from] obj::MtlLibsLoadError),
 
+    #[error(transparent)]
+    JsonError(#[from] serde_json::Error),
+
     #[error("parse error: {0}")]
     ParseError(String),
 
@@ -49,6 +52,15 @@ pub enum Error {
     #[error("Unknown file extension {0}")]
     UnknownFileExtension(String),
 
+    #[error("Unknown camera {0}")]
+    UnknownCamera(String),
+
+    #[error("Unknown animated object {0}")]
+    UnknownObject(String),
+
+    #[error("Could not save {0}")]
+    IncompleteSave(String),
+
     #[error(transparent)]
     BuildError(#[from] rtbvh::BuildError),
 
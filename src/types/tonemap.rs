@@ -0,0 +1,172 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::types::{Color, Error, Float};
+
+/// Operator used to compress linear (high dynamic range) radiance into the
+/// range `[0, 1]`. The result is still linear, see [`ToneMapper`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ToneMap {
+    /// Clamp each channel to `[0, 1]`
+    #[default]
+    Clamp,
+    /// Reinhard operator, `c / (1 + c)`
+    Reinhard,
+    /// ACES filmic curve (Narkowicz approximation)
+    Aces,
+}
+
+impl ToneMap {
+    pub const ALL: [Self; 3] = [Self::Clamp, Self::Reinhard, Self::Aces];
+
+    fn map<F: Float>(self, c: F) -> F {
+        let c = c.max(F::ZERO);
+        match self {
+            Self::Clamp => c,
+            Self::Reinhard => c / (F::ONE + c),
+            Self::Aces => {
+                let a = F::from_f32(2.51);
+                let b = F::from_f32(0.03);
+                let c2 = F::from_f32(2.43);
+                let d = F::from_f32(0.59);
+                let e = F::from_f32(0.14);
+                (c * (a * c + b)) / (c * (c2 * c + d) + e)
+            }
+        }
+        .clamp(F::ZERO, F::ONE)
+    }
+}
+
+/// sRGB transfer function (gamma correction) of linear `c` in `[0, 1]`
+fn srgb_encode<F: Float>(c: F) -> F {
+    if c <= F::from_f32(0.003_130_8) {
+        c * F::from_f32(12.92)
+    } else {
+        F::from_f32(1.055) * c.powf(F::ONE / F::from_f32(2.4)) - F::from_f32(0.055)
+    }
+}
+
+impl FromStr for ToneMap {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "clamp" | "none" => Ok(Self::Clamp),
+            "reinhard" => Ok(Self::Reinhard),
+            "aces" => Ok(Self::Aces),
+            _ => Err(Error::ParseError(format!("unknown tone mapping {s:?}"))),
+        }
+    }
+}
+
+impl Display for ToneMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Clamp => write!(f, "clamp"),
+            Self::Reinhard => write!(f, "reinhard"),
+            Self::Aces => write!(f, "aces"),
+        }
+    }
+}
+
+/// Conversion of linear radiance to displayable colors: an exposure
+/// adjustment (in stops), followed by a tone mapping operator, optionally
+/// followed by sRGB encoding.
+///
+/// The default is a linear clamp, which leaves colors in `[0, 1]` unchanged.
+#[derive(Copy, Clone, Debug)]
+pub struct ToneMapper<F: Float> {
+    pub op: ToneMap,
+    pub exposure: F,
+    /// Encode the result with the sRGB transfer function, as expected by
+    /// image viewers (off by default, and for data like normal or depth
+    /// passes)
+    pub srgb: bool,
+}
+
+impl<F: Float> Default for ToneMapper<F> {
+    fn default() -> Self {
+        Self::new(ToneMap::Clamp)
+    }
+}
+
+impl<F: Float> ToneMapper<F> {
+    #[must_use]
+    pub const fn new(op: ToneMap) -> Self {
+        Self {
+            op,
+            exposure: F::ZERO,
+            srgb: false,
+        }
+    }
+
+    #[must_use]
+    pub const fn with_exposure(self, exposure: F) -> Self {
+        Self { exposure, ..self }
+    }
+
+    #[must_use]
+    pub const fn with_srgb(self, srgb: bool) -> Self {
+        Self { srgb, ..self }
+    }
+
+    /// Map linear `color` to a displayable color in `[0, 1]`.
+    #[must_use]
+    pub fn apply(&self, color: Color<F>) -> Color<F> {
+        let color = color * F::TWO.powf(self.exposure);
+
+        let map = |c: F| {
+            let c = self.op.map(c);
+            if self.srgb {
+                srgb_encode(c)
+            } else {
+                c
+            }
+        };
+
+        Color::new(map(color.r), map(color.g), map(color.b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assert_float_eq::{afe_is_f64_near, afe_near_error_msg, assert_f64_near};
+
+    use super::{ToneMap, ToneMapper};
+    use crate::types::Color;
+
+    #[test]
+    fn test_tonemap_range() {
+        for op in ToneMap::ALL {
+            let tm = ToneMapper::<f64>::new(op);
+            for c in [0.0, 0.5, 1.0, 4.0, 1000.0] {
+                let res = tm.apply(Color::gray(c));
+                assert!((0.0..=1.0).contains(&res.r), "{op}: {c} -> {}", res.r);
+            }
+            assert_f64_near!(tm.apply(Color::BLACK).r, 0.0);
+        }
+    }
+
+    #[test]
+    fn test_tonemap_exposure() {
+        let tm = ToneMapper::<f64>::new(ToneMap::Clamp).with_exposure(1.0);
+        assert_f64_near!(tm.apply(Color::gray(0.25)).r, 0.5);
+
+        /* The default leaves colors in range unchanged */
+        let tm = ToneMapper::<f64>::default();
+        assert_f64_near!(tm.apply(Color::gray(0.25)).r, 0.25);
+    }
+
+    #[test]
+    fn test_tonemap_srgb() {
+        /* Any operator can be followed by sRGB encoding: mid gray (0.214
+         * linear, after Reinhard) is encoded as 0.5 */
+        let tm = ToneMapper::<f64>::new(ToneMap::Reinhard).with_srgb(true);
+        let linear = 0.214_041_140_482_232_55;
+        let res = tm.apply(Color::gray(linear / (1.0 - linear)));
+        assert!((res.r - 0.5).abs() < 1e-6, "{}", res.r);
+
+        let tm = tm.with_srgb(false);
+        assert_f64_near!(tm.apply(Color::gray(1.0)).r, 0.5);
+    }
+}
@@ -1,5 +1,5 @@
-use cgmath::{EuclideanSpace, InnerSpace, Matrix, Matrix4, Point3};
-use cgmath::{SquareMatrix, Transform as _};
+use cgmath::{EuclideanSpace, InnerSpace, Matrix, Matrix3, Matrix4, Point3, Quaternion};
+use cgmath::{One, SquareMatrix, Transform as _, VectorSpace, Zero};
 
 use crate::types::{Float, Vector, Vector4x};
 
@@ -7,11 +7,27 @@ use crate::types::{Float, Vector, Vector4x};
 pub struct Transform<F: Float> {
     xfrm: Matrix4<F>,
     ifrm: Matrix4<F>,
+    /* Translation, rotation and scale of `xfrm`, kept for interpolation */
+    parts: (Vector<F>, Quaternion<F>, Vector<F>),
 }
 
 pub trait HasTransform<F: Float> {
     fn get_transform(&self) -> &Transform<F>;
     fn set_transform(&mut self, xfrm: &Transform<F>);
+
+    /// Transform at the end of the motion (time 1), for moving objects
+    fn get_motion(&self) -> Option<&Transform<F>>;
+    fn set_motion(&mut self, end: Option<&Transform<F>>);
+
+    /// Transform at `time`, interpolated from the start (time 0) to the end
+    /// (time 1) of the motion. Static objects return their own transform, so
+    /// no matrices are computed.
+    fn transform_at(&self, time: F) -> Transform<F> {
+        self.get_motion().map_or_else(
+            || *self.get_transform(),
+            |end| self.get_transform().interpolate(end, time),
+        )
+    }
 }
 
 impl<F: Float> Transform<F> {
@@ -19,6 +35,7 @@ impl<F: Float> Transform<F> {
         Self {
             xfrm,
             ifrm: xfrm.inverse_transform().unwrap(),
+            parts: Self::decompose(&xfrm),
         }
     }
 
@@ -27,9 +44,66 @@ impl<F: Float> Transform<F> {
         Self {
             xfrm: Matrix4::identity(),
             ifrm: Matrix4::identity(),
+            parts: (
+                Vector::zero(),
+                Quaternion::one(),
+                Vector::new(F::ONE, F::ONE, F::ONE),
+            ),
+        }
+    }
+
+    pub const fn matrix(&self) -> &Matrix4<F> {
+        &self.xfrm
+    }
+
+    /// Interpolate from `self` (at `t = 0`) to `other` (at `t = 1`).
+    ///
+    /// Both transforms are decomposed into translation, rotation and scale,
+    /// which are interpolated separately, so rotating objects keep their shape.
+    /// Shear is not preserved.
+    ///
+    /// The decomposition is computed once, when the transforms are created,
+    /// and the inverse is built from the inverted parts, so this is cheap
+    /// enough to call for every ray.
+    #[must_use]
+    pub fn interpolate(&self, other: &Self, t: F) -> Self {
+        let (pos0, rot0, scale0) = self.parts;
+        let (pos1, rot1, scale1) = other.parts;
+
+        let pos = pos0.lerp(pos1, t);
+        let rot = rot0.slerp(rot1, t);
+        let scale = scale0.lerp(scale1, t);
+
+        let xfrm = Matrix4::from_translation(pos)
+            * Matrix4::from(rot)
+            * Matrix4::from_nonuniform_scale(scale.x, scale.y, scale.z);
+
+        let ifrm =
+            Matrix4::from_nonuniform_scale(F::ONE / scale.x, F::ONE / scale.y, F::ONE / scale.z)
+                * Matrix4::from(rot.conjugate())
+                * Matrix4::from_translation(-pos);
+
+        Self {
+            xfrm,
+            ifrm,
+            parts: (pos, rot, scale),
         }
     }
 
+    fn decompose(xfrm: &Matrix4<F>) -> (Vector<F>, Quaternion<F>, Vector<F>) {
+        let (x, y, z) = (xfrm.x.truncate(), xfrm.y.truncate(), xfrm.z.truncate());
+        let mut scale = Vector::new(x.magnitude(), y.magnitude(), z.magnitude());
+        let mut rot = Matrix3::from_cols(x / scale.x, y / scale.y, z / scale.z);
+
+        /* Mirroring is not a rotation, so move it to the scale */
+        if rot.determinant() < F::ZERO {
+            rot.x = -rot.x;
+            scale.x = -scale.x;
+        }
+
+        (xfrm.w.truncate(), Quaternion::from(rot), scale)
+    }
+
     pub fn pos(&self, vec: Vector<F>) -> Vector<F> {
         self.xfrm.transform_point(Point3::from_vec(vec)).to_vec()
     }
@@ -54,6 +128,12 @@ impl<F: Float> Transform<F> {
         self.xfrm.transform_vector(vec).normalize()
     }
 
+    /// Factor by which a small area with (unit) normal `nml` grows
+    pub fn area(&self, nml: Vector<F>) -> F {
+        let scale = self.ifrm.transpose().transform_vector(nml).magnitude();
+        self.xfrm.determinant().abs() * scale
+    }
+
     pub fn into_mint(&self) -> mint::ColumnMatrix4<f32> {
         let t = self.xfrm;
         mint::ColumnMatrix4 {
@@ -86,3 +166,36 @@ impl<F: Float> From<mint::ColumnMatrix4<f32>> for Transform<F> {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, InnerSpace, Matrix4};
+
+    use super::Transform;
+    use crate::types::Vector;
+
+    #[test]
+    fn test_interpolate() {
+        let start = Transform::new(Matrix4::from_scale(2.0));
+        let end = Transform::new(
+            Matrix4::from_translation(Vector::new(4.0, 0.0, 0.0))
+                * Matrix4::from_angle_y(Deg(90.0))
+                * Matrix4::from_scale(2.0),
+        );
+
+        /* Halfway, the object is rotated by 45 degrees, without changing size */
+        let mid = start.interpolate(&end, 0.5);
+        let pos = mid.pos(Vector::new(1.0, 0.0, 0.0));
+        let half = 2.0_f64.sqrt();
+        assert!((pos - Vector::new(2.0 + half, 0.0, -half)).magnitude() < 1e-9);
+
+        /* The end points are reproduced */
+        let pos = start.interpolate(&end, 1.0).pos(Vector::new(0.0, 0.0, 1.0));
+        assert!((pos - end.pos(Vector::new(0.0, 0.0, 1.0))).magnitude() < 1e-9);
+
+        /* The inverse is built from the parts, and must match */
+        let vec = Vector::new(0.3, -1.0, 2.0);
+        assert!((mid.pos_inv(mid.pos(vec)) - vec).magnitude() < 1e-9);
+        assert!((mid.dir_inv(mid.dir(vec)) - vec).magnitude() < 1e-9);
+    }
+}